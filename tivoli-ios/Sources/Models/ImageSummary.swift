@@ -9,6 +9,8 @@ struct ImageSummary: Codable, Identifiable, Hashable, Sendable {
     let width: Int
     let height: Int
     let fileSize: Int
+    let blurhash: String?
+    let dominantColor: String?
 
     var aspectRatio: CGFloat {
         guard height > 0 else { return 1 }
@@ -16,8 +18,9 @@ struct ImageSummary: Codable, Identifiable, Hashable, Sendable {
     }
 
     enum CodingKeys: String, CodingKey {
-        case uuid, path, collection, gallery, width, height
+        case uuid, path, collection, gallery, width, height, blurhash
         case fileSize = "file_size"
+        case dominantColor = "dominant_color"
     }
 }
 
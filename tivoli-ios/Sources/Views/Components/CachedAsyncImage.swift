@@ -3,6 +3,7 @@ import SwiftUI
 struct CachedAsyncImage: View {
     let url: URL
     var contentMode: ContentMode = .fill
+    var placeholderColor: Color?
     @State private var image: UIImage?
     @State private var isLoading = true
 
@@ -15,6 +16,8 @@ struct CachedAsyncImage: View {
                         .aspectRatio(contentMode: contentMode)
                         .frame(width: geo.size.width, height: geo.size.height)
                         .clipped()
+                } else if isLoading, let placeholderColor {
+                    placeholderColor
                 } else if isLoading {
                     Color(.systemGray6)
                         .overlay { ProgressView().scaleEffect(0.7) }
@@ -38,3 +41,16 @@ struct CachedAsyncImage: View {
         }
     }
 }
+
+extension Color {
+    /// Parses `#rrggbb` as sent by the server for placeholder colors.
+    init?(hex: String) {
+        let digits = hex.hasPrefix("#") ? String(hex.dropFirst()) : hex
+        guard digits.count == 6, let value = UInt32(digits, radix: 16) else { return nil }
+        self.init(
+            red: Double((value >> 16) & 0xFF) / 255,
+            green: Double((value >> 8) & 0xFF) / 255,
+            blue: Double(value & 0xFF) / 255
+        )
+    }
+}
@@ -173,7 +173,10 @@ struct MainView: View {
                     Button {
                         selectedIndex = index
                     } label: {
-                        CachedAsyncImage(url: gridImageURL(image.uuid))
+                        CachedAsyncImage(
+                            url: gridImageURL(image.uuid),
+                            placeholderColor: image.dominantColor.flatMap(Color.init(hex:))
+                        )
                             .aspectRatio(image.aspectRatio, contentMode: .fill)
                     }
                     .buttonStyle(.plain)
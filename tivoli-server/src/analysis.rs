@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+
+use image::DynamicImage;
+
+use crate::errors::AppError;
+
+/// Width of the downscaled copy that placeholders are computed from.
+const SAMPLE_WIDTH: u32 = 32;
+
+/// Default Hamming distance under which two perceptual hashes count as the
+/// same picture (resized, recompressed or lightly edited).
+pub const SIMILAR_MAX_DISTANCE: u32 = 8;
+
+/// Default CIELAB distance (ΔE*76) under which a palette colour counts as
+/// matching a `color` search. Around 2 is barely noticeable; 20 keeps "warm
+/// red" from drifting into orange or pink.
+pub const COLOR_MAX_DISTANCE: u32 = 20;
+
+/// Most colours kept per palette.
+const PALETTE_SIZE: usize = 5;
+
+/// Palette colours covering less of the image than this are dropped, so a
+/// red earring doesn't make a portrait "red".
+const PALETTE_MIN_WEIGHT: f64 = 0.05;
+
+/// Everything derived from an image's pixels during ingest.
+pub struct ImageAnalysis {
+    pub blurhash: String,
+    pub dominant_color: String,
+    pub perceptual_hash: u64,
+    /// Largest share first.
+    pub palette: Vec<PaletteEntry>,
+}
+
+pub struct PaletteEntry {
+    /// `#rrggbb`
+    pub color: String,
+    /// Share of the image's pixels, 0..1.
+    pub weight: f64,
+    pub lab: [f64; 3],
+}
+
+pub fn analyze(img: &DynamicImage) -> Result<ImageAnalysis, AppError> {
+    let sample = img.thumbnail(SAMPLE_WIDTH, SAMPLE_WIDTH).to_rgba8();
+    let (width, height) = sample.dimensions();
+
+    // 4x3 components is the BlurHash default and suits mixed orientations
+    let blurhash = blurhash::encode(4, 3, width, height, sample.as_raw())
+        .map_err(|e| AppError::BadRequest(format!("Failed to encode blurhash: {e}")))?;
+
+    Ok(ImageAnalysis {
+        blurhash,
+        dominant_color: dominant_color(sample.as_raw()),
+        palette: palette(sample.as_raw()),
+        perceptual_hash: difference_hash(&DynamicImage::ImageRgba8(sample)),
+    })
+}
+
+/// 64-bit dHash: shrink to 9x8 greyscale and record whether each pixel is
+/// brighter than its right-hand neighbour. Survives resizing and recompression,
+/// so copies of a shot land within a few bits of each other.
+fn difference_hash(img: &DynamicImage) -> u64 {
+    let grey = img
+        .resize_exact(9, 8, image::imageops::FilterType::Triangle)
+        .to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if grey.get_pixel(x, y)[0] > grey.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    hash
+}
+
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Group hashes into clusters where each member is within `max_distance` of
+/// at least one other member. Returns index lists, singletons omitted.
+pub fn cluster_hashes(hashes: &[u64], max_distance: u32) -> Vec<Vec<usize>> {
+    // Union-find over every close pair
+    let mut parent: Vec<usize> = (0..hashes.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    for i in 0..hashes.len() {
+        for j in i + 1..hashes.len() {
+            if hamming_distance(hashes[i], hashes[j]) <= max_distance {
+                let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+                parent[a.max(b)] = a.min(b);
+            }
+        }
+    }
+
+    let mut clusters: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..hashes.len() {
+        let r = root(&mut parent, i);
+        clusters.entry(r).or_default().push(i);
+    }
+    let mut clusters: Vec<Vec<usize>> = clusters.into_values().filter(|c| c.len() > 1).collect();
+    clusters.sort_by_key(|c| c[0]);
+    clusters
+}
+
+/// Most common colour, found by bucketing pixels to 4 bits per channel and
+/// averaging the fullest bucket. Returned as `#rrggbb`.
+fn dominant_color(rgba: &[u8]) -> String {
+    let mut buckets: HashMap<u16, (u32, [u32; 3])> = HashMap::new();
+    for px in rgba.chunks_exact(4) {
+        let key = (u16::from(px[0] >> 4) << 8) | (u16::from(px[1] >> 4) << 4) | u16::from(px[2] >> 4);
+        let (count, sum) = buckets.entry(key).or_default();
+        *count += 1;
+        for (s, &c) in sum.iter_mut().zip(px) {
+            *s += u32::from(c);
+        }
+    }
+
+    // Ties go to the higher bucket key so the result is deterministic
+    let (count, sum) = buckets
+        .into_iter()
+        .max_by_key(|(key, (count, _))| (*count, *key))
+        .map_or((1, [0; 3]), |(_, bucket)| bucket);
+    format!(
+        "#{:02x}{:02x}{:02x}",
+        sum[0] / count,
+        sum[1] / count,
+        sum[2] / count
+    )
+}
+
+/// Up to `PALETTE_SIZE` colours found by k-means in CIELAB, seeded from the
+/// fullest 4-bit buckets so the result is deterministic.
+fn palette(rgba: &[u8]) -> Vec<PaletteEntry> {
+    let pixels: Vec<([u8; 3], [f64; 3])> = rgba
+        .chunks_exact(4)
+        .map(|px| {
+            let rgb = [px[0], px[1], px[2]];
+            (rgb, srgb_to_lab(rgb))
+        })
+        .collect();
+    if pixels.is_empty() {
+        return Vec::new();
+    }
+
+    let mut buckets: HashMap<u16, (u32, [u32; 3])> = HashMap::new();
+    for (rgb, _) in &pixels {
+        let key = (u16::from(rgb[0] >> 4) << 8) | (u16::from(rgb[1] >> 4) << 4) | u16::from(rgb[2] >> 4);
+        let (count, sum) = buckets.entry(key).or_default();
+        *count += 1;
+        for (s, &c) in sum.iter_mut().zip(rgb) {
+            *s += u32::from(c);
+        }
+    }
+    let mut buckets: Vec<(u16, (u32, [u32; 3]))> = buckets.into_iter().collect();
+    buckets.sort_by_key(|(key, (count, _))| std::cmp::Reverse((*count, *key)));
+
+    // Seeds must be visibly different, or two clusters split one colour
+    let mut centroids: Vec<[f64; 3]> = Vec::new();
+    for (_, (count, sum)) in buckets {
+        let seed = srgb_to_lab(sum.map(|s| (s / count) as u8));
+        if centroids.iter().all(|c| lab_distance(*c, seed) > 10.0) {
+            centroids.push(seed);
+            if centroids.len() == PALETTE_SIZE {
+                break;
+            }
+        }
+    }
+
+    let mut assignment = vec![0usize; pixels.len()];
+    for _ in 0..8 {
+        for (slot, (_, lab)) in assignment.iter_mut().zip(&pixels) {
+            *slot = (0..centroids.len())
+                .min_by(|&a, &b| lab_distance(centroids[a], *lab).total_cmp(&lab_distance(centroids[b], *lab)))
+                .unwrap_or(0);
+        }
+        for (k, centroid) in centroids.iter_mut().enumerate() {
+            let members: Vec<&[f64; 3]> =
+                assignment.iter().zip(&pixels).filter(|(a, _)| **a == k).map(|(_, (_, lab))| lab).collect();
+            if !members.is_empty() {
+                let n = members.len() as f64;
+                *centroid = [0, 1, 2].map(|i| members.iter().map(|lab| lab[i]).sum::<f64>() / n);
+            }
+        }
+    }
+
+    // Report each cluster as the average of its pixels' sRGB values
+    let mut entries: Vec<PaletteEntry> = (0..centroids.len())
+        .filter_map(|k| {
+            let members: Vec<[u8; 3]> =
+                assignment.iter().zip(&pixels).filter(|(a, _)| **a == k).map(|(_, (rgb, _))| *rgb).collect();
+            let weight = members.len() as f64 / pixels.len() as f64;
+            if weight < PALETTE_MIN_WEIGHT {
+                return None;
+            }
+            let n = members.len() as u32;
+            let rgb = [0, 1, 2].map(|i| (members.iter().map(|px| u32::from(px[i])).sum::<u32>() / n) as u8);
+            Some(PaletteEntry {
+                color: format!("#{:02x}{:02x}{:02x}", rgb[0], rgb[1], rgb[2]),
+                weight,
+                lab: srgb_to_lab(rgb),
+            })
+        })
+        .collect();
+    entries.sort_by(|a, b| b.weight.total_cmp(&a.weight));
+    entries
+}
+
+/// `#rrggbb`, `rrggbb`, `#rgb` or `rgb` to CIELAB.
+pub fn hex_to_lab(hex: &str) -> Option<[f64; 3]> {
+    let digits = hex.strip_prefix('#').unwrap_or(hex);
+    if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let channel = |s: &str| u8::from_str_radix(s, 16).ok();
+    let rgb = match digits.len() {
+        6 => [channel(&digits[0..2])?, channel(&digits[2..4])?, channel(&digits[4..6])?],
+        3 => [0, 1, 2].map(|i| channel(&digits[i..i + 1]).map_or(0, |c| c * 17)),
+        _ => return None,
+    };
+    Some(srgb_to_lab(rgb))
+}
+
+/// sRGB (D65) to CIELAB.
+fn srgb_to_lab(rgb: [u8; 3]) -> [f64; 3] {
+    let [r, g, b] = rgb.map(|c| {
+        let c = f64::from(c) / 255.0;
+        if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+    });
+    let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+    let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+    let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+    let f = |t: f64| {
+        const DELTA: f64 = 6.0 / 29.0;
+        if t > DELTA.powi(3) { t.cbrt() } else { t / (3.0 * DELTA * DELTA) + 4.0 / 29.0 }
+    };
+    let (fx, fy, fz) = (f(x), f(y), f(z));
+    [116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz)]
+}
+
+/// CIE76 colour difference.
+fn lab_distance(a: [f64; 3], b: [f64; 3]) -> f64 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
@@ -0,0 +1,238 @@
+use serde_json::Value;
+
+use crate::auth::Caller;
+use crate::errors::AppError;
+use crate::models::{AuditEntry, AuditQuery};
+use crate::unix_now;
+
+const DEFAULT_LIMIT: u32 = 100;
+const MAX_LIMIT: u32 = 1000;
+
+/// The record a change applies to.
+pub enum Target<'a> {
+    Image(&'a str),
+    Comment { image: &'a str, uuid: &'a str },
+    Album(&'a str),
+    SavedSearch(&'a str),
+    Share(&'a str),
+    Tag(&'a str),
+    TagGroup(&'a str),
+    User(&'a str),
+    ApiKey(&'a str),
+    /// A background job or the thumbnail cache, by name.
+    Job(&'a str),
+    /// An undoable edit, by `edit_operations` id.
+    Operation(&'a str),
+}
+
+impl Target<'_> {
+    fn kind(&self) -> &'static str {
+        match self {
+            Target::Image(_) => "image",
+            Target::Comment { .. } => "comment",
+            Target::Album(_) => "album",
+            Target::SavedSearch(_) => "saved_search",
+            Target::Share(_) => "share",
+            Target::Tag(_) => "tag",
+            Target::TagGroup(_) => "tag_group",
+            Target::User(_) => "user",
+            Target::ApiKey(_) => "api_key",
+            Target::Job(_) => "job",
+            Target::Operation(_) => "operation",
+        }
+    }
+
+    fn id(&self) -> &str {
+        match self {
+            Target::Image(id)
+            | Target::Comment { uuid: id, .. }
+            | Target::Album(id)
+            | Target::SavedSearch(id)
+            | Target::Share(id)
+            | Target::Tag(id)
+            | Target::TagGroup(id)
+            | Target::User(id)
+            | Target::ApiKey(id)
+            | Target::Job(id)
+            | Target::Operation(id) => id,
+        }
+    }
+
+    /// The image an entry is listed under when filtering by image.
+    fn image(&self) -> Option<&str> {
+        match self {
+            Target::Image(image) | Target::Comment { image, .. } => Some(image),
+            _ => None,
+        }
+    }
+}
+
+/// Record a change made by `caller`. Call inside the change's transaction,
+/// so entries are in the order changes happened and a change is never kept
+/// without its entry.
+pub fn record(
+    conn: &rusqlite::Connection,
+    caller: &Caller,
+    action: &str,
+    target: Target,
+    before: Option<Value>,
+    after: Option<Value>,
+) -> Result<(), AppError> {
+    let actor = Actor {
+        name: &caller.name,
+        id: &caller.principal(),
+        request_id: &caller.request_id,
+    };
+    insert(conn, actor, None, action, target, before, after)
+}
+
+/// Record a change that is part of an undoable edit.
+pub fn record_edit(
+    conn: &rusqlite::Connection,
+    caller: &Caller,
+    operation: i64,
+    action: &str,
+    target: Target,
+    before: Value,
+    after: Value,
+) -> Result<(), AppError> {
+    let actor = Actor {
+        name: &caller.name,
+        id: &caller.principal(),
+        request_id: &caller.request_id,
+    };
+    insert(conn, actor, Some(operation), action, target, Some(before), Some(after))
+}
+
+/// Record a change made from the command line, which has no request.
+pub fn record_cli(
+    conn: &rusqlite::Connection,
+    action: &str,
+    target: Target,
+    after: Option<Value>,
+) -> Result<(), String> {
+    let actor = Actor {
+        name: "command line",
+        id: "cli",
+        request_id: "",
+    };
+    insert(conn, actor, None, action, target, None, after).map_err(|e| e.to_string())
+}
+
+struct Actor<'a> {
+    name: &'a str,
+    id: &'a str,
+    request_id: &'a str,
+}
+
+fn insert(
+    conn: &rusqlite::Connection,
+    actor: Actor,
+    operation: Option<i64>,
+    action: &str,
+    target: Target,
+    before: Option<Value>,
+    after: Option<Value>,
+) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT INTO audit_log (at, request_id, actor, actor_id, action, target_kind, target_id,
+            image_uuid, before, after, operation_id)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        rusqlite::params![
+            unix_now() as i64,
+            actor.request_id,
+            actor.name,
+            actor.id,
+            action,
+            target.kind(),
+            target.id(),
+            target.image(),
+            before.map(|v| v.to_string()),
+            after.map(|v| v.to_string()),
+            operation,
+        ],
+    )?;
+    Ok(())
+}
+
+pub(crate) fn json_value(row: &rusqlite::Row, i: usize) -> rusqlite::Result<Option<Value>> {
+    let json: Option<String> = row.get(i)?;
+    json.map(|json| {
+        serde_json::from_str(&json).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(i, rusqlite::types::Type::Text, Box::new(e))
+        })
+    })
+    .transpose()
+}
+
+/// Entries matching every given filter, newest first.
+pub fn query_audit_log(
+    conn: &rusqlite::Connection,
+    query: &AuditQuery,
+) -> Result<Vec<AuditEntry>, AppError> {
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return Err(AppError::BadRequest(format!("limit must be between 1 and {MAX_LIMIT}")));
+    }
+
+    // Each `?` in a condition stands for that condition's single value
+    let mut conditions: Vec<String> = Vec::new();
+    let mut params: Vec<rusqlite::types::Value> = Vec::new();
+    let mut bind = |condition: &str, value: rusqlite::types::Value| {
+        params.push(value);
+        conditions.push(condition.replace('?', &format!("?{}", params.len())));
+    };
+    if let Some(image) = &query.image {
+        bind("image_uuid = ?", image.clone().into());
+    }
+    if let Some(user) = &query.user {
+        bind(
+            "(actor = ? COLLATE NOCASE OR actor_id = ? OR actor_id = 'user:' || ?)",
+            user.clone().into(),
+        );
+    }
+    if let Some(action) = &query.action {
+        bind("(action = ? OR action LIKE ? || '.%')", action.clone().into());
+    }
+    if let Some(target) = &query.target {
+        bind("target_id = ?", target.clone().into());
+    }
+    if let Some(since) = query.since {
+        bind("at >= ?", (since as i64).into());
+    }
+    if let Some(until) = query.until {
+        bind("at <= ?", (until as i64).into());
+    }
+    if let Some(before) = query.before {
+        bind("id < ?", before.into());
+    }
+
+    let mut sql = String::from(
+        "SELECT id, at, request_id, actor, actor_id, action, target_kind, target_id, image_uuid,
+            before, after, operation_id FROM audit_log",
+    );
+    if !conditions.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
+    }
+    sql.push_str(&format!(" ORDER BY id DESC LIMIT {limit}"));
+
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(params), |row| {
+        Ok(AuditEntry {
+            id: row.get(0)?,
+            at: row.get::<_, i64>(1)? as u64,
+            request_id: row.get(2)?,
+            actor: row.get(3)?,
+            actor_id: row.get(4)?,
+            action: row.get(5)?,
+            target_kind: row.get(6)?,
+            target_id: row.get(7)?,
+            image_uuid: row.get(8)?,
+            before: json_value(row, 9)?,
+            after: json_value(row, 10)?,
+            operation_id: row.get(11)?,
+        })
+    })?;
+    Ok(rows.collect::<Result<_, _>>()?)
+}
@@ -0,0 +1,774 @@
+use std::sync::{Arc, OnceLock};
+
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use axum::extract::{FromRequestParts, Request, State};
+use axum::http::request::Parts;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use crate::audit::{self, Target};
+use crate::errors::AppError;
+use crate::handlers::AppState;
+use crate::models::{
+    ApiKey, CollectionAccess, LoginResponse, NewApiKey, Role, UpdateUserRequest, User, WhoAmI,
+};
+use crate::unix_now;
+
+/// How long a login session lasts.
+const SESSION_TTL_SECS: u64 = 30 * 24 * 60 * 60;
+
+const MIN_PASSWORD_LEN: usize = 8;
+
+/// Longer client-supplied request IDs are cut short before they are stored.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Authentication and authorization failures, as `{"error", "code"}` JSON.
+#[derive(Clone)]
+pub enum AuthError {
+    Missing,
+    Invalid,
+    BadLogin,
+    InsufficientRole { required: Role, granted: Role },
+}
+
+impl AuthError {
+    fn parts(&self) -> (StatusCode, &'static str, String) {
+        match self {
+            AuthError::Missing => (
+                StatusCode::UNAUTHORIZED,
+                "missing_credentials",
+                "Credentials are required: send `Authorization: Bearer <api key or session token>`"
+                    .to_string(),
+            ),
+            AuthError::Invalid => (
+                StatusCode::UNAUTHORIZED,
+                "invalid_credentials",
+                "The API key or session token is not valid, has expired or has been revoked"
+                    .to_string(),
+            ),
+            AuthError::BadLogin => (
+                StatusCode::UNAUTHORIZED,
+                "invalid_login",
+                "Invalid username or password".to_string(),
+            ),
+            AuthError::InsufficientRole { required, granted } => (
+                StatusCode::FORBIDDEN,
+                "insufficient_role",
+                format!(
+                    "This request needs the {} role; you are {}",
+                    required.name(),
+                    granted.name()
+                ),
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.parts().2)
+    }
+}
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        let (status, code, message) = self.parts();
+        let body = axum::Json(serde_json::json!({ "error": message, "code": code }));
+        if status == StatusCode::UNAUTHORIZED {
+            (status, [(header::WWW_AUTHENTICATE, "Bearer")], body).into_response()
+        } else {
+            (status, body).into_response()
+        }
+    }
+}
+
+/// The authenticated user or API key behind a request. Handlers take it as
+/// an extractor and check the role they need with [`Caller::require`].
+#[derive(Clone)]
+pub struct Caller {
+    /// Username, key name, or `local`.
+    pub name: String,
+    pub role: Role,
+    pub identity: Identity,
+    /// Always `All` for admins.
+    pub collections: CollectionAccess,
+    /// `X-Request-Id` of the request, recorded in the audit log.
+    pub request_id: String,
+}
+
+#[derive(Clone)]
+pub enum Identity {
+    User { uuid: String, session_hash: String },
+    ApiKey { id: String },
+    /// Authentication is disabled; every request acts as an admin.
+    Local,
+}
+
+impl Caller {
+    pub(crate) fn local() -> Caller {
+        Caller {
+            name: "local".into(),
+            role: Role::Admin,
+            identity: Identity::Local,
+            collections: CollectionAccess::All,
+            request_id: String::new(),
+        }
+    }
+
+    pub fn require(&self, required: Role) -> Result<(), AppError> {
+        if self.role < required {
+            return Err(AppError::Auth(AuthError::InsufficientRole {
+                required,
+                granted: self.role,
+            }));
+        }
+        Ok(())
+    }
+
+    /// Stable identifier for records the caller owns, e.g. `user:<uuid>`.
+    pub fn principal(&self) -> String {
+        match &self.identity {
+            Identity::User { uuid, .. } => format!("user:{uuid}"),
+            Identity::ApiKey { id } => format!("key:{id}"),
+            Identity::Local => "local".into(),
+        }
+    }
+
+    pub fn who_am_i(&self) -> WhoAmI {
+        let (kind, user_uuid, key_id) = match &self.identity {
+            Identity::User { uuid, .. } => ("user", Some(uuid.clone()), None),
+            Identity::ApiKey { id } => ("api_key", None, Some(id.clone())),
+            Identity::Local => ("local", None, None),
+        };
+        WhoAmI {
+            name: self.name.clone(),
+            role: self.role,
+            kind,
+            user_uuid,
+            key_id,
+            collections: self.collections.to_list(),
+        }
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Caller {
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        // Both middlewares insert one, so a missing caller means a route was
+        // mounted outside them: fail closed.
+        let mut caller = parts.extensions.get::<Caller>().cloned().ok_or(AuthError::Missing)?;
+        caller.request_id = request_id(&parts.headers);
+        Ok(caller)
+    }
+}
+
+/// The request's `X-Request-Id`, as recorded in the audit log.
+pub(crate) fn request_id(headers: &HeaderMap) -> String {
+    headers
+        .get("x-request-id")
+        .and_then(|v| v.to_str().ok())
+        .map_or_else(String::new, |id| id.chars().take(MAX_REQUEST_ID_LEN).collect())
+}
+
+/// Middleware: identify the caller from an API key or session token. What
+/// the caller may do is up to each handler.
+pub async fn authenticate(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, AuthError> {
+    // Login has to work without credentials; share links carry their own
+    let path = request.uri().path();
+    if path == "/auth/login" || path.starts_with("/share/") {
+        return Ok(next.run(request).await);
+    }
+    let token = presented_token(&request).ok_or(AuthError::Missing)?;
+    let caller = {
+        let conn = state.db.conn().map_err(|_| AuthError::Invalid)?;
+        if token.starts_with("tvs_") {
+            verify_session(&conn, &token)
+        } else {
+            verify_key(&conn, &token)
+        }
+        .ok_or(AuthError::Invalid)?
+    };
+    request.extensions_mut().insert(caller);
+    Ok(next.run(request).await)
+}
+
+/// Middleware used when authentication is disabled.
+pub async fn local_caller(mut request: Request, next: Next) -> Response {
+    request.extensions_mut().insert(Caller::local());
+    next.run(request).await
+}
+
+/// `Authorization: Bearer <token>`, or `X-API-Key: <key>` for clients that
+/// can't set the Authorization header.
+fn presented_token(request: &Request) -> Option<String> {
+    let headers = request.headers();
+    if let Some(value) = headers.get(header::AUTHORIZATION) {
+        let value = value.to_str().ok()?;
+        let (scheme, key) = value.split_once(' ')?;
+        return scheme.eq_ignore_ascii_case("bearer").then(|| key.trim().to_string());
+    }
+    headers
+        .get("x-api-key")
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().to_string())
+}
+
+/// 256 random bits as hex, from two v4 UUIDs.
+fn random_secret() -> String {
+    format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+fn token_hash(token: &str) -> String {
+    blake3::hash(token.as_bytes()).to_hex().to_string()
+}
+
+/// The `collections` column: a JSON array of names, NULL for all.
+pub(crate) fn collections_column(row: &rusqlite::Row, i: usize) -> rusqlite::Result<Option<Vec<String>>> {
+    let json: Option<String> = row.get(i)?;
+    json.map(|json| {
+        serde_json::from_str(&json).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(i, rusqlite::types::Type::Text, Box::new(e))
+        })
+    })
+    .transpose()
+}
+
+/// Trimmed, deduplicated and serialized for the `collections` column.
+pub(crate) fn collections_json(collections: Option<&[String]>) -> Result<Option<String>, AppError> {
+    let Some(collections) = collections else {
+        return Ok(None);
+    };
+    let mut names: Vec<&str> = Vec::new();
+    for name in collections {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(AppError::BadRequest("collection names must not be empty".into()));
+        }
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    Ok(Some(serde_json::to_string(&names).expect("string list serializes")))
+}
+
+/// Admins see everything whatever their list says.
+fn access_for(role: Role, collections: Option<Vec<String>>) -> CollectionAccess {
+    if role == Role::Admin {
+        CollectionAccess::All
+    } else {
+        CollectionAccess::from_list(collections)
+    }
+}
+
+// --- API keys ---
+
+/// Keys look like `tvl_<id>_<secret>`: the id finds the row, the whole key
+/// is checked against the stored BLAKE3 hash.
+fn split_key(key: &str) -> Option<&str> {
+    let rest = key.strip_prefix("tvl_")?;
+    let (id, secret) = rest.split_once('_')?;
+    (!id.is_empty() && !secret.is_empty()).then_some(id)
+}
+
+/// The caller behind a valid, unrevoked key.
+fn verify_key(conn: &rusqlite::Connection, key: &str) -> Option<Caller> {
+    let id = split_key(key)?;
+    let (name, key_hash, role, collections): (String, String, String, Option<Vec<String>>) = conn
+        .query_row(
+            "SELECT name, key_hash, role, collections FROM api_keys WHERE id = ? AND revoked_at IS NULL",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, collections_column(row, 3)?)),
+        )
+        .ok()?;
+    // blake3::Hash compares in constant time
+    let stored = blake3::Hash::from_hex(key_hash).ok()?;
+    if blake3::hash(key.as_bytes()) != stored {
+        return None;
+    }
+
+    // Coarse so a burst of thumbnail requests doesn't write on every one
+    let now = unix_now() as i64;
+    let _ = conn.execute(
+        "UPDATE api_keys SET last_used_at = ?1 WHERE id = ?2 AND (last_used_at IS NULL OR last_used_at < ?1 - 60)",
+        rusqlite::params![now, id],
+    );
+    let role = Role::parse(&role)?;
+    Some(Caller {
+        name,
+        role,
+        identity: Identity::ApiKey { id: id.to_string() },
+        collections: access_for(role, collections),
+        request_id: String::new(),
+    })
+}
+
+/// The current role and collection access of the account behind a
+/// `Caller::principal`, or `None` once it has been disabled, revoked or
+/// deleted.
+pub(crate) fn principal_access(
+    conn: &rusqlite::Connection,
+    principal: &str,
+) -> Result<Option<(Role, CollectionAccess)>, AppError> {
+    let (sql, id) = match principal.split_once(':') {
+        Some(("user", uuid)) => {
+            ("SELECT role, collections FROM users WHERE uuid = ? AND disabled_at IS NULL", uuid)
+        }
+        Some(("key", id)) => {
+            ("SELECT role, collections FROM api_keys WHERE id = ? AND revoked_at IS NULL", id)
+        }
+        _ if principal == "local" => return Ok(Some((Role::Admin, CollectionAccess::All))),
+        _ => return Ok(None),
+    };
+    let found: Option<(String, Option<Vec<String>>)> = match conn.query_row(sql, [id], |row| {
+        Ok((row.get(0)?, collections_column(row, 1)?))
+    }) {
+        Ok(found) => Some(found),
+        Err(rusqlite::Error::QueryReturnedNoRows) => None,
+        Err(e) => return Err(e.into()),
+    };
+    Ok(found.and_then(|(role, collections)| {
+        let role = Role::parse(&role)?;
+        Some((role, access_for(role, collections)))
+    }))
+}
+
+/// Create a key and return it in full. Only its hash is stored, so this is
+/// the one chance to see it.
+pub fn create_key(
+    conn: &rusqlite::Connection,
+    name: &str,
+    role: Role,
+    collections: Option<&[String]>,
+) -> Result<NewApiKey, AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::BadRequest("key name must not be empty".into()));
+    }
+    let collections = collections_json(collections)?;
+    let id = uuid::Uuid::new_v4().simple().to_string()[..8].to_string();
+    let key = format!("tvl_{id}_{}", random_secret());
+    conn.execute(
+        "INSERT INTO api_keys (id, name, key_hash, role, collections, created_at) VALUES (?, ?, ?, ?, ?, ?)",
+        rusqlite::params![id, name, token_hash(&key), role.name(), collections, unix_now() as i64],
+    )?;
+    Ok(NewApiKey {
+        key,
+        api_key: query_key(conn, &id)?,
+    })
+}
+
+const KEY_COLUMNS: &str = "id, name, role, collections, created_at, last_used_at, revoked_at";
+
+fn key_from_row(row: &rusqlite::Row) -> rusqlite::Result<ApiKey> {
+    Ok(ApiKey {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        role: row.get(2)?,
+        collections: collections_column(row, 3)?,
+        created_at: row.get::<_, i64>(4)? as u64,
+        last_used_at: row.get::<_, Option<i64>>(5)?.map(|t| t as u64),
+        revoked_at: row.get::<_, Option<i64>>(6)?.map(|t| t as u64),
+    })
+}
+
+pub fn list_keys(conn: &rusqlite::Connection) -> Result<Vec<ApiKey>, AppError> {
+    let mut stmt = conn.prepare(&format!("SELECT {KEY_COLUMNS} FROM api_keys ORDER BY created_at, id"))?;
+    let keys = stmt.query_map([], key_from_row)?.collect::<Result<_, _>>()?;
+    Ok(keys)
+}
+
+pub fn query_key(conn: &rusqlite::Connection, id: &str) -> Result<ApiKey, AppError> {
+    conn.query_row(&format!("SELECT {KEY_COLUMNS} FROM api_keys WHERE id = ?"), [id], key_from_row)
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => AppError::NotFound("API key not found".into()),
+            other => AppError::from(other),
+        })
+}
+
+/// Revoke a key. It stops working on the next request that presents it.
+pub fn revoke_key(conn: &rusqlite::Connection, id: &str) -> Result<ApiKey, AppError> {
+    let changed = conn.execute(
+        "UPDATE api_keys SET revoked_at = ? WHERE id = ? AND revoked_at IS NULL",
+        rusqlite::params![unix_now() as i64, id],
+    )?;
+    if changed == 0 {
+        return Err(AppError::NotFound(format!("No active key with id {id}")));
+    }
+    query_key(conn, id)
+}
+
+// --- Users and sessions ---
+
+fn hash_password(password: &str) -> Result<String, AppError> {
+    let salt = SaltString::encode_b64(uuid::Uuid::new_v4().as_bytes())
+        .map_err(|e| AppError::DbError(format!("Failed to build salt: {e}")))?;
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| AppError::DbError(format!("Failed to hash password: {e}")))
+}
+
+pub fn password_matches(stored: &str, password: &str) -> bool {
+    PasswordHash::new(stored)
+        .is_ok_and(|hash| Argon2::default().verify_password(password.as_bytes(), &hash).is_ok())
+}
+
+/// Check a new password and hash it. Hashing is slow by design, so handlers
+/// run this with `spawn_blocking` before taking the database lock.
+pub fn new_password_hash(password: &str) -> Result<String, AppError> {
+    if password.chars().count() < MIN_PASSWORD_LEN {
+        return Err(AppError::BadRequest(format!(
+            "password must be at least {MIN_PASSWORD_LEN} characters"
+        )));
+    }
+    hash_password(password)
+}
+
+const USER_COLUMNS: &str = "uuid, username, role, collections, created_at, disabled_at";
+
+fn user_from_row(row: &rusqlite::Row) -> rusqlite::Result<User> {
+    let role: String = row.get(2)?;
+    Ok(User {
+        uuid: row.get(0)?,
+        username: row.get(1)?,
+        role: Role::parse(&role).unwrap_or(Role::Viewer),
+        collections: collections_column(row, 3)?,
+        created_at: row.get::<_, i64>(4)? as u64,
+        disabled: row.get::<_, Option<i64>>(5)?.is_some(),
+    })
+}
+
+pub fn list_users(conn: &rusqlite::Connection) -> Result<Vec<User>, AppError> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {USER_COLUMNS} FROM users ORDER BY username COLLATE NOCASE"
+    ))?;
+    let users = stmt.query_map([], user_from_row)?.collect::<Result<_, _>>()?;
+    Ok(users)
+}
+
+pub fn query_user(conn: &rusqlite::Connection, uuid: &str) -> Result<User, AppError> {
+    conn.query_row(
+        &format!("SELECT {USER_COLUMNS} FROM users WHERE uuid = ?"),
+        [uuid],
+        user_from_row,
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => AppError::NotFound("User not found".into()),
+        other => AppError::from(other),
+    })
+}
+
+/// `password_hash` comes from [`new_password_hash`].
+pub fn create_user(
+    conn: &rusqlite::Connection,
+    username: &str,
+    password_hash: &str,
+    role: Role,
+    collections: Option<&[String]>,
+) -> Result<User, AppError> {
+    let username = username.trim();
+    if username.is_empty() || username.len() > 64 || username.chars().any(char::is_whitespace) {
+        return Err(AppError::BadRequest(
+            "username must be 1-64 characters without spaces".into(),
+        ));
+    }
+    let taken: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM users WHERE username = ?)",
+        [username],
+        |row| row.get(0),
+    )?;
+    if taken {
+        return Err(AppError::Conflict(format!("Username already taken: {username}")));
+    }
+    let collections = collections_json(collections)?;
+    let uuid = uuid::Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO users (uuid, username, password_hash, role, collections, created_at) VALUES (?, ?, ?, ?, ?, ?)",
+        rusqlite::params![
+            uuid,
+            username,
+            password_hash,
+            role.name(),
+            collections,
+            unix_now() as i64
+        ],
+    )?;
+    query_user(conn, &uuid)
+}
+
+/// Change a user's role, password or disabled state. A new password, already
+/// checked and hashed by [`new_password_hash`], or disabling the account ends
+/// the user's sessions.
+pub fn update_user(
+    conn: &rusqlite::Connection,
+    uuid: &str,
+    request: &UpdateUserRequest,
+    password_hash: Option<&str>,
+) -> Result<User, AppError> {
+    if request.role.is_none() && request.password.is_none() && request.disabled.is_none() {
+        return Err(AppError::BadRequest(
+            "set at least one of role, password or disabled".into(),
+        ));
+    }
+    query_user(conn, uuid)?;
+    if let Some(role) = request.role {
+        conn.execute(
+            "UPDATE users SET role = ? WHERE uuid = ?",
+            rusqlite::params![role.name(), uuid],
+        )?;
+    }
+    if let Some(password_hash) = password_hash {
+        conn.execute(
+            "UPDATE users SET password_hash = ? WHERE uuid = ?",
+            rusqlite::params![password_hash, uuid],
+        )?;
+    }
+    match request.disabled {
+        Some(true) => {
+            conn.execute(
+                "UPDATE users SET disabled_at = COALESCE(disabled_at, ?) WHERE uuid = ?",
+                rusqlite::params![unix_now() as i64, uuid],
+            )?;
+        }
+        Some(false) => {
+            conn.execute("UPDATE users SET disabled_at = NULL WHERE uuid = ?", [uuid])?;
+        }
+        None => {}
+    }
+    if password_hash.is_some() || request.disabled == Some(true) {
+        conn.execute("DELETE FROM sessions WHERE user_uuid = ?", [uuid])?;
+    }
+    query_user(conn, uuid)
+}
+
+/// Limit a user to some collections, or with `None` lift the limit. Takes
+/// effect on the user's next request.
+pub fn set_user_collections(
+    conn: &rusqlite::Connection,
+    uuid: &str,
+    collections: Option<&[String]>,
+) -> Result<User, AppError> {
+    query_user(conn, uuid)?;
+    conn.execute(
+        "UPDATE users SET collections = ? WHERE uuid = ?",
+        rusqlite::params![collections_json(collections)?, uuid],
+    )?;
+    query_user(conn, uuid)
+}
+
+/// The user and stored password hash for a login attempt. Checking the
+/// password is slow by design, so callers do it without holding the
+/// database lock. Unknown and disabled usernames get no user but a stand-in
+/// hash, so that checking against it takes as long and response times don't
+/// reveal which accounts exist.
+pub fn login_account(
+    conn: &rusqlite::Connection,
+    username: &str,
+) -> Result<(Option<String>, String), AppError> {
+    let account = conn.query_row(
+        "SELECT uuid, password_hash FROM users WHERE username = ? AND disabled_at IS NULL",
+        [username.trim()],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    );
+    match account {
+        Ok((uuid, hash)) => Ok((Some(uuid), hash)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok((None, stand_in_hash()?)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn stand_in_hash() -> Result<String, AppError> {
+    static HASH: OnceLock<String> = OnceLock::new();
+    if let Some(hash) = HASH.get() {
+        return Ok(hash.clone());
+    }
+    let hash = hash_password(&random_secret())?;
+    Ok(HASH.get_or_init(|| hash).clone())
+}
+
+/// Start a session for a user whose password checked out. The token is only
+/// stored hashed, so the response is the one chance to see it.
+pub fn start_session(conn: &rusqlite::Connection, user_uuid: &str) -> Result<LoginResponse, AppError> {
+    let now = unix_now();
+    let expires_at = now + SESSION_TTL_SECS;
+    let token = format!("tvs_{}", random_secret());
+    conn.execute("DELETE FROM sessions WHERE expires_at <= ?", [now as i64])?;
+    conn.execute(
+        "INSERT INTO sessions (token_hash, user_uuid, created_at, expires_at) VALUES (?, ?, ?, ?)",
+        rusqlite::params![token_hash(&token), user_uuid, now as i64, expires_at as i64],
+    )?;
+    Ok(LoginResponse {
+        token,
+        expires_at,
+        user: query_user(conn, user_uuid)?,
+    })
+}
+
+/// The caller behind an unexpired session of an enabled user.
+pub(crate) fn verify_session(conn: &rusqlite::Connection, token: &str) -> Option<Caller> {
+    let hash = token_hash(token);
+    let (uuid, username, role, collections): (String, String, String, Option<Vec<String>>) = conn
+        .query_row(
+            "SELECT u.uuid, u.username, u.role, u.collections FROM sessions s JOIN users u ON u.uuid = s.user_uuid
+             WHERE s.token_hash = ? AND s.expires_at > ? AND u.disabled_at IS NULL",
+            rusqlite::params![hash, unix_now() as i64],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, collections_column(row, 3)?)),
+        )
+        .ok()?;
+    let role = Role::parse(&role)?;
+    Some(Caller {
+        name: username,
+        role,
+        identity: Identity::User { uuid, session_hash: hash },
+        collections: access_for(role, collections),
+        request_id: String::new(),
+    })
+}
+
+/// End the caller's session. A no-op for API keys, which are revoked
+/// through `/keys` instead.
+pub fn logout(conn: &rusqlite::Connection, caller: &Caller) -> Result<(), AppError> {
+    if let Identity::User { session_hash, .. } = &caller.identity {
+        conn.execute("DELETE FROM sessions WHERE token_hash = ?", [session_hash])?;
+    }
+    Ok(())
+}
+
+// --- Command line ---
+
+const KEYS_USAGE: &str = "usage:
+  tivoli-server keys create <name> [--role viewer|tagger|admin] [--collections a,b]
+  tivoli-server keys list
+  tivoli-server keys revoke <id>
+default role: viewer; default collections: all";
+
+const USERS_USAGE: &str = "usage:
+  tivoli-server users create <username> [--role viewer|tagger|admin] [--collections a,b]
+  tivoli-server users list
+the password is read from stdin; default role: viewer; default collections: all";
+
+/// `--role` and `--collections`, in either order.
+fn parse_create_flags(mut rest: &[&str], usage: &str) -> Result<(Role, Option<Vec<String>>), String> {
+    let mut role = Role::Viewer;
+    let mut collections = None;
+    loop {
+        match rest {
+            [] => return Ok((role, collections)),
+            ["--role", value, tail @ ..] => {
+                role = Role::parse(value).ok_or_else(|| format!("unknown role '{value}'\n{usage}"))?;
+                rest = tail;
+            }
+            ["--collections", value, tail @ ..] => {
+                collections = Some(value.split(',').map(str::to_string).collect());
+                rest = tail;
+            }
+            _ => return Err(usage.into()),
+        }
+    }
+}
+
+fn describe_collections(collections: &Option<Vec<String>>) -> String {
+    collections.as_ref().map_or_else(|| "all collections".to_string(), |c| c.join(","))
+}
+
+/// `tivoli-server keys ...`: manage API keys in the database on disk. The
+/// server keeps the database in memory and writes it back on every change,
+/// so run these while it is stopped; it picks the keys up when it starts. A
+/// running server has the `/keys` routes instead.
+pub fn keys_command(db_path: &str, args: &[String]) -> Result<String, String> {
+    let conn = crate::db::open_on_disk(db_path).map_err(|e| format!("{db_path}: {e}"))?;
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["create", name, rest @ ..] => {
+            let (role, collections) = parse_create_flags(rest, KEYS_USAGE)?;
+            let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+            let created =
+                create_key(&conn, name, role, collections.as_deref()).map_err(|e| e.to_string())?;
+            let after = serde_json::to_value(&created.api_key).map_err(|e| e.to_string())?;
+            audit::record_cli(&conn, "api_key.create", Target::ApiKey(&created.api_key.id), Some(after))?;
+            tx.commit().map_err(|e| e.to_string())?;
+            Ok(format!("{}\n", created.key))
+        }
+        ["list"] => {
+            let mut out = String::new();
+            for key in list_keys(&conn).map_err(|e| e.to_string())? {
+                let status = match key.revoked_at {
+                    Some(_) => "revoked",
+                    None => "active",
+                };
+                let last_used = key.last_used_at.map_or_else(|| "never".to_string(), |t| t.to_string());
+                out.push_str(&format!(
+                    "{}\t{}\t{}\t{status}\t{}\tcreated: {}\tlast used: {last_used}\n",
+                    key.id,
+                    key.role,
+                    key.name,
+                    describe_collections(&key.collections),
+                    key.created_at
+                ));
+            }
+            Ok(out)
+        }
+        ["revoke", id] => {
+            let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+            revoke_key(&conn, id).map_err(|e| e.to_string())?;
+            audit::record_cli(&conn, "api_key.revoke", Target::ApiKey(id), None)?;
+            tx.commit().map_err(|e| e.to_string())?;
+            Ok(format!("revoked {id}\n"))
+        }
+        _ => Err(KEYS_USAGE.into()),
+    }
+}
+
+/// `tivoli-server users ...`: create the first admin, or any user, on disk.
+/// Once an admin exists the `/users` endpoints do the same on a running
+/// server. Same caveat as `keys`: stop the server first.
+pub fn users_command(db_path: &str, args: &[String], stdin: impl std::io::BufRead) -> Result<String, String> {
+    let conn = crate::db::open_on_disk(db_path).map_err(|e| format!("{db_path}: {e}"))?;
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    match args.as_slice() {
+        ["create", username, rest @ ..] => {
+            let (role, collections) = parse_create_flags(rest, USERS_USAGE)?;
+            let password = stdin
+                .lines()
+                .next()
+                .transpose()
+                .map_err(|e| e.to_string())?
+                .ok_or("no password on stdin")?;
+            let password_hash = new_password_hash(&password).map_err(|e| e.to_string())?;
+            let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+            let user = create_user(&conn, username, &password_hash, role, collections.as_deref())
+                .map_err(|e| e.to_string())?;
+            let after = serde_json::to_value(&user).map_err(|e| e.to_string())?;
+            audit::record_cli(&conn, "user.create", Target::User(&user.uuid), Some(after))?;
+            tx.commit().map_err(|e| e.to_string())?;
+            Ok(format!("{}\t{}\t{}\n", user.uuid, user.role.name(), user.username))
+        }
+        ["list"] => {
+            let mut out = String::new();
+            for user in list_users(&conn).map_err(|e| e.to_string())? {
+                let status = if user.disabled { "disabled" } else { "active" };
+                out.push_str(&format!(
+                    "{}\t{}\t{}\t{status}\t{}\tcreated: {}\n",
+                    user.uuid,
+                    user.role.name(),
+                    user.username,
+                    describe_collections(&user.collections),
+                    user.created_at
+                ));
+            }
+            Ok(out)
+        }
+        _ => Err(USERS_USAGE.into()),
+    }
+}
@@ -0,0 +1,141 @@
+use std::path::PathBuf;
+
+use crate::models::Role;
+use crate::watermark::{WatermarkConfig, WatermarkOverlay};
+
+/// Server configuration. `Config::new` gives library defaults; `Config::from_env`
+/// layers the `TIVOLI_*` environment variables on top. Most invalid values
+/// are logged and ignored; the ones that would weaken protection fail instead.
+#[derive(Clone)]
+pub struct Config {
+    pub db_path: String,
+    pub galleries_dir: String,
+    /// Defaults to `.thumbnails` inside the galleries directory.
+    pub thumbnail_cache_dir: Option<PathBuf>,
+    pub thumbnail_cache_max_bytes: u64,
+    /// Maximum number of thumbnails decoded at once.
+    pub thumbnail_workers: usize,
+    /// Widths rendered by the pre-generation job when a run doesn't name any.
+    pub pregenerate_widths: Vec<u32>,
+    /// Start a pre-generation run over the whole library at startup.
+    pub pregenerate_on_startup: bool,
+    /// Share of wall-clock time the pre-generation job may spend rendering.
+    pub pregenerate_cpu_percent: u32,
+    /// Process images with missing derived data (placeholders etc.) at startup.
+    pub ingest_on_startup: bool,
+    /// JSON file of rules mapping XMP keywords onto tags during ingest.
+    pub keyword_rules_path: Option<PathBuf>,
+    /// Serve every route without an API key. For local development only.
+    pub auth_disabled: bool,
+    /// Overlay for watermarked responses: share links that ask for it and
+    /// callers with one of `watermark_roles`.
+    pub watermark: Option<WatermarkConfig>,
+    /// Roles that only ever get watermarked images.
+    pub watermark_roles: Vec<Role>,
+}
+
+impl Config {
+    pub fn new(db_path: &str, galleries_dir: &str) -> Self {
+        Config {
+            db_path: db_path.to_string(),
+            galleries_dir: galleries_dir.to_string(),
+            thumbnail_cache_dir: None,
+            thumbnail_cache_max_bytes: 1024 * 1024 * 1024,
+            thumbnail_workers: default_thumbnail_workers(),
+            pregenerate_widths: vec![400],
+            pregenerate_on_startup: false,
+            pregenerate_cpu_percent: 25,
+            ingest_on_startup: false,
+            keyword_rules_path: None,
+            auth_disabled: false,
+            watermark: None,
+            watermark_roles: Vec::new(),
+        }
+    }
+
+    pub fn from_env() -> Result<Self, String> {
+        let db_path = std::env::var("TIVOLI_DB_PATH")
+            .unwrap_or_else(|_| "../data/tivoli.db".to_string());
+        let galleries_dir = std::env::var("TIVOLI_GALLERIES_PATH")
+            .unwrap_or_else(|_| "../galleries".to_string());
+
+        let mut config = Config::new(&db_path, &galleries_dir);
+        if let Ok(dir) = std::env::var("TIVOLI_THUMBNAIL_CACHE_PATH") {
+            config.thumbnail_cache_dir = Some(PathBuf::from(dir));
+        }
+        if let Some(mb) = env_parse::<u64>("TIVOLI_THUMBNAIL_CACHE_MAX_MB") {
+            config.thumbnail_cache_max_bytes = mb * 1024 * 1024;
+        }
+        if let Some(workers) = env_parse::<usize>("TIVOLI_THUMBNAIL_WORKERS") {
+            config.thumbnail_workers = workers.max(1);
+        }
+        if let Ok(raw) = std::env::var("TIVOLI_PREGENERATE_WIDTHS") {
+            match raw.split(',').map(|w| w.trim().parse()).collect() {
+                Ok(widths) => config.pregenerate_widths = widths,
+                Err(_) => tracing::warn!("Ignoring invalid value for TIVOLI_PREGENERATE_WIDTHS: {raw}"),
+            }
+        }
+        if let Some(on) = env_parse::<bool>("TIVOLI_PREGENERATE_ON_STARTUP") {
+            config.pregenerate_on_startup = on;
+        }
+        if let Some(percent) = env_parse::<u32>("TIVOLI_PREGENERATE_CPU_PERCENT") {
+            config.pregenerate_cpu_percent = percent;
+        }
+        if let Some(on) = env_parse::<bool>("TIVOLI_INGEST_ON_STARTUP") {
+            config.ingest_on_startup = on;
+        }
+        if let Ok(path) = std::env::var("TIVOLI_KEYWORD_RULES") {
+            config.keyword_rules_path = Some(PathBuf::from(path));
+        }
+        if let Some(off) = env_parse::<bool>("TIVOLI_AUTH_DISABLED") {
+            config.auth_disabled = off;
+        }
+
+        let overlay = match (std::env::var("TIVOLI_WATERMARK_IMAGE"), std::env::var("TIVOLI_WATERMARK_TEXT")) {
+            (Ok(path), _) => Some(WatermarkOverlay::Image(PathBuf::from(path))),
+            (Err(_), Ok(text)) => Some(WatermarkOverlay::Text(text)),
+            _ => None,
+        };
+        if let Some(overlay) = overlay {
+            let mut watermark = WatermarkConfig::new(overlay);
+            if let Some(position) = env_parse("TIVOLI_WATERMARK_POSITION") {
+                watermark.position = position;
+            }
+            if let Some(opacity) = env_parse("TIVOLI_WATERMARK_OPACITY") {
+                watermark.opacity = opacity;
+            }
+            if let Some(scale) = env_parse("TIVOLI_WATERMARK_SCALE") {
+                watermark.scale = scale;
+            }
+            config.watermark = Some(watermark);
+        }
+        // Dropping a misspelt role would serve clean originals to it
+        if let Ok(raw) = std::env::var("TIVOLI_WATERMARK_ROLES") {
+            config.watermark_roles = raw
+                .split(',')
+                .map(str::trim)
+                .filter(|r| !r.is_empty())
+                .map(|r| {
+                    Role::parse(r).ok_or_else(|| format!("TIVOLI_WATERMARK_ROLES: unknown role '{r}'"))
+                })
+                .collect::<Result<_, _>>()?;
+        }
+        Ok(config)
+    }
+}
+
+/// Half the available cores, so a cold gallery leaves room for everything else.
+fn default_thumbnail_workers() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| (n.get() / 2).max(1))
+}
+
+fn env_parse<T: std::str::FromStr>(name: &str) -> Option<T> {
+    let raw = std::env::var(name).ok()?;
+    match raw.trim().parse() {
+        Ok(v) => Some(v),
+        Err(_) => {
+            tracing::warn!("Ignoring invalid value for {name}: {raw}");
+            None
+        }
+    }
+}
@@ -29,6 +29,9 @@ impl InMemoryDb {
             .execute_batch("PRAGMA cache_size = -64000;")
             .expect("Failed to set pragmas");
 
+        migrate(&mem_conn).expect("Failed to migrate database schema");
+        register_functions(&mem_conn).expect("Failed to register SQL functions");
+
         tracing::info!(
             "Loaded database into memory from {}",
             disk_path.display()
@@ -54,7 +57,7 @@ impl InMemoryDb {
         let mut disk_conn =
             Connection::open(&self.disk_path).map_err(|e| format!("Failed to open disk DB: {e}"))?;
 
-        let backup = rusqlite::backup::Backup::new(&*mem_conn, &mut disk_conn)
+        let backup = rusqlite::backup::Backup::new(&mem_conn, &mut disk_conn)
             .map_err(|e| format!("Failed to init backup: {e}"))?;
         backup
             .run_to_completion(5000, std::time::Duration::ZERO, None)
@@ -73,3 +76,399 @@ impl Clone for InMemoryDb {
         }
     }
 }
+
+/// Open the database file itself, migrated, for offline tools such as key
+/// management. Don't use while a server is running on the same file: its
+/// next flush overwrites whatever was written here.
+pub fn open_on_disk(path: &str) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(path)?;
+    migrate(&conn)?;
+    Ok(conn)
+}
+
+/// SQL functions the query layer relies on.
+fn register_functions(conn: &Connection) -> rusqlite::Result<()> {
+    use rusqlite::functions::FunctionFlags;
+
+    // hamming(a, b): differing bits between two perceptual hashes
+    conn.create_scalar_function(
+        "hamming",
+        2,
+        FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC,
+        |ctx| {
+            let a: Option<i64> = ctx.get(0)?;
+            let b: Option<i64> = ctx.get(1)?;
+            Ok(a.zip(b).map(|(a, b)| (a ^ b).count_ones()))
+        },
+    )
+}
+
+/// Bring a database created by the ingest scripts up to the schema the server
+/// expects. Every step is idempotent, so this runs on each load; changes reach
+/// disk with the next flush.
+fn migrate(conn: &Connection) -> rusqlite::Result<()> {
+    // Placeholders shown while thumbnails load
+    add_column(conn, "images", "blurhash", "TEXT")?;
+    add_column(conn, "images", "dominant_color", "TEXT")?;
+
+    // EXIF/IPTC/XMP metadata, one row per ingested image
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS image_metadata (
+            image_uuid TEXT PRIMARY KEY REFERENCES images(uuid),
+            camera_make TEXT,
+            camera_model TEXT,
+            lens TEXT,
+            focal_length REAL,
+            aperture REAL,
+            exposure_time REAL,
+            iso INTEGER,
+            captured_at TEXT,
+            copyright TEXT,
+            caption TEXT
+        );
+        CREATE INDEX IF NOT EXISTS idx_image_metadata_captured_at ON image_metadata(captured_at);
+        CREATE INDEX IF NOT EXISTS idx_image_metadata_lens ON image_metadata(lens);",
+    )?;
+
+    // Capture time used for the timeline: EXIF when present, file mtime otherwise
+    add_column(conn, "images", "taken_at", "TEXT")?;
+    conn.execute_batch("CREATE INDEX IF NOT EXISTS idx_images_taken_at ON images(taken_at);")?;
+
+    // 64-bit perceptual hash (dHash) for duplicate detection, stored as i64 bits
+    add_column(conn, "images", "phash", "INTEGER")?;
+
+    // BLAKE3 of the original plus the mtime it had when hashed, so moved files
+    // can be recognised and changed ones reported
+    add_column(conn, "images", "content_hash", "TEXT")?;
+    add_column(conn, "images", "file_mtime", "INTEGER")?;
+    conn.execute_batch("CREATE INDEX IF NOT EXISTS idx_images_content_hash ON images(content_hash);")?;
+
+    // Dominant palette, largest share first, with CIELAB coordinates for
+    // perceptual colour search
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS image_palette (
+            image_uuid TEXT NOT NULL REFERENCES images(uuid),
+            position INTEGER NOT NULL,
+            color TEXT NOT NULL,
+            weight REAL NOT NULL,
+            lab_l REAL NOT NULL,
+            lab_a REAL NOT NULL,
+            lab_b REAL NOT NULL,
+            PRIMARY KEY (image_uuid, position)
+        );",
+    )?;
+    // Set once the palette has been computed, since some images have none
+    if !has_column(conn, "images", "palette_analyzed")? {
+        add_column(conn, "images", "palette_analyzed", "INTEGER NOT NULL DEFAULT 0")?;
+        conn.execute_batch(
+            "UPDATE images SET palette_analyzed = 1 WHERE uuid IN (SELECT image_uuid FROM image_palette);",
+        )?;
+    }
+
+    // Culling: star rating, colour label and pick/reject flag
+    add_column(conn, "images", "rating", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column(conn, "images", "color_label", "TEXT NOT NULL DEFAULT 'none'")?;
+    add_column(conn, "images", "flag", "TEXT NOT NULL DEFAULT 'none'")?;
+
+    // Review comments, threaded through parent_uuid
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS comments (
+            uuid TEXT PRIMARY KEY,
+            image_uuid TEXT NOT NULL REFERENCES images(uuid),
+            parent_uuid TEXT REFERENCES comments(uuid),
+            author TEXT NOT NULL,
+            body TEXT NOT NULL,
+            region_x REAL,
+            region_y REAL,
+            region_width REAL,
+            region_height REAL,
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER
+        );
+        CREATE INDEX IF NOT EXISTS idx_comments_image ON comments(image_uuid);",
+    )?;
+    // Who wrote a comment, as the caller's principal; NULL for comments from
+    // before there were logins, which only admins may change
+    add_column(conn, "comments", "author_id", "TEXT")?;
+
+    // Albums: hand-picked, ordered selections independent of the folder layout
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS albums (
+            uuid TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            description TEXT,
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS album_images (
+            album_uuid TEXT NOT NULL REFERENCES albums(uuid),
+            image_uuid TEXT NOT NULL REFERENCES images(uuid),
+            position INTEGER NOT NULL,
+            PRIMARY KEY (album_uuid, image_uuid)
+        );
+        CREATE INDEX IF NOT EXISTS idx_album_images_image ON album_images(image_uuid);",
+    )?;
+
+    // Saved searches: filter and sort payloads stored as JSON
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS saved_searches (
+            uuid TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            filters TEXT NOT NULL,
+            sort TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL
+        );",
+    )?;
+
+    // API keys, stored as BLAKE3 hashes of the full key
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS api_keys (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            key_hash TEXT NOT NULL,
+            role TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            last_used_at INTEGER,
+            revoked_at INTEGER
+        );",
+    )?;
+    // Keys created before roles carried a read/write/admin scope
+    if has_column(conn, "api_keys", "scope")? {
+        conn.execute_batch(
+            "ALTER TABLE api_keys RENAME COLUMN scope TO role;
+            UPDATE api_keys SET role = CASE role WHEN 'read' THEN 'viewer' WHEN 'write' THEN 'tagger' ELSE role END;",
+        )?;
+    }
+
+    // Users log in with a password (argon2 PHC string) for a session token;
+    // only the BLAKE3 hash of each token is stored
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS users (
+            uuid TEXT PRIMARY KEY,
+            username TEXT NOT NULL UNIQUE COLLATE NOCASE,
+            password_hash TEXT NOT NULL,
+            role TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            disabled_at INTEGER
+        );
+        CREATE TABLE IF NOT EXISTS sessions (
+            token_hash TEXT PRIMARY KEY,
+            user_uuid TEXT NOT NULL REFERENCES users(uuid),
+            created_at INTEGER NOT NULL,
+            expires_at INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_sessions_user ON sessions(user_uuid);",
+    )?;
+
+    // Per-collection access: a JSON array of collection names, NULL for all
+    add_column(conn, "users", "collections", "TEXT")?;
+    add_column(conn, "api_keys", "collections", "TEXT")?;
+
+    // Server-wide settings; the share link signing key is generated once
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS settings (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL
+        );",
+    )?;
+    conn.execute(
+        "INSERT OR IGNORE INTO settings (key, value) VALUES ('share_signing_key', ?)",
+        [format!("{}{}", uuid::Uuid::new_v4().simple(), uuid::Uuid::new_v4().simple())],
+    )?;
+
+    // Share links: the target as JSON, plus the creator's collection access
+    // at the time so a link never shows more than its creator could see
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS share_links (
+            id TEXT PRIMARY KEY,
+            target TEXT NOT NULL,
+            collections TEXT,
+            allow_download INTEGER NOT NULL,
+            watermark INTEGER NOT NULL,
+            created_by TEXT NOT NULL,
+            created_by_principal TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            expires_at INTEGER NOT NULL,
+            revoked_at INTEGER
+        );",
+    )?;
+
+    // Audit log of every change: who, when, in which request, and the
+    // affected record before and after as JSON
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS audit_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            at INTEGER NOT NULL,
+            request_id TEXT NOT NULL,
+            actor TEXT NOT NULL,
+            actor_id TEXT NOT NULL,
+            action TEXT NOT NULL,
+            target_kind TEXT NOT NULL,
+            target_id TEXT,
+            image_uuid TEXT,
+            before TEXT,
+            after TEXT
+        );
+        CREATE INDEX IF NOT EXISTS idx_audit_log_at ON audit_log(at);
+        CREATE INDEX IF NOT EXISTS idx_audit_log_image ON audit_log(image_uuid);
+        CREATE INDEX IF NOT EXISTS idx_audit_log_actor ON audit_log(actor_id);",
+    )?;
+
+    // Images whose original verification found missing, so that it is
+    // announced once rather than on every run
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS missing_originals (
+            image_uuid TEXT PRIMARY KEY REFERENCES images(uuid),
+            since INTEGER NOT NULL
+        );",
+    )?;
+
+    // Tag and culling edits, one row per request, for undo and redo. The
+    // audit entries of an operation point back at it; state is done, undone,
+    // or discarded once a newer edit makes it impossible to redo
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS edit_operations (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            actor_id TEXT NOT NULL,
+            request_id TEXT NOT NULL,
+            at INTEGER NOT NULL,
+            state TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_edit_operations_actor ON edit_operations(actor_id, state);",
+    )?;
+    add_column(conn, "audit_log", "operation_id", "INTEGER")?;
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS idx_audit_log_operation ON audit_log(operation_id)",
+    )?;
+
+    // Change feed events; the id is the SSE event id clients resume from.
+    // collections is a JSON array of those the event concerns, NULL for all
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS change_events (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            at INTEGER NOT NULL,
+            kind TEXT NOT NULL,
+            collections TEXT,
+            data TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_change_events_at ON change_events(at);",
+    )?;
+
+    // Delta sync: every insert or update in a synced table stamps the row
+    // with the next value of one counter, and every delete leaves a
+    // tombstone under it. Triggers, so no write path can forget.
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS sync_state (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            seq INTEGER NOT NULL,
+            pruned_seq INTEGER NOT NULL
+        );
+        INSERT OR IGNORE INTO sync_state (id, seq, pruned_seq) VALUES (1, 1, 0);
+        CREATE TABLE IF NOT EXISTS sync_tombstones (
+            seq INTEGER PRIMARY KEY,
+            at INTEGER NOT NULL,
+            table_name TEXT NOT NULL,
+            key TEXT NOT NULL,
+            collection TEXT
+        );",
+    )?;
+    // When a tag was put on an image, for telling offline edits apart from
+    // real changes: sync_seq and sync_at also move when the image changes
+    // collection. Pairs from before these existed count as added before any
+    // sync point a client could hold
+    if !has_column(conn, "image_tags", "added_seq")? {
+        add_column(conn, "image_tags", "added_seq", "INTEGER NOT NULL DEFAULT 1")?;
+        add_column(conn, "image_tags", "added_at", "INTEGER")?;
+        conn.execute_batch("DROP TRIGGER IF EXISTS image_tags_sync_insert;")?;
+    }
+    for (table, key, collection) in SYNCED_TABLES {
+        // Rows from before sync existed all start at 1
+        add_column(conn, table, "sync_seq", "INTEGER NOT NULL DEFAULT 1")?;
+        // When the row last changed, for last-writer-wins on offline edits.
+        // Triggers from before it was stamped are replaced
+        if !has_column(conn, table, "sync_at")? {
+            add_column(conn, table, "sync_at", "INTEGER")?;
+            conn.execute_batch(&format!(
+                "DROP TRIGGER IF EXISTS {table}_sync_insert;
+                DROP TRIGGER IF EXISTS {table}_sync_update;"
+            ))?;
+        }
+        let stamp = |extra: &str| {
+            format!(
+                "UPDATE sync_state SET seq = seq + 1;
+                UPDATE {table} SET sync_seq = (SELECT seq FROM sync_state), sync_at = unixepoch(){extra}
+                WHERE rowid = NEW.rowid;"
+            )
+        };
+        let added = match table {
+            "image_tags" => ", added_seq = (SELECT seq FROM sync_state), added_at = unixepoch()",
+            _ => "",
+        };
+        let (insert_stamp, stamp) = (stamp(added), stamp(""));
+        conn.execute_batch(&format!(
+            "CREATE INDEX IF NOT EXISTS idx_{table}_sync_seq ON {table}(sync_seq);
+            CREATE TRIGGER IF NOT EXISTS {table}_sync_insert AFTER INSERT ON {table}
+            BEGIN {insert_stamp} END;
+            CREATE TRIGGER IF NOT EXISTS {table}_sync_update AFTER UPDATE ON {table}
+            WHEN NEW.sync_seq IS OLD.sync_seq
+            BEGIN {stamp} END;
+            CREATE TRIGGER IF NOT EXISTS {table}_sync_delete AFTER DELETE ON {table}
+            BEGIN
+                UPDATE sync_state SET seq = seq + 1;
+                INSERT INTO sync_tombstones (seq, at, table_name, key, collection)
+                VALUES ((SELECT seq FROM sync_state), unixepoch(), '{table}', {key}, {collection});
+            END;"
+        ))?;
+    }
+    // An image moved out of a collection is gone as far as clients limited
+    // to that collection know, and one moved in should arrive with its tags
+    // and models
+    conn.execute_batch(
+        "CREATE TRIGGER IF NOT EXISTS images_sync_move AFTER UPDATE OF collection ON images
+        WHEN NEW.collection IS NOT OLD.collection
+        BEGIN
+            UPDATE sync_state SET seq = seq + 1;
+            INSERT INTO sync_tombstones (seq, at, table_name, key, collection)
+            VALUES ((SELECT seq FROM sync_state), unixepoch(), 'images', json_object('uuid', OLD.uuid),
+                    OLD.collection);
+            UPDATE image_tags SET image_uuid = image_uuid WHERE image_uuid = NEW.uuid;
+            UPDATE image_models SET image_uuid = image_uuid WHERE image_uuid = NEW.uuid;
+        END;",
+    )?;
+    Ok(())
+}
+
+/// Tables clients mirror through `GET /sync`, with the SQL for a deleted
+/// row's key and collection.
+const SYNCED_TABLES: [(&str, &str, &str); 6] = [
+    ("images", "json_object('uuid', OLD.uuid)", "OLD.collection"),
+    ("tag_groups", "json_object('uuid', OLD.uuid)", "NULL"),
+    ("tags", "json_object('uuid', OLD.uuid)", "NULL"),
+    ("models", "json_object('uuid', OLD.uuid)", "OLD.collection"),
+    (
+        "image_tags",
+        "json_object('image_uuid', OLD.image_uuid, 'tag_uuid', OLD.tag_uuid)",
+        "(SELECT collection FROM images WHERE uuid = OLD.image_uuid)",
+    ),
+    (
+        "image_models",
+        "json_object('image_uuid', OLD.image_uuid, 'model_uuid', OLD.model_uuid)",
+        "(SELECT collection FROM images WHERE uuid = OLD.image_uuid)",
+    ),
+];
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> rusqlite::Result<bool> {
+    conn.query_row(
+        &format!("SELECT EXISTS(SELECT 1 FROM pragma_table_info('{table}') WHERE name = ?)"),
+        [column],
+        |row| row.get(0),
+    )
+}
+
+fn add_column(conn: &Connection, table: &str, column: &str, decl: &str) -> rusqlite::Result<()> {
+    if !has_column(conn, table, column)? {
+        conn.execute_batch(&format!("ALTER TABLE {table} ADD COLUMN {column} {decl}"))?;
+    }
+    Ok(())
+}
@@ -1,21 +1,46 @@
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 
+use crate::auth::AuthError;
+
+#[derive(Clone)]
 pub enum AppError {
     NotFound(String),
     DbError(String),
     BadRequest(String),
+    Conflict(String),
+    Forbidden(String),
+    Gone(String),
+    Auth(AuthError),
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppError::NotFound(msg)
+            | AppError::DbError(msg)
+            | AppError::BadRequest(msg)
+            | AppError::Conflict(msg)
+            | AppError::Forbidden(msg)
+            | AppError::Gone(msg) => f.write_str(msg),
+            AppError::Auth(e) => e.fmt(f),
+        }
+    }
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
         let (status, message) = match self {
+            AppError::Auth(e) => return e.into_response(),
             AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
             AppError::DbError(msg) => {
                 tracing::error!("Database error: {msg}");
                 (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".into())
             }
             AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
+            AppError::Conflict(msg) => (StatusCode::CONFLICT, msg),
+            AppError::Forbidden(msg) => (StatusCode::FORBIDDEN, msg),
+            AppError::Gone(msg) => (StatusCode::GONE, msg),
         };
         (status, axum::Json(serde_json::json!({ "error": message }))).into_response()
     }
@@ -0,0 +1,216 @@
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::sync::Arc;
+
+use axum::response::sse::Event;
+use futures_util::stream::{self, Stream};
+use serde_json::{json, Value};
+use tokio::sync::watch;
+
+use crate::errors::AppError;
+use crate::handlers::AppState;
+use crate::models::{CollectionAccess, TagRef};
+use crate::queries;
+use crate::unix_now;
+
+/// How long events are kept for clients to resume from.
+const RETENTION_SECS: u64 = 7 * 24 * 60 * 60;
+/// Events read from the database per query while catching up.
+const BATCH: i64 = 200;
+
+/// Wakes live subscribers when new events have been stored. Events are
+/// written by the queries that make the changes, under the same lock; this
+/// only tells streams to go and read them.
+pub struct ChangeFeed {
+    wake: watch::Sender<()>,
+}
+
+impl Default for ChangeFeed {
+    fn default() -> Self {
+        ChangeFeed { wake: watch::channel(()).0 }
+    }
+}
+
+impl ChangeFeed {
+    pub fn wake(&self) {
+        self.wake.send_replace(());
+    }
+}
+
+// --- Recording ---
+
+/// Store an event. `collections` are those whose images it concerns; events
+/// with none, such as tag renames, go to every subscriber.
+pub fn emit(
+    conn: &rusqlite::Connection,
+    kind: &str,
+    collections: &[&str],
+    data: Value,
+) -> Result<(), AppError> {
+    let collections = match collections {
+        [] => None,
+        names => Some(serde_json::to_string(names).expect("collection names serialize")),
+    };
+    conn.execute(
+        "INSERT INTO change_events (at, kind, collections, data) VALUES (?, ?, ?, ?)",
+        rusqlite::params![unix_now() as i64, kind, collections, data.to_string()],
+    )?;
+    Ok(())
+}
+
+/// An `image.tags` event with the image's tags as they are now.
+pub fn image_tags_changed(conn: &rusqlite::Connection, image_uuid: &str) -> Result<(), AppError> {
+    let (collection, gallery): (String, String) = conn.query_row(
+        "SELECT collection, gallery FROM images WHERE uuid = ?",
+        [image_uuid],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    let tags: Vec<TagRef> = queries::query_tags_of_image(conn, image_uuid)?;
+    let data = json!({
+        "image_uuid": image_uuid,
+        "collection": collection,
+        "gallery": gallery,
+        "tags": tags,
+    });
+    emit(conn, "image.tags", &[&collection], data)
+}
+
+/// Note whether an image's original is on disk. When it goes missing an
+/// `image.missing` event is sent, once: nothing more until the file has been
+/// seen again. The image itself stays, so a later ingest can find the file.
+/// Returns whether an event was sent.
+pub fn original_missing(
+    conn: &rusqlite::Connection,
+    image_uuid: &str,
+    missing: bool,
+) -> Result<bool, AppError> {
+    if !missing {
+        conn.execute("DELETE FROM missing_originals WHERE image_uuid = ?", [image_uuid])?;
+        return Ok(false);
+    }
+    let noted = conn.execute(
+        "INSERT OR IGNORE INTO missing_originals (image_uuid, since) VALUES (?, ?)",
+        rusqlite::params![image_uuid, unix_now() as i64],
+    )?;
+    if noted == 0 {
+        return Ok(false);
+    }
+    let (path, collection, gallery): (String, String, String) = conn.query_row(
+        "SELECT path, collection, gallery FROM images WHERE uuid = ?",
+        [image_uuid],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+    let data = json!({
+        "image_uuid": image_uuid,
+        "path": path,
+        "collection": collection,
+        "gallery": gallery,
+    });
+    emit(conn, "image.missing", &[&collection], data)?;
+    Ok(true)
+}
+
+/// Drop events too old to resume from. Run periodically.
+pub fn prune(conn: &rusqlite::Connection) -> Result<(), AppError> {
+    let cutoff = unix_now().saturating_sub(RETENTION_SECS);
+    conn.execute("DELETE FROM change_events WHERE at < ?", [cutoff as i64])?;
+    Ok(())
+}
+
+/// The id of the newest event, where a client with no `Last-Event-ID` starts.
+pub fn latest_id(conn: &rusqlite::Connection) -> Result<i64, AppError> {
+    Ok(conn.query_row("SELECT COALESCE(MAX(id), 0) FROM change_events", [], |row| row.get(0))?)
+}
+
+// --- Streaming ---
+
+struct Subscriber {
+    state: Arc<AppState>,
+    wake: watch::Receiver<()>,
+    access: CollectionAccess,
+    last_id: i64,
+    pending: VecDeque<Event>,
+}
+
+/// Events after `last_id` the subscriber may see, then live ones as they
+/// happen. If events the client missed have been pruned, it first gets a
+/// `reset` event: it should reload everything and carry on from there.
+pub fn subscribe(
+    state: Arc<AppState>,
+    access: CollectionAccess,
+    last_id: i64,
+) -> Result<impl Stream<Item = Result<Event, Infallible>>, AppError> {
+    // Subscribe before the first read, so nothing stored in between is missed
+    let wake = state.feed.wake.subscribe();
+    let mut pending = VecDeque::new();
+    {
+        let conn = state.db.conn()?;
+        let oldest: Option<i64> =
+            conn.query_row("SELECT MIN(id) FROM change_events", [], |row| row.get(0))?;
+        if oldest.is_some_and(|oldest| last_id + 1 < oldest) {
+            pending.push_back(Event::default().event("reset").data("{}"));
+        }
+    }
+    let subscriber = Subscriber {
+        state,
+        wake,
+        access,
+        last_id,
+        pending,
+    };
+    Ok(stream::unfold(subscriber, |mut subscriber| async move {
+        loop {
+            if let Some(event) = subscriber.pending.pop_front() {
+                return Some((Ok(event), subscriber));
+            }
+            match subscriber.read() {
+                Ok(true) => continue,
+                Ok(false) => {}
+                Err(e) => {
+                    tracing::warn!("Change feed: {e}");
+                    return None;
+                }
+            }
+            // The feed lives as long as the server; it going away ends the stream
+            subscriber.wake.changed().await.ok()?;
+        }
+    }))
+}
+
+impl Subscriber {
+    /// Queue the next batch of visible events; false once caught up.
+    fn read(&mut self) -> Result<bool, AppError> {
+        let conn = self.state.db.conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, at, kind, collections, data FROM change_events
+             WHERE id > ? ORDER BY id LIMIT ?",
+        )?;
+        let rows = stmt
+            .query_map(rusqlite::params![self.last_id, BATCH], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i64>(1)? as u64,
+                    row.get::<_, String>(2)?,
+                    crate::auth::collections_column(row, 3)?,
+                    row.get::<_, String>(4)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        let Some((newest, ..)) = rows.last() else {
+            return Ok(false);
+        };
+        self.last_id = *newest;
+        for (id, at, kind, collections, data) in rows {
+            let visible = collections.is_none_or(|names| names.iter().any(|n| self.access.allows(n)));
+            if !visible {
+                continue;
+            }
+            let mut data: Value = serde_json::from_str(&data)
+                .map_err(|e| AppError::DbError(format!("Bad change event: {e}")))?;
+            data["at"] = json!(at);
+            self.pending
+                .push_back(Event::default().id(id.to_string()).event(kind).data(data.to_string()));
+        }
+        Ok(true)
+    }
+}
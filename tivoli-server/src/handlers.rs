@@ -1,129 +1,182 @@
 use std::sync::Arc;
 
+use axum::body::Bytes;
 use axum::extract::{Path, Query, State};
+use axum::response::sse::{KeepAlive, Sse};
 use axum::response::IntoResponse;
 use axum::Json;
+use serde_json::json;
 
+use crate::analysis;
+use crate::audit::{self, Target};
+use crate::auth::{self, Caller};
 use crate::db::InMemoryDb;
 use crate::errors::AppError;
+use crate::events::{self, ChangeFeed};
+use crate::history::{self, Edit};
 use crate::models::*;
 use crate::queries;
+use crate::shares;
+use crate::ingest::Ingestor;
+use crate::pregenerate::Pregenerator;
+use crate::sidecars::SidecarExporter;
+use crate::sync;
+use crate::verify::Verifier;
+use crate::thumbnails::{self, SourceFingerprint, ThumbnailCache};
+use crate::unix_now;
+use crate::watermark::Watermark;
 
 pub struct AppState {
     pub db: InMemoryDb,
     pub galleries_path: std::path::PathBuf,
-    pub thumbnail_cache_dir: std::path::PathBuf,
+    pub thumbnails: Arc<ThumbnailCache>,
+    pub pregenerator: Pregenerator,
+    pub ingestor: Ingestor,
+    pub sidecars: SidecarExporter,
+    pub verifier: Verifier,
+    pub watermark: Option<Arc<Watermark>>,
+    pub watermark_roles: Vec<Role>,
+    pub feed: ChangeFeed,
+}
+
+/// Resolve an image's stored relative path to the file on disk, refusing
+/// anything that escapes the galleries directory.
+pub fn resolve_original(
+    galleries_path: &std::path::Path,
+    relative: &str,
+) -> Result<std::path::PathBuf, AppError> {
+    let canonical = galleries_path
+        .join(relative)
+        .canonicalize()
+        .map_err(|_| AppError::NotFound("File not found".into()))?;
+
+    if !canonical.starts_with(galleries_path) {
+        return Err(AppError::BadRequest("Invalid path".into()));
+    }
+    Ok(canonical)
 }
 
 pub async fn search_images(
+    caller: Caller,
     State(state): State<Arc<AppState>>,
     Json(request): Json<SearchRequest>,
 ) -> Result<Json<Vec<ImageRow>>, AppError> {
-    let (sql, params) = queries::build_image_query(&request.filters)?;
+    caller.require(Role::Viewer)?;
     let conn = state.db.conn()?;
+    let (sql, params) =
+        queries::build_image_query(&conn, &request.filters, &request.sort, &caller.collections)?;
     let images = queries::query_images(&conn, &sql, &params)?;
     Ok(Json(images))
 }
 
 pub async fn search_filter_options(
+    caller: Caller,
     State(state): State<Arc<AppState>>,
     Json(request): Json<SearchRequest>,
 ) -> Result<Json<FilterOptions>, AppError> {
+    caller.require(Role::Viewer)?;
     let conn = state.db.conn()?;
-    let options = queries::query_filter_options(&conn, &request.filters)?;
+    let options = queries::query_filter_options(&conn, &request.filters, &caller.collections)?;
     Ok(Json(options))
 }
 
+pub async fn search_timeline(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<TimelineRequest>,
+) -> Result<Json<Timeline>, AppError> {
+    caller.require(Role::Viewer)?;
+    let conn = state.db.conn()?;
+    let timeline = queries::query_timeline(&conn, &request.filters, request.granularity, &caller.collections)?;
+    Ok(Json(timeline))
+}
+
+pub async fn list_duplicates(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<DuplicateParams>,
+) -> Result<Json<Vec<DuplicateCluster>>, AppError> {
+    caller.require(Role::Viewer)?;
+    let max_distance = params.max_distance.unwrap_or(analysis::SIMILAR_MAX_DISTANCE);
+    let conn = state.db.conn()?;
+    let hashes = queries::query_image_hashes(&conn, params.collection.as_deref(), &caller.collections)?;
+    let values: Vec<u64> = hashes.iter().map(|(_, hash)| *hash).collect();
+
+    let mut clusters = Vec::new();
+    for members in analysis::cluster_hashes(&values, max_distance) {
+        let uuids: Vec<String> = members.into_iter().map(|i| hashes[i].0.clone()).collect();
+        let images = queries::query_images_by_uuid(&conn, &uuids)?;
+        clusters.push(DuplicateCluster { images });
+    }
+    // Biggest clusters first
+    clusters.sort_by_key(|c| std::cmp::Reverse(c.images.len()));
+    Ok(Json(clusters))
+}
+
 pub async fn get_image_detail(
+    caller: Caller,
     State(state): State<Arc<AppState>>,
     Path(uuid): Path<String>,
 ) -> Result<Json<ImageDetail>, AppError> {
+    caller.require(Role::Viewer)?;
     let conn = state.db.conn()?;
-    let detail = queries::query_image_detail(&conn, &uuid)?;
+    let detail = queries::query_image_detail(&conn, &uuid, &caller.collections)?;
     Ok(Json(detail))
 }
 
 pub async fn get_image_file(
+    caller: Caller,
     State(state): State<Arc<AppState>>,
     Path(uuid): Path<String>,
     Query(params): Query<ImageFileParams>,
 ) -> Result<impl IntoResponse, AppError> {
-    let full_path = {
-        let conn = state.db.conn()?;
-        let path: String = conn
-            .query_row("SELECT path FROM images WHERE uuid = ?", [&uuid], |row| {
-                row.get::<_, String>(0)
-            })
-            .map_err(|e| match e {
-                rusqlite::Error::QueryReturnedNoRows => {
-                    AppError::NotFound("Image not found".into())
-                }
-                other => AppError::from(other),
-            })?;
-        state.galleries_path.join(&path)
-    };
-    let canonical = full_path
-        .canonicalize()
-        .map_err(|_| AppError::NotFound("File not found".into()))?;
+    caller.require(Role::Viewer)?;
+    let relative_path = {
+        let conn = state.db.conn()?;
+        queries::query_image_path(&conn, &uuid, &caller.collections)?
+    };
+    let watermark = if state.watermark_roles.contains(&caller.role) {
+        state.watermark.as_ref()
+    } else {
+        None
+    };
+    serve_image(&state, &uuid, &relative_path, params.w, watermark).await
+}
 
-    if !canonical.starts_with(&state.galleries_path) {
-        return Err(AppError::BadRequest("Invalid path".into()));
-    }
+/// The original, or a cached resize when a width is given, as JPEG. With a
+/// watermark both are stamped and cached under the watermark's id.
+async fn serve_image(
+    state: &AppState,
+    uuid: &str,
+    relative_path: &str,
+    width: Option<u32>,
+    watermark: Option<&Arc<Watermark>>,
+) -> Result<impl IntoResponse, AppError> {
+    let canonical = resolve_original(&state.galleries_path, relative_path)?;
 
-    // No width requested — serve full-resolution file
-    let Some(target_width) = params.w else {
+    // No width requested and nothing to stamp — serve full-resolution file
+    if width.is_none() && watermark.is_none() {
         let body = tokio::fs::read(&canonical)
             .await
             .map_err(|_| AppError::NotFound("File not found on disk".into()))?;
         return Ok((
             [(axum::http::header::CONTENT_TYPE, "image/jpeg")],
-            body,
-        ));
-    };
-
-    let target_width = target_width.clamp(50, 1920);
-
-    // Check disk cache
-    let cache_path = state
-        .thumbnail_cache_dir
-        .join(format!("{uuid}_{target_width}.jpg"));
-
-    if let Ok(cached) = tokio::fs::read(&cache_path).await {
-        return Ok((
-            [(axum::http::header::CONTENT_TYPE, "image/jpeg")],
-            cached,
+            Bytes::from(body),
         ));
     }
 
-    // Generate thumbnail on blocking thread
-    let source_path = canonical.clone();
-    let out_path = cache_path.clone();
-    let body = tokio::task::spawn_blocking(move || -> Result<Vec<u8>, AppError> {
-        let img = image::open(&source_path)
-            .map_err(|e| AppError::BadRequest(format!("Failed to decode image: {e}")))?;
-
-        let thumb = if img.width() > target_width {
-            img.thumbnail(target_width, u32::MAX)
-        } else {
-            img
-        };
-
-        let mut buf = Vec::new();
-        thumb
-            .write_to(
-                &mut std::io::Cursor::new(&mut buf),
-                image::ImageFormat::Jpeg,
-            )
-            .map_err(|e| AppError::BadRequest(format!("Failed to encode thumbnail: {e}")))?;
-
-        if let Err(e) = std::fs::write(&out_path, &buf) {
-            tracing::warn!("Failed to cache thumbnail: {e}");
-        }
+    let target_width = width.map_or(thumbnails::FULL_SIZE, thumbnails::clamp_width);
 
-        Ok(buf)
-    })
-    .await
-    .map_err(|e| AppError::DbError(format!("Thumbnail task failed: {e}")))??;
+    // Cache is keyed on the current version of the original
+    let metadata = tokio::fs::metadata(&canonical)
+        .await
+        .map_err(|_| AppError::NotFound("File not found on disk".into()))?;
+    let fingerprint = SourceFingerprint::from_metadata(&metadata);
+
+    let body = state
+        .thumbnails
+        .get_or_render(uuid, target_width, &canonical, fingerprint, watermark)
+        .await?;
 
     Ok((
         [(axum::http::header::CONTENT_TYPE, "image/jpeg")],
@@ -132,54 +185,1146 @@ pub async fn get_image_file(
 }
 
 pub async fn update_image_tags(
+    caller: Caller,
     State(state): State<Arc<AppState>>,
     Path(uuid): Path<String>,
     Json(request): Json<UpdateTagsRequest>,
 ) -> Result<impl IntoResponse, AppError> {
+    caller.require(Role::Tagger)?;
+    {
+        let conn = state.db.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        let before = queries::query_tags_of_image(&conn, &uuid)?;
+        queries::replace_image_tags(&conn, &uuid, &request.tag_uuids, &caller.collections)?;
+        let after = queries::query_tags_of_image(&conn, &uuid)?;
+        Edit::new(&caller).tags(&conn, &uuid, &before, &after)?;
+        tx.commit()?;
+    }
+    flush_in_background(&state);
+    Ok(axum::http::StatusCode::NO_CONTENT)
+}
+
+pub async fn get_tag_history(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Path(uuid): Path<String>,
+) -> Result<Json<Vec<TagHistoryEntry>>, AppError> {
+    caller.require(Role::Viewer)?;
+    let conn = state.db.conn()?;
+    Ok(Json(history::tag_history(&conn, &uuid, &caller.collections)?))
+}
+
+pub async fn revert_image_tags(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Path(uuid): Path<String>,
+    Json(request): Json<RevertTagsRequest>,
+) -> Result<Json<TagRevertResult>, AppError> {
+    caller.require(Role::Tagger)?;
+    let result = {
+        let conn = state.db.conn()?;
+        history::revert_tags(&conn, &caller, &uuid, request.entry_id)?
+    };
+    flush_in_background(&state);
+    Ok(Json(result))
+}
+
+pub async fn undo(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<UndoResult>, AppError> {
+    caller.require(Role::Tagger)?;
+    let result = {
+        let conn = state.db.conn()?;
+        history::undo(&conn, &caller)?
+    };
+    flush_in_background(&state);
+    Ok(Json(result))
+}
+
+pub async fn redo(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<UndoResult>, AppError> {
+    caller.require(Role::Tagger)?;
+    let result = {
+        let conn = state.db.conn()?;
+        history::redo(&conn, &caller)?
+    };
+    flush_in_background(&state);
+    Ok(Json(result))
+}
+
+pub async fn update_image_culling(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Path(uuid): Path<String>,
+    Json(request): Json<UpdateCullingRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    caller.require(Role::Tagger)?;
+    {
+        let conn = state.db.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        let uuids = [uuid];
+        let before = culling_states(&conn, &uuids);
+        queries::update_culling(&conn, &uuids, &request, &caller.collections)?;
+        record_culling(&conn, &caller, before)?;
+        tx.commit()?;
+    }
+    flush_in_background(&state);
+    Ok(axum::http::StatusCode::NO_CONTENT)
+}
+
+pub async fn bulk_update_culling(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<BulkUpdateCullingRequest>,
+) -> Result<Json<BulkUpdateResult>, AppError> {
+    caller.require(Role::Tagger)?;
+    let updated = {
+        let conn = state.db.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        let before = culling_states(&conn, &request.image_uuids);
+        let updated =
+            queries::update_culling(&conn, &request.image_uuids, &request.update, &caller.collections)?;
+        record_culling(&conn, &caller, before)?;
+        tx.commit()?;
+        updated
+    };
+    flush_in_background(&state);
+    Ok(Json(BulkUpdateResult { updated }))
+}
+
+/// Culling fields of those listed images that exist, before a change.
+fn culling_states(
+    conn: &rusqlite::Connection,
+    uuids: &[String],
+) -> std::collections::BTreeMap<String, CullingState> {
+    uuids
+        .iter()
+        .filter_map(|uuid| Some((uuid.clone(), queries::query_culling(conn, uuid).ok()?)))
+        .collect()
+}
+
+/// Record the images whose culling fields actually changed, as one edit.
+fn record_culling(
+    conn: &rusqlite::Connection,
+    caller: &Caller,
+    before: std::collections::BTreeMap<String, CullingState>,
+) -> Result<(), AppError> {
+    let mut edit = Edit::new(caller);
+    for (uuid, before) in before {
+        let after = queries::query_culling(conn, &uuid)?;
+        edit.culling(conn, &uuid, &before, &after)?;
+    }
+    Ok(())
+}
+
+pub async fn list_comments(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Path(uuid): Path<String>,
+) -> Result<Json<Vec<Comment>>, AppError> {
+    caller.require(Role::Viewer)?;
+    let conn = state.db.conn()?;
+    let comments = queries::query_comments(&conn, &uuid, &caller.collections)?;
+    Ok(Json(comments))
+}
+
+pub async fn create_comment(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Path(uuid): Path<String>,
+    Json(request): Json<NewCommentRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    caller.require(Role::Tagger)?;
+    let comment = {
+        let conn = state.db.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        let comment = queries::insert_comment(
+            &conn,
+            &uuid,
+            &request,
+            &caller.name,
+            &caller.principal(),
+            unix_now(),
+            &caller.collections,
+        )?;
+        let target = Target::Comment { image: &uuid, uuid: &comment.uuid };
+        audit::record(&conn, &caller, "comment.create", target, None, Some(json!(comment)))?;
+        tx.commit()?;
+        comment
+    };
+    flush_in_background(&state);
+    Ok((axum::http::StatusCode::CREATED, Json(comment)))
+}
+
+pub async fn update_comment(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Path((uuid, comment_uuid)): Path<(String, String)>,
+    Json(request): Json<UpdateCommentRequest>,
+) -> Result<Json<Comment>, AppError> {
+    caller.require(Role::Tagger)?;
+    let comment = {
+        let conn = state.db.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        let before = queries::query_comment(&conn, &uuid, &comment_uuid);
+        let comment = queries::update_comment(
+            &conn,
+            &uuid,
+            &comment_uuid,
+            &request.body,
+            unix_now(),
+            comment_owner(&caller).as_deref(),
+            &caller.collections,
+        )?;
+        let target = Target::Comment { image: &uuid, uuid: &comment_uuid };
+        audit::record(&conn, &caller, "comment.update", target, Some(json!(before?)), Some(json!(comment)))?;
+        tx.commit()?;
+        comment
+    };
+    flush_in_background(&state);
+    Ok(Json(comment))
+}
+
+pub async fn delete_comment(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Path((uuid, comment_uuid)): Path<(String, String)>,
+) -> Result<impl IntoResponse, AppError> {
+    caller.require(Role::Tagger)?;
     {
         let conn = state.db.conn()?;
-        queries::replace_image_tags(&conn, &uuid, &request.tag_uuids)?;
+        let tx = conn.unchecked_transaction()?;
+        let before = queries::query_comment(&conn, &uuid, &comment_uuid);
+        let owner = comment_owner(&caller);
+        queries::delete_comment(&conn, &uuid, &comment_uuid, owner.as_deref(), &caller.collections)?;
+        let target = Target::Comment { image: &uuid, uuid: &comment_uuid };
+        audit::record(&conn, &caller, "comment.delete", target, Some(json!(before?)), None)?;
+        tx.commit()?;
     }
-    // Flush to disk in background
+    flush_in_background(&state);
+    Ok(axum::http::StatusCode::NO_CONTENT)
+}
+
+/// Admins may change any comment; everyone else only their own.
+fn comment_owner(caller: &Caller) -> Option<String> {
+    (caller.role < Role::Admin).then(|| caller.principal())
+}
+
+/// Every change through the API ends here, so live change feeds are told
+/// about new events here too.
+fn flush_in_background(state: &AppState) {
+    state.feed.wake();
     let db = state.db.clone();
     tokio::task::spawn_blocking(move || {
         if let Err(e) = db.flush_to_disk() {
             tracing::error!("Failed to flush DB to disk: {e}");
         }
     });
-    Ok(axum::http::StatusCode::NO_CONTENT)
 }
 
 pub async fn list_collections(
+    caller: Caller,
     State(state): State<Arc<AppState>>,
 ) -> Result<Json<Vec<CollectionSummary>>, AppError> {
+    caller.require(Role::Viewer)?;
     let conn = state.db.conn()?;
-    let collections = queries::query_collections(&conn)?;
+    let collections = queries::query_collections(&conn, &caller.collections)?;
     Ok(Json(collections))
 }
 
 pub async fn list_galleries(
+    caller: Caller,
     State(state): State<Arc<AppState>>,
     Query(filter): Query<CollectionFilter>,
 ) -> Result<Json<Vec<GallerySummary>>, AppError> {
+    caller.require(Role::Viewer)?;
     let conn = state.db.conn()?;
-    let galleries = queries::query_galleries(&conn, filter.collection.as_deref())?;
+    let galleries = queries::query_galleries(&conn, filter.collection.as_deref(), &caller.collections)?;
     Ok(Json(galleries))
 }
 
+pub async fn list_albums(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<AlbumSummary>>, AppError> {
+    caller.require(Role::Viewer)?;
+    let conn = state.db.conn()?;
+    let albums = queries::query_albums(&conn, &caller.collections)?;
+    Ok(Json(albums))
+}
+
+pub async fn get_album(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Path(uuid): Path<String>,
+) -> Result<Json<AlbumDetail>, AppError> {
+    caller.require(Role::Viewer)?;
+    let conn = state.db.conn()?;
+    let album = queries::query_album(&conn, &uuid, &caller.collections)?;
+    Ok(Json(album))
+}
+
+pub async fn create_album(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<NewAlbumRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    caller.require(Role::Tagger)?;
+    let album = {
+        let conn = state.db.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        let album = queries::insert_album(&conn, &request, unix_now(), &caller.collections)?;
+        let after = queries::query_album_snapshot(&conn, &album.uuid)?;
+        audit::record(&conn, &caller, "album.create", Target::Album(&album.uuid), None, Some(json!(after)))?;
+        tx.commit()?;
+        album
+    };
+    flush_in_background(&state);
+    Ok((axum::http::StatusCode::CREATED, Json(album)))
+}
+
+pub async fn update_album(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Path(uuid): Path<String>,
+    Json(request): Json<UpdateAlbumRequest>,
+) -> Result<Json<AlbumDetail>, AppError> {
+    caller.require(Role::Tagger)?;
+    let album = {
+        let conn = state.db.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        let before = queries::query_album_snapshot(&conn, &uuid);
+        let album = queries::update_album(&conn, &uuid, &request, unix_now(), &caller.collections)?;
+        record_album_change(&conn, &caller, "album.update", &uuid, before?)?;
+        tx.commit()?;
+        album
+    };
+    flush_in_background(&state);
+    Ok(Json(album))
+}
+
+pub async fn delete_album(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Path(uuid): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    caller.require(Role::Tagger)?;
+    {
+        let conn = state.db.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        let before = queries::query_album_snapshot(&conn, &uuid);
+        queries::delete_album(&conn, &uuid, &caller.collections)?;
+        audit::record(&conn, &caller, "album.delete", Target::Album(&uuid), Some(json!(before?)), None)?;
+        tx.commit()?;
+    }
+    flush_in_background(&state);
+    Ok(axum::http::StatusCode::NO_CONTENT)
+}
+
+pub async fn add_album_images(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Path(uuid): Path<String>,
+    Json(request): Json<AddAlbumImagesRequest>,
+) -> Result<Json<AlbumDetail>, AppError> {
+    caller.require(Role::Tagger)?;
+    let album = {
+        let conn = state.db.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        let before = queries::query_album_snapshot(&conn, &uuid);
+        let album = queries::add_album_images(&conn, &uuid, &request.image_uuids, request.position, unix_now(), &caller.collections)?;
+        record_album_change(&conn, &caller, "album.add_images", &uuid, before?)?;
+        tx.commit()?;
+        album
+    };
+    flush_in_background(&state);
+    Ok(Json(album))
+}
+
+pub async fn remove_album_image(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Path((uuid, image_uuid)): Path<(String, String)>,
+) -> Result<Json<AlbumDetail>, AppError> {
+    caller.require(Role::Tagger)?;
+    let album = {
+        let conn = state.db.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        let before = queries::query_album_snapshot(&conn, &uuid);
+        let album = queries::remove_album_image(&conn, &uuid, &image_uuid, unix_now(), &caller.collections)?;
+        record_album_change(&conn, &caller, "album.remove_image", &uuid, before?)?;
+        tx.commit()?;
+        album
+    };
+    flush_in_background(&state);
+    Ok(Json(album))
+}
+
+pub async fn reorder_album(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Path(uuid): Path<String>,
+    Json(request): Json<ReorderAlbumRequest>,
+) -> Result<Json<AlbumDetail>, AppError> {
+    caller.require(Role::Tagger)?;
+    let album = {
+        let conn = state.db.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        let before = queries::query_album_snapshot(&conn, &uuid);
+        let album = queries::reorder_album(&conn, &uuid, &request.image_uuids, unix_now(), &caller.collections)?;
+        record_album_change(&conn, &caller, "album.reorder", &uuid, before?)?;
+        tx.commit()?;
+        album
+    };
+    flush_in_background(&state);
+    Ok(Json(album))
+}
+
+fn record_album_change(
+    conn: &rusqlite::Connection,
+    caller: &Caller,
+    action: &str,
+    uuid: &str,
+    before: AlbumSnapshot,
+) -> Result<(), AppError> {
+    let after = queries::query_album_snapshot(conn, uuid)?;
+    audit::record(conn, caller, action, Target::Album(uuid), Some(json!(before)), Some(json!(after)))
+}
+
+pub async fn list_saved_searches(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<SavedSearch>>, AppError> {
+    caller.require(Role::Viewer)?;
+    let conn = state.db.conn()?;
+    let searches = queries::query_saved_searches(&conn, &caller.collections)?;
+    Ok(Json(searches))
+}
+
+pub async fn get_saved_search(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Path(uuid): Path<String>,
+) -> Result<Json<SavedSearch>, AppError> {
+    caller.require(Role::Viewer)?;
+    let conn = state.db.conn()?;
+    let search = queries::query_saved_search(&conn, &uuid, &caller.collections)?;
+    Ok(Json(search))
+}
+
+pub async fn create_saved_search(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<SavedSearchRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    caller.require(Role::Tagger)?;
+    let search = {
+        let conn = state.db.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        let search = queries::insert_saved_search(&conn, &request, unix_now(), &caller.collections)?;
+        let target = Target::SavedSearch(&search.uuid);
+        audit::record(&conn, &caller, "saved_search.create", target, None, Some(json!(search)))?;
+        tx.commit()?;
+        search
+    };
+    flush_in_background(&state);
+    Ok((axum::http::StatusCode::CREATED, Json(search)))
+}
+
+pub async fn update_saved_search(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Path(uuid): Path<String>,
+    Json(request): Json<SavedSearchRequest>,
+) -> Result<Json<SavedSearch>, AppError> {
+    caller.require(Role::Tagger)?;
+    let search = {
+        let conn = state.db.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        let before = queries::query_saved_search(&conn, &uuid, &caller.collections);
+        let search = queries::update_saved_search(&conn, &uuid, &request, unix_now(), &caller.collections)?;
+        let target = Target::SavedSearch(&uuid);
+        audit::record(&conn, &caller, "saved_search.update", target, Some(json!(before?)), Some(json!(search)))?;
+        tx.commit()?;
+        search
+    };
+    flush_in_background(&state);
+    Ok(Json(search))
+}
+
+pub async fn delete_saved_search(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Path(uuid): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    caller.require(Role::Tagger)?;
+    {
+        let conn = state.db.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        let before = queries::query_saved_search(&conn, &uuid, &caller.collections);
+        queries::delete_saved_search(&conn, &uuid, &caller.collections)?;
+        audit::record(&conn, &caller, "saved_search.delete", Target::SavedSearch(&uuid), Some(json!(before?)), None)?;
+        tx.commit()?;
+    }
+    flush_in_background(&state);
+    Ok(axum::http::StatusCode::NO_CONTENT)
+}
+
+/// Run a saved search: same response as `POST /images/search`.
+pub async fn run_saved_search(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Path(uuid): Path<String>,
+) -> Result<Json<Vec<ImageRow>>, AppError> {
+    caller.require(Role::Viewer)?;
+    let conn = state.db.conn()?;
+    let search = queries::query_saved_search(&conn, &uuid, &caller.collections)?;
+    let (sql, params) = queries::build_image_query(&conn, &search.filters, &search.sort, &caller.collections)?;
+    let images = queries::query_images(&conn, &sql, &params)?;
+    Ok(Json(images))
+}
+
+pub async fn list_shares(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<ShareLink>>, AppError> {
+    caller.require(Role::Tagger)?;
+    let conn = state.db.conn()?;
+    let shares = shares::list_shares(&conn, &caller)?;
+    Ok(Json(shares))
+}
+
+pub async fn create_share(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<NewShareRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    caller.require(Role::Tagger)?;
+    if request.watermark && state.watermark.is_none() {
+        return Err(AppError::BadRequest(
+            "No watermark is configured on this server; set TIVOLI_WATERMARK_TEXT or TIVOLI_WATERMARK_IMAGE".into(),
+        ));
+    }
+    let share = {
+        let conn = state.db.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        let share = shares::create_share(&conn, &caller, &request)?;
+        // The link itself is a bearer credential, so only its id goes in the log
+        let after = json!({ "id": share.id });
+        audit::record(&conn, &caller, "share.create", Target::Share(&share.id), None, Some(after))?;
+        tx.commit()?;
+        share
+    };
+    flush_in_background(&state);
+    Ok((axum::http::StatusCode::CREATED, Json(share)))
+}
+
+pub async fn revoke_share(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    caller.require(Role::Tagger)?;
+    {
+        let conn = state.db.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        shares::revoke_share(&conn, &caller, &id)?;
+        audit::record(&conn, &caller, "share.revoke", Target::Share(&id), None, Some(json!({ "id": id })))?;
+        tx.commit()?;
+    }
+    flush_in_background(&state);
+    Ok(axum::http::StatusCode::NO_CONTENT)
+}
+
+/// Public: the images behind a share link. The token is the credential.
+pub async fn open_share(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+) -> Result<Json<SharedView>, AppError> {
+    let conn = state.db.conn()?;
+    let share = shares::open_share(&conn, &token)?;
+    let view = shares::shared_view(&conn, &share)?;
+    Ok(Json(view))
+}
+
+/// Public: one shared image, resized or (if the link allows) full size.
+pub async fn get_shared_image_file(
+    State(state): State<Arc<AppState>>,
+    Path((token, uuid)): Path<(String, String)>,
+    Query(params): Query<ImageFileParams>,
+) -> Result<impl IntoResponse, AppError> {
+    let (relative_path, watermarked) = {
+        let conn = state.db.conn()?;
+        let share = shares::open_share(&conn, &token)?;
+        if params.w.is_none() && !share.allow_download {
+            return Err(AppError::Forbidden(
+                "Downloads are disabled for this share link; request a width".into(),
+            ));
+        }
+        if share.watermark && state.watermark.is_none() {
+            return Err(AppError::Forbidden(
+                "This share link requires a watermark, but none is configured".into(),
+            ));
+        }
+        (shares::shared_image_path(&conn, &share, &uuid)?, share.watermark)
+    };
+    let watermark = state.watermark.as_ref().filter(|_| watermarked);
+    serve_image(&state, &uuid, &relative_path, params.w, watermark).await
+}
+
 pub async fn list_models(
+    caller: Caller,
     State(state): State<Arc<AppState>>,
     Query(filter): Query<CollectionFilter>,
 ) -> Result<Json<Vec<Model>>, AppError> {
+    caller.require(Role::Viewer)?;
     let conn = state.db.conn()?;
-    let models = queries::query_models(&conn, filter.collection.as_deref())?;
+    let models = queries::query_models(&conn, filter.collection.as_deref(), &caller.collections)?;
     Ok(Json(models))
 }
 
 pub async fn list_tags(
+    caller: Caller,
     State(state): State<Arc<AppState>>,
 ) -> Result<Json<Vec<TagGroup>>, AppError> {
+    caller.require(Role::Viewer)?;
     let conn = state.db.conn()?;
     let groups = queries::query_tag_groups(&conn)?;
     Ok(Json(groups))
 }
+
+pub async fn create_tag(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<NewTagRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    caller.require(Role::Admin)?;
+    let tag = {
+        let conn = state.db.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        let tag = queries::insert_tag(&conn, &request)?;
+        audit::record(&conn, &caller, "tag.create", Target::Tag(&tag.uuid), None, Some(json!(tag)))?;
+        tx.commit()?;
+        tag
+    };
+    flush_in_background(&state);
+    Ok((axum::http::StatusCode::CREATED, Json(tag)))
+}
+
+pub async fn update_tag(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Path(uuid): Path<String>,
+    Json(request): Json<UpdateTagRequest>,
+) -> Result<Json<TagRef>, AppError> {
+    caller.require(Role::Admin)?;
+    let tag = {
+        let conn = state.db.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        let before = queries::query_tag(&conn, &uuid);
+        let tag = queries::update_tag(&conn, &uuid, &request)?;
+        audit::record(&conn, &caller, "tag.update", Target::Tag(&uuid), Some(json!(before?)), Some(json!(tag)))?;
+        tx.commit()?;
+        tag
+    };
+    flush_in_background(&state);
+    Ok(Json(tag))
+}
+
+pub async fn delete_tag(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Path(uuid): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    caller.require(Role::Admin)?;
+    {
+        let conn = state.db.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        // Deleting a tag edits every image carrying it; log those as tag changes too
+        let before = queries::query_tag(&conn, &uuid);
+        let mut images = Vec::new();
+        for image in queries::query_tagged_images(&conn, &uuid)? {
+            let tags = queries::query_tags_of_image(&conn, &image)?;
+            images.push((image, tags));
+        }
+        queries::delete_tag(&conn, &uuid)?;
+        for (image, before) in images {
+            let after = queries::query_tags_of_image(&conn, &image)?;
+            audit::record(&conn, &caller, "image.tags", Target::Image(&image), Some(json!(before)), Some(json!(after)))?;
+        }
+        audit::record(&conn, &caller, "tag.delete", Target::Tag(&uuid), Some(json!(before?)), None)?;
+        tx.commit()?;
+    }
+    flush_in_background(&state);
+    Ok(axum::http::StatusCode::NO_CONTENT)
+}
+
+pub async fn create_tag_group(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<TagGroupRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    caller.require(Role::Admin)?;
+    let group = {
+        let conn = state.db.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        let group = queries::insert_tag_group(&conn, &request.name)?;
+        audit::record(&conn, &caller, "tag_group.create", Target::TagGroup(&group.uuid), None, Some(json!(group)))?;
+        tx.commit()?;
+        group
+    };
+    flush_in_background(&state);
+    Ok((axum::http::StatusCode::CREATED, Json(group)))
+}
+
+pub async fn update_tag_group(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Path(uuid): Path<String>,
+    Json(request): Json<TagGroupRequest>,
+) -> Result<Json<TagGroup>, AppError> {
+    caller.require(Role::Admin)?;
+    let group = {
+        let conn = state.db.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        let before = queries::query_tag_group(&conn, &uuid);
+        let group = queries::rename_tag_group(&conn, &uuid, &request.name)?;
+        let target = Target::TagGroup(&uuid);
+        audit::record(&conn, &caller, "tag_group.update", target, Some(json!(before?)), Some(json!(group)))?;
+        tx.commit()?;
+        group
+    };
+    flush_in_background(&state);
+    Ok(Json(group))
+}
+
+pub async fn delete_tag_group(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Path(uuid): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    caller.require(Role::Admin)?;
+    {
+        let conn = state.db.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        let before = queries::query_tag_group(&conn, &uuid);
+        queries::delete_tag_group(&conn, &uuid)?;
+        audit::record(&conn, &caller, "tag_group.delete", Target::TagGroup(&uuid), Some(json!(before?)), None)?;
+        tx.commit()?;
+    }
+    flush_in_background(&state);
+    Ok(axum::http::StatusCode::NO_CONTENT)
+}
+
+pub async fn thumbnail_cache_stats(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<ThumbnailCacheStats>, AppError> {
+    caller.require(Role::Admin)?;
+    Ok(Json(state.thumbnails.stats()))
+}
+
+pub async fn purge_thumbnail_cache(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<ThumbnailPurgeParams>,
+) -> Result<Json<ThumbnailPurgeResult>, AppError> {
+    caller.require(Role::Admin)?;
+    // Resolve the selection to image UUIDs up front; no selection purges everything
+    let uuids = match (&params.image, &params.collection, &params.gallery) {
+        (None, None, None) => None,
+        (Some(image), None, None) => Some(std::iter::once(image.clone()).collect()),
+        (None, Some(collection), gallery) => {
+            let conn = state.db.conn()?;
+            Some(queries::query_image_uuids(&conn, collection, gallery.as_deref())?)
+        }
+        (None, None, Some(_)) => {
+            return Err(AppError::BadRequest(
+                "gallery requires a collection".into(),
+            ))
+        }
+        _ => {
+            return Err(AppError::BadRequest(
+                "image cannot be combined with collection or gallery".into(),
+            ))
+        }
+    };
+
+    let thumbnails = state.thumbnails.clone();
+    let result = tokio::task::spawn_blocking(move || match uuids {
+        Some(uuids) => thumbnails.purge_images(&uuids),
+        None => thumbnails.purge_all(),
+    })
+    .await
+    .map_err(|e| AppError::DbError(format!("Purge task failed: {e}")))?;
+    {
+        let conn = state.db.conn()?;
+        let selection = json!({
+            "image": params.image,
+            "collection": params.collection,
+            "gallery": params.gallery,
+        });
+        let after = json!({ "selection": selection, "result": result });
+        audit::record(&conn, &caller, "thumbnails.purge", Target::Job("thumbnails"), None, Some(after))?;
+    }
+    flush_in_background(&state);
+    Ok(Json(result))
+}
+
+pub async fn pregenerate_status(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<PregenerateStatus>, AppError> {
+    caller.require(Role::Admin)?;
+    Ok(Json(state.pregenerator.status()))
+}
+
+pub async fn start_pregenerate(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<PregenerateRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    caller.require(Role::Admin)?;
+    let widths = request
+        .widths
+        .unwrap_or_else(|| state.pregenerator.default_widths().to_vec());
+    if widths.is_empty() {
+        return Err(AppError::BadRequest("widths must not be empty".into()));
+    }
+    let after = json!({ "collection": request.collection, "widths": widths });
+    let status = Pregenerator::start(&state, request.collection, widths)?;
+    record_job_start(&state, &caller, "pregenerate", after)?;
+    Ok((axum::http::StatusCode::ACCEPTED, Json(status)))
+}
+
+pub async fn ingest_status(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<IngestStatus>, AppError> {
+    caller.require(Role::Admin)?;
+    Ok(Json(state.ingestor.status()))
+}
+
+pub async fn start_ingest(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<IngestRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    caller.require(Role::Admin)?;
+    let after = json!({ "collection": request.collection, "force": request.force });
+    let status = Ingestor::start(&state, caller.clone(), request.collection, request.force)?;
+    record_job_start(&state, &caller, "ingest", after)?;
+    Ok((axum::http::StatusCode::ACCEPTED, Json(status)))
+}
+
+pub async fn sidecar_export_status(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<SidecarExportStatus>, AppError> {
+    caller.require(Role::Admin)?;
+    Ok(Json(state.sidecars.status()))
+}
+
+pub async fn start_sidecar_export(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<SidecarExportRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    caller.require(Role::Admin)?;
+    let after = json!({ "collection": request.collection });
+    let status = SidecarExporter::start(&state, request.collection)?;
+    record_job_start(&state, &caller, "sidecar_export", after)?;
+    Ok((axum::http::StatusCode::ACCEPTED, Json(status)))
+}
+
+pub async fn verify_status(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<VerifyStatus>, AppError> {
+    caller.require(Role::Admin)?;
+    Ok(Json(state.verifier.status()))
+}
+
+pub async fn start_verify(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<VerifyRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    caller.require(Role::Admin)?;
+    let after = json!({ "collection": request.collection });
+    let status = Verifier::start(&state, request.collection)?;
+    record_job_start(&state, &caller, "verify", after)?;
+    Ok((axum::http::StatusCode::ACCEPTED, Json(status)))
+}
+
+fn record_job_start(
+    state: &Arc<AppState>,
+    caller: &Caller,
+    job: &str,
+    options: serde_json::Value,
+) -> Result<(), AppError> {
+    {
+        let conn = state.db.conn()?;
+        audit::record(&conn, caller, "job.start", Target::Job(job), None, Some(options))?;
+    }
+    flush_in_background(state);
+    Ok(())
+}
+
+/// Server-Sent Events of changes, resuming after `Last-Event-ID` when given
+/// and starting from now otherwise.
+pub async fn change_feed(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    headers: axum::http::HeaderMap,
+    Query(params): Query<ChangeFeedParams>,
+) -> Result<impl IntoResponse, AppError> {
+    caller.require(Role::Viewer)?;
+    let header = match headers.get("last-event-id") {
+        Some(value) => Some(
+            value
+                .to_str()
+                .ok()
+                .and_then(|v| v.trim().parse::<i64>().ok())
+                .ok_or_else(|| AppError::BadRequest("Last-Event-ID must be an event id".into()))?,
+        ),
+        None => None,
+    };
+    let last_id = match header.or(params.last_event_id) {
+        Some(id) => id,
+        None => events::latest_id(&*state.db.conn()?)?,
+    };
+    let stream = events::subscribe(Arc::clone(&state), caller.collections.clone(), last_id)?;
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// Rows changed and deleted since a sync point, for offline mirrors.
+pub async fn sync(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Query(params): Query<SyncParams>,
+) -> Result<Json<SyncResponse>, AppError> {
+    caller.require(Role::Viewer)?;
+    let conn = state.db.conn()?;
+    Ok(Json(sync::sync(&conn, &caller.collections, params.since)?))
+}
+
+/// Tag edits a client made offline, reconciled with changes made since.
+pub async fn reconcile_tags(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<ReconcileTagsRequest>,
+) -> Result<Json<Vec<TagOperationResult>>, AppError> {
+    caller.require(Role::Tagger)?;
+    let results = {
+        let conn = state.db.conn()?;
+        sync::reconcile_tags(&conn, &caller, &request)?
+    };
+    flush_in_background(&state);
+    Ok(Json(results))
+}
+
+pub async fn list_audit_log(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<AuditEntry>>, AppError> {
+    caller.require(Role::Admin)?;
+    let conn = state.db.conn()?;
+    Ok(Json(audit::query_audit_log(&conn, &query)?))
+}
+
+/// Public, like the share routes: it is how callers get a token.
+pub async fn login(
+    State(state): State<Arc<AppState>>,
+    headers: axum::http::HeaderMap,
+    Json(request): Json<LoginRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let (user_uuid, stored) = {
+        let conn = state.db.conn()?;
+        auth::login_account(&conn, &request.username)?
+    };
+    let matches = tokio::task::spawn_blocking(move || auth::password_matches(&stored, &request.password))
+        .await
+        .map_err(|e| AppError::DbError(format!("Password check failed: {e}")))?;
+    let Some(user_uuid) = user_uuid.filter(|_| matches) else {
+        return Err(AppError::Auth(auth::AuthError::BadLogin));
+    };
+    let session = {
+        let conn = state.db.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        let session = auth::start_session(&conn, &user_uuid)?;
+        let mut caller = auth::verify_session(&conn, &session.token)
+            .ok_or(AppError::Auth(auth::AuthError::BadLogin))?;
+        caller.request_id = auth::request_id(&headers);
+        audit::record(&conn, &caller, "user.login", Target::User(&user_uuid), None, None)?;
+        tx.commit()?;
+        session
+    };
+    flush_in_background(&state);
+    Ok(Json(session))
+}
+
+pub async fn logout(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+) -> Result<impl IntoResponse, AppError> {
+    caller.require(Role::Viewer)?;
+    {
+        let conn = state.db.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        auth::logout(&conn, &caller)?;
+        if let auth::Identity::User { uuid, .. } = &caller.identity {
+            audit::record(&conn, &caller, "user.logout", Target::User(uuid), None, None)?;
+        }
+        tx.commit()?;
+    }
+    flush_in_background(&state);
+    Ok(axum::http::StatusCode::NO_CONTENT)
+}
+
+pub async fn who_am_i(caller: Caller) -> Result<Json<WhoAmI>, AppError> {
+    caller.require(Role::Viewer)?;
+    Ok(Json(caller.who_am_i()))
+}
+
+pub async fn list_users(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<User>>, AppError> {
+    caller.require(Role::Admin)?;
+    let conn = state.db.conn()?;
+    Ok(Json(auth::list_users(&conn)?))
+}
+
+pub async fn create_user(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<NewUserRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    caller.require(Role::Admin)?;
+    let password_hash = hash_new_password(request.password).await?;
+    let user = {
+        let conn = state.db.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        let user = auth::create_user(
+            &conn,
+            &request.username,
+            &password_hash,
+            request.role,
+            request.collections.as_deref(),
+        )?;
+        audit::record(&conn, &caller, "user.create", Target::User(&user.uuid), None, Some(json!(user)))?;
+        tx.commit()?;
+        user
+    };
+    flush_in_background(&state);
+    Ok((axum::http::StatusCode::CREATED, Json(user)))
+}
+
+/// Check and hash a new password on a blocking thread, so that no other
+/// request waits on it.
+async fn hash_new_password(password: String) -> Result<String, AppError> {
+    tokio::task::spawn_blocking(move || auth::new_password_hash(&password))
+        .await
+        .map_err(|e| AppError::DbError(format!("Password hashing failed: {e}")))?
+}
+
+pub async fn update_user(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Path(uuid): Path<String>,
+    Json(request): Json<UpdateUserRequest>,
+) -> Result<Json<User>, AppError> {
+    caller.require(Role::Admin)?;
+    let password_hash = match request.password.clone() {
+        Some(password) => Some(hash_new_password(password).await?),
+        None => None,
+    };
+    let user = {
+        let conn = state.db.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        let before = auth::query_user(&conn, &uuid);
+        let user = auth::update_user(&conn, &uuid, &request, password_hash.as_deref())?;
+        let mut after = json!(user);
+        if password_hash.is_some() {
+            after["password_changed"] = json!(true);
+        }
+        audit::record(&conn, &caller, "user.update", Target::User(&uuid), Some(json!(before?)), Some(after))?;
+        tx.commit()?;
+        user
+    };
+    flush_in_background(&state);
+    Ok(Json(user))
+}
+
+pub async fn set_user_collections(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Path(uuid): Path<String>,
+    Json(request): Json<UserCollectionsRequest>,
+) -> Result<Json<User>, AppError> {
+    caller.require(Role::Admin)?;
+    let user = {
+        let conn = state.db.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        let before = auth::query_user(&conn, &uuid);
+        let user = auth::set_user_collections(&conn, &uuid, request.collections.as_deref())?;
+        let target = Target::User(&uuid);
+        audit::record(&conn, &caller, "user.collections", target, Some(json!(before?)), Some(json!(user)))?;
+        tx.commit()?;
+        user
+    };
+    flush_in_background(&state);
+    Ok(Json(user))
+}
+
+pub async fn list_keys(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<ApiKey>>, AppError> {
+    caller.require(Role::Admin)?;
+    let conn = state.db.conn()?;
+    Ok(Json(auth::list_keys(&conn)?))
+}
+
+pub async fn create_key(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Json(request): Json<NewApiKeyRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    caller.require(Role::Admin)?;
+    let created = {
+        let conn = state.db.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        let created = auth::create_key(&conn, &request.name, request.role, request.collections.as_deref())?;
+        let target = Target::ApiKey(&created.api_key.id);
+        audit::record(&conn, &caller, "api_key.create", target, None, Some(json!(created.api_key)))?;
+        tx.commit()?;
+        created
+    };
+    flush_in_background(&state);
+    Ok((axum::http::StatusCode::CREATED, Json(created)))
+}
+
+/// Takes effect at once, unlike `keys revoke` on the command line, which a
+/// running server would overwrite.
+pub async fn revoke_key(
+    caller: Caller,
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    caller.require(Role::Admin)?;
+    {
+        let conn = state.db.conn()?;
+        let tx = conn.unchecked_transaction()?;
+        auth::revoke_key(&conn, &id)?;
+        audit::record(&conn, &caller, "api_key.revoke", Target::ApiKey(&id), None, None)?;
+        tx.commit()?;
+    }
+    flush_in_background(&state);
+    Ok(axum::http::StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,345 @@
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use crate::audit::{self, json_value, Target};
+use crate::auth::Caller;
+use crate::errors::AppError;
+use crate::models::{CollectionAccess, CullingState, TagHistoryEntry, TagRef, TagRevertResult, UndoResult};
+use crate::queries;
+use crate::unix_now;
+
+const TAGS: &str = "image.tags";
+const CULLING: &str = "image.culling";
+
+// --- Recording edits ---
+
+/// The tag and culling changes one request makes, kept as one undoable
+/// operation. The operation is only stored once something actually changes,
+/// so an edit that changes nothing leaves the caller's undo history alone.
+pub struct Edit<'a> {
+    caller: &'a Caller,
+    operation: Option<i64>,
+}
+
+impl<'a> Edit<'a> {
+    pub fn new(caller: &'a Caller) -> Self {
+        Edit { caller, operation: None }
+    }
+
+    pub fn tags(
+        &mut self,
+        conn: &rusqlite::Connection,
+        image: &str,
+        before: &[TagRef],
+        after: &[TagRef],
+    ) -> Result<(), AppError> {
+        self.record(conn, TAGS, image, json!(before), json!(after))
+    }
+
+    pub fn culling(
+        &mut self,
+        conn: &rusqlite::Connection,
+        image: &str,
+        before: &CullingState,
+        after: &CullingState,
+    ) -> Result<(), AppError> {
+        self.record(conn, CULLING, image, json!(before), json!(after))
+    }
+
+    fn record(
+        &mut self,
+        conn: &rusqlite::Connection,
+        action: &str,
+        image: &str,
+        before: Value,
+        after: Value,
+    ) -> Result<(), AppError> {
+        if before == after {
+            return Ok(());
+        }
+        let operation = match self.operation {
+            Some(id) => id,
+            None => {
+                let id = start_operation(conn, self.caller)?;
+                self.operation = Some(id);
+                id
+            }
+        };
+        audit::record_edit(conn, self.caller, operation, action, Target::Image(image), before, after)
+    }
+}
+
+fn start_operation(conn: &rusqlite::Connection, caller: &Caller) -> Result<i64, AppError> {
+    let actor = caller.principal();
+    // As in any editor, a new edit ends the chance to redo what was undone
+    conn.execute(
+        "UPDATE edit_operations SET state = 'discarded' WHERE actor_id = ? AND state = 'undone'",
+        [&actor],
+    )?;
+    conn.execute(
+        "INSERT INTO edit_operations (actor_id, request_id, at, state) VALUES (?, ?, ?, 'done')",
+        rusqlite::params![actor, caller.request_id, unix_now() as i64],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+fn parse<T: DeserializeOwned>(value: Option<Value>) -> Result<T, AppError> {
+    serde_json::from_value(value.unwrap_or(Value::Null))
+        .map_err(|e| AppError::DbError(format!("Bad audit entry: {e}")))
+}
+
+// --- Tag history ---
+
+/// Every change to an image's tags, newest first.
+pub fn tag_history(
+    conn: &rusqlite::Connection,
+    image: &str,
+    access: &CollectionAccess,
+) -> Result<Vec<TagHistoryEntry>, AppError> {
+    queries::query_image_path(conn, image, access)?;
+    let mut stmt = conn.prepare(
+        "SELECT id, at, actor, request_id, before, after FROM audit_log
+         WHERE image_uuid = ? AND action = ? ORDER BY id DESC",
+    )?;
+    let rows = stmt
+        .query_map([image, TAGS], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)? as u64,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                json_value(row, 4)?,
+                json_value(row, 5)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    rows.into_iter()
+        .map(|(id, at, actor, request_id, before, after)| {
+            Ok(TagHistoryEntry {
+                id,
+                at,
+                actor,
+                request_id,
+                before: parse(before)?,
+                after: parse(after)?,
+            })
+        })
+        .collect()
+}
+
+/// Give an image back the tags it had just before history entry `entry_id`.
+/// This is itself an edit, so it shows up in the history and can be undone.
+pub fn revert_tags(
+    conn: &rusqlite::Connection,
+    caller: &Caller,
+    image: &str,
+    entry_id: i64,
+) -> Result<TagRevertResult, AppError> {
+    queries::query_image_path(conn, image, &caller.collections)?;
+    let state = conn
+        .query_row(
+            "SELECT before FROM audit_log WHERE id = ? AND image_uuid = ? AND action = ?",
+            rusqlite::params![entry_id, image, TAGS],
+            |row| json_value(row, 0),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => AppError::NotFound("History entry not found".into()),
+            other => AppError::from(other),
+        })?;
+    let state: Vec<TagRef> = parse(state)?;
+
+    let tx = conn.unchecked_transaction()?;
+    let before = queries::query_tags_of_image(conn, image)?;
+    let missing = put_tags(conn, image, &state, &caller.collections)?;
+    let tags = queries::query_tags_of_image(conn, image)?;
+    Edit::new(caller).tags(conn, image, &before, &tags)?;
+    tx.commit()?;
+    Ok(TagRevertResult { tags, missing })
+}
+
+/// Set an image's tags to a recorded state, leaving out tags deleted since.
+/// Returns those.
+fn put_tags(
+    conn: &rusqlite::Connection,
+    image: &str,
+    state: &[TagRef],
+    access: &CollectionAccess,
+) -> Result<Vec<TagRef>, AppError> {
+    let mut present = Vec::new();
+    let mut missing = Vec::new();
+    for tag in state {
+        match queries::query_tag(conn, &tag.uuid) {
+            Ok(_) => present.push(tag.uuid.clone()),
+            Err(AppError::NotFound(_)) => missing.push(tag.clone()),
+            Err(e) => return Err(e),
+        }
+    }
+    queries::replace_image_tags(conn, image, &present, access)?;
+    Ok(missing)
+}
+
+// --- Undo and redo ---
+
+#[derive(Clone, Copy)]
+enum Step {
+    Undo,
+    Redo,
+}
+
+impl Step {
+    fn name(self) -> &'static str {
+        match self {
+            Step::Undo => "undo",
+            Step::Redo => "redo",
+        }
+    }
+
+    /// The state of operations this step picks from, and the state it
+    /// leaves them in.
+    fn states(self) -> (&'static str, &'static str) {
+        match self {
+            Step::Undo => ("done", "undone"),
+            Step::Redo => ("undone", "done"),
+        }
+    }
+}
+
+struct Change {
+    action: String,
+    image: String,
+    before: Value,
+    after: Value,
+}
+
+/// Undo the caller's latest tag or culling edit that is still in effect.
+pub fn undo(conn: &rusqlite::Connection, caller: &Caller) -> Result<UndoResult, AppError> {
+    step(conn, caller, Step::Undo)
+}
+
+/// Redo the caller's most recently undone edit.
+pub fn redo(conn: &rusqlite::Connection, caller: &Caller) -> Result<UndoResult, AppError> {
+    step(conn, caller, Step::Redo)
+}
+
+fn step(conn: &rusqlite::Connection, caller: &Caller, step: Step) -> Result<UndoResult, AppError> {
+    let (from, to) = step.states();
+    // Undone operations form a stack: undo takes the newest done one, redo
+    // the oldest undone one, which is the one undone last
+    let order = match step {
+        Step::Undo => "DESC",
+        Step::Redo => "ASC",
+    };
+    let (operation_id, request_id, at) = conn
+        .query_row(
+            &format!(
+                "SELECT id, request_id, at FROM edit_operations
+                 WHERE actor_id = ? AND state = ? ORDER BY id {order} LIMIT 1"
+            ),
+            rusqlite::params![caller.principal(), from],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, i64>(2)? as u64)),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => AppError::Conflict(format!("Nothing to {}", step.name())),
+            other => AppError::from(other),
+        })?;
+
+    let mut stmt = conn.prepare(
+        "SELECT action, image_uuid, before, after FROM audit_log WHERE operation_id = ? ORDER BY id",
+    )?;
+    let mut changes = stmt
+        .query_map([operation_id], |row| {
+            Ok(Change {
+                action: row.get(0)?,
+                image: row.get(1)?,
+                before: json_value(row, 2)?.unwrap_or(Value::Null),
+                after: json_value(row, 3)?.unwrap_or(Value::Null),
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    if let Step::Undo = step {
+        changes.reverse();
+    }
+    // Images that are gone, or that the caller can no longer see, are left alone
+    changes.retain(|change| queries::query_image_path(conn, &change.image, &caller.collections).is_ok());
+
+    // Only step over images still as this operation left them (or found them,
+    // for redo); anything else would silently throw away someone's later edit
+    let mut changed_since = 0;
+    for change in &changes {
+        let (expected, _) = endpoints(step, change);
+        if !same_state(&change.action, &current_state(conn, change)?, expected)? {
+            changed_since += 1;
+        }
+    }
+    if changed_since > 0 {
+        return Err(AppError::Conflict(format!(
+            "{changed_since} of the images in this edit have been changed since; {} would overwrite that",
+            step.name()
+        )));
+    }
+
+    // All or nothing: a failure part-way must not leave some images stepped
+    // and the operation still in its old state
+    let tx = conn.unchecked_transaction()?;
+    let mut images: Vec<String> = Vec::new();
+    for change in &changes {
+        let (_, target) = endpoints(step, change);
+        let before = current_state(conn, change)?;
+        match change.action.as_str() {
+            TAGS => {
+                put_tags(conn, &change.image, &parse::<Vec<TagRef>>(Some(target.clone()))?, &caller.collections)?;
+            }
+            _ => queries::set_culling(conn, &change.image, &parse(Some(target.clone()))?)?,
+        }
+        let after = current_state(conn, change)?;
+        audit::record(conn, caller, &change.action, Target::Image(&change.image), Some(before), Some(after))?;
+        if !images.contains(&change.image) {
+            images.push(change.image.clone());
+        }
+    }
+
+    conn.execute(
+        "UPDATE edit_operations SET state = ? WHERE id = ?",
+        rusqlite::params![to, operation_id],
+    )?;
+    let id = operation_id.to_string();
+    let action = format!("edit.{}", step.name());
+    audit::record(conn, caller, &action, Target::Operation(&id), None, Some(json!({ "images": images })))?;
+    tx.commit()?;
+    Ok(UndoResult {
+        operation_id,
+        request_id,
+        at,
+        images,
+    })
+}
+
+/// The state an image must be in for the step, and the state it goes to.
+fn endpoints(step: Step, change: &Change) -> (&Value, &Value) {
+    match step {
+        Step::Undo => (&change.after, &change.before),
+        Step::Redo => (&change.before, &change.after),
+    }
+}
+
+fn current_state(conn: &rusqlite::Connection, change: &Change) -> Result<Value, AppError> {
+    Ok(match change.action.as_str() {
+        TAGS => json!(queries::query_tags_of_image(conn, &change.image)?),
+        CULLING => json!(queries::query_culling(conn, &change.image)?),
+        other => return Err(AppError::DbError(format!("Cannot undo {other}"))),
+    })
+}
+
+/// Tags compare by UUID alone, so renaming a tag doesn't count as a change.
+fn same_state(action: &str, a: &Value, b: &Value) -> Result<bool, AppError> {
+    if action != TAGS {
+        return Ok(a == b);
+    }
+    let uuids = |state: &Value| -> Result<Vec<String>, AppError> {
+        let tags: Vec<TagRef> = parse(Some(state.clone()))?;
+        let mut uuids: Vec<String> = tags.into_iter().map(|t| t.uuid).collect();
+        uuids.sort();
+        Ok(uuids)
+    };
+    Ok(uuids(a)? == uuids(b)?)
+}
@@ -0,0 +1,226 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::UNIX_EPOCH;
+
+use tokio::task::JoinSet;
+
+use crate::analysis::{self, ImageAnalysis};
+use crate::auth::Caller;
+use crate::errors::AppError;
+use crate::handlers::{resolve_original, AppState};
+use crate::history::Edit;
+use crate::keywords::{KeywordRules, TagIndex};
+use crate::metadata;
+use crate::models::{FileIdentity, ImageLocation, ImageMetadata, IngestStatus};
+use crate::queries;
+use crate::scanner;
+use crate::sidecars;
+use crate::thumbnails::SourceFingerprint;
+use crate::unix_now;
+
+/// Background job that fills in per-image data derived from the originals
+/// (placeholders, EXIF/IPTC/XMP metadata, content hashes) for images that
+/// don't have it yet, and links the images to tags named by their XMP
+/// keywords. Each run starts by scanning the galleries directory for new and
+/// moved originals.
+pub struct Ingestor {
+    workers: usize,
+    keyword_rules: KeywordRules,
+    status: Mutex<IngestStatus>,
+}
+
+impl Ingestor {
+    pub fn new(workers: usize, keyword_rules: KeywordRules) -> Self {
+        Ingestor {
+            workers: workers.max(1),
+            keyword_rules,
+            status: Mutex::new(IngestStatus::default()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, IngestStatus> {
+        self.status.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn status(&self) -> IngestStatus {
+        self.lock().clone()
+    }
+
+    /// Kick off a run over one collection (or everything) in the background.
+    /// With `force`, images that were already processed are redone. Imported
+    /// tags are recorded as one edit by `caller`.
+    pub fn start(
+        state: &Arc<AppState>,
+        caller: Caller,
+        collection: Option<String>,
+        force: bool,
+    ) -> Result<IngestStatus, AppError> {
+        let snapshot = {
+            let mut status = state.ingestor.lock();
+            if status.running {
+                return Err(AppError::Conflict("Ingest is already running".into()));
+            }
+            *status = IngestStatus {
+                running: true,
+                collection: collection.clone(),
+                force,
+                started_at: Some(unix_now()),
+                ..IngestStatus::default()
+            };
+            status.clone()
+        };
+
+        let state = Arc::clone(state);
+        tokio::spawn(async move {
+            let result = run(&state, &caller, collection.as_deref(), force).await;
+            let mut status = state.ingestor.lock();
+            status.running = false;
+            status.finished_at = Some(unix_now());
+            match result {
+                Ok(()) => tracing::info!(
+                    "Ingest finished: {} updated, {} failed",
+                    status.updated,
+                    status.failed
+                ),
+                Err(e) => {
+                    tracing::error!("Ingest aborted: {e}");
+                    status.error = Some(e.to_string());
+                }
+            }
+        });
+        Ok(snapshot)
+    }
+}
+
+async fn run(
+    state: &Arc<AppState>,
+    caller: &Caller,
+    collection: Option<&str>,
+    force: bool,
+) -> Result<(), AppError> {
+    let scanned = {
+        let state = Arc::clone(state);
+        let collection = collection.map(str::to_string);
+        tokio::task::spawn_blocking(move || scanner::scan(&state, collection.as_deref()))
+            .await
+            .map_err(|e| AppError::DbError(format!("Scan task failed: {e}")))??
+    };
+    {
+        let mut status = state.ingestor.lock();
+        status.discovered = scanned.discovered;
+        status.moved = scanned.moved;
+    }
+
+    let (images, tag_index) = {
+        let conn = state.db.conn()?;
+        (
+            queries::query_images_pending_ingest(&conn, collection, force)?,
+            TagIndex::new(&queries::query_tag_groups(&conn)?),
+        )
+    };
+    state.ingestor.lock().total = images.len() as u64;
+
+    let mut edit = Edit::new(caller);
+    let mut pending = images.into_iter();
+    let mut tasks: JoinSet<(ImageLocation, Result<Ingested, AppError>)> = JoinSet::new();
+    loop {
+        while tasks.len() < state.ingestor.workers {
+            let Some(image) = pending.next() else { break };
+            let galleries_path = state.galleries_path.clone();
+            tasks.spawn_blocking(move || {
+                let result = resolve_original(&galleries_path, &image.path)
+                    .and_then(|path| ingest_file(&path));
+                (image, result)
+            });
+        }
+        let Some(joined) = tasks.join_next().await else { break };
+        let (image, result) =
+            joined.map_err(|e| AppError::DbError(format!("Ingest task failed: {e}")))?;
+
+        let stored = result.and_then(|ingested| {
+            let conn = state.db.conn()?;
+            queries::store_ingested(
+                &conn,
+                &image.uuid,
+                &ingested.analysis,
+                &ingested.metadata,
+                ingested.taken_at.as_deref(),
+                &ingested.identity,
+            )?;
+            let tag_uuids = state.ingestor.keyword_rules.resolve(&ingested.keywords, &tag_index);
+            let before = queries::query_tags_of_image(&conn, &image.uuid)?;
+            let added = queries::add_image_tags(&conn, &image.uuid, &tag_uuids)?;
+            let after = queries::query_tags_of_image(&conn, &image.uuid)?;
+            edit.tags(&conn, &image.uuid, &before, &after)?;
+            Ok(added)
+        });
+        state.feed.wake();
+        let mut status = state.ingestor.lock();
+        match stored {
+            Ok(tags_added) => {
+                status.updated += 1;
+                status.tags_imported += tags_added as u64;
+            }
+            Err(e) => {
+                tracing::warn!("Failed to ingest {}: {e}", image.path);
+                status.failed += 1;
+            }
+        }
+        status.processed += 1;
+    }
+
+    let changed = {
+        let status = state.ingestor.lock();
+        status.updated + status.discovered + status.moved > 0
+    };
+    if changed {
+        let db = state.db.clone();
+        tokio::task::spawn_blocking(move || db.flush_to_disk())
+            .await
+            .map_err(|e| AppError::DbError(format!("Flush task failed: {e}")))?
+            .map_err(AppError::DbError)?;
+    }
+    Ok(())
+}
+
+struct Ingested {
+    analysis: ImageAnalysis,
+    metadata: ImageMetadata,
+    /// EXIF capture time, or the file's mtime (UTC) when the camera didn't say.
+    taken_at: Option<String>,
+    /// XMP keywords, embedded and from an `.xmp` sidecar.
+    keywords: Vec<String>,
+    identity: FileIdentity,
+}
+
+/// Read an original once and derive everything ingest stores for it. Blocking.
+fn ingest_file(source: &Path) -> Result<Ingested, AppError> {
+    let not_found = |_| AppError::NotFound("File not found on disk".into());
+    let file_meta = std::fs::metadata(source).map_err(not_found)?;
+    let bytes = std::fs::read(source).map_err(not_found)?;
+    let img = image::load_from_memory(&bytes)
+        .map_err(|e| AppError::BadRequest(format!("Failed to decode image: {e}")))?;
+    let metadata = metadata::extract(&bytes);
+    let taken_at = metadata.captured_at.clone().or_else(|| {
+        let modified = file_meta.modified().ok()?;
+        let secs = modified.duration_since(UNIX_EPOCH).ok()?.as_secs();
+        Some(metadata::format_timestamp(secs))
+    });
+    let mut keywords = metadata::embedded_xmp(&bytes)
+        .map(metadata::xmp_keywords)
+        .unwrap_or_default();
+    if let Ok(sidecar) = std::fs::read(sidecars::sidecar_path(source)) {
+        keywords.extend(metadata::xmp_keywords(&sidecar));
+    }
+    Ok(Ingested {
+        analysis: analysis::analyze(&img)?,
+        metadata,
+        taken_at,
+        keywords,
+        identity: FileIdentity {
+            content_hash: scanner::hash_bytes(&bytes),
+            file_mtime: SourceFingerprint::from_metadata(&file_meta).mtime,
+            file_size: bytes.len() as u64,
+        },
+    })
+}
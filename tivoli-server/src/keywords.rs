@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::models::TagGroup;
+
+/// One entry of the keyword mapping file. Exactly one of `keyword` or
+/// `prefix` is set:
+///
+/// - `{"keyword": "Golden Hour", "tag": "golden-hour"}` maps a keyword (or a
+///   full `A|B` path) to a tag by name
+/// - `{"prefix": "Studio|Light", "group": "lighting"}` looks the leaf of any
+///   path under the prefix up in the given tag group
+/// - either form with `"ignore": true` drops matching keywords
+#[derive(Deserialize, Clone)]
+pub struct KeywordRule {
+    pub keyword: Option<String>,
+    pub prefix: Option<String>,
+    pub tag: Option<String>,
+    pub group: Option<String>,
+    #[serde(default)]
+    pub ignore: bool,
+}
+
+/// Rules for mapping embedded XMP keywords onto existing tags. Rules are tried
+/// in order and the first match decides; keywords no rule matches fall back to
+/// matching tag names directly. Tags are never created by import.
+#[derive(Clone, Default)]
+pub struct KeywordRules {
+    rules: Vec<KeywordRule>,
+}
+
+impl KeywordRules {
+    pub fn new(rules: Vec<KeywordRule>) -> Result<Self, String> {
+        for (i, rule) in rules.iter().enumerate() {
+            match (&rule.keyword, &rule.prefix) {
+                (Some(_), None) if rule.ignore || rule.tag.is_some() => {}
+                (None, Some(_)) if rule.ignore || rule.group.is_some() => {}
+                (Some(_), None) => return Err(format!("rule {i}: keyword rules need a tag or ignore")),
+                (None, Some(_)) => return Err(format!("rule {i}: prefix rules need a group or ignore")),
+                _ => return Err(format!("rule {i}: set exactly one of keyword or prefix")),
+            }
+        }
+        Ok(KeywordRules { rules })
+    }
+
+    /// Load rules from a JSON array on disk.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let raw = std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+        let rules = serde_json::from_str(&raw).map_err(|e| format!("{}: {e}", path.display()))?;
+        Self::new(rules)
+    }
+
+    /// Resolve keywords to tag UUIDs, deduplicated, in first-seen order.
+    pub fn resolve(&self, keywords: &[String], index: &TagIndex) -> Vec<String> {
+        let mut uuids: Vec<String> = Vec::new();
+        for keyword in keywords {
+            if let Some(uuid) = self.resolve_one(keyword, index) {
+                if !uuids.contains(&uuid) {
+                    uuids.push(uuid);
+                }
+            }
+        }
+        uuids
+    }
+
+    fn resolve_one(&self, keyword: &str, index: &TagIndex) -> Option<String> {
+        let path = split_path(keyword);
+        let leaf = path.last()?;
+
+        for rule in &self.rules {
+            if let Some(wanted) = &rule.keyword {
+                if split_path(wanted) == path {
+                    return if rule.ignore { None } else { index.find(rule.tag.as_deref()?, None) };
+                }
+            }
+            if let Some(prefix) = &rule.prefix {
+                let prefix = split_path(prefix);
+                if path.len() > prefix.len() && path.starts_with(&prefix) {
+                    return if rule.ignore { None } else { index.find(leaf, rule.group.as_deref()) };
+                }
+            }
+        }
+
+        // Default: `group|tag` paths match within the group, else by name alone
+        if path.len() >= 2 {
+            if let Some(uuid) = index.find(leaf, Some(&path[path.len() - 2])) {
+                return Some(uuid);
+            }
+        }
+        index.find(leaf, None)
+    }
+}
+
+/// Existing tags by normalized name (tag names are unique library-wide).
+pub struct TagIndex {
+    tags: HashMap<String, (String, String)>,
+}
+
+impl TagIndex {
+    pub fn new(groups: &[TagGroup]) -> Self {
+        let tags = groups
+            .iter()
+            .flat_map(|group| {
+                group
+                    .tags
+                    .iter()
+                    .map(|tag| (normalize(&tag.name), (tag.uuid.clone(), normalize(&group.name))))
+            })
+            .collect();
+        TagIndex { tags }
+    }
+
+    fn find(&self, name: &str, group: Option<&str>) -> Option<String> {
+        let (uuid, tag_group) = self.tags.get(&normalize(name))?;
+        match group {
+            Some(group) if normalize(group) != *tag_group => None,
+            _ => Some(uuid.clone()),
+        }
+    }
+}
+
+/// Compare keywords the way tags are named: lowercase, words joined by `-`,
+/// so Lightroom's "Golden Hour" matches the `golden-hour` tag.
+fn normalize(name: &str) -> String {
+    name.split(|c: char| c.is_whitespace() || c == '_' || c == '-')
+        .filter(|part| !part.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn split_path(keyword: &str) -> Vec<String> {
+    keyword
+        .split('|')
+        .map(normalize)
+        .filter(|part| !part.is_empty())
+        .collect()
+}
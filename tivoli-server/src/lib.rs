@@ -1,42 +1,252 @@
+mod analysis;
+mod audit;
+mod auth;
+mod config;
 mod db;
 mod errors;
+mod events;
 mod handlers;
+mod history;
+mod ingest;
+mod keywords;
+mod metadata;
 mod models;
+mod pregenerate;
 mod queries;
+mod scanner;
+mod shares;
+mod sidecars;
+mod sync;
+mod thumbnails;
+mod verify;
+mod watermark;
 
 use std::sync::Arc;
+use std::time::Duration;
 
-use axum::routing::{get, post, put};
+use axum::routing::{delete, get, patch, post, put};
 use axum::Router;
 use handlers::AppState;
+use ingest::Ingestor;
+use keywords::KeywordRules;
+use pregenerate::Pregenerator;
+use thumbnails::ThumbnailCache;
 
-pub fn build_app(db_path: &str, galleries_dir: &str) -> Router {
-    let db = db::InMemoryDb::load_from_disk(db_path);
+pub use auth::{keys_command, users_command};
+pub use config::Config;
+pub use models::Role;
+pub use watermark::{WatermarkConfig, WatermarkOverlay, WatermarkPosition};
 
-    let galleries_path =
-        std::fs::canonicalize(galleries_dir).expect("galleries directory not found");
+/// How often old change events and sync tombstones are dropped.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
 
-    let thumbnail_cache_dir = galleries_path.join(".thumbnails");
-    std::fs::create_dir_all(&thumbnail_cache_dir).expect("failed to create thumbnail cache dir");
+pub fn build_app(db_path: &str, galleries_dir: &str) -> Result<Router, String> {
+    build_app_with_config(&Config::new(db_path, galleries_dir))
+}
+
+/// Fails on configuration the server can't run with, e.g. a watermark that
+/// won't load.
+pub fn build_app_with_config(config: &Config) -> Result<Router, String> {
+    let db = db::InMemoryDb::load_from_disk(&config.db_path);
+    spawn_pruning(db.clone());
+
+    let galleries_path = std::fs::canonicalize(&config.galleries_dir)
+        .map_err(|e| format!("Galleries directory {}: {e}", config.galleries_dir))?;
+
+    let thumbnail_cache_dir = config
+        .thumbnail_cache_dir
+        .clone()
+        .unwrap_or_else(|| galleries_path.join(".thumbnails"));
+    let thumbnails = Arc::new(ThumbnailCache::open(
+        thumbnail_cache_dir,
+        config.thumbnail_cache_max_bytes,
+        config.thumbnail_workers,
+    ));
+
+    let keyword_rules = match &config.keyword_rules_path {
+        Some(path) => KeywordRules::load(path).unwrap_or_else(|e| {
+            tracing::warn!("Ignoring keyword rules: {e}");
+            KeywordRules::default()
+        }),
+        None => KeywordRules::default(),
+    };
+
+    let watermark = config
+        .watermark
+        .as_ref()
+        .map(|watermark| watermark::Watermark::load(watermark).map(Arc::new))
+        .transpose()
+        .map_err(|e| format!("Failed to load watermark: {e}"))?;
+    // Serving clean images to a role meant to get watermarked ones would leak
+    // exactly what the setting protects
+    if !config.watermark_roles.is_empty() && watermark.is_none() {
+        return Err(
+            "TIVOLI_WATERMARK_ROLES needs TIVOLI_WATERMARK_TEXT or TIVOLI_WATERMARK_IMAGE".into(),
+        );
+    }
 
     let state = Arc::new(AppState {
         db,
         galleries_path,
-        thumbnail_cache_dir,
+        thumbnails,
+        pregenerator: Pregenerator::new(
+            config.pregenerate_widths.clone(),
+            config.pregenerate_cpu_percent,
+        ),
+        ingestor: Ingestor::new(config.thumbnail_workers, keyword_rules),
+        sidecars: sidecars::SidecarExporter::default(),
+        verifier: verify::Verifier::default(),
+        watermark,
+        watermark_roles: config.watermark_roles.clone(),
+        feed: events::ChangeFeed::default(),
     });
 
-    Router::new()
+    if config.ingest_on_startup {
+        if let Err(e) = Ingestor::start(&state, auth::Caller::local(), None, false) {
+            tracing::warn!("Could not start ingest: {e}");
+        }
+    }
+
+    if config.pregenerate_on_startup {
+        let widths = config.pregenerate_widths.clone();
+        if let Err(e) = Pregenerator::start(&state, None, widths) {
+            tracing::warn!("Could not start thumbnail pre-generation: {e}");
+        }
+    }
+
+    let router = Router::new()
         .route("/images/search", post(handlers::search_images))
         .route("/images/search/options", post(handlers::search_filter_options))
+        .route("/images/search/timeline", post(handlers::search_timeline))
+        .route("/images/duplicates", get(handlers::list_duplicates))
+        .route("/images/culling", patch(handlers::bulk_update_culling))
         .route("/images/{uuid}", get(handlers::get_image_detail))
         .route("/images/{uuid}/file", get(handlers::get_image_file))
         .route("/images/{uuid}/tags", put(handlers::update_image_tags))
+        .route("/images/{uuid}/tags/history", get(handlers::get_tag_history))
+        .route("/images/{uuid}/tags/revert", post(handlers::revert_image_tags))
+        .route("/undo", post(handlers::undo))
+        .route("/redo", post(handlers::redo))
+        .route("/images/{uuid}/culling", patch(handlers::update_image_culling))
+        .route(
+            "/images/{uuid}/comments",
+            get(handlers::list_comments).post(handlers::create_comment),
+        )
+        .route(
+            "/images/{uuid}/comments/{comment_uuid}",
+            patch(handlers::update_comment).delete(handlers::delete_comment),
+        )
         .route("/collections", get(handlers::list_collections))
         .route("/galleries", get(handlers::list_galleries))
+        .route("/albums", get(handlers::list_albums).post(handlers::create_album))
+        .route(
+            "/albums/{uuid}",
+            get(handlers::get_album)
+                .patch(handlers::update_album)
+                .delete(handlers::delete_album),
+        )
+        .route("/albums/{uuid}/images", post(handlers::add_album_images))
+        .route("/albums/{uuid}/images/{image_uuid}", delete(handlers::remove_album_image))
+        .route("/albums/{uuid}/order", put(handlers::reorder_album))
+        .route(
+            "/saved-searches",
+            get(handlers::list_saved_searches).post(handlers::create_saved_search),
+        )
+        .route(
+            "/saved-searches/{uuid}",
+            get(handlers::get_saved_search)
+                .put(handlers::update_saved_search)
+                .delete(handlers::delete_saved_search),
+        )
+        .route("/saved-searches/{uuid}/images", get(handlers::run_saved_search))
+        .route("/shares", get(handlers::list_shares).post(handlers::create_share))
+        .route("/shares/{id}", delete(handlers::revoke_share))
+        .route("/share/{token}", get(handlers::open_share))
+        .route("/share/{token}/images/{uuid}/file", get(handlers::get_shared_image_file))
         .route("/models", get(handlers::list_models))
-        .route("/tags", get(handlers::list_tags))
-        .with_state(state)
+        .route("/tags", get(handlers::list_tags).post(handlers::create_tag))
+        .route(
+            "/tags/{uuid}",
+            patch(handlers::update_tag).delete(handlers::delete_tag),
+        )
+        .route("/tag-groups", post(handlers::create_tag_group))
+        .route(
+            "/tag-groups/{uuid}",
+            patch(handlers::update_tag_group).delete(handlers::delete_tag_group),
+        )
+        .route("/auth/login", post(handlers::login))
+        .route("/auth/logout", post(handlers::logout))
+        .route("/auth/me", get(handlers::who_am_i))
+        .route("/users", get(handlers::list_users).post(handlers::create_user))
+        .route("/users/{uuid}", patch(handlers::update_user))
+        .route("/users/{uuid}/collections", put(handlers::set_user_collections))
+        .route("/keys", get(handlers::list_keys).post(handlers::create_key))
+        .route("/keys/{id}", delete(handlers::revoke_key))
+        .route("/events", get(handlers::change_feed))
+        .route("/sync", get(handlers::sync))
+        .route("/sync/tags", post(handlers::reconcile_tags))
+        .route("/audit", get(handlers::list_audit_log))
+        .route(
+            "/admin/thumbnails",
+            get(handlers::thumbnail_cache_stats).delete(handlers::purge_thumbnail_cache),
+        )
+        .route(
+            "/admin/ingest",
+            get(handlers::ingest_status).post(handlers::start_ingest),
+        )
+        .route(
+            "/admin/sidecars",
+            get(handlers::sidecar_export_status).post(handlers::start_sidecar_export),
+        )
+        .route(
+            "/admin/verify",
+            get(handlers::verify_status).post(handlers::start_verify),
+        )
+        .route(
+            "/admin/pregenerate",
+            get(handlers::pregenerate_status).post(handlers::start_pregenerate),
+        )
+        .with_state(Arc::clone(&state));
+
+    // Inside CORS so preflight requests never need credentials
+    let router = if config.auth_disabled {
+        tracing::warn!("Authentication is disabled: every request acts as an admin");
+        router.layer(axum::middleware::from_fn(auth::local_caller))
+    } else {
+        router.layer(axum::middleware::from_fn_with_state(state, auth::authenticate))
+    };
+
+    // Every request gets an X-Request-Id (a client-sent one is kept) before
+    // anything else sees it, and the response echoes it
+    Ok(router
+        .layer(tower_http::request_id::PropagateRequestIdLayer::x_request_id())
+        .layer(tower_http::request_id::SetRequestIdLayer::x_request_id(
+            tower_http::request_id::MakeRequestUuid,
+        ))
         .layer(tower_http::compression::CompressionLayer::new())
         .layer(tower_http::cors::CorsLayer::permissive())
-        .layer(tower_http::trace::TraceLayer::new_for_http())
+        .layer(tower_http::trace::TraceLayer::new_for_http()))
+}
+
+/// Prune at startup, then every `PRUNE_INTERVAL` for as long as the server runs.
+fn spawn_pruning(db: db::InMemoryDb) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = db.conn().and_then(|conn| events::prune(&conn)) {
+                tracing::warn!("Could not prune old change events: {e}");
+            }
+            if let Err(e) = db.conn().and_then(|conn| sync::prune(&conn)) {
+                tracing::warn!("Could not prune old sync tombstones: {e}");
+            }
+        }
+    });
+}
+
+/// Seconds since the Unix epoch, as reported in background job status.
+pub(crate) fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
 }
@@ -1,15 +1,40 @@
-use tivoli_server::build_app;
+use tivoli_server::{build_app_with_config, keys_command, users_command, Config};
 
 #[tokio::main]
 async fn main() {
     tracing_subscriber::fmt::init();
 
-    let db_path = std::env::var("TIVOLI_DB_PATH")
-        .unwrap_or_else(|_| "../data/tivoli.db".to_string());
-    let galleries_dir = std::env::var("TIVOLI_GALLERIES_PATH")
-        .unwrap_or_else(|_| "../galleries".to_string());
+    let config = match Config::from_env() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
 
-    let app = build_app(&db_path, &galleries_dir);
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = match args.split_first().map(|(cmd, rest)| (cmd.as_str(), rest)) {
+        Some(("keys", rest)) => Some(keys_command(&config.db_path, rest)),
+        Some(("users", rest)) => Some(users_command(&config.db_path, rest, std::io::stdin().lock())),
+        _ => None,
+    };
+    if let Some(result) = command {
+        match result {
+            Ok(output) => print!("{output}"),
+            Err(e) => {
+                eprintln!("{e}");
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    let app = match build_app_with_config(&config) {
+        Ok(app) => app,
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    };
 
     let port = std::env::var("PORT").unwrap_or_else(|_| "3000".to_string());
     let addr = format!("0.0.0.0:{port}");
@@ -0,0 +1,300 @@
+use exif::{In, Tag, Value};
+
+use crate::models::ImageMetadata;
+
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const PHOTOSHOP_HEADER: &[u8] = b"Photoshop 3.0\0";
+
+pub const NS_DC: &str = "http://purl.org/dc/elements/1.1/";
+pub const NS_RDF: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+const NS_AUX: &str = "http://ns.adobe.com/exif/1.0/aux/";
+pub const NS_LR: &str = "http://ns.adobe.com/lightroom/1.0/";
+
+/// Pull camera, exposure and rights metadata out of a JPEG's EXIF, XMP and
+/// IPTC blocks. Missing or unreadable blocks simply leave fields empty.
+///
+/// Where the same information can live in several places, EXIF wins for
+/// camera data and XMP (then IPTC, then EXIF) for the human-entered text.
+pub fn extract(jpeg: &[u8]) -> ImageMetadata {
+    let mut metadata = ImageMetadata::default();
+    let mut xmp: Option<&[u8]> = None;
+    let mut iptc = IptcFields::default();
+
+    for (marker, payload) in jpeg_segments(jpeg) {
+        match marker {
+            0xE1 if payload.starts_with(EXIF_HEADER) => {
+                read_exif(&payload[EXIF_HEADER.len()..], &mut metadata);
+            }
+            0xE1 if payload.starts_with(XMP_HEADER) => {
+                xmp = Some(&payload[XMP_HEADER.len()..]);
+            }
+            0xED if payload.starts_with(PHOTOSHOP_HEADER) => {
+                iptc = read_iptc(&payload[PHOTOSHOP_HEADER.len()..]);
+            }
+            _ => {}
+        }
+    }
+
+    let xmp = xmp.map(read_xmp).unwrap_or_default();
+    metadata.copyright = xmp.rights.or(iptc.copyright).or(metadata.copyright);
+    metadata.caption = xmp.description.or(iptc.caption).or(metadata.caption);
+    if metadata.lens.is_none() {
+        metadata.lens = xmp.lens;
+    }
+    metadata
+}
+
+/// The XMP packet embedded in a JPEG, if any.
+pub fn embedded_xmp(jpeg: &[u8]) -> Option<&[u8]> {
+    jpeg_segments(jpeg)
+        .into_iter()
+        .find(|(marker, payload)| *marker == 0xE1 && payload.starts_with(XMP_HEADER))
+        .map(|(_, payload)| &payload[XMP_HEADER.len()..])
+}
+
+/// Keywords from an XMP packet: flat `dc:subject` entries and Lightroom's
+/// `lr:hierarchicalSubject` paths (`Parent|Child`), in document order.
+pub fn xmp_keywords(packet: &[u8]) -> Vec<String> {
+    let Some(doc) = parse_xmp(packet) else {
+        return Vec::new();
+    };
+    doc.descendants()
+        .filter(|n| n.has_tag_name((NS_DC, "subject")) || n.has_tag_name((NS_LR, "hierarchicalSubject")))
+        .flat_map(|property| property.descendants().filter(|n| n.has_tag_name((NS_RDF, "li"))))
+        .filter_map(|li| li.text())
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+        .collect()
+}
+
+fn parse_xmp(packet: &[u8]) -> Option<roxmltree::Document<'_>> {
+    let text = std::str::from_utf8(packet).ok()?;
+    // Packets are often padded with whitespace or wrapped in <?xpacket?>
+    match roxmltree::Document::parse(text.trim_end_matches(['\0', ' ', '\n'])) {
+        Ok(doc) => Some(doc),
+        Err(e) => {
+            tracing::debug!("Unparseable XMP packet: {e}");
+            None
+        }
+    }
+}
+
+/// Walk the marker segments of a JPEG up to the start of scan, yielding
+/// `(marker, payload)` pairs.
+fn jpeg_segments(jpeg: &[u8]) -> Vec<(u8, &[u8])> {
+    let mut segments = Vec::new();
+    if !jpeg.starts_with(&[0xFF, 0xD8]) {
+        return segments;
+    }
+    let mut pos = 2;
+    while pos + 4 <= jpeg.len() {
+        if jpeg[pos] != 0xFF {
+            break;
+        }
+        let marker = jpeg[pos + 1];
+        // Fill bytes and standalone markers carry no length
+        if marker == 0xFF {
+            pos += 1;
+            continue;
+        }
+        if (0xD0..=0xD7).contains(&marker) || marker == 0x01 {
+            pos += 2;
+            continue;
+        }
+        // Start of scan: entropy-coded data follows, no more metadata
+        if marker == 0xDA || marker == 0xD9 {
+            break;
+        }
+        let len = usize::from(u16::from_be_bytes([jpeg[pos + 2], jpeg[pos + 3]]));
+        let end = pos + 2 + len;
+        if len < 2 || end > jpeg.len() {
+            break;
+        }
+        segments.push((marker, &jpeg[pos + 4..end]));
+        pos = end;
+    }
+    segments
+}
+
+fn read_exif(tiff: &[u8], metadata: &mut ImageMetadata) {
+    let exif = match exif::Reader::new().read_raw(tiff.to_vec()) {
+        Ok(exif) => exif,
+        Err(e) => {
+            tracing::debug!("Unreadable EXIF block: {e}");
+            return;
+        }
+    };
+    let field = |tag: Tag| exif.get_field(tag, In::PRIMARY).map(|f| &f.value);
+
+    metadata.camera_make = field(Tag::Make).and_then(ascii);
+    metadata.camera_model = field(Tag::Model).and_then(ascii);
+    metadata.lens = field(Tag::LensModel).and_then(ascii);
+    metadata.focal_length = field(Tag::FocalLength).and_then(rational);
+    metadata.aperture = field(Tag::FNumber).and_then(rational);
+    metadata.exposure_time = field(Tag::ExposureTime).and_then(rational);
+    metadata.shutter_speed = metadata.exposure_time.map(format_shutter_speed);
+    metadata.iso = field(Tag::PhotographicSensitivity).and_then(|v| v.get_uint(0));
+    metadata.captured_at = field(Tag::DateTimeOriginal)
+        .or_else(|| field(Tag::DateTime))
+        .and_then(|v| match v {
+            Value::Ascii(parts) => parts.first(),
+            _ => None,
+        })
+        .and_then(|raw| exif::DateTime::from_ascii(raw).ok())
+        .map(|dt| {
+            format!(
+                "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+                dt.year, dt.month, dt.day, dt.hour, dt.minute, dt.second
+            )
+        });
+    metadata.copyright = field(Tag::Copyright).and_then(ascii);
+    metadata.caption = field(Tag::ImageDescription).and_then(ascii);
+}
+
+/// Render an exposure time the way cameras display it: `1/250` below a
+/// second, `2.5"` at or above.
+pub fn format_shutter_speed(seconds: f64) -> String {
+    if seconds > 0.0 && seconds < 1.0 {
+        format!("1/{}", (1.0 / seconds).round())
+    } else {
+        format!("{seconds}\"")
+    }
+}
+
+/// Format Unix seconds as `YYYY-MM-DDTHH:MM:SS` (UTC), matching the shape of
+/// EXIF capture times.
+pub fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    // Civil-from-days, after Howard Hinnant's date algorithms
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+fn ascii(value: &Value) -> Option<String> {
+    let Value::Ascii(parts) = value else {
+        return None;
+    };
+    parts
+        .iter()
+        .map(|p| String::from_utf8_lossy(p).trim_matches(['\0', ' ']).to_string())
+        .find(|s| !s.is_empty())
+}
+
+fn rational(value: &Value) -> Option<f64> {
+    match value {
+        Value::Rational(v) => v.first().filter(|r| r.denom != 0).map(|r| r.to_f64()),
+        _ => value.get_uint(0).map(f64::from),
+    }
+}
+
+#[derive(Default)]
+struct IptcFields {
+    copyright: Option<String>,
+    caption: Option<String>,
+}
+
+/// Parse the IPTC-IIM block (Photoshop image resource 0x0404) out of an APP13
+/// payload.
+fn read_iptc(resources: &[u8]) -> IptcFields {
+    let mut fields = IptcFields::default();
+    let Some(iptc) = photoshop_resource(resources, 0x0404) else {
+        return fields;
+    };
+
+    let mut pos = 0;
+    while pos + 5 <= iptc.len() && iptc[pos] == 0x1C {
+        let (record, dataset) = (iptc[pos + 1], iptc[pos + 2]);
+        let len = usize::from(u16::from_be_bytes([iptc[pos + 3], iptc[pos + 4]]));
+        // Extended-length datasets (high bit set) never hold the text we want
+        if len & 0x8000 != 0 || pos + 5 + len > iptc.len() {
+            break;
+        }
+        let value = String::from_utf8_lossy(&iptc[pos + 5..pos + 5 + len])
+            .trim()
+            .to_string();
+        match (record, dataset) {
+            (2, 116) if !value.is_empty() => fields.copyright = Some(value),
+            (2, 120) if !value.is_empty() => fields.caption = Some(value),
+            _ => {}
+        }
+        pos += 5 + len;
+    }
+    fields
+}
+
+fn photoshop_resource(mut data: &[u8], wanted: u16) -> Option<&[u8]> {
+    while data.len() >= 12 && data.starts_with(b"8BIM") {
+        let id = u16::from_be_bytes([data[4], data[5]]);
+        // Pascal-string name, padded so length byte + name is even
+        let name_len = usize::from(data[6]);
+        let name_total = (1 + name_len + 1) & !1;
+        let size_at = 6 + name_total;
+        let size = u32::from_be_bytes(data.get(size_at..size_at + 4)?.try_into().ok()?) as usize;
+        let start = size_at + 4;
+        let body = data.get(start..start + size)?;
+        if id == wanted {
+            return Some(body);
+        }
+        data = data.get(start + ((size + 1) & !1)..)?;
+    }
+    None
+}
+
+#[derive(Default)]
+struct XmpFields {
+    rights: Option<String>,
+    description: Option<String>,
+    lens: Option<String>,
+}
+
+fn read_xmp(packet: &[u8]) -> XmpFields {
+    let mut fields = XmpFields::default();
+    let Some(doc) = parse_xmp(packet) else {
+        return fields;
+    };
+
+    for node in doc.descendants().filter(|n| n.is_element()) {
+        let name = node.tag_name();
+        match (name.namespace(), name.name()) {
+            (Some(NS_DC), "rights") => fields.rights = lang_alt(node),
+            (Some(NS_DC), "description") => fields.description = lang_alt(node),
+            (Some(NS_AUX), "Lens") => fields.lens = node.text().map(|t| t.trim().to_string()),
+            _ => {}
+        }
+        // Simple properties are frequently written as attributes instead
+        if let Some(lens) = node.attribute((NS_AUX, "Lens")) {
+            fields.lens = Some(lens.trim().to_string());
+        }
+    }
+    fields
+}
+
+/// Value of an `rdf:Alt` language alternative, preferring `x-default`.
+fn lang_alt(property: roxmltree::Node) -> Option<String> {
+    let items: Vec<roxmltree::Node> = property
+        .descendants()
+        .filter(|n| n.has_tag_name((NS_RDF, "li")))
+        .collect();
+    items
+        .iter()
+        .find(|n| n.attribute(("http://www.w3.org/XML/1998/namespace", "lang")) == Some("x-default"))
+        .or(items.first())
+        .and_then(|n| n.text())
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+}
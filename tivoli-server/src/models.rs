@@ -5,25 +5,123 @@ use serde::{Deserialize, Serialize};
 #[derive(Deserialize)]
 pub struct SearchRequest {
     pub filters: Vec<FilterClause>,
+    /// Applied in order; browse order (collection, gallery, path) breaks ties.
+    #[serde(default)]
+    pub sort: Vec<SortClause>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SortClause {
+    pub field: SortField,
+    #[serde(default)]
+    pub direction: SortDirection,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    Path,
+    TakenAt,
+    Rating,
+    ColorLabel,
+    Flag,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
 }
 
 #[derive(Deserialize)]
+pub struct TimelineRequest {
+    pub filters: Vec<FilterClause>,
+    #[serde(default)]
+    pub granularity: TimelineGranularity,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TimelineGranularity {
+    Year,
+    #[default]
+    Month,
+    Day,
+}
+
+impl TimelineGranularity {
+    /// Length of the `taken_at` prefix that identifies a bucket.
+    pub fn prefix_len(self) -> usize {
+        match self {
+            TimelineGranularity::Year => 4,
+            TimelineGranularity::Month => 7,
+            TimelineGranularity::Day => 10,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
 pub struct FilterClause {
     pub field: FilterField,
     pub op: FilterOp,
     pub value: FilterValue,
+    /// Hamming distance for `similar_to`, CIELAB ΔE for `color`; other
+    /// fields reject it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_distance: Option<u32>,
 }
 
-#[derive(Deserialize, PartialEq)]
+#[derive(Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum FilterField {
     Collection,
     Gallery,
     Models,
     Tags,
+    Camera,
+    Lens,
+    FocalLength,
+    Aperture,
+    Iso,
+    CapturedAt,
+    TakenAt,
+    SimilarTo,
+    Color,
+    Rating,
+    ColorLabel,
+    Flag,
+    HasComments,
+    Album,
 }
 
-#[derive(Deserialize, PartialEq)]
+impl FilterField {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FilterField::Collection => "collection",
+            FilterField::Gallery => "gallery",
+            FilterField::Models => "models",
+            FilterField::Tags => "tags",
+            FilterField::Camera => "camera",
+            FilterField::Lens => "lens",
+            FilterField::FocalLength => "focal_length",
+            FilterField::Aperture => "aperture",
+            FilterField::Iso => "iso",
+            FilterField::CapturedAt => "captured_at",
+            FilterField::TakenAt => "taken_at",
+            FilterField::SimilarTo => "similar_to",
+            FilterField::Color => "color",
+            FilterField::Rating => "rating",
+            FilterField::ColorLabel => "color_label",
+            FilterField::Flag => "flag",
+            FilterField::HasComments => "has_comments",
+            FilterField::Album => "album",
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
 pub enum FilterOp {
     Eq,
@@ -31,13 +129,38 @@ pub enum FilterOp {
     AllOf,
     Exact,
     NoneOf,
+    Gt,
+    Gte,
+    Lt,
+    Lte,
+    Between,
 }
 
-#[derive(Deserialize)]
+impl FilterOp {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FilterOp::Eq => "eq",
+            FilterOp::AnyOf => "any_of",
+            FilterOp::AllOf => "all_of",
+            FilterOp::Exact => "exact",
+            FilterOp::NoneOf => "none_of",
+            FilterOp::Gt => "gt",
+            FilterOp::Gte => "gte",
+            FilterOp::Lt => "lt",
+            FilterOp::Lte => "lte",
+            FilterOp::Between => "between",
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum FilterValue {
+    Bool(bool),
     Single(String),
+    Number(f64),
     Multiple(Vec<String>),
+    Numbers(Vec<f64>),
 }
 
 impl FilterValue {
@@ -53,6 +176,25 @@ impl FilterValue {
         match self {
             FilterValue::Single(s) => vec![s.as_str()],
             FilterValue::Multiple(v) => v.iter().map(|s| s.as_str()).collect(),
+            FilterValue::Bool(_) | FilterValue::Number(_) | FilterValue::Numbers(_) => Vec::new(),
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            FilterValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// All values as SQL parameters, numbers included.
+    pub fn as_scalars(&self) -> Vec<String> {
+        match self {
+            FilterValue::Bool(b) => vec![b.to_string()],
+            FilterValue::Single(s) => vec![s.clone()],
+            FilterValue::Number(n) => vec![n.to_string()],
+            FilterValue::Multiple(v) => v.clone(),
+            FilterValue::Numbers(v) => v.iter().map(|n| n.to_string()).collect(),
         }
     }
 }
@@ -64,6 +206,400 @@ pub struct UpdateTagsRequest {
     pub tag_uuids: Vec<String>,
 }
 
+// --- Tag vocabulary (admin) ---
+
+#[derive(Deserialize)]
+pub struct TagGroupRequest {
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+pub struct NewTagRequest {
+    pub name: String,
+    pub group_uuid: String,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateTagRequest {
+    pub name: Option<String>,
+    /// Move the tag to another group.
+    pub group_uuid: Option<String>,
+}
+
+// --- Culling ---
+
+/// Fields left out are unchanged; at least one must be set.
+#[derive(Deserialize)]
+pub struct UpdateCullingRequest {
+    pub rating: Option<u8>,
+    pub color_label: Option<ColorLabel>,
+    pub flag: Option<Flag>,
+}
+
+#[derive(Deserialize)]
+pub struct BulkUpdateCullingRequest {
+    pub image_uuids: Vec<String>,
+    #[serde(flatten)]
+    pub update: UpdateCullingRequest,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorLabel {
+    None,
+    Red,
+    Yellow,
+    Green,
+    Blue,
+    Purple,
+}
+
+impl ColorLabel {
+    /// In sort order.
+    pub const ALL: [ColorLabel; 6] = [
+        ColorLabel::None,
+        ColorLabel::Red,
+        ColorLabel::Yellow,
+        ColorLabel::Green,
+        ColorLabel::Blue,
+        ColorLabel::Purple,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorLabel::None => "none",
+            ColorLabel::Red => "red",
+            ColorLabel::Yellow => "yellow",
+            ColorLabel::Green => "green",
+            ColorLabel::Blue => "blue",
+            ColorLabel::Purple => "purple",
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Flag {
+    Reject,
+    None,
+    Pick,
+}
+
+impl Flag {
+    /// In sort order, so descending puts picks first.
+    pub const ALL: [Flag; 3] = [Flag::Reject, Flag::None, Flag::Pick];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Flag::Reject => "reject",
+            Flag::None => "none",
+            Flag::Pick => "pick",
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct BulkUpdateResult {
+    pub updated: u32,
+}
+
+// --- Comments ---
+
+#[derive(Deserialize)]
+pub struct NewCommentRequest {
+    pub body: String,
+    /// Comment being replied to, on the same image.
+    pub parent_uuid: Option<String>,
+    pub region: Option<CommentRegion>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateCommentRequest {
+    pub body: String,
+}
+
+/// Area of the image a comment refers to, as fractions of its width and
+/// height from the top-left corner. Zero size marks a point.
+#[derive(Deserialize, Serialize, Clone, Copy)]
+pub struct CommentRegion {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+#[derive(Serialize)]
+pub struct Comment {
+    pub uuid: String,
+    pub image_uuid: String,
+    pub parent_uuid: Option<String>,
+    pub author: String,
+    pub body: String,
+    pub region: Option<CommentRegion>,
+    pub created_at: u64,
+    pub updated_at: Option<u64>,
+}
+
+// --- Albums ---
+
+#[derive(Deserialize)]
+pub struct NewAlbumRequest {
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub image_uuids: Vec<String>,
+}
+
+/// Fields left out are unchanged.
+#[derive(Deserialize)]
+pub struct UpdateAlbumRequest {
+    pub name: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AddAlbumImagesRequest {
+    pub image_uuids: Vec<String>,
+    /// Insert before this index; appended when absent or past the end.
+    pub position: Option<usize>,
+}
+
+#[derive(Deserialize)]
+pub struct ReorderAlbumRequest {
+    pub image_uuids: Vec<String>,
+}
+
+// --- Saved searches ---
+
+#[derive(Deserialize)]
+pub struct SavedSearchRequest {
+    pub name: String,
+    pub filters: Vec<FilterClause>,
+    #[serde(default)]
+    pub sort: Vec<SortClause>,
+}
+
+// --- Users and sessions ---
+
+/// What a user or API key may do. Each role includes the ones before it.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Browse, search and download.
+    Viewer,
+    /// Also tag, cull, comment, and curate albums and saved searches.
+    Tagger,
+    /// Also manage users, the tag vocabulary and the `/admin` jobs.
+    Admin,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::Viewer, Role::Tagger, Role::Admin];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Tagger => "tagger",
+            Role::Admin => "admin",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Role> {
+        Role::ALL.into_iter().find(|r| r.name() == name)
+    }
+}
+
+/// Collections a caller may see. Images in other collections, and the
+/// models and facets that come from them, behave as if they don't exist.
+#[derive(Clone)]
+pub enum CollectionAccess {
+    All,
+    Only(Vec<String>),
+}
+
+impl CollectionAccess {
+    /// `None` is unrestricted, as stored in the `collections` column.
+    pub fn from_list(collections: Option<Vec<String>>) -> CollectionAccess {
+        collections.map_or(CollectionAccess::All, CollectionAccess::Only)
+    }
+
+    pub fn to_list(&self) -> Option<Vec<String>> {
+        match self {
+            CollectionAccess::All => None,
+            CollectionAccess::Only(names) => Some(names.clone()),
+        }
+    }
+
+    pub fn allows(&self, collection: &str) -> bool {
+        match self {
+            CollectionAccess::All => true,
+            CollectionAccess::Only(names) => names.iter().any(|n| n == collection),
+        }
+    }
+
+    /// Collections both grant.
+    pub fn intersect(&self, other: &CollectionAccess) -> CollectionAccess {
+        match (self, other) {
+            (CollectionAccess::All, access) | (access, CollectionAccess::All) => access.clone(),
+            (CollectionAccess::Only(names), other) => {
+                CollectionAccess::Only(names.iter().filter(|n| other.allows(n)).cloned().collect())
+            }
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct LoginRequest {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Deserialize)]
+pub struct NewUserRequest {
+    pub username: String,
+    pub password: String,
+    pub role: Role,
+    /// Collections the user may see; omitted or null for all of them.
+    #[serde(default)]
+    pub collections: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+pub struct UserCollectionsRequest {
+    /// `null` lifts the restriction.
+    pub collections: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+pub struct UpdateUserRequest {
+    pub role: Option<Role>,
+    pub password: Option<String>,
+    pub disabled: Option<bool>,
+}
+
+// --- Share links ---
+
+/// What a share link opens, tagged by `kind`.
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ShareTarget {
+    Gallery { collection: String, gallery: String },
+    Album { album_uuid: String },
+    SavedSearch { saved_search_uuid: String },
+}
+
+#[derive(Deserialize)]
+pub struct NewShareRequest {
+    #[serde(flatten)]
+    pub target: ShareTarget,
+    /// Lifetime in seconds; seven days when omitted.
+    pub expires_in: Option<u64>,
+    /// Whether full-size originals can be fetched; defaults to true.
+    pub allow_download: Option<bool>,
+    #[serde(default)]
+    pub watermark: bool,
+}
+
+// --- Audit log ---
+
+#[derive(Deserialize)]
+pub struct AuditQuery {
+    pub image: Option<String>,
+    /// Actor name, or id such as `user:<uuid>` or `key:<id>`.
+    pub user: Option<String>,
+    /// Exact action, or a prefix such as `album` for every `album.*`.
+    pub action: Option<String>,
+    /// UUID or id of the changed record.
+    pub target: Option<String>,
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+    /// Only entries older than this id, for paging.
+    pub before: Option<i64>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct SyncParams {
+    /// `seq` from the previous sync; leave out for everything.
+    pub since: Option<i64>,
+}
+
+/// Tag edits made offline, against the sync point the client had.
+#[derive(Deserialize)]
+pub struct ReconcileTagsRequest {
+    /// `seq` of the client's last sync before making the edits.
+    pub since: i64,
+    #[serde(default)]
+    pub policy: ConflictPolicy,
+    pub operations: Vec<TagOperation>,
+}
+
+/// What to do with an offline edit to a pair someone else changed since.
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// The later of the two changes stands.
+    #[default]
+    LastWriterWins,
+    /// The server's change stands.
+    Reject,
+}
+
+#[derive(Deserialize)]
+pub struct TagOperation {
+    /// The client's own id for the operation, echoed in its result.
+    pub id: Option<String>,
+    pub image_uuid: String,
+    pub tag_uuid: String,
+    pub op: TagOperationKind,
+    /// When the edit was made, in unix seconds.
+    pub at: u64,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum TagOperationKind {
+    Add,
+    Remove,
+}
+
+#[derive(Deserialize)]
+pub struct ChangeFeedParams {
+    /// For clients that can't set the `Last-Event-ID` header.
+    pub last_event_id: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct RevertTagsRequest {
+    /// A tag history entry; the image goes back to its tags before it.
+    pub entry_id: i64,
+}
+
+// --- Admin jobs ---
+
+#[derive(Deserialize)]
+pub struct PregenerateRequest {
+    pub collection: Option<String>,
+    pub widths: Option<Vec<u32>>,
+}
+
+#[derive(Deserialize)]
+pub struct SidecarExportRequest {
+    pub collection: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct VerifyRequest {
+    pub collection: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct IngestRequest {
+    pub collection: Option<String>,
+    #[serde(default)]
+    pub force: bool,
+}
+
 // --- Query parameter structs ---
 
 #[derive(Deserialize)]
@@ -76,6 +612,19 @@ pub struct ImageFileParams {
     pub w: Option<u32>,
 }
 
+#[derive(Deserialize)]
+pub struct DuplicateParams {
+    pub collection: Option<String>,
+    pub max_distance: Option<u32>,
+}
+
+#[derive(Deserialize)]
+pub struct ThumbnailPurgeParams {
+    pub image: Option<String>,
+    pub collection: Option<String>,
+    pub gallery: Option<String>,
+}
+
 // --- Response structs ---
 
 #[derive(Serialize)]
@@ -87,7 +636,7 @@ pub struct FilterOptions {
     pub tags: Vec<TagRef>,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct TagRef {
     pub uuid: String,
     pub name: String,
@@ -108,6 +657,258 @@ pub struct GallerySummary {
     pub image_count: u32,
 }
 
+#[derive(Serialize)]
+pub struct AlbumSummary {
+    pub uuid: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub image_count: u32,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Serialize)]
+pub struct AlbumDetail {
+    pub uuid: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_at: u64,
+    pub updated_at: u64,
+    /// In album order.
+    pub images: Vec<ImageRow>,
+}
+
+#[derive(Serialize)]
+pub struct SavedSearch {
+    pub uuid: String,
+    pub name: String,
+    pub filters: Vec<FilterClause>,
+    pub sort: Vec<SortClause>,
+    pub created_at: u64,
+    pub updated_at: u64,
+}
+
+#[derive(Serialize)]
+pub struct ShareLink {
+    pub id: String,
+    pub token: String,
+    /// `/share/<token>`, relative to the server root.
+    pub path: String,
+    #[serde(flatten)]
+    pub target: ShareTarget,
+    pub allow_download: bool,
+    pub watermark: bool,
+    pub created_by: String,
+    pub created_at: u64,
+    pub expires_at: u64,
+    pub revoked_at: Option<u64>,
+}
+
+/// What a share link recipient sees.
+#[derive(Serialize)]
+pub struct SharedView {
+    /// Gallery, album or saved search name.
+    pub name: String,
+    pub kind: &'static str,
+    pub expires_at: u64,
+    pub allow_download: bool,
+    pub watermark: bool,
+    pub images: Vec<ImageRow>,
+}
+
+#[derive(Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub at: u64,
+    pub request_id: String,
+    pub actor: String,
+    pub actor_id: String,
+    pub action: String,
+    pub target_kind: String,
+    pub target_id: Option<String>,
+    pub image_uuid: Option<String>,
+    pub before: Option<serde_json::Value>,
+    pub after: Option<serde_json::Value>,
+    /// The undoable edit this change belongs to, if any.
+    pub operation_id: Option<i64>,
+}
+
+/// Rows changed since a sync point, and rows deleted since, for a client
+/// keeping a local mirror.
+#[derive(Serialize)]
+pub struct SyncResponse {
+    /// Where this response leaves off; pass it as `since` next time.
+    pub seq: i64,
+    /// Every row rather than changes: the client should replace its mirror.
+    pub full: bool,
+    pub images: Vec<SyncImage>,
+    pub tag_groups: Vec<SyncTagGroup>,
+    pub tags: Vec<SyncTag>,
+    pub models: Vec<SyncModel>,
+    pub image_tags: Vec<SyncImageTag>,
+    pub image_models: Vec<SyncImageModel>,
+    pub tombstones: Vec<SyncTombstone>,
+}
+
+#[derive(Serialize)]
+pub struct SyncImage {
+    pub uuid: String,
+    pub path: String,
+    pub collection: String,
+    pub gallery: String,
+    pub width: u32,
+    pub height: u32,
+    pub file_size: i64,
+    pub blurhash: Option<String>,
+    pub dominant_color: Option<String>,
+    pub taken_at: Option<String>,
+    pub rating: u8,
+    pub color_label: String,
+    pub flag: String,
+}
+
+#[derive(Serialize)]
+pub struct SyncTagGroup {
+    pub uuid: String,
+    pub name: String,
+}
+
+#[derive(Serialize)]
+pub struct SyncTag {
+    pub uuid: String,
+    pub name: String,
+    pub group_uuid: String,
+}
+
+#[derive(Serialize)]
+pub struct SyncModel {
+    pub uuid: String,
+    pub name: String,
+    pub collection: String,
+}
+
+#[derive(Serialize)]
+pub struct SyncImageTag {
+    pub image_uuid: String,
+    pub tag_uuid: String,
+}
+
+#[derive(Serialize)]
+pub struct SyncImageModel {
+    pub image_uuid: String,
+    pub model_uuid: String,
+}
+
+/// A deleted row, by table and primary key.
+#[derive(Serialize)]
+pub struct SyncTombstone {
+    pub table: String,
+    pub key: serde_json::Value,
+    pub seq: i64,
+}
+
+/// The outcome of one offline tag operation, in the order they were sent.
+#[derive(Serialize)]
+pub struct TagOperationResult {
+    pub id: Option<String>,
+    pub status: TagOperationStatus,
+    /// Why a `failed` operation failed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// When the server's copy of the pair last changed, for `conflict`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server_at: Option<u64>,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TagOperationStatus {
+    Applied,
+    /// The image already had, or already lacked, the tag.
+    Unchanged,
+    /// Someone else changed the pair since; their change was kept.
+    Conflict,
+    Failed,
+}
+
+/// One change to an image's tags.
+#[derive(Serialize)]
+pub struct TagHistoryEntry {
+    pub id: i64,
+    pub at: u64,
+    pub actor: String,
+    pub request_id: String,
+    pub before: Vec<TagRef>,
+    pub after: Vec<TagRef>,
+}
+
+#[derive(Serialize)]
+pub struct TagRevertResult {
+    pub tags: Vec<TagRef>,
+    /// Tags of the restored state that have been deleted since.
+    pub missing: Vec<TagRef>,
+}
+
+/// An operation that was undone or redone, and the images it touched.
+#[derive(Serialize)]
+pub struct UndoResult {
+    pub operation_id: i64,
+    pub request_id: String,
+    pub at: u64,
+    pub images: Vec<String>,
+}
+
+/// An image's culling fields, as recorded in the audit log.
+#[derive(Serialize, Deserialize)]
+pub struct CullingState {
+    pub rating: u8,
+    pub color_label: String,
+    pub flag: String,
+}
+
+/// An album's own fields and membership, as recorded in the audit log.
+#[derive(Serialize)]
+pub struct AlbumSnapshot {
+    pub name: String,
+    pub description: Option<String>,
+    /// Every member in album order, whatever the caller could see.
+    pub image_uuids: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct User {
+    pub uuid: String,
+    pub username: String,
+    pub role: Role,
+    /// Collections the user may see, or `null` for all of them.
+    pub collections: Option<Vec<String>>,
+    pub created_at: u64,
+    pub disabled: bool,
+}
+
+#[derive(Serialize)]
+pub struct LoginResponse {
+    /// Bearer token for `Authorization: Bearer <token>`.
+    pub token: String,
+    pub expires_at: u64,
+    pub user: User,
+}
+
+/// Who a request is authenticated as.
+#[derive(Serialize)]
+pub struct WhoAmI {
+    pub name: String,
+    pub role: Role,
+    /// `user`, `api_key`, or `local` when authentication is disabled.
+    pub kind: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_uuid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_id: Option<String>,
+    /// Collections visible to this caller, or `null` for all of them.
+    pub collections: Option<Vec<String>>,
+}
+
 #[derive(Serialize)]
 pub struct Model {
     pub uuid: String,
@@ -128,6 +929,165 @@ pub struct Tag {
     pub name: String,
 }
 
+#[derive(Serialize)]
+pub struct ThumbnailCacheStats {
+    pub path: String,
+    pub max_bytes: u64,
+    pub writable: bool,
+    pub total_bytes: u64,
+    pub entry_count: u64,
+    pub image_count: u64,
+    pub hits: u64,
+    pub misses: u64,
+    pub renders: u64,
+    pub evictions: u64,
+}
+
+#[derive(Serialize)]
+pub struct ThumbnailPurgeResult {
+    pub removed_files: u64,
+    pub removed_bytes: u64,
+}
+
+#[derive(Serialize, Clone, Default)]
+pub struct PregenerateStatus {
+    pub running: bool,
+    pub collection: Option<String>,
+    pub widths: Vec<u32>,
+    pub total: u64,
+    pub processed: u64,
+    pub rendered: u64,
+    pub already_cached: u64,
+    pub failed: u64,
+    pub started_at: Option<u64>,
+    pub finished_at: Option<u64>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Clone, Default)]
+pub struct IngestStatus {
+    pub running: bool,
+    pub collection: Option<String>,
+    pub force: bool,
+    pub total: u64,
+    pub processed: u64,
+    pub updated: u64,
+    pub failed: u64,
+    /// New originals found on disk and added to the library.
+    pub discovered: u64,
+    /// Originals found at a new path and matched to their image by content.
+    pub moved: u64,
+    /// Image-tag links added from embedded or sidecar XMP keywords.
+    pub tags_imported: u64,
+    pub started_at: Option<u64>,
+    pub finished_at: Option<u64>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Clone, Default)]
+pub struct VerifyStatus {
+    pub running: bool,
+    pub collection: Option<String>,
+    pub total: u64,
+    pub processed: u64,
+    pub ok: u64,
+    /// Images ingest hasn't hashed yet, so there's nothing to compare.
+    pub unhashed: u64,
+    pub issues: Vec<VerifyIssue>,
+    pub started_at: Option<u64>,
+    pub finished_at: Option<u64>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct VerifyIssue {
+    pub uuid: String,
+    pub path: String,
+    pub problem: VerifyProblem,
+    pub expected_hash: String,
+    pub actual_hash: Option<String>,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum VerifyProblem {
+    /// The file is gone.
+    Missing,
+    /// Contents and modification time changed: edited or replaced.
+    Modified,
+    /// Contents changed but the modification time didn't: likely bit rot or
+    /// a tool that preserves timestamps.
+    Corrupted,
+}
+
+impl VerifyProblem {
+    pub fn name(self) -> &'static str {
+        match self {
+            VerifyProblem::Missing => "missing",
+            VerifyProblem::Modified => "modified",
+            VerifyProblem::Corrupted => "corrupted",
+        }
+    }
+}
+
+#[derive(Serialize, Clone, Default)]
+pub struct SidecarExportStatus {
+    pub running: bool,
+    pub collection: Option<String>,
+    pub total: u64,
+    pub processed: u64,
+    pub written: u64,
+    pub unchanged: u64,
+    pub failed: u64,
+    pub started_at: Option<u64>,
+    pub finished_at: Option<u64>,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct DuplicateCluster {
+    pub images: Vec<ImageRow>,
+}
+
+#[derive(Serialize)]
+pub struct Timeline {
+    pub granularity: TimelineGranularity,
+    pub buckets: Vec<TimelineBucket>,
+    /// Matching images with no known capture time (not yet ingested).
+    pub undated_count: u32,
+}
+
+#[derive(Serialize)]
+pub struct TimelineBucket {
+    /// `YYYY`, `YYYY-MM` or `YYYY-MM-DD` depending on granularity.
+    pub period: String,
+    pub image_count: u32,
+}
+
+#[derive(Serialize)]
+pub struct PaletteColor {
+    /// `#rrggbb`
+    pub color: String,
+    /// Share of the image's pixels, 0..1.
+    pub weight: f64,
+}
+
+/// Camera and rights metadata read from EXIF/IPTC/XMP at ingest.
+#[derive(Serialize, Default)]
+pub struct ImageMetadata {
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    pub lens: Option<String>,
+    pub focal_length: Option<f64>,
+    pub aperture: Option<f64>,
+    pub exposure_time: Option<f64>,
+    pub shutter_speed: Option<String>,
+    pub iso: Option<u32>,
+    pub captured_at: Option<String>,
+    pub copyright: Option<String>,
+    pub caption: Option<String>,
+}
+
 // --- Internal types ---
 
 #[derive(Serialize)]
@@ -139,6 +1099,12 @@ pub struct ImageRow {
     pub width: u32,
     pub height: u32,
     pub file_size: i64,
+    pub blurhash: Option<String>,
+    pub dominant_color: Option<String>,
+    pub rating: u8,
+    pub color_label: String,
+    pub flag: String,
+    pub comment_count: u32,
 }
 
 #[derive(Serialize)]
@@ -150,6 +1116,80 @@ pub struct ImageDetail {
     pub width: u32,
     pub height: u32,
     pub file_size: i64,
+    pub blurhash: Option<String>,
+    pub dominant_color: Option<String>,
+    pub rating: u8,
+    pub color_label: String,
+    pub flag: String,
+    pub comment_count: u32,
+    pub palette: Vec<PaletteColor>,
+    pub metadata: Option<ImageMetadata>,
     pub models: Vec<Model>,
     pub tags: Vec<TagRef>,
 }
+
+/// A newly discovered original, before ingest fills in the rest.
+pub struct NewImage {
+    pub uuid: String,
+    pub path: String,
+    pub collection: String,
+    pub gallery: String,
+    pub width: u32,
+    pub height: u32,
+    pub file_size: u64,
+    pub content_hash: String,
+    pub file_mtime: u64,
+}
+
+/// What the bytes of an original looked like when it was last ingested.
+pub struct FileIdentity {
+    pub content_hash: String,
+    pub file_mtime: u64,
+    pub file_size: u64,
+}
+
+pub struct ImageFingerprint {
+    pub uuid: String,
+    pub path: String,
+    pub identity: Option<FileIdentity>,
+}
+
+#[derive(Serialize)]
+pub struct ApiKey {
+    pub id: String,
+    pub name: String,
+    pub role: String,
+    pub collections: Option<Vec<String>>,
+    pub created_at: u64,
+    pub last_used_at: Option<u64>,
+    pub revoked_at: Option<u64>,
+}
+
+/// A key just created, with the full key shown this once.
+#[derive(Serialize)]
+pub struct NewApiKey {
+    pub key: String,
+    #[serde(flatten)]
+    pub api_key: ApiKey,
+}
+
+#[derive(Deserialize)]
+pub struct NewApiKeyRequest {
+    pub name: String,
+    #[serde(default = "default_key_role")]
+    pub role: Role,
+    /// Collections the key may see; omitted or null for all of them.
+    #[serde(default)]
+    pub collections: Option<Vec<String>>,
+}
+
+fn default_key_role() -> Role {
+    Role::Viewer
+}
+
+pub struct ImageLocation {
+    pub uuid: String,
+    pub path: String,
+    pub collection: String,
+    pub gallery: String,
+}
@@ -0,0 +1,177 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::errors::AppError;
+use crate::handlers::{resolve_original, AppState};
+use crate::models::PregenerateStatus;
+use crate::queries;
+use crate::thumbnails::{self, SourceFingerprint};
+use crate::unix_now;
+
+/// Background job that renders thumbnails ahead of the first scroll through a
+/// gallery. One run at a time; progress is polled through `status`.
+pub struct Pregenerator {
+    default_widths: Vec<u32>,
+    cpu_percent: u32,
+    status: Mutex<PregenerateStatus>,
+}
+
+impl Pregenerator {
+    pub fn new(default_widths: Vec<u32>, cpu_percent: u32) -> Self {
+        Pregenerator {
+            default_widths,
+            cpu_percent: cpu_percent.clamp(1, 100),
+            status: Mutex::new(PregenerateStatus::default()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, PregenerateStatus> {
+        self.status.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn default_widths(&self) -> &[u32] {
+        &self.default_widths
+    }
+
+    pub fn status(&self) -> PregenerateStatus {
+        self.lock().clone()
+    }
+
+    /// Kick off a run over one collection (or everything) in the background.
+    pub fn start(
+        state: &Arc<AppState>,
+        collection: Option<String>,
+        widths: Vec<u32>,
+    ) -> Result<PregenerateStatus, AppError> {
+        let mut widths: Vec<u32> = widths.into_iter().map(thumbnails::clamp_width).collect();
+        widths.sort_unstable();
+        widths.dedup();
+
+        let snapshot = {
+            let mut status = state.pregenerator.lock();
+            if status.running {
+                return Err(AppError::Conflict(
+                    "Thumbnail pre-generation is already running".into(),
+                ));
+            }
+            *status = PregenerateStatus {
+                running: true,
+                collection: collection.clone(),
+                widths: widths.clone(),
+                started_at: Some(unix_now()),
+                ..PregenerateStatus::default()
+            };
+            status.clone()
+        };
+
+        let state = Arc::clone(state);
+        tokio::spawn(async move {
+            let result = run(&state, collection.as_deref(), &widths).await;
+            let mut status = state.pregenerator.lock();
+            status.running = false;
+            status.finished_at = Some(unix_now());
+            match result {
+                Ok(()) => tracing::info!(
+                    "Thumbnail pre-generation finished: {} rendered, {} already cached, {} failed",
+                    status.rendered,
+                    status.already_cached,
+                    status.failed
+                ),
+                Err(e) => {
+                    tracing::error!("Thumbnail pre-generation aborted: {e}");
+                    status.error = Some(e.to_string());
+                }
+            }
+        });
+        Ok(snapshot)
+    }
+}
+
+async fn run(state: &Arc<AppState>, collection: Option<&str>, widths: &[u32]) -> Result<(), AppError> {
+    let mut images = {
+        let conn = state.db.conn()?;
+        queries::query_image_locations(&conn, collection)?
+    };
+
+    // Newest galleries first: a gallery directory's mtime moves whenever files
+    // are added to it, so it's a good proxy for "recently added"
+    let mut gallery_mtimes: HashMap<(String, String), SystemTime> = HashMap::new();
+    for image in &images {
+        let key = (image.collection.clone(), image.gallery.clone());
+        if gallery_mtimes.contains_key(&key) {
+            continue;
+        }
+        let dir = state.galleries_path.join(&key.0).join(&key.1);
+        let mtime = tokio::fs::metadata(&dir)
+            .await
+            .and_then(|m| m.modified())
+            .unwrap_or(UNIX_EPOCH);
+        gallery_mtimes.insert(key, mtime);
+    }
+    // Stable sort keeps the query's collection/gallery/path order within ties
+    images.sort_by_cached_key(|image| {
+        Reverse(gallery_mtimes[&(image.collection.clone(), image.gallery.clone())])
+    });
+
+    state.pregenerator.lock().total = (images.len() * widths.len()) as u64;
+    let cpu_percent = state.pregenerator.cpu_percent;
+
+    for image in images {
+        let galleries_path = state.galleries_path.clone();
+        let relative = image.path.clone();
+        let source = tokio::task::spawn_blocking(move || {
+            resolve_original(&galleries_path, &relative).and_then(|path| {
+                let meta = std::fs::metadata(&path)
+                    .map_err(|_| AppError::NotFound("File not found on disk".into()))?;
+                Ok((path, SourceFingerprint::from_metadata(&meta)))
+            })
+        })
+        .await
+        .unwrap_or_else(|e| Err(AppError::DbError(format!("File lookup failed: {e}"))));
+        let (path, fingerprint) = match source {
+            Ok(found) => found,
+            Err(e) => {
+                tracing::warn!("Skipping pre-generation for {}: {e}", image.path);
+                let mut status = state.pregenerator.lock();
+                status.failed += widths.len() as u64;
+                status.processed += widths.len() as u64;
+                continue;
+            }
+        };
+
+        for &width in widths {
+            if state.thumbnails.contains(&image.uuid, width, fingerprint) {
+                let mut status = state.pregenerator.lock();
+                status.already_cached += 1;
+                status.processed += 1;
+                continue;
+            }
+
+            let started = Instant::now();
+            let rendered = state
+                .thumbnails
+                .get_or_render(&image.uuid, width, &path, fingerprint, None)
+                .await;
+            let busy = started.elapsed();
+            {
+                let mut status = state.pregenerator.lock();
+                match rendered {
+                    Ok(_) => status.rendered += 1,
+                    Err(e) => {
+                        tracing::warn!("Failed to pre-generate {} at {width}px: {e}", image.path);
+                        status.failed += 1;
+                    }
+                }
+                status.processed += 1;
+            }
+
+            // Idle long enough that rendering takes at most cpu_percent of the time
+            if cpu_percent < 100 {
+                tokio::time::sleep(busy * (100 - cpu_percent) / cpu_percent).await;
+            }
+        }
+    }
+    Ok(())
+}
@@ -1,11 +1,38 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::analysis::{self, ImageAnalysis};
 use crate::errors::AppError;
+use crate::events;
+use crate::metadata;
 use crate::models::*;
 
 // --- Filter DSL query builder ---
 
-fn build_where_clause(filters: &[FilterClause]) -> Result<(Vec<String>, Vec<String>), AppError> {
+/// Condition limiting `column` to the collections `access` allows, or `None`
+/// when it allows all of them. Pushes its parameters onto `params`.
+pub(crate) fn access_condition(
+    access: &CollectionAccess,
+    column: &str,
+    params: &mut Vec<String>,
+) -> Option<String> {
+    match access {
+        CollectionAccess::All => None,
+        CollectionAccess::Only(names) if names.is_empty() => Some("0".into()),
+        CollectionAccess::Only(names) => {
+            params.extend(names.iter().cloned());
+            Some(format!("{column} IN ({})", make_placeholders(names.len())))
+        }
+    }
+}
+
+fn build_where_clause(
+    conn: &rusqlite::Connection,
+    filters: &[FilterClause],
+    access: &CollectionAccess,
+) -> Result<(Vec<String>, Vec<String>), AppError> {
     let mut conditions: Vec<String> = Vec::new();
     let mut params: Vec<String> = Vec::new();
+    conditions.extend(access_condition(access, "i.collection", &mut params));
 
     for clause in filters {
         validate_clause(clause)?;
@@ -104,39 +131,307 @@ fn build_where_clause(filters: &[FilterClause]) -> Result<(Vec<String>, Vec<Stri
                     params.extend(vals.iter().map(|v| v.to_string()));
                 }
             }
+            FilterField::Camera | FilterField::Lens => {
+                let column = if clause.field == FilterField::Camera { "camera_model" } else { "lens" };
+                let vals = clause.value.as_scalars();
+                let placeholders = make_placeholders(vals.len());
+                let condition = match clause.op {
+                    FilterOp::Eq => {
+                        if vals.len() != 1 {
+                            return Err(AppError::BadRequest(format!(
+                                "{} eq requires a single value",
+                                clause.field.name()
+                            )));
+                        }
+                        format!("{column} = ?")
+                    }
+                    _ => format!("{column} IN ({placeholders})"),
+                };
+                let negate = if clause.op == FilterOp::NoneOf { "NOT " } else { "" };
+                conditions.push(format!(
+                    "i.uuid {negate}IN (SELECT image_uuid FROM image_metadata WHERE {condition})"
+                ));
+                params.extend(vals);
+            }
+            FilterField::FocalLength | FilterField::Aperture | FilterField::Iso => {
+                let column = clause.field.name();
+                let condition = range_condition(clause, column, false, &mut params)?;
+                conditions.push(format!(
+                    "i.uuid IN (SELECT image_uuid FROM image_metadata WHERE {condition})"
+                ));
+            }
+            FilterField::CapturedAt => {
+                let condition = range_condition(clause, "captured_at", true, &mut params)?;
+                conditions.push(format!(
+                    "i.uuid IN (SELECT image_uuid FROM image_metadata WHERE {condition})"
+                ));
+            }
+            FilterField::TakenAt => {
+                conditions.push(range_condition(clause, "i.taken_at", true, &mut params)?);
+            }
+            FilterField::SimilarTo => {
+                let uuid = clause.value.as_single().ok_or_else(|| {
+                    AppError::BadRequest("similar_to eq requires a single image UUID".into())
+                })?;
+                // The reference image's hash is read below; it must be one the caller can see
+                ensure_image_exists(conn, uuid, access)?;
+                let max_distance = clause.max_distance.unwrap_or(analysis::SIMILAR_MAX_DISTANCE);
+                conditions.push(
+                    "i.uuid != ? AND hamming(i.phash, (SELECT phash FROM images WHERE uuid = ?)) <= CAST(? AS INTEGER)"
+                        .into(),
+                );
+                params.extend([uuid.to_string(), uuid.to_string(), max_distance.to_string()]);
+            }
+            FilterField::Color => {
+                let hex = clause.value.as_single().ok_or_else(|| {
+                    AppError::BadRequest("color eq requires a single hex color".into())
+                })?;
+                let lab = analysis::hex_to_lab(hex).ok_or_else(|| {
+                    AppError::BadRequest(format!("color: '{hex}' is not a hex color like #c0392b"))
+                })?;
+                let max_distance = clause.max_distance.unwrap_or(analysis::COLOR_MAX_DISTANCE);
+                let terms = ["lab_l", "lab_a", "lab_b"]
+                    .map(|col| format!("({col} - CAST(? AS REAL)) * ({col} - CAST(? AS REAL))"));
+                conditions.push(format!(
+                    "i.uuid IN (SELECT image_uuid FROM image_palette WHERE {} <= CAST(? AS REAL))",
+                    terms.join(" + ")
+                ));
+                for v in lab {
+                    params.extend([v.to_string(), v.to_string()]);
+                }
+                params.push((f64::from(max_distance) * f64::from(max_distance)).to_string());
+            }
+            FilterField::Rating => {
+                conditions.push(range_condition(clause, "i.rating", false, &mut params)?);
+            }
+            FilterField::ColorLabel | FilterField::Flag => {
+                let (column, known): (&str, Vec<&str>) = if clause.field == FilterField::Flag {
+                    ("i.flag", Flag::ALL.map(Flag::name).to_vec())
+                } else {
+                    ("i.color_label", ColorLabel::ALL.map(ColorLabel::name).to_vec())
+                };
+                let vals = clause.value.as_multiple();
+                if let Some(bad) = vals.iter().find(|v| !known.contains(v)) {
+                    return Err(AppError::BadRequest(format!(
+                        "{}: unknown value '{bad}', expected one of {}",
+                        clause.field.name(),
+                        known.join(", ")
+                    )));
+                }
+                let condition = match clause.op {
+                    FilterOp::Eq if vals.len() == 1 => format!("{column} = ?"),
+                    FilterOp::Eq => {
+                        return Err(AppError::BadRequest(format!(
+                            "{} eq requires a single value",
+                            clause.field.name()
+                        )))
+                    }
+                    FilterOp::NoneOf => format!("{column} NOT IN ({})", make_placeholders(vals.len())),
+                    _ => format!("{column} IN ({})", make_placeholders(vals.len())),
+                };
+                conditions.push(condition);
+                params.extend(vals.iter().map(|v| v.to_string()));
+            }
+            FilterField::Album => {
+                let vals = clause.value.as_multiple();
+                if clause.op == FilterOp::Eq && vals.len() != 1 {
+                    return Err(AppError::BadRequest("album eq requires a single album UUID".into()));
+                }
+                conditions.push(format!(
+                    "i.uuid IN (SELECT image_uuid FROM album_images WHERE album_uuid IN ({}))",
+                    make_placeholders(vals.len())
+                ));
+                params.extend(vals.iter().map(|v| v.to_string()));
+            }
+            FilterField::HasComments => {
+                let has = clause.value.as_bool().ok_or_else(|| {
+                    AppError::BadRequest("has_comments eq requires true or false".into())
+                })?;
+                let negate = if has { "" } else { "NOT " };
+                conditions.push(format!(
+                    "{negate}EXISTS (SELECT 1 FROM comments c WHERE c.image_uuid = i.uuid)"
+                ));
+            }
         }
     }
 
     Ok((conditions, params))
 }
 
-pub fn build_image_query(filters: &[FilterClause]) -> Result<(String, Vec<String>), AppError> {
-    let (conditions, params) = build_where_clause(filters)?;
-    let mut sql = "SELECT i.uuid, i.path, i.collection, i.gallery, i.width, i.height, i.file_size FROM images i".to_string();
+/// Columns read by `query_images`, in order.
+const IMAGE_ROW_COLUMNS: &str =
+    "i.uuid, i.path, i.collection, i.gallery, i.width, i.height, i.file_size, i.blurhash, i.dominant_color, \
+     i.rating, i.color_label, i.flag, (SELECT COUNT(*) FROM comments c WHERE c.image_uuid = i.uuid)";
+
+pub fn build_image_query(
+    conn: &rusqlite::Connection,
+    filters: &[FilterClause],
+    sort: &[SortClause],
+    access: &CollectionAccess,
+) -> Result<(String, Vec<String>), AppError> {
+    let (conditions, params) = build_where_clause(conn, filters, access)?;
+    let mut sql = format!("SELECT {IMAGE_ROW_COLUMNS} FROM images i");
     if !conditions.is_empty() {
         sql.push_str(" WHERE ");
         sql.push_str(&conditions.join(" AND "));
     }
-    sql.push_str(" ORDER BY i.collection, i.gallery, i.path");
+    let mut order: Vec<String> = sort.iter().map(order_term).collect();
+    order.push("i.collection, i.gallery, i.path".into());
+    sql.push_str(" ORDER BY ");
+    sql.push_str(&order.join(", "));
     Ok((sql, params))
 }
 
+/// Whether one image matches the filters, without running the whole search.
+pub fn image_matches(
+    conn: &rusqlite::Connection,
+    image_uuid: &str,
+    filters: &[FilterClause],
+    access: &CollectionAccess,
+) -> Result<bool, AppError> {
+    let (mut conditions, mut params) = build_where_clause(conn, filters, access)?;
+    conditions.push("i.uuid = ?".into());
+    params.push(image_uuid.to_string());
+    let sql = format!("SELECT EXISTS(SELECT 1 FROM images i WHERE {})", conditions.join(" AND "));
+    Ok(conn.query_row(&sql, rusqlite::params_from_iter(params), |row| row.get(0))?)
+}
+
+fn order_term(clause: &SortClause) -> String {
+    let direction = match clause.direction {
+        SortDirection::Asc => "ASC",
+        SortDirection::Desc => "DESC",
+    };
+    match clause.field {
+        SortField::Path => format!("i.path {direction}"),
+        // Undated images go last either way
+        SortField::TakenAt => format!("i.taken_at {direction} NULLS LAST"),
+        SortField::Rating => format!("i.rating {direction}"),
+        SortField::ColorLabel => format!(
+            "{} {direction}",
+            rank_expr("i.color_label", ColorLabel::ALL.map(ColorLabel::name))
+        ),
+        SortField::Flag => format!("{} {direction}", rank_expr("i.flag", Flag::ALL.map(Flag::name))),
+    }
+}
+
+/// `CASE` mapping each named value to its position, for enum-ordered sorts.
+fn rank_expr<const N: usize>(column: &str, names: [&str; N]) -> String {
+    let arms: String = names
+        .iter()
+        .enumerate()
+        .map(|(rank, name)| format!(" WHEN '{name}' THEN {rank}"))
+        .collect();
+    format!("CASE {column}{arms} END")
+}
+
+/// Comparison against a numeric or date column. Dates are ISO 8601 strings
+/// compared by prefix, so `"2024"` or `"2024-06"` stand for the whole
+/// year/month: `eq "2024-06"` matches any time in June, `lte "2024-06"`
+/// includes all of June and `gt "2024-06"` starts in July.
+fn range_condition(
+    clause: &FilterClause,
+    column: &str,
+    is_date: bool,
+    params: &mut Vec<String>,
+) -> Result<String, AppError> {
+    let vals = clause.value.as_scalars();
+    let expected = if clause.op == FilterOp::Between { 2 } else { 1 };
+    if vals.len() != expected {
+        return Err(AppError::BadRequest(format!(
+            "{} {} requires {}",
+            clause.field.name(),
+            clause.op.name(),
+            if expected == 2 { "exactly two values" } else { "a single value" }
+        )));
+    }
+    if !is_date && vals.iter().any(|v| v.parse::<f64>().is_err()) {
+        return Err(AppError::BadRequest(format!(
+            "{} requires numeric values",
+            clause.field.name()
+        )));
+    }
+
+    // Numbers bind as text, so cast them back for the comparison
+    let placeholder = if is_date { "?" } else { "CAST(? AS REAL)" };
+    let prefix = format!("substr({column}, 1, length(?))");
+    let (sql, binds) = match (&clause.op, is_date) {
+        (FilterOp::Eq, true) => (format!("{prefix} = ?"), vec![&vals[0], &vals[0]]),
+        (FilterOp::Gt, true) => (format!("{prefix} > ?"), vec![&vals[0], &vals[0]]),
+        (FilterOp::Lte, true) => (format!("{prefix} <= ?"), vec![&vals[0], &vals[0]]),
+        (FilterOp::Between, true) => (
+            format!("{column} >= ? AND {prefix} <= ?"),
+            vec![&vals[0], &vals[1], &vals[1]],
+        ),
+        (FilterOp::Between, false) => (
+            format!("{column} BETWEEN {placeholder} AND {placeholder}"),
+            vec![&vals[0], &vals[1]],
+        ),
+        (op, _) => {
+            let sql_op = match op {
+                FilterOp::Eq => "=",
+                FilterOp::Gt => ">",
+                FilterOp::Gte => ">=",
+                FilterOp::Lt => "<",
+                FilterOp::Lte => "<=",
+                _ => unreachable!(),
+            };
+            (format!("{column} {sql_op} {placeholder}"), vec![&vals[0]])
+        }
+    };
+    params.extend(binds.into_iter().cloned());
+    Ok(sql)
+}
+
 fn validate_clause(clause: &FilterClause) -> Result<(), AppError> {
-    match (&clause.field, &clause.op) {
-        (FilterField::Collection | FilterField::Gallery, op) if *op != FilterOp::Eq => {
-            Err(AppError::BadRequest(format!(
-                "{} only supports the 'eq' operator",
-                if clause.field == FilterField::Collection { "collection" } else { "gallery" }
-            )))
+    if clause.max_distance.is_some() && !matches!(clause.field, FilterField::SimilarTo | FilterField::Color) {
+        return Err(AppError::BadRequest(format!(
+            "{} does not take max_distance",
+            clause.field.name()
+        )));
+    }
+    let allowed: &[FilterOp] = match clause.field {
+        FilterField::Collection
+        | FilterField::Gallery
+        | FilterField::SimilarTo
+        | FilterField::Color
+        | FilterField::HasComments => {
+            if clause.op != FilterOp::Eq {
+                return Err(AppError::BadRequest(format!(
+                    "{} only supports the 'eq' operator",
+                    clause.field.name()
+                )));
+            }
+            return Ok(());
+        }
+        FilterField::Models | FilterField::Tags => {
+            &[FilterOp::AnyOf, FilterOp::AllOf, FilterOp::Exact, FilterOp::NoneOf]
         }
-        (FilterField::Tags, FilterOp::Exact) => Ok(()),
-        (FilterField::Tags | FilterField::Models, FilterOp::Eq) => {
-            Err(AppError::BadRequest(format!(
-                "{} does not support the 'eq' operator",
-                if clause.field == FilterField::Tags { "tags" } else { "models" }
-            )))
+        FilterField::Camera | FilterField::Lens | FilterField::ColorLabel | FilterField::Flag => {
+            &[FilterOp::Eq, FilterOp::AnyOf, FilterOp::NoneOf]
         }
-        _ => Ok(()),
+        FilterField::Album => &[FilterOp::Eq, FilterOp::AnyOf],
+        FilterField::Rating
+        | FilterField::FocalLength
+        | FilterField::Aperture
+        | FilterField::Iso
+        | FilterField::CapturedAt
+        | FilterField::TakenAt => &[
+            FilterOp::Eq,
+            FilterOp::Gt,
+            FilterOp::Gte,
+            FilterOp::Lt,
+            FilterOp::Lte,
+            FilterOp::Between,
+        ],
+    };
+    if allowed.contains(&clause.op) {
+        Ok(())
+    } else {
+        Err(AppError::BadRequest(format!(
+            "{} does not support the '{}' operator",
+            clause.field.name(),
+            clause.op.name()
+        )))
     }
 }
 
@@ -164,6 +459,12 @@ pub fn query_images(
             width: row.get(4)?,
             height: row.get(5)?,
             file_size: row.get(6)?,
+            blurhash: row.get(7)?,
+            dominant_color: row.get(8)?,
+            rating: row.get(9)?,
+            color_label: row.get(10)?,
+            flag: row.get(11)?,
+            comment_count: row.get(12)?,
         })
     })?;
     rows.collect()
@@ -172,8 +473,9 @@ pub fn query_images(
 pub fn query_filter_options(
     conn: &rusqlite::Connection,
     filters: &[FilterClause],
+    access: &CollectionAccess,
 ) -> Result<FilterOptions, AppError> {
-    let (conditions, params) = build_where_clause(filters)?;
+    let (conditions, params) = build_where_clause(conn, filters, access)?;
     let param_refs: Vec<&dyn rusqlite::types::ToSql> =
         params.iter().map(|s| s as &dyn rusqlite::types::ToSql).collect();
 
@@ -202,7 +504,7 @@ pub fn query_filter_options(
             .collect::<Result<_, _>>()?;
         for (collection, gallery, total) in &rows {
             image_count = *total;
-            if collections.last().map_or(true, |c| c != collection) {
+            if collections.last() != Some(collection) {
                 collections.push(collection.clone());
             }
             galleries.push(GallerySummary {
@@ -256,13 +558,86 @@ pub fn query_filter_options(
     result
 }
 
+/// Ingested images' perceptual hashes, in browse order.
+pub fn query_image_hashes(
+    conn: &rusqlite::Connection,
+    collection: Option<&str>,
+    access: &CollectionAccess,
+) -> Result<Vec<(String, u64)>, rusqlite::Error> {
+    let mut params = vec![collection.map(str::to_string)];
+    let mut visible = Vec::new();
+    let restriction = access_condition(access, "collection", &mut visible)
+        .map(|c| format!(" AND {c}"))
+        .unwrap_or_default();
+    params.extend(visible.into_iter().map(Some));
+    let mut stmt = conn.prepare(&format!(
+        "SELECT uuid, phash FROM images WHERE phash IS NOT NULL AND (?1 IS NULL OR collection = ?1){restriction} \
+         ORDER BY collection, gallery, path"
+    ))?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(params), |row| {
+        Ok((row.get(0)?, row.get::<_, i64>(1)? as u64))
+    })?;
+    rows.collect()
+}
+
+/// Image rows for the given UUIDs, in browse order.
+pub fn query_images_by_uuid(
+    conn: &rusqlite::Connection,
+    uuids: &[String],
+) -> Result<Vec<ImageRow>, rusqlite::Error> {
+    let sql = format!(
+        "SELECT {IMAGE_ROW_COLUMNS} FROM images i WHERE i.uuid IN ({}) ORDER BY i.collection, i.gallery, i.path",
+        make_placeholders(uuids.len())
+    );
+    query_images(conn, &sql, uuids)
+}
+
+pub fn query_timeline(
+    conn: &rusqlite::Connection,
+    filters: &[FilterClause],
+    granularity: TimelineGranularity,
+    access: &CollectionAccess,
+) -> Result<Timeline, AppError> {
+    let (conditions, params) = build_where_clause(conn, filters, access)?;
+    let param_refs: Vec<&dyn rusqlite::types::ToSql> =
+        params.iter().map(|s| s as &dyn rusqlite::types::ToSql).collect();
+
+    let mut sql = format!(
+        "SELECT substr(i.taken_at, 1, {}) AS period, COUNT(*) FROM images i",
+        granularity.prefix_len()
+    );
+    if !conditions.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
+    }
+    sql.push_str(" GROUP BY period ORDER BY period");
+
+    let mut stmt = conn.prepare(&sql)?;
+    let rows: Vec<(Option<String>, u32)> = stmt
+        .query_map(param_refs.as_slice(), |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_, _>>()?;
+
+    let mut buckets = Vec::new();
+    let mut undated_count = 0;
+    for (period, image_count) in rows {
+        match period {
+            Some(period) => buckets.push(TimelineBucket { period, image_count }),
+            None => undated_count = image_count,
+        }
+    }
+    Ok(Timeline { granularity, buckets, undated_count })
+}
+
 pub fn query_image_detail(
     conn: &rusqlite::Connection,
     uuid: &str,
+    access: &CollectionAccess,
 ) -> Result<ImageDetail, AppError> {
     let image = conn
         .query_row(
-            "SELECT uuid, path, collection, gallery, width, height, file_size FROM images WHERE uuid = ?",
+            "SELECT uuid, path, collection, gallery, width, height, file_size, blurhash, dominant_color, \
+             rating, color_label, flag, (SELECT COUNT(*) FROM comments c WHERE c.image_uuid = images.uuid) \
+             FROM images WHERE uuid = ?",
             [uuid],
             |row| {
                 Ok(ImageRow {
@@ -273,6 +648,12 @@ pub fn query_image_detail(
                     width: row.get(4)?,
                     height: row.get(5)?,
                     file_size: row.get(6)?,
+                    blurhash: row.get(7)?,
+                    dominant_color: row.get(8)?,
+                    rating: row.get(9)?,
+                    color_label: row.get(10)?,
+                    flag: row.get(11)?,
+                    comment_count: row.get(12)?,
                 })
             },
         )
@@ -282,6 +663,9 @@ pub fn query_image_detail(
             }
             other => AppError::from(other),
         })?;
+    if !access.allows(&image.collection) {
+        return Err(AppError::NotFound("Image not found".into()));
+    }
 
     let mut stmt = conn.prepare(
         "SELECT m.uuid, m.name, m.collection FROM image_models im JOIN models m ON im.model_uuid = m.uuid WHERE im.image_uuid = ? ORDER BY m.name",
@@ -309,6 +693,20 @@ pub fn query_image_detail(
         })?
         .collect::<Result<_, _>>()?;
 
+    let mut stmt = conn.prepare(
+        "SELECT color, weight FROM image_palette WHERE image_uuid = ? ORDER BY position",
+    )?;
+    let palette: Vec<PaletteColor> = stmt
+        .query_map([uuid], |row| {
+            Ok(PaletteColor {
+                color: row.get(0)?,
+                weight: row.get(1)?,
+            })
+        })?
+        .collect::<Result<_, _>>()?;
+
+    let metadata = query_image_metadata(conn, uuid)?;
+
     Ok(ImageDetail {
         uuid: image.uuid,
         path: image.path,
@@ -317,27 +715,53 @@ pub fn query_image_detail(
         width: image.width,
         height: image.height,
         file_size: image.file_size,
+        blurhash: image.blurhash,
+        dominant_color: image.dominant_color,
+        rating: image.rating,
+        color_label: image.color_label,
+        flag: image.flag,
+        comment_count: image.comment_count,
+        palette,
+        metadata,
         models,
         tags,
     })
 }
 
+fn query_image_metadata(
+    conn: &rusqlite::Connection,
+    uuid: &str,
+) -> Result<Option<ImageMetadata>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT camera_make, camera_model, lens, focal_length, aperture, exposure_time, iso, \
+         captured_at, copyright, caption FROM image_metadata WHERE image_uuid = ?",
+    )?;
+    let mut rows = stmt.query_map([uuid], |row| {
+        let exposure_time: Option<f64> = row.get(5)?;
+        Ok(ImageMetadata {
+            camera_make: row.get(0)?,
+            camera_model: row.get(1)?,
+            lens: row.get(2)?,
+            focal_length: row.get(3)?,
+            aperture: row.get(4)?,
+            exposure_time,
+            shutter_speed: exposure_time.map(metadata::format_shutter_speed),
+            iso: row.get(6)?,
+            captured_at: row.get(7)?,
+            copyright: row.get(8)?,
+            caption: row.get(9)?,
+        })
+    })?;
+    rows.next().transpose()
+}
+
 pub fn replace_image_tags(
     conn: &rusqlite::Connection,
     image_uuid: &str,
     tag_uuids: &[String],
+    access: &CollectionAccess,
 ) -> Result<(), AppError> {
-    // Verify image exists
-    let exists: bool = conn
-        .query_row(
-            "SELECT EXISTS(SELECT 1 FROM images WHERE uuid = ?)",
-            [image_uuid],
-            |row| row.get(0),
-        )
-        .map_err(|e| AppError::DbError(e.to_string()))?;
-    if !exists {
-        return Err(AppError::NotFound("Image not found".into()));
-    }
+    ensure_image_exists(conn, image_uuid, access)?;
 
     // Verify all tag UUIDs exist
     for tag_uuid in tag_uuids {
@@ -355,22 +779,1052 @@ pub fn replace_image_tags(
         }
     }
 
-    conn.execute("DELETE FROM image_tags WHERE image_uuid = ?", [image_uuid])?;
-    let mut stmt =
+    // Only touch rows that change, so delta sync sees just the real edits
+    let current = query_image_tag_uuids(conn, image_uuid)?;
+    let mut delete =
+        conn.prepare("DELETE FROM image_tags WHERE image_uuid = ? AND tag_uuid = ?")?;
+    for tag_uuid in current.iter().filter(|t| !tag_uuids.contains(t)) {
+        delete.execute(rusqlite::params![image_uuid, tag_uuid])?;
+    }
+    let mut insert =
         conn.prepare("INSERT INTO image_tags (image_uuid, tag_uuid) VALUES (?, ?)")?;
-    for tag_uuid in tag_uuids {
-        stmt.execute(rusqlite::params![image_uuid, tag_uuid])?;
+    for tag_uuid in tag_uuids.iter().filter(|t| !current.contains(t)) {
+        insert.execute(rusqlite::params![image_uuid, tag_uuid])?;
+    }
+    if query_image_tag_uuids(conn, image_uuid)? != current {
+        events::image_tags_changed(conn, image_uuid)?;
     }
     Ok(())
 }
 
-pub fn query_collections(
+pub fn query_image_tag_uuids(conn: &rusqlite::Connection, image_uuid: &str) -> Result<Vec<String>, rusqlite::Error> {
+    let mut stmt =
+        conn.prepare("SELECT tag_uuid FROM image_tags WHERE image_uuid = ? ORDER BY tag_uuid")?;
+    let rows = stmt.query_map([image_uuid], |row| row.get(0))?;
+    rows.collect()
+}
+
+/// Set rating, colour label and/or flag on every listed image. Nothing is
+/// written unless all images exist. Returns how many images were updated.
+pub fn update_culling(
     conn: &rusqlite::Connection,
-) -> Result<Vec<CollectionSummary>, rusqlite::Error> {
+    image_uuids: &[String],
+    update: &UpdateCullingRequest,
+    access: &CollectionAccess,
+) -> Result<u32, AppError> {
+    if update.rating.is_none() && update.color_label.is_none() && update.flag.is_none() {
+        return Err(AppError::BadRequest(
+            "Set at least one of rating, color_label or flag".into(),
+        ));
+    }
+    if update.rating.is_some_and(|r| r > 5) {
+        return Err(AppError::BadRequest("rating must be between 0 and 5".into()));
+    }
+
+    let mut uuids: Vec<&String> = image_uuids.iter().collect();
+    uuids.sort();
+    uuids.dedup();
+    for uuid in &uuids {
+        ensure_image_exists(conn, uuid, access).map_err(|e| match e {
+            AppError::NotFound(_) => AppError::NotFound(format!("Image not found: {uuid}")),
+            other => other,
+        })?;
+    }
+
+    // Unset fields bind NULL and keep their current value. Rows already in
+    // the requested state are left alone, so the sync stamp only moves on a
+    // real change
     let mut stmt = conn.prepare(
-        "SELECT i.collection, COUNT(*) as image_count, COUNT(DISTINCT i.gallery) as gallery_count FROM images i GROUP BY i.collection ORDER BY i.collection",
+        "UPDATE images SET rating = COALESCE(?1, rating), color_label = COALESCE(?2, color_label), \
+         flag = COALESCE(?3, flag) WHERE uuid = ?4 \
+         AND (rating IS NOT COALESCE(?1, rating) OR color_label IS NOT COALESCE(?2, color_label) \
+         OR flag IS NOT COALESCE(?3, flag))",
     )?;
-    let rows = stmt.query_map([], |row| {
+    for uuid in &uuids {
+        stmt.execute(rusqlite::params![
+            update.rating,
+            update.color_label.map(ColorLabel::name),
+            update.flag.map(Flag::name),
+            uuid
+        ])?;
+    }
+    Ok(uuids.len() as u32)
+}
+
+/// An image's tags, by group then name.
+pub fn query_tags_of_image(
+    conn: &rusqlite::Connection,
+    image_uuid: &str,
+) -> Result<Vec<TagRef>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT t.uuid, t.name, tg.name FROM image_tags it \
+         JOIN tags t ON it.tag_uuid = t.uuid \
+         JOIN tag_groups tg ON t.tag_group_uuid = tg.uuid \
+         WHERE it.image_uuid = ? ORDER BY tg.name, t.name",
+    )?;
+    let rows = stmt.query_map([image_uuid], |row| {
+        Ok(TagRef {
+            uuid: row.get(0)?,
+            name: row.get(1)?,
+            group: row.get(2)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// UUIDs of the images carrying a tag.
+pub fn query_tagged_images(
+    conn: &rusqlite::Connection,
+    tag_uuid: &str,
+) -> Result<Vec<String>, rusqlite::Error> {
+    let mut stmt =
+        conn.prepare("SELECT image_uuid FROM image_tags WHERE tag_uuid = ? ORDER BY image_uuid")?;
+    let rows = stmt.query_map([tag_uuid], |row| row.get(0))?;
+    rows.collect()
+}
+
+pub fn query_culling(conn: &rusqlite::Connection, image_uuid: &str) -> Result<CullingState, AppError> {
+    conn.query_row(
+        "SELECT rating, color_label, flag FROM images WHERE uuid = ?",
+        [image_uuid],
+        |row| {
+            Ok(CullingState {
+                rating: row.get(0)?,
+                color_label: row.get(1)?,
+                flag: row.get(2)?,
+            })
+        },
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => AppError::NotFound("Image not found".into()),
+        other => AppError::from(other),
+    })
+}
+
+/// Put back culling fields read with `query_culling`.
+pub fn set_culling(conn: &rusqlite::Connection, image_uuid: &str, state: &CullingState) -> Result<(), AppError> {
+    conn.execute(
+        "UPDATE images SET rating = ?1, color_label = ?2, flag = ?3 \
+         WHERE uuid = ?4 AND (rating IS NOT ?1 OR color_label IS NOT ?2 OR flag IS NOT ?3)",
+        rusqlite::params![state.rating, state.color_label, state.flag, image_uuid],
+    )?;
+    Ok(())
+}
+
+/// 404 unless the image exists in a collection the caller may see.
+fn ensure_image_exists(
+    conn: &rusqlite::Connection,
+    image_uuid: &str,
+    access: &CollectionAccess,
+) -> Result<(), AppError> {
+    query_image_path(conn, image_uuid, access).map(|_| ())
+}
+
+/// An image's path relative to the galleries directory.
+pub fn query_image_path(
+    conn: &rusqlite::Connection,
+    image_uuid: &str,
+    access: &CollectionAccess,
+) -> Result<String, AppError> {
+    let (path, collection): (String, String) = conn
+        .query_row(
+            "SELECT path, collection FROM images WHERE uuid = ?",
+            [image_uuid],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => AppError::NotFound("Image not found".into()),
+            other => AppError::from(other),
+        })?;
+    if !access.allows(&collection) {
+        return Err(AppError::NotFound("Image not found".into()));
+    }
+    Ok(path)
+}
+
+const COMMENT_COLUMNS: &str = "uuid, image_uuid, parent_uuid, author, body, \
+    region_x, region_y, region_width, region_height, created_at, updated_at";
+
+fn comment_from_row(row: &rusqlite::Row) -> rusqlite::Result<Comment> {
+    let region = match (row.get(5)?, row.get(6)?, row.get(7)?, row.get(8)?) {
+        (Some(x), Some(y), Some(width), Some(height)) => Some(CommentRegion { x, y, width, height }),
+        _ => None,
+    };
+    Ok(Comment {
+        uuid: row.get(0)?,
+        image_uuid: row.get(1)?,
+        parent_uuid: row.get(2)?,
+        author: row.get(3)?,
+        body: row.get(4)?,
+        region,
+        created_at: row.get::<_, i64>(9)? as u64,
+        updated_at: row.get::<_, Option<i64>>(10)?.map(|t| t as u64),
+    })
+}
+
+/// All comments on an image, oldest first. Replies carry their parent's UUID.
+pub fn query_comments(
+    conn: &rusqlite::Connection,
+    image_uuid: &str,
+    access: &CollectionAccess,
+) -> Result<Vec<Comment>, AppError> {
+    ensure_image_exists(conn, image_uuid, access)?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {COMMENT_COLUMNS} FROM comments WHERE image_uuid = ? ORDER BY created_at, rowid"
+    ))?;
+    let comments = stmt.query_map([image_uuid], comment_from_row)?.collect::<Result<_, _>>()?;
+    Ok(comments)
+}
+
+pub fn query_comment(
+    conn: &rusqlite::Connection,
+    image_uuid: &str,
+    comment_uuid: &str,
+) -> Result<Comment, AppError> {
+    conn.query_row(
+        &format!("SELECT {COMMENT_COLUMNS} FROM comments WHERE uuid = ? AND image_uuid = ?"),
+        [comment_uuid, image_uuid],
+        comment_from_row,
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => AppError::NotFound("Comment not found".into()),
+        other => AppError::from(other),
+    })
+}
+
+pub fn insert_comment(
+    conn: &rusqlite::Connection,
+    image_uuid: &str,
+    request: &NewCommentRequest,
+    author: &str,
+    author_id: &str,
+    now: u64,
+    access: &CollectionAccess,
+) -> Result<Comment, AppError> {
+    ensure_image_exists(conn, image_uuid, access)?;
+    validate_comment_body(&request.body)?;
+    if let Some(region) = &request.region {
+        let in_range = |start: f64, size: f64| start >= 0.0 && size >= 0.0 && start + size <= 1.0;
+        if !in_range(region.x, region.width) || !in_range(region.y, region.height) {
+            return Err(AppError::BadRequest(
+                "region must lie within the image (fractions from 0 to 1)".into(),
+            ));
+        }
+    }
+    if let Some(parent) = &request.parent_uuid {
+        query_comment(conn, image_uuid, parent).map_err(|e| match e {
+            AppError::NotFound(_) => AppError::BadRequest(format!("Parent comment not found: {parent}")),
+            other => other,
+        })?;
+    }
+
+    let uuid = uuid::Uuid::new_v4().to_string();
+    conn.execute(
+        &format!(
+            "INSERT INTO comments ({COMMENT_COLUMNS}, author_id) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, NULL, ?)"
+        ),
+        rusqlite::params![
+            uuid,
+            image_uuid,
+            request.parent_uuid,
+            author,
+            request.body,
+            request.region.map(|r| r.x),
+            request.region.map(|r| r.y),
+            request.region.map(|r| r.width),
+            request.region.map(|r| r.height),
+            now as i64,
+            author_id,
+        ],
+    )?;
+    query_comment(conn, image_uuid, &uuid)
+}
+
+/// `owner` is the caller's principal when only their own comments may be
+/// changed, and `None` for admins.
+pub fn update_comment(
+    conn: &rusqlite::Connection,
+    image_uuid: &str,
+    comment_uuid: &str,
+    body: &str,
+    now: u64,
+    owner: Option<&str>,
+    access: &CollectionAccess,
+) -> Result<Comment, AppError> {
+    validate_comment_body(body)?;
+    ensure_image_exists(conn, image_uuid, access)?;
+    query_comment(conn, image_uuid, comment_uuid)?;
+    if let Some(owner) = owner {
+        let author_id: Option<String> =
+            conn.query_row("SELECT author_id FROM comments WHERE uuid = ?", [comment_uuid], |row| row.get(0))?;
+        if author_id.as_deref() != Some(owner) {
+            return Err(AppError::Forbidden("Only the author or an admin can edit this comment".into()));
+        }
+    }
+    conn.execute(
+        "UPDATE comments SET body = ?, updated_at = ? WHERE uuid = ?",
+        rusqlite::params![body, now as i64, comment_uuid],
+    )?;
+    query_comment(conn, image_uuid, comment_uuid)
+}
+
+/// Delete a comment and every reply under it. Returns how many were removed.
+/// With an `owner`, every comment in the thread must be theirs.
+pub fn delete_comment(
+    conn: &rusqlite::Connection,
+    image_uuid: &str,
+    comment_uuid: &str,
+    owner: Option<&str>,
+    access: &CollectionAccess,
+) -> Result<usize, AppError> {
+    ensure_image_exists(conn, image_uuid, access)?;
+    query_comment(conn, image_uuid, comment_uuid)?;
+    const THREAD: &str = "WITH RECURSIVE thread(uuid) AS (
+            SELECT ?1
+            UNION SELECT c.uuid FROM comments c JOIN thread t ON c.parent_uuid = t.uuid
+        )";
+    if let Some(owner) = owner {
+        let others: bool = conn.query_row(
+            &format!(
+                "{THREAD} SELECT EXISTS(SELECT 1 FROM comments
+                 WHERE uuid IN (SELECT uuid FROM thread) AND author_id IS NOT ?2)"
+            ),
+            [comment_uuid, owner],
+            |row| row.get(0),
+        )?;
+        if others {
+            return Err(AppError::Forbidden(
+                "Only the author or an admin can delete this comment, and only an admin once others have replied"
+                    .into(),
+            ));
+        }
+    }
+    let removed = conn.execute(
+        &format!("{THREAD} DELETE FROM comments WHERE uuid IN (SELECT uuid FROM thread)"),
+        [comment_uuid],
+    )?;
+    Ok(removed)
+}
+
+fn validate_comment_body(body: &str) -> Result<(), AppError> {
+    if body.trim().is_empty() {
+        return Err(AppError::BadRequest("body must not be empty".into()));
+    }
+    Ok(())
+}
+
+/// Albums with the number of images in them the caller may see. Albums whose
+/// members are all out of reach are left out; empty albums are listed.
+pub fn query_albums(
+    conn: &rusqlite::Connection,
+    access: &CollectionAccess,
+) -> Result<Vec<AlbumSummary>, rusqlite::Error> {
+    let mut params = Vec::new();
+    let visible = access_condition(access, "i.collection", &mut params)
+        .map(|c| format!(" AND {c}"))
+        .unwrap_or_default();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT a.uuid, a.name, a.description, COUNT(i.uuid), a.created_at, a.updated_at \
+         FROM albums a LEFT JOIN album_images ai ON ai.album_uuid = a.uuid \
+         LEFT JOIN images i ON i.uuid = ai.image_uuid{visible} \
+         GROUP BY a.uuid HAVING COUNT(ai.image_uuid) = 0 OR COUNT(i.uuid) > 0 \
+         ORDER BY a.name COLLATE NOCASE, a.created_at"
+    ))?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(params), |row| {
+        Ok(AlbumSummary {
+            uuid: row.get(0)?,
+            name: row.get(1)?,
+            description: row.get(2)?,
+            image_count: row.get(3)?,
+            created_at: row.get::<_, i64>(4)? as u64,
+            updated_at: row.get::<_, i64>(5)? as u64,
+        })
+    })?;
+    rows.collect()
+}
+
+/// An album with the images in it the caller may see, in order. 404 when
+/// none of its members are visible, as if the album weren't there.
+pub fn query_album(
+    conn: &rusqlite::Connection,
+    album_uuid: &str,
+    access: &CollectionAccess,
+) -> Result<AlbumDetail, AppError> {
+    album_member_counts(conn, album_uuid, access)?;
+    let (name, description, created_at, updated_at): (String, Option<String>, i64, i64) = conn
+        .query_row(
+            "SELECT name, description, created_at, updated_at FROM albums WHERE uuid = ?",
+            [album_uuid],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => AppError::NotFound("Album not found".into()),
+            other => AppError::from(other),
+        })?;
+    let mut params = vec![album_uuid.to_string()];
+    let visible = access_condition(access, "i.collection", &mut params)
+        .map(|c| format!(" AND {c}"))
+        .unwrap_or_default();
+    let sql = format!(
+        "SELECT {IMAGE_ROW_COLUMNS} FROM album_images ai JOIN images i ON i.uuid = ai.image_uuid \
+         WHERE ai.album_uuid = ?{visible} ORDER BY ai.position"
+    );
+    let images = query_images(conn, &sql, &params)?;
+    Ok(AlbumDetail {
+        uuid: album_uuid.to_string(),
+        name,
+        description,
+        created_at: created_at as u64,
+        updated_at: updated_at as u64,
+        images,
+    })
+}
+
+pub fn insert_album(
+    conn: &rusqlite::Connection,
+    request: &NewAlbumRequest,
+    now: u64,
+    access: &CollectionAccess,
+) -> Result<AlbumDetail, AppError> {
+    let name = validate_album_name(&request.name)?;
+    let images = dedup_images(conn, &request.image_uuids, access)?;
+    let uuid = uuid::Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO albums (uuid, name, description, created_at, updated_at) VALUES (?, ?, ?, ?, ?)",
+        rusqlite::params![uuid, name, request.description, now as i64, now as i64],
+    )?;
+    write_album_order(conn, &uuid, &images)?;
+    query_album(conn, &uuid, access)
+}
+
+pub fn update_album(
+    conn: &rusqlite::Connection,
+    album_uuid: &str,
+    request: &UpdateAlbumRequest,
+    now: u64,
+    access: &CollectionAccess,
+) -> Result<AlbumDetail, AppError> {
+    let name = request.name.as_deref().map(validate_album_name).transpose()?;
+    ensure_album_writable(conn, album_uuid, access)?;
+    let changed = conn.execute(
+        "UPDATE albums SET name = COALESCE(?, name), description = COALESCE(?, description), updated_at = ? \
+         WHERE uuid = ?",
+        rusqlite::params![name, request.description, now as i64, album_uuid],
+    )?;
+    if changed == 0 {
+        return Err(AppError::NotFound("Album not found".into()));
+    }
+    query_album(conn, album_uuid, access)
+}
+
+pub fn delete_album(
+    conn: &rusqlite::Connection,
+    album_uuid: &str,
+    access: &CollectionAccess,
+) -> Result<(), AppError> {
+    ensure_album_writable(conn, album_uuid, access)?;
+    conn.execute("DELETE FROM album_images WHERE album_uuid = ?", [album_uuid])?;
+    if conn.execute("DELETE FROM albums WHERE uuid = ?", [album_uuid])? == 0 {
+        return Err(AppError::NotFound("Album not found".into()));
+    }
+    Ok(())
+}
+
+/// Insert images at `position` (or the end). Images already in the album
+/// stay where they are.
+pub fn add_album_images(
+    conn: &rusqlite::Connection,
+    album_uuid: &str,
+    image_uuids: &[String],
+    position: Option<usize>,
+    now: u64,
+    access: &CollectionAccess,
+) -> Result<AlbumDetail, AppError> {
+    ensure_album_writable(conn, album_uuid, access)?;
+    let mut order = album_order(conn, album_uuid)?;
+    let added: Vec<String> = dedup_images(conn, image_uuids, access)?
+        .into_iter()
+        .filter(|uuid| !order.contains(uuid))
+        .collect();
+    let at = position.unwrap_or(order.len()).min(order.len());
+    order.splice(at..at, added);
+    write_album_order(conn, album_uuid, &order)?;
+    touch_album(conn, album_uuid, now)?;
+    query_album(conn, album_uuid, access)
+}
+
+pub fn remove_album_image(
+    conn: &rusqlite::Connection,
+    album_uuid: &str,
+    image_uuid: &str,
+    now: u64,
+    access: &CollectionAccess,
+) -> Result<AlbumDetail, AppError> {
+    ensure_album_writable(conn, album_uuid, access)?;
+    let mut order = album_order(conn, album_uuid)?;
+    let before = order.len();
+    order.retain(|uuid| uuid != image_uuid);
+    if order.len() == before {
+        return Err(AppError::NotFound("Image is not in this album".into()));
+    }
+    write_album_order(conn, album_uuid, &order)?;
+    touch_album(conn, album_uuid, now)?;
+    query_album(conn, album_uuid, access)
+}
+
+/// Replace the album's order. The new order must list exactly the current
+/// members, so a reorder can't silently add or drop images.
+pub fn reorder_album(
+    conn: &rusqlite::Connection,
+    album_uuid: &str,
+    image_uuids: &[String],
+    now: u64,
+    access: &CollectionAccess,
+) -> Result<AlbumDetail, AppError> {
+    ensure_album_writable(conn, album_uuid, access)?;
+    let current = album_order(conn, album_uuid)?;
+    let mut wanted: Vec<&String> = image_uuids.iter().collect();
+    let mut members: Vec<&String> = current.iter().collect();
+    wanted.sort();
+    members.sort();
+    if wanted != members {
+        return Err(AppError::BadRequest(
+            "image_uuids must list each image in the album exactly once".into(),
+        ));
+    }
+    write_album_order(conn, album_uuid, image_uuids)?;
+    touch_album(conn, album_uuid, now)?;
+    query_album(conn, album_uuid, access)
+}
+
+/// An album's fields and full membership, regardless of collection access.
+pub fn query_album_snapshot(
+    conn: &rusqlite::Connection,
+    album_uuid: &str,
+) -> Result<AlbumSnapshot, AppError> {
+    let image_uuids = album_order(conn, album_uuid)?;
+    let (name, description) = conn.query_row(
+        "SELECT name, description FROM albums WHERE uuid = ?",
+        [album_uuid],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    Ok(AlbumSnapshot { name, description, image_uuids })
+}
+
+/// Member UUIDs in order; 404 if the album doesn't exist.
+fn album_order(conn: &rusqlite::Connection, album_uuid: &str) -> Result<Vec<String>, AppError> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM albums WHERE uuid = ?)",
+        [album_uuid],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        return Err(AppError::NotFound("Album not found".into()));
+    }
+    let mut stmt =
+        conn.prepare("SELECT image_uuid FROM album_images WHERE album_uuid = ? ORDER BY position")?;
+    let order = stmt.query_map([album_uuid], |row| row.get(0))?.collect::<Result<_, _>>()?;
+    Ok(order)
+}
+
+/// How many images the album holds and how many of those the caller may
+/// see. 404 if the album doesn't exist or every member is out of reach.
+fn album_member_counts(
+    conn: &rusqlite::Connection,
+    album_uuid: &str,
+    access: &CollectionAccess,
+) -> Result<(i64, i64), AppError> {
+    let mut params = Vec::new();
+    let visible = access_condition(access, "i.collection", &mut params).unwrap_or_else(|| "1".into());
+    params.push(album_uuid.to_string());
+    let (members, visible): (i64, i64) = conn
+        .query_row(
+            &format!(
+                "SELECT COUNT(ai.image_uuid), COUNT(CASE WHEN {visible} THEN 1 END) \
+                 FROM albums a LEFT JOIN album_images ai ON ai.album_uuid = a.uuid \
+                 LEFT JOIN images i ON i.uuid = ai.image_uuid \
+                 WHERE a.uuid = ? GROUP BY a.uuid"
+            ),
+            rusqlite::params_from_iter(params),
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => AppError::NotFound("Album not found".into()),
+            other => AppError::from(other),
+        })?;
+    if members > 0 && visible == 0 {
+        return Err(AppError::NotFound("Album not found".into()));
+    }
+    Ok((members, visible))
+}
+
+/// Changing an album rewrites its whole membership, so only callers who can
+/// see every member may do it.
+fn ensure_album_writable(
+    conn: &rusqlite::Connection,
+    album_uuid: &str,
+    access: &CollectionAccess,
+) -> Result<(), AppError> {
+    let (members, visible) = album_member_counts(conn, album_uuid, access)?;
+    if visible < members {
+        return Err(AppError::Forbidden(
+            "Album holds images from collections you can't access".into(),
+        ));
+    }
+    Ok(())
+}
+
+fn write_album_order(
+    conn: &rusqlite::Connection,
+    album_uuid: &str,
+    image_uuids: &[String],
+) -> Result<(), AppError> {
+    conn.execute("DELETE FROM album_images WHERE album_uuid = ?", [album_uuid])?;
+    let mut stmt =
+        conn.prepare("INSERT INTO album_images (album_uuid, image_uuid, position) VALUES (?, ?, ?)")?;
+    for (position, image_uuid) in image_uuids.iter().enumerate() {
+        stmt.execute(rusqlite::params![album_uuid, image_uuid, position as i64])?;
+    }
+    Ok(())
+}
+
+fn touch_album(conn: &rusqlite::Connection, album_uuid: &str, now: u64) -> Result<(), AppError> {
+    conn.execute(
+        "UPDATE albums SET updated_at = ? WHERE uuid = ?",
+        rusqlite::params![now as i64, album_uuid],
+    )?;
+    Ok(())
+}
+
+/// Drop repeats, keeping first-seen order, and check every image exists.
+fn dedup_images(
+    conn: &rusqlite::Connection,
+    image_uuids: &[String],
+    access: &CollectionAccess,
+) -> Result<Vec<String>, AppError> {
+    let mut seen = HashSet::new();
+    let mut unique = Vec::new();
+    for uuid in image_uuids {
+        if seen.insert(uuid) {
+            ensure_image_exists(conn, uuid, access).map_err(|e| match e {
+                AppError::NotFound(_) => AppError::BadRequest(format!("Image not found: {uuid}")),
+                other => other,
+            })?;
+            unique.push(uuid.clone());
+        }
+    }
+    Ok(unique)
+}
+
+fn validate_album_name(name: &str) -> Result<&str, AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::BadRequest("name must not be empty".into()));
+    }
+    Ok(name)
+}
+
+const SAVED_SEARCH_COLUMNS: &str = "uuid, name, filters, sort, created_at, updated_at";
+
+fn saved_search_from_row(row: &rusqlite::Row) -> rusqlite::Result<SavedSearch> {
+    Ok(SavedSearch {
+        uuid: row.get(0)?,
+        name: row.get(1)?,
+        filters: json_column(row, 2)?,
+        sort: json_column(row, 3)?,
+        created_at: row.get::<_, i64>(4)? as u64,
+        updated_at: row.get::<_, i64>(5)? as u64,
+    })
+}
+
+/// A JSON column written by this server; failing to parse it is a bug.
+fn json_column<T: serde::de::DeserializeOwned>(row: &rusqlite::Row, i: usize) -> rusqlite::Result<T> {
+    let text: String = row.get(i)?;
+    serde_json::from_str(&text)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(i, rusqlite::types::Type::Text, Box::new(e)))
+}
+
+/// Whether every collection the filters name is one the caller may see.
+fn names_visible_collections(filters: &[FilterClause], access: &CollectionAccess) -> bool {
+    filters
+        .iter()
+        .filter(|clause| clause.field == FilterField::Collection)
+        .all(|clause| clause.value.as_multiple().iter().all(|name| access.allows(name)))
+}
+
+/// Saved searches, leaving out those naming collections the caller can't see.
+pub fn query_saved_searches(
+    conn: &rusqlite::Connection,
+    access: &CollectionAccess,
+) -> Result<Vec<SavedSearch>, rusqlite::Error> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {SAVED_SEARCH_COLUMNS} FROM saved_searches ORDER BY name COLLATE NOCASE, created_at"
+    ))?;
+    let rows = stmt.query_map([], saved_search_from_row)?;
+    let mut searches = rows.collect::<Result<Vec<_>, _>>()?;
+    searches.retain(|search| names_visible_collections(&search.filters, access));
+    Ok(searches)
+}
+
+pub fn query_saved_search(
+    conn: &rusqlite::Connection,
+    uuid: &str,
+    access: &CollectionAccess,
+) -> Result<SavedSearch, AppError> {
+    let not_found = || AppError::NotFound("Saved search not found".into());
+    let search = conn
+        .query_row(
+            &format!("SELECT {SAVED_SEARCH_COLUMNS} FROM saved_searches WHERE uuid = ?"),
+            [uuid],
+            saved_search_from_row,
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => not_found(),
+            other => AppError::from(other),
+        })?;
+    if !names_visible_collections(&search.filters, access) {
+        return Err(not_found());
+    }
+    Ok(search)
+}
+
+pub fn insert_saved_search(
+    conn: &rusqlite::Connection,
+    request: &SavedSearchRequest,
+    now: u64,
+    access: &CollectionAccess,
+) -> Result<SavedSearch, AppError> {
+    let (name, filters, sort) = validate_saved_search(conn, request, access)?;
+    let uuid = uuid::Uuid::new_v4().to_string();
+    conn.execute(
+        &format!("INSERT INTO saved_searches ({SAVED_SEARCH_COLUMNS}) VALUES (?, ?, ?, ?, ?, ?)"),
+        rusqlite::params![uuid, name, filters, sort, now as i64, now as i64],
+    )?;
+    query_saved_search(conn, &uuid, access)
+}
+
+pub fn update_saved_search(
+    conn: &rusqlite::Connection,
+    uuid: &str,
+    request: &SavedSearchRequest,
+    now: u64,
+    access: &CollectionAccess,
+) -> Result<SavedSearch, AppError> {
+    query_saved_search(conn, uuid, access)?;
+    let (name, filters, sort) = validate_saved_search(conn, request, access)?;
+    let changed = conn.execute(
+        "UPDATE saved_searches SET name = ?, filters = ?, sort = ?, updated_at = ? WHERE uuid = ?",
+        rusqlite::params![name, filters, sort, now as i64, uuid],
+    )?;
+    if changed == 0 {
+        return Err(AppError::NotFound("Saved search not found".into()));
+    }
+    query_saved_search(conn, uuid, access)
+}
+
+pub fn delete_saved_search(
+    conn: &rusqlite::Connection,
+    uuid: &str,
+    access: &CollectionAccess,
+) -> Result<(), AppError> {
+    query_saved_search(conn, uuid, access)?;
+    if conn.execute("DELETE FROM saved_searches WHERE uuid = ?", [uuid])? == 0 {
+        return Err(AppError::NotFound("Saved search not found".into()));
+    }
+    Ok(())
+}
+
+/// Reject searches that `/images/search` would reject, so a broken search
+/// fails when it's saved rather than every time it runs. Returns the trimmed
+/// name and the JSON to store.
+fn validate_saved_search<'a>(
+    conn: &rusqlite::Connection,
+    request: &'a SavedSearchRequest,
+    access: &CollectionAccess,
+) -> Result<(&'a str, String, String), AppError> {
+    let name = request.name.trim();
+    if name.is_empty() {
+        return Err(AppError::BadRequest("name must not be empty".into()));
+    }
+    if !names_visible_collections(&request.filters, access) {
+        return Err(AppError::Forbidden("Saved searches may only name collections you can see".into()));
+    }
+    build_where_clause(conn, &request.filters, access)?;
+    let to_json = |e: serde_json::Error| AppError::DbError(format!("Failed to encode saved search: {e}"));
+    Ok((
+        name,
+        serde_json::to_string(&request.filters).map_err(to_json)?,
+        serde_json::to_string(&request.sort).map_err(to_json)?,
+    ))
+}
+
+pub fn query_image_uuids(
+    conn: &rusqlite::Connection,
+    collection: &str,
+    gallery: Option<&str>,
+) -> Result<HashSet<String>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT uuid FROM images WHERE collection = ?1 AND (?2 IS NULL OR gallery = ?2)",
+    )?;
+    let rows = stmt.query_map(rusqlite::params![collection, gallery], |row| row.get(0))?;
+    rows.collect()
+}
+
+pub fn query_image_locations(
+    conn: &rusqlite::Connection,
+    collection: Option<&str>,
+) -> Result<Vec<ImageLocation>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT uuid, path, collection, gallery FROM images WHERE ?1 IS NULL OR collection = ?1 ORDER BY collection, gallery, path",
+    )?;
+    let rows = stmt.query_map([collection], |row| {
+        Ok(ImageLocation {
+            uuid: row.get(0)?,
+            path: row.get(1)?,
+            collection: row.get(2)?,
+            gallery: row.get(3)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Tags of every image (in one collection or everywhere), keyed by image UUID.
+pub fn query_image_tag_refs(
+    conn: &rusqlite::Connection,
+    collection: Option<&str>,
+) -> Result<HashMap<String, Vec<TagRef>>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT it.image_uuid, t.uuid, t.name, tg.name FROM image_tags it \
+         JOIN images i ON it.image_uuid = i.uuid \
+         JOIN tags t ON it.tag_uuid = t.uuid \
+         JOIN tag_groups tg ON t.tag_group_uuid = tg.uuid \
+         WHERE ?1 IS NULL OR i.collection = ?1 \
+         ORDER BY tg.name, t.name",
+    )?;
+    let mut tags: HashMap<String, Vec<TagRef>> = HashMap::new();
+    let rows = stmt.query_map([collection], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            TagRef {
+                uuid: row.get(1)?,
+                name: row.get(2)?,
+                group: row.get(3)?,
+            },
+        ))
+    })?;
+    for row in rows {
+        let (image_uuid, tag) = row?;
+        tags.entry(image_uuid).or_default().push(tag);
+    }
+    Ok(tags)
+}
+
+pub fn query_images_pending_ingest(
+    conn: &rusqlite::Connection,
+    collection: Option<&str>,
+    force: bool,
+) -> Result<Vec<ImageLocation>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT uuid, path, collection, gallery FROM images \
+         WHERE (?1 IS NULL OR collection = ?1) \
+         AND (?2 OR blurhash IS NULL OR phash IS NULL OR taken_at IS NULL OR content_hash IS NULL \
+              OR uuid NOT IN (SELECT image_uuid FROM image_metadata) OR NOT palette_analyzed) \
+         ORDER BY collection, gallery, path",
+    )?;
+    let rows = stmt.query_map(rusqlite::params![collection, force], |row| {
+        Ok(ImageLocation {
+            uuid: row.get(0)?,
+            path: row.get(1)?,
+            collection: row.get(2)?,
+            gallery: row.get(3)?,
+        })
+    })?;
+    rows.collect()
+}
+
+pub fn store_ingested(
+    conn: &rusqlite::Connection,
+    image_uuid: &str,
+    analysis: &ImageAnalysis,
+    metadata: &ImageMetadata,
+    taken_at: Option<&str>,
+    identity: &FileIdentity,
+) -> Result<(), AppError> {
+    conn.execute(
+        "UPDATE images SET blurhash = ?, dominant_color = ?, phash = ?, taken_at = ?, \
+         content_hash = ?, file_mtime = ?, file_size = ?, palette_analyzed = 1 WHERE uuid = ?",
+        rusqlite::params![
+            analysis.blurhash,
+            analysis.dominant_color,
+            analysis.perceptual_hash as i64,
+            taken_at,
+            identity.content_hash,
+            identity.file_mtime as i64,
+            identity.file_size as i64,
+            image_uuid
+        ],
+    )?;
+    conn.execute(
+        "INSERT OR REPLACE INTO image_metadata \
+         (image_uuid, camera_make, camera_model, lens, focal_length, aperture, exposure_time, iso, captured_at, copyright, caption) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        rusqlite::params![
+            image_uuid,
+            metadata.camera_make,
+            metadata.camera_model,
+            metadata.lens,
+            metadata.focal_length,
+            metadata.aperture,
+            metadata.exposure_time,
+            metadata.iso,
+            metadata.captured_at,
+            metadata.copyright,
+            metadata.caption,
+        ],
+    )?;
+    conn.execute("DELETE FROM image_palette WHERE image_uuid = ?", [image_uuid])?;
+    let mut stmt = conn.prepare(
+        "INSERT INTO image_palette (image_uuid, position, color, weight, lab_l, lab_a, lab_b) \
+         VALUES (?, ?, ?, ?, ?, ?, ?)",
+    )?;
+    for (position, entry) in analysis.palette.iter().enumerate() {
+        stmt.execute(rusqlite::params![
+            image_uuid,
+            position as i64,
+            entry.color,
+            entry.weight,
+            entry.lab[0],
+            entry.lab[1],
+            entry.lab[2],
+        ])?;
+    }
+    Ok(())
+}
+
+pub fn query_image_paths(conn: &rusqlite::Connection) -> Result<HashSet<String>, rusqlite::Error> {
+    let mut stmt = conn.prepare("SELECT path FROM images")?;
+    let rows = stmt.query_map([], |row| row.get(0))?;
+    rows.collect()
+}
+
+/// `(uuid, path)` of images whose original had the given content hash.
+pub fn query_images_by_content_hash(
+    conn: &rusqlite::Connection,
+    content_hash: &str,
+) -> Result<Vec<(String, String)>, rusqlite::Error> {
+    let mut stmt = conn.prepare("SELECT uuid, path FROM images WHERE content_hash = ? ORDER BY path")?;
+    let rows = stmt.query_map([content_hash], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    rows.collect()
+}
+
+pub fn move_image(
+    conn: &rusqlite::Connection,
+    image_uuid: &str,
+    path: &str,
+    collection: &str,
+    gallery: &str,
+) -> Result<(), AppError> {
+    let (old_path, old_collection, old_gallery): (String, String, String) = conn.query_row(
+        "SELECT path, collection, gallery FROM images WHERE uuid = ?",
+        [image_uuid],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+    conn.execute(
+        "UPDATE images SET path = ?, collection = ?, gallery = ? WHERE uuid = ?",
+        rusqlite::params![path, collection, gallery, image_uuid],
+    )?;
+    events::original_missing(conn, image_uuid, false)?;
+    let data = serde_json::json!({
+        "image_uuid": image_uuid,
+        "path": path,
+        "collection": collection,
+        "gallery": gallery,
+        "from": { "path": old_path, "collection": old_collection, "gallery": old_gallery },
+    });
+    events::emit(conn, "image.moved", &[collection, &old_collection], data)
+}
+
+pub fn insert_image(conn: &rusqlite::Connection, image: &NewImage) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT INTO images (uuid, path, collection, gallery, width, height, file_size, content_hash, file_mtime) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        rusqlite::params![
+            image.uuid,
+            image.path,
+            image.collection,
+            image.gallery,
+            image.width,
+            image.height,
+            image.file_size as i64,
+            image.content_hash,
+            image.file_mtime as i64,
+        ],
+    )?;
+    let data = serde_json::json!({
+        "image_uuid": image.uuid,
+        "path": image.path,
+        "collection": image.collection,
+        "gallery": image.gallery,
+    });
+    events::emit(conn, "image.added", &[&image.collection], data)
+}
+
+pub fn query_image_fingerprints(
+    conn: &rusqlite::Connection,
+    collection: Option<&str>,
+) -> Result<Vec<ImageFingerprint>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT uuid, path, content_hash, file_mtime, file_size FROM images \
+         WHERE ?1 IS NULL OR collection = ?1 ORDER BY collection, gallery, path",
+    )?;
+    let rows = stmt.query_map([collection], |row| {
+        let content_hash: Option<String> = row.get(2)?;
+        let file_mtime: Option<i64> = row.get(3)?;
+        let file_size: i64 = row.get(4)?;
+        Ok(ImageFingerprint {
+            uuid: row.get(0)?,
+            path: row.get(1)?,
+            identity: content_hash.zip(file_mtime).map(|(content_hash, file_mtime)| FileIdentity {
+                content_hash,
+                file_mtime: file_mtime as u64,
+                file_size: file_size as u64,
+            }),
+        })
+    })?;
+    rows.collect()
+}
+
+/// Link tags to an image, keeping the tags it already has. Returns how many
+/// links were new.
+pub fn add_image_tags(
+    conn: &rusqlite::Connection,
+    image_uuid: &str,
+    tag_uuids: &[String],
+) -> Result<usize, AppError> {
+    let mut stmt =
+        conn.prepare("INSERT OR IGNORE INTO image_tags (image_uuid, tag_uuid) VALUES (?, ?)")?;
+    let mut added = 0;
+    for tag_uuid in tag_uuids {
+        added += stmt.execute(rusqlite::params![image_uuid, tag_uuid])?;
+    }
+    if added > 0 {
+        events::image_tags_changed(conn, image_uuid)?;
+    }
+    Ok(added)
+}
+
+pub fn query_collections(
+    conn: &rusqlite::Connection,
+    access: &CollectionAccess,
+) -> Result<Vec<CollectionSummary>, rusqlite::Error> {
+    let mut params = Vec::new();
+    let visible = access_condition(access, "i.collection", &mut params)
+        .map(|c| format!(" WHERE {c}"))
+        .unwrap_or_default();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT i.collection, COUNT(*) as image_count, COUNT(DISTINCT i.gallery) as gallery_count FROM images i{visible} GROUP BY i.collection ORDER BY i.collection",
+    ))?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(params), |row| {
         Ok(CollectionSummary {
             name: row.get(0)?,
             image_count: row.get(1)?,
@@ -383,18 +1837,24 @@ pub fn query_collections(
 pub fn query_galleries(
     conn: &rusqlite::Connection,
     collection: Option<&str>,
+    access: &CollectionAccess,
 ) -> Result<Vec<GallerySummary>, rusqlite::Error> {
-    let (sql, params_owned);
+    let mut params_owned = Vec::new();
+    let mut conditions: Vec<String> =
+        access_condition(access, "i.collection", &mut params_owned).into_iter().collect();
     if let Some(c) = collection {
-        sql = "SELECT i.gallery, i.collection, COUNT(*) as image_count FROM images i WHERE i.collection = ? GROUP BY i.collection, i.gallery ORDER BY i.collection, i.gallery";
-        params_owned = vec![c.to_string()];
-    } else {
-        sql = "SELECT i.gallery, i.collection, COUNT(*) as image_count FROM images i GROUP BY i.collection, i.gallery ORDER BY i.collection, i.gallery";
-        params_owned = vec![];
+        conditions.push("i.collection = ?".into());
+        params_owned.push(c.to_string());
+    }
+    let mut sql = "SELECT i.gallery, i.collection, COUNT(*) as image_count FROM images i".to_string();
+    if !conditions.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
     }
+    sql.push_str(" GROUP BY i.collection, i.gallery ORDER BY i.collection, i.gallery");
     let param_refs: Vec<&dyn rusqlite::types::ToSql> =
         params_owned.iter().map(|s| s as &dyn rusqlite::types::ToSql).collect();
-    let mut stmt = conn.prepare(sql)?;
+    let mut stmt = conn.prepare(&sql)?;
     let rows = stmt.query_map(param_refs.as_slice(), |row| {
         Ok(GallerySummary {
             name: row.get(0)?,
@@ -408,18 +1868,24 @@ pub fn query_galleries(
 pub fn query_models(
     conn: &rusqlite::Connection,
     collection: Option<&str>,
+    access: &CollectionAccess,
 ) -> Result<Vec<Model>, rusqlite::Error> {
-    let (sql, params_owned);
+    let mut params_owned = Vec::new();
+    let mut conditions: Vec<String> =
+        access_condition(access, "collection", &mut params_owned).into_iter().collect();
     if let Some(c) = collection {
-        sql = "SELECT uuid, name, collection FROM models WHERE collection = ? ORDER BY name";
-        params_owned = vec![c.to_string()];
-    } else {
-        sql = "SELECT uuid, name, collection FROM models ORDER BY collection, name";
-        params_owned = vec![];
+        conditions.push("collection = ?".into());
+        params_owned.push(c.to_string());
     }
+    let mut sql = "SELECT uuid, name, collection FROM models".to_string();
+    if !conditions.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
+    }
+    sql.push_str(if collection.is_some() { " ORDER BY name" } else { " ORDER BY collection, name" });
     let param_refs: Vec<&dyn rusqlite::types::ToSql> =
         params_owned.iter().map(|s| s as &dyn rusqlite::types::ToSql).collect();
-    let mut stmt = conn.prepare(sql)?;
+    let mut stmt = conn.prepare(&sql)?;
     let rows = stmt.query_map(param_refs.as_slice(), |row| {
         Ok(Model {
             uuid: row.get(0)?,
@@ -477,3 +1943,171 @@ pub fn query_tag_groups(
     }
     Ok(groups)
 }
+
+pub fn query_tag_group(conn: &rusqlite::Connection, uuid: &str) -> Result<TagGroup, AppError> {
+    query_tag_groups(conn)?
+        .into_iter()
+        .find(|g| g.uuid == uuid)
+        .ok_or_else(|| AppError::NotFound("Tag group not found".into()))
+}
+
+pub fn query_tag(conn: &rusqlite::Connection, uuid: &str) -> Result<TagRef, AppError> {
+    conn.query_row(
+        "SELECT t.uuid, t.name, tg.name FROM tags t JOIN tag_groups tg ON t.tag_group_uuid = tg.uuid WHERE t.uuid = ?",
+        [uuid],
+        |row| {
+            Ok(TagRef {
+                uuid: row.get(0)?,
+                name: row.get(1)?,
+                group: row.get(2)?,
+            })
+        },
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => AppError::NotFound("Tag not found".into()),
+        other => AppError::from(other),
+    })
+}
+
+/// Trimmed, non-empty, and not already used by another row of `table`.
+fn validate_vocabulary_name<'a>(
+    conn: &rusqlite::Connection,
+    table: &str,
+    name: &'a str,
+    except_uuid: &str,
+) -> Result<&'a str, AppError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::BadRequest("name must not be empty".into()));
+    }
+    let taken: bool = conn.query_row(
+        &format!("SELECT EXISTS(SELECT 1 FROM {table} WHERE name = ? AND uuid != ?)"),
+        [name, except_uuid],
+        |row| row.get(0),
+    )?;
+    if taken {
+        return Err(AppError::Conflict(format!("Name already in use: {name}")));
+    }
+    Ok(name)
+}
+
+pub fn insert_tag_group(conn: &rusqlite::Connection, name: &str) -> Result<TagGroup, AppError> {
+    let name = validate_vocabulary_name(conn, "tag_groups", name, "")?;
+    let uuid = uuid::Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO tag_groups (uuid, name) VALUES (?, ?)",
+        rusqlite::params![uuid, name],
+    )?;
+    let group = query_tag_group(conn, &uuid)?;
+    let data = serde_json::json!({ "uuid": group.uuid, "name": group.name });
+    events::emit(conn, "tag_group.created", &[], data)?;
+    Ok(group)
+}
+
+pub fn rename_tag_group(
+    conn: &rusqlite::Connection,
+    uuid: &str,
+    name: &str,
+) -> Result<TagGroup, AppError> {
+    let before = query_tag_group(conn, uuid)?;
+    let name = validate_vocabulary_name(conn, "tag_groups", name, uuid)?;
+    conn.execute(
+        "UPDATE tag_groups SET name = ? WHERE uuid = ?",
+        rusqlite::params![name, uuid],
+    )?;
+    let group = query_tag_group(conn, uuid)?;
+    if group.name != before.name {
+        let data = serde_json::json!({
+            "uuid": group.uuid,
+            "name": group.name,
+            "before": { "name": before.name },
+        });
+        events::emit(conn, "tag_group.updated", &[], data)?;
+    }
+    Ok(group)
+}
+
+/// Only empty groups can be deleted; move or delete their tags first.
+pub fn delete_tag_group(conn: &rusqlite::Connection, uuid: &str) -> Result<(), AppError> {
+    let group = query_tag_group(conn, uuid)?;
+    if !group.tags.is_empty() {
+        return Err(AppError::Conflict(format!(
+            "Tag group '{}' still has {} tags",
+            group.name,
+            group.tags.len()
+        )));
+    }
+    conn.execute("DELETE FROM tag_groups WHERE uuid = ?", [uuid])?;
+    let data = serde_json::json!({ "uuid": group.uuid, "name": group.name });
+    events::emit(conn, "tag_group.deleted", &[], data)
+}
+
+fn ensure_tag_group_exists(conn: &rusqlite::Connection, uuid: &str) -> Result<(), AppError> {
+    let exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM tag_groups WHERE uuid = ?)",
+        [uuid],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        return Err(AppError::BadRequest(format!("Tag group not found: {uuid}")));
+    }
+    Ok(())
+}
+
+pub fn insert_tag(conn: &rusqlite::Connection, request: &NewTagRequest) -> Result<TagRef, AppError> {
+    let name = validate_vocabulary_name(conn, "tags", &request.name, "")?;
+    ensure_tag_group_exists(conn, &request.group_uuid)?;
+    let uuid = uuid::Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO tags (uuid, name, tag_group_uuid) VALUES (?, ?, ?)",
+        rusqlite::params![uuid, name, request.group_uuid],
+    )?;
+    let tag = query_tag(conn, &uuid)?;
+    events::emit(conn, "tag.created", &[], serde_json::json!(tag))?;
+    Ok(tag)
+}
+
+pub fn update_tag(
+    conn: &rusqlite::Connection,
+    uuid: &str,
+    request: &UpdateTagRequest,
+) -> Result<TagRef, AppError> {
+    if request.name.is_none() && request.group_uuid.is_none() {
+        return Err(AppError::BadRequest("set at least one of name or group_uuid".into()));
+    }
+    let before = query_tag(conn, uuid)?;
+    if let Some(name) = &request.name {
+        let name = validate_vocabulary_name(conn, "tags", name, uuid)?;
+        conn.execute("UPDATE tags SET name = ? WHERE uuid = ?", rusqlite::params![name, uuid])?;
+    }
+    if let Some(group_uuid) = &request.group_uuid {
+        ensure_tag_group_exists(conn, group_uuid)?;
+        conn.execute(
+            "UPDATE tags SET tag_group_uuid = ? WHERE uuid = ?",
+            rusqlite::params![group_uuid, uuid],
+        )?;
+    }
+    let tag = query_tag(conn, uuid)?;
+    if tag.name != before.name || tag.group != before.group {
+        let data = serde_json::json!({
+            "uuid": tag.uuid,
+            "name": tag.name,
+            "group": tag.group,
+            "before": { "name": before.name, "group": before.group },
+        });
+        events::emit(conn, "tag.updated", &[], data)?;
+    }
+    Ok(tag)
+}
+
+/// Delete a tag and remove it from every image.
+pub fn delete_tag(conn: &rusqlite::Connection, uuid: &str) -> Result<(), AppError> {
+    let tag = query_tag(conn, uuid)?;
+    let images = query_tagged_images(conn, uuid)?;
+    conn.execute("DELETE FROM image_tags WHERE tag_uuid = ?", [uuid])?;
+    conn.execute("DELETE FROM tags WHERE uuid = ?", [uuid])?;
+    for image in images {
+        events::image_tags_changed(conn, &image)?;
+    }
+    events::emit(conn, "tag.deleted", &[], serde_json::json!(tag))
+}
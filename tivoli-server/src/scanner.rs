@@ -0,0 +1,149 @@
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::errors::AppError;
+use crate::handlers::AppState;
+use crate::models::NewImage;
+use crate::queries;
+use crate::thumbnails::SourceFingerprint;
+
+/// What a scan changed in the `images` table.
+#[derive(Default)]
+pub struct ScanResult {
+    pub discovered: u64,
+    pub moved: u64,
+}
+
+/// BLAKE3 of a file's bytes, hex encoded. Blocking.
+pub fn hash_file(path: &Path) -> std::io::Result<String> {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update_reader(std::fs::File::open(path)?)?;
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+pub fn hash_bytes(bytes: &[u8]) -> String {
+    blake3::hash(bytes).to_hex().to_string()
+}
+
+/// Walk `<collection>/<gallery>/*.jpg` under the galleries directory and
+/// reconcile it with the `images` table. A file at an unknown path whose
+/// content hash matches an image whose own file has gone missing is treated
+/// as that image having moved: it keeps its UUID, tags and models. Anything
+/// else is added as a new image. Images whose files are gone are left alone
+/// (the verification job reports them). Blocking.
+pub fn scan(state: &AppState, collection: Option<&str>) -> Result<ScanResult, AppError> {
+    let known: HashSet<String> = {
+        let conn = state.db.conn()?;
+        queries::query_image_paths(&conn)?
+    };
+
+    let mut result = ScanResult::default();
+    for relative in list_originals(&state.galleries_path, collection) {
+        if known.contains(&relative) {
+            continue;
+        }
+        let path = state.galleries_path.join(&relative);
+        match reconcile(state, &path, &relative) {
+            Ok(Reconciled::Moved(from)) => {
+                tracing::info!("{from} moved to {relative}");
+                result.moved += 1;
+            }
+            Ok(Reconciled::Added) => result.discovered += 1,
+            Err(e) => tracing::warn!("Skipping {relative}: {e}"),
+        }
+    }
+    Ok(result)
+}
+
+enum Reconciled {
+    Moved(String),
+    Added,
+}
+
+fn reconcile(state: &AppState, path: &Path, relative: &str) -> Result<Reconciled, AppError> {
+    let unreadable = |e: std::io::Error| AppError::BadRequest(format!("Unreadable file: {e}"));
+    let content_hash = hash_file(path).map_err(unreadable)?;
+    let meta = std::fs::metadata(path).map_err(unreadable)?;
+    let (collection, gallery) = split_location(relative);
+
+    let conn = state.db.conn()?;
+    for (uuid, old_path) in queries::query_images_by_content_hash(&conn, &content_hash)? {
+        if !state.galleries_path.join(&old_path).exists() {
+            queries::move_image(&conn, &uuid, relative, collection, gallery)?;
+            drop(conn);
+            state.feed.wake();
+            return Ok(Reconciled::Moved(old_path));
+        }
+    }
+    drop(conn);
+
+    let (width, height) = image::image_dimensions(path)
+        .map_err(|e| AppError::BadRequest(format!("Failed to read image size: {e}")))?;
+    let image = NewImage {
+        uuid: uuid::Uuid::new_v4().to_string(),
+        path: relative.to_string(),
+        collection: collection.to_string(),
+        gallery: gallery.to_string(),
+        width,
+        height,
+        file_size: meta.len(),
+        content_hash,
+        file_mtime: SourceFingerprint::from_metadata(&meta).mtime,
+    };
+    let conn = state.db.conn()?;
+    queries::insert_image(&conn, &image)?;
+    drop(conn);
+    state.feed.wake();
+    Ok(Reconciled::Added)
+}
+
+fn split_location(relative: &str) -> (&str, &str) {
+    let mut parts = relative.split('/');
+    (parts.next().unwrap_or(""), parts.next().unwrap_or(""))
+}
+
+/// Relative paths of the JPEG originals, skipping hidden entries such as the
+/// thumbnail cache and temp files.
+fn list_originals(galleries_path: &Path, collection: Option<&str>) -> Vec<String> {
+    let visible_dirs = |dir: &Path| -> Vec<String> {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return Vec::new();
+        };
+        let mut names: Vec<String> = entries
+            .flatten()
+            .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .filter(|name| !name.starts_with('.'))
+            .collect();
+        names.sort();
+        names
+    };
+
+    let collections = match collection {
+        // A collection name is a single directory, never a path
+        Some(c) if c.contains(['/', '\\']) || c.starts_with('.') => Vec::new(),
+        Some(c) => vec![c.to_string()],
+        None => visible_dirs(galleries_path),
+    };
+    let mut originals = Vec::new();
+    for collection in collections {
+        for gallery in visible_dirs(&galleries_path.join(&collection)) {
+            let Ok(entries) = std::fs::read_dir(galleries_path.join(&collection).join(&gallery)) else {
+                continue;
+            };
+            let mut files: Vec<String> = entries
+                .flatten()
+                .filter(|e| e.file_type().is_ok_and(|t| t.is_file()))
+                .map(|e| e.file_name().to_string_lossy().into_owned())
+                .filter(|name| !name.starts_with('.'))
+                .filter(|name| {
+                    let lower = name.to_ascii_lowercase();
+                    lower.ends_with(".jpg") || lower.ends_with(".jpeg")
+                })
+                .collect();
+            files.sort();
+            originals.extend(files.into_iter().map(|f| format!("{collection}/{gallery}/{f}")));
+        }
+    }
+    originals
+}
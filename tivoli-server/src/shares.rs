@@ -0,0 +1,316 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use crate::auth::{self, collections_column, collections_json, Caller};
+use crate::errors::AppError;
+use crate::models::{
+    CollectionAccess, FilterClause, FilterField, FilterOp, FilterValue, ImageRow, NewShareRequest, Role,
+    ShareLink, ShareTarget, SharedView,
+};
+use crate::queries;
+use crate::unix_now;
+
+/// Lifetime of a link created without `expires_in`.
+const DEFAULT_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+const MIN_TTL_SECS: u64 = 60;
+const MAX_TTL_SECS: u64 = 90 * 24 * 60 * 60;
+
+/// A verified, live share link, as the public routes use it.
+pub struct Share {
+    pub target: ShareTarget,
+    /// The creator's collection access when the link was made, narrowed to
+    /// what they may still see now.
+    pub access: CollectionAccess,
+    pub allow_download: bool,
+    pub watermark: bool,
+    pub expires_at: u64,
+}
+
+// --- Tokens ---
+
+/// Tokens look like `<id>.<expires_at>.<signature>`, the signature being
+/// HMAC-SHA256 of `<id>.<expires_at>` under the server's signing key,
+/// base64url-encoded. A forged or altered token fails before any lookup.
+fn signing_key(conn: &rusqlite::Connection) -> Result<Vec<u8>, AppError> {
+    let key: String = conn.query_row(
+        "SELECT value FROM settings WHERE key = 'share_signing_key'",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(key.into_bytes())
+}
+
+fn mac(key: &[u8], payload: &str) -> Hmac<Sha256> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+fn sign(key: &[u8], id: &str, expires_at: u64) -> String {
+    let payload = format!("{id}.{expires_at}");
+    let signature = mac(key, &payload).finalize().into_bytes();
+    format!("{payload}.{}", URL_SAFE_NO_PAD.encode(signature))
+}
+
+/// The link id and expiry, if the signature checks out.
+fn verify_token(key: &[u8], token: &str) -> Option<(String, u64)> {
+    let (payload, signature) = token.rsplit_once('.')?;
+    let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+    mac(key, payload).verify_slice(&signature).ok()?;
+    let (id, expires_at) = payload.split_once('.')?;
+    Some((id.to_string(), expires_at.parse().ok()?))
+}
+
+// --- Link management ---
+
+const SHARE_COLUMNS: &str =
+    "id, target, allow_download, watermark, created_by, created_at, expires_at, revoked_at";
+
+fn share_from_row(key: &[u8], row: &rusqlite::Row) -> rusqlite::Result<ShareLink> {
+    let id: String = row.get(0)?;
+    let target: String = row.get(1)?;
+    let target = serde_json::from_str(&target).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, Box::new(e))
+    })?;
+    let expires_at = row.get::<_, i64>(6)? as u64;
+    let token = sign(key, &id, expires_at);
+    Ok(ShareLink {
+        path: format!("/share/{token}"),
+        token,
+        id,
+        target,
+        allow_download: row.get(2)?,
+        watermark: row.get(3)?,
+        created_by: row.get(4)?,
+        created_at: row.get::<_, i64>(5)? as u64,
+        expires_at,
+        revoked_at: row.get::<_, Option<i64>>(7)?.map(|t| t as u64),
+    })
+}
+
+/// Links the caller may manage: all of them for unrestricted callers, only
+/// their own for callers limited to some collections.
+fn owner_condition(caller: &Caller) -> (&'static str, Option<String>) {
+    match caller.collections {
+        CollectionAccess::All => ("1", None),
+        CollectionAccess::Only(_) => ("created_by_principal = ?", Some(caller.principal())),
+    }
+}
+
+pub fn list_shares(conn: &rusqlite::Connection, caller: &Caller) -> Result<Vec<ShareLink>, AppError> {
+    let key = signing_key(conn)?;
+    let (condition, param) = owner_condition(caller);
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {SHARE_COLUMNS} FROM share_links WHERE {condition} ORDER BY created_at DESC, id"
+    ))?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(param), |row| share_from_row(&key, row))?;
+    Ok(rows.collect::<Result<_, _>>()?)
+}
+
+pub fn query_share(conn: &rusqlite::Connection, id: &str) -> Result<ShareLink, AppError> {
+    let key = signing_key(conn)?;
+    conn.query_row(
+        &format!("SELECT {SHARE_COLUMNS} FROM share_links WHERE id = ?"),
+        [id],
+        |row| share_from_row(&key, row),
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => AppError::NotFound("Share link not found".into()),
+        other => AppError::from(other),
+    })
+}
+
+/// Check the target exists and is visible to the caller, then store the link.
+pub fn create_share(
+    conn: &rusqlite::Connection,
+    caller: &Caller,
+    request: &NewShareRequest,
+) -> Result<ShareLink, AppError> {
+    let ttl = request.expires_in.unwrap_or(DEFAULT_TTL_SECS);
+    if !(MIN_TTL_SECS..=MAX_TTL_SECS).contains(&ttl) {
+        return Err(AppError::BadRequest(format!(
+            "expires_in must be between {MIN_TTL_SECS} and {MAX_TTL_SECS} seconds"
+        )));
+    }
+    let (_, images) = shared_images(conn, &request.target, &caller.collections)?;
+    if let ShareTarget::Gallery { .. } = request.target {
+        if images.is_empty() {
+            return Err(AppError::NotFound("Gallery not found".into()));
+        }
+    }
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let now = unix_now();
+    conn.execute(
+        "INSERT INTO share_links (id, target, collections, allow_download, watermark, created_by,
+            created_by_principal, created_at, expires_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        rusqlite::params![
+            id,
+            serde_json::to_string(&request.target).expect("share target serializes"),
+            collections_json(caller.collections.to_list().as_deref())?,
+            request.allow_download.unwrap_or(true),
+            request.watermark,
+            caller.name,
+            caller.principal(),
+            now as i64,
+            (now + ttl) as i64,
+        ],
+    )?;
+    query_share(conn, &id)
+}
+
+/// Revoke a link. Revoking twice is harmless; the first time is kept.
+pub fn revoke_share(conn: &rusqlite::Connection, caller: &Caller, id: &str) -> Result<(), AppError> {
+    let (condition, param) = owner_condition(caller);
+    let params: Vec<String> = std::iter::once(id.to_string()).chain(param).collect();
+    let found: bool = conn.query_row(
+        &format!("SELECT EXISTS(SELECT 1 FROM share_links WHERE id = ? AND {condition})"),
+        rusqlite::params_from_iter(&params),
+        |row| row.get(0),
+    )?;
+    if !found {
+        return Err(AppError::NotFound("Share link not found".into()));
+    }
+    conn.execute(
+        "UPDATE share_links SET revoked_at = COALESCE(revoked_at, ?) WHERE id = ?",
+        rusqlite::params![unix_now() as i64, id],
+    )?;
+    Ok(())
+}
+
+// --- Public access ---
+
+/// The live link behind a token. Bad signatures are indistinguishable from
+/// unknown links; expired and revoked ones say so, as do links whose creator
+/// has since lost the right to share.
+pub fn open_share(conn: &rusqlite::Connection, token: &str) -> Result<Share, AppError> {
+    let key = signing_key(conn)?;
+    let not_found = || AppError::NotFound("Share link not found".into());
+    let (id, expires_at) = verify_token(&key, token).ok_or_else(not_found)?;
+    if expires_at <= unix_now() {
+        return Err(AppError::Gone("This share link has expired".into()));
+    }
+    let (target, collections, allow_download, watermark, revoked_at, creator): (
+        String,
+        Option<Vec<String>>,
+        bool,
+        bool,
+        Option<i64>,
+        String,
+    ) = conn
+        .query_row(
+            "SELECT target, collections, allow_download, watermark, revoked_at, created_by_principal
+             FROM share_links WHERE id = ? AND expires_at = ?",
+            rusqlite::params![id, expires_at as i64],
+            |row| {
+                Ok((
+                    row.get(0)?,
+                    collections_column(row, 1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(4)?,
+                    row.get(5)?,
+                ))
+            },
+        )
+        .map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => not_found(),
+            other => AppError::from(other),
+        })?;
+    if revoked_at.is_some() {
+        return Err(AppError::Gone("This share link has been revoked".into()));
+    }
+    let creator_access = match auth::principal_access(conn, &creator)? {
+        Some((role, access)) if role >= Role::Tagger => access,
+        _ => {
+            return Err(AppError::Gone(
+                "The account that shared this link can no longer share it".into(),
+            ))
+        }
+    };
+    Ok(Share {
+        target: serde_json::from_str(&target)
+            .map_err(|e| AppError::DbError(format!("Bad share target: {e}")))?,
+        access: CollectionAccess::from_list(collections).intersect(&creator_access),
+        allow_download,
+        watermark,
+        expires_at,
+    })
+}
+
+/// The name and images behind a target, as seen with `access`.
+fn shared_images(
+    conn: &rusqlite::Connection,
+    target: &ShareTarget,
+    access: &CollectionAccess,
+) -> Result<(String, Vec<ImageRow>), AppError> {
+    match target {
+        ShareTarget::Gallery { collection, gallery } => {
+            let filters = gallery_filters(collection, gallery);
+            let (sql, params) = queries::build_image_query(conn, &filters, &[], access)?;
+            Ok((gallery.clone(), queries::query_images(conn, &sql, &params)?))
+        }
+        ShareTarget::Album { album_uuid } => {
+            let album = queries::query_album(conn, album_uuid, access)?;
+            Ok((album.name, album.images))
+        }
+        ShareTarget::SavedSearch { saved_search_uuid } => {
+            let search = queries::query_saved_search(conn, saved_search_uuid, access)?;
+            let (sql, params) = queries::build_image_query(conn, &search.filters, &search.sort, access)?;
+            Ok((search.name, queries::query_images(conn, &sql, &params)?))
+        }
+    }
+}
+
+fn gallery_filters(collection: &str, gallery: &str) -> Vec<FilterClause> {
+    let eq = |field, value: &str| FilterClause {
+        field,
+        op: FilterOp::Eq,
+        value: FilterValue::Single(value.to_string()),
+        max_distance: None,
+    };
+    vec![eq(FilterField::Collection, collection), eq(FilterField::Gallery, gallery)]
+}
+
+pub fn shared_view(conn: &rusqlite::Connection, share: &Share) -> Result<SharedView, AppError> {
+    let (name, images) = shared_images(conn, &share.target, &share.access)?;
+    Ok(SharedView {
+        name,
+        kind: match share.target {
+            ShareTarget::Gallery { .. } => "gallery",
+            ShareTarget::Album { .. } => "album",
+            ShareTarget::SavedSearch { .. } => "saved_search",
+        },
+        expires_at: share.expires_at,
+        allow_download: share.allow_download,
+        watermark: share.watermark,
+        images,
+    })
+}
+
+/// The stored path of an image the share covers; anything else is 404.
+pub fn shared_image_path(
+    conn: &rusqlite::Connection,
+    share: &Share,
+    image_uuid: &str,
+) -> Result<String, AppError> {
+    let filters = match &share.target {
+        ShareTarget::Gallery { collection, gallery } => gallery_filters(collection, gallery),
+        ShareTarget::Album { album_uuid } => vec![FilterClause {
+            field: FilterField::Album,
+            op: FilterOp::Eq,
+            value: FilterValue::Single(album_uuid.clone()),
+            max_distance: None,
+        }],
+        ShareTarget::SavedSearch { saved_search_uuid } => {
+            queries::query_saved_search(conn, saved_search_uuid, &share.access)?.filters
+        }
+    };
+    if !queries::image_matches(conn, image_uuid, &filters, &share.access)? {
+        return Err(AppError::NotFound("Image not found".into()));
+    }
+    queries::query_image_path(conn, image_uuid, &share.access)
+}
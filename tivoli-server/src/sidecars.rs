@@ -0,0 +1,235 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::errors::AppError;
+use crate::handlers::{resolve_original, AppState};
+use crate::metadata::{NS_DC, NS_LR, NS_RDF};
+use crate::models::{SidecarExportStatus, TagRef};
+use crate::queries;
+use crate::unix_now;
+
+/// Background job that writes each image's tags to an `.xmp` sidecar next to
+/// the original, so they travel with the files into Lightroom and friends.
+#[derive(Default)]
+pub struct SidecarExporter {
+    status: Mutex<SidecarExportStatus>,
+}
+
+impl SidecarExporter {
+    fn lock(&self) -> MutexGuard<'_, SidecarExportStatus> {
+        self.status.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn status(&self) -> SidecarExportStatus {
+        self.lock().clone()
+    }
+
+    /// Kick off an export over one collection (or everything) in the background.
+    pub fn start(
+        state: &Arc<AppState>,
+        collection: Option<String>,
+    ) -> Result<SidecarExportStatus, AppError> {
+        let snapshot = {
+            let mut status = state.sidecars.lock();
+            if status.running {
+                return Err(AppError::Conflict("Sidecar export is already running".into()));
+            }
+            *status = SidecarExportStatus {
+                running: true,
+                collection: collection.clone(),
+                started_at: Some(unix_now()),
+                ..SidecarExportStatus::default()
+            };
+            status.clone()
+        };
+
+        let state = Arc::clone(state);
+        tokio::spawn(async move {
+            let worker = Arc::clone(&state);
+            let result = tokio::task::spawn_blocking(move || run(&worker, collection.as_deref()))
+                .await
+                .unwrap_or_else(|e| Err(AppError::DbError(format!("Sidecar export task failed: {e}"))));
+            let mut status = state.sidecars.lock();
+            status.running = false;
+            status.finished_at = Some(unix_now());
+            match result {
+                Ok(()) => tracing::info!(
+                    "Sidecar export finished: {} written, {} unchanged, {} failed",
+                    status.written,
+                    status.unchanged,
+                    status.failed
+                ),
+                Err(e) => {
+                    tracing::error!("Sidecar export aborted: {e}");
+                    status.error = Some(e.to_string());
+                }
+            }
+        });
+        Ok(snapshot)
+    }
+}
+
+/// Blocking: the whole export runs on one blocking thread.
+fn run(state: &AppState, collection: Option<&str>) -> Result<(), AppError> {
+    let (images, mut tags) = {
+        let conn = state.db.conn()?;
+        (
+            queries::query_image_locations(&conn, collection)?,
+            queries::query_image_tag_refs(&conn, collection)?,
+        )
+    };
+    state.sidecars.lock().total = images.len() as u64;
+
+    for image in images {
+        let image_tags = tags.remove(&image.uuid).unwrap_or_default();
+        let result = resolve_original(&state.galleries_path, &image.path)
+            .and_then(|original| write_sidecar(&sidecar_path(&original), &image_tags));
+        let mut status = state.sidecars.lock();
+        match result {
+            Ok(true) => status.written += 1,
+            Ok(false) => status.unchanged += 1,
+            Err(e) => {
+                tracing::warn!("Failed to write sidecar for {}: {e}", image.path);
+                status.failed += 1;
+            }
+        }
+        status.processed += 1;
+    }
+    Ok(())
+}
+
+/// `photo.jpg` -> `photo.xmp`, the naming Lightroom and Bridge look for.
+pub fn sidecar_path(original: &Path) -> PathBuf {
+    original.with_extension("xmp")
+}
+
+/// Write (or update) a sidecar with the given tags. Returns whether the file
+/// changed. Existing sidecars keep everything except their keyword lists.
+fn write_sidecar(path: &Path, tags: &[TagRef]) -> Result<bool, AppError> {
+    let existing = match std::fs::read_to_string(path) {
+        Ok(text) => Some(text),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(AppError::BadRequest(format!("Unreadable sidecar: {e}"))),
+    };
+    let content = match &existing {
+        Some(text) => merge_keywords(text, tags)?,
+        None => new_sidecar(tags),
+    };
+    if existing.as_deref() == Some(content.as_str()) {
+        return Ok(false);
+    }
+
+    // Write next to the target and rename so readers never see half a file
+    let file_name = path.file_name().map_or_else(String::new, |n| n.to_string_lossy().into_owned());
+    let temp = path.with_file_name(format!(".{file_name}.tmp"));
+    std::fs::write(&temp, content)
+        .and_then(|()| std::fs::rename(&temp, path))
+        .map_err(|e| {
+            let _ = std::fs::remove_file(&temp);
+            AppError::BadRequest(format!("Failed to write sidecar: {e}"))
+        })?;
+    Ok(true)
+}
+
+fn new_sidecar(tags: &[TagRef]) -> String {
+    format!(
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+         <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n \
+         <rdf:RDF xmlns:rdf=\"{NS_RDF}\">\n  \
+         <rdf:Description rdf:about=\"\">\n{}  \
+         </rdf:Description>\n \
+         </rdf:RDF>\n\
+         </x:xmpmeta>\n\
+         <?xpacket end=\"w\"?>\n",
+        keyword_properties(tags)
+    )
+}
+
+/// `dc:subject` with tag names and `lr:hierarchicalSubject` with
+/// `group|tag` paths. Namespaces are declared inline so the block can be
+/// dropped into any `rdf:Description`.
+fn keyword_properties(tags: &[TagRef]) -> String {
+    if tags.is_empty() {
+        return String::new();
+    }
+    let bag = |items: Vec<String>| {
+        items
+            .iter()
+            .map(|item| format!("     <rdf:li>{}</rdf:li>\n", escape(item)))
+            .collect::<String>()
+    };
+    format!(
+        "   <dc:subject xmlns:dc=\"{NS_DC}\">\n    <rdf:Bag>\n{}    </rdf:Bag>\n   </dc:subject>\n   \
+         <lr:hierarchicalSubject xmlns:lr=\"{NS_LR}\">\n    <rdf:Bag>\n{}    </rdf:Bag>\n   </lr:hierarchicalSubject>\n",
+        bag(tags.iter().map(|t| t.name.clone()).collect()),
+        bag(tags.iter().map(|t| format!("{}|{}", t.group, t.name)).collect()),
+    )
+}
+
+/// Replace the keyword properties of an existing sidecar, leaving the rest of
+/// the document (develop settings, ratings, ...) byte for byte as it was.
+fn merge_keywords(existing: &str, tags: &[TagRef]) -> Result<String, AppError> {
+    let doc = roxmltree::Document::parse(existing)
+        .map_err(|e| AppError::BadRequest(format!("Unparseable sidecar: {e}")))?;
+    let description = doc
+        .descendants()
+        .find(|n| n.has_tag_name((NS_RDF, "Description")))
+        .ok_or_else(|| AppError::BadRequest("Sidecar has no rdf:Description".into()))?;
+
+    // Edits as (range, replacement), applied back to front
+    let mut edits: Vec<(std::ops::Range<usize>, String)> = doc
+        .descendants()
+        .filter(|n| n.has_tag_name((NS_DC, "subject")) || n.has_tag_name((NS_LR, "hierarchicalSubject")))
+        .map(|n| (whole_lines(existing, n.range()), String::new()))
+        .collect();
+
+    let properties = keyword_properties(tags);
+    let range = description.range();
+    let element = &existing[range.clone()];
+    if element.ends_with("/>") {
+        // <rdf:Description .../> has nowhere to put children: open it up
+        let name_len = element[1..]
+            .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+            .unwrap_or(0);
+        let name = &element[1..1 + name_len];
+        edits.push((range.end - 2..range.end, format!(">\n{properties}  </{name}>")));
+    } else {
+        let close = range.start + element.rfind("</").unwrap_or(element.len());
+        let close = whole_lines(existing, close..close).start;
+        edits.push((close..close, properties));
+    }
+
+    edits.sort_by_key(|(range, _)| std::cmp::Reverse(range.start));
+    let mut merged = existing.to_string();
+    for (range, replacement) in edits {
+        merged.replace_range(range, &replacement);
+    }
+    Ok(merged)
+}
+
+/// Widen a range to cover its indentation, and its line break too when the
+/// range starts its line, so removing and re-adding keywords is stable.
+fn whole_lines(text: &str, range: std::ops::Range<usize>) -> std::ops::Range<usize> {
+    let bytes = text.as_bytes();
+    let mut start = range.start;
+    while start > 0 && matches!(bytes[start - 1], b' ' | b'\t') {
+        start -= 1;
+    }
+    if start > 0 && bytes[start - 1] != b'\n' {
+        return range;
+    }
+    let mut end = range.end;
+    if end > range.start {
+        while end < bytes.len() && matches!(bytes[end], b' ' | b'\t') {
+            end += 1;
+        }
+        if bytes.get(end) == Some(&b'\n') {
+            end += 1;
+        }
+    }
+    start..end
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
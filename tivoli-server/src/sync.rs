@@ -0,0 +1,355 @@
+use rusqlite::types::Value as SqlValue;
+
+use crate::audit::json_value;
+use crate::auth::Caller;
+use crate::errors::AppError;
+use crate::history::Edit;
+use crate::models::{
+    CollectionAccess, ConflictPolicy, ReconcileTagsRequest, SyncImage, SyncImageModel, SyncImageTag, SyncModel,
+    SyncResponse, SyncTag, SyncTagGroup, SyncTombstone, TagOperation, TagOperationKind, TagOperationResult,
+    TagOperationStatus, TagRef,
+};
+use crate::queries::{self, access_condition};
+use crate::unix_now;
+
+/// How long tombstones are kept. A client that last synced before the
+/// oldest pruned one gets a full sync instead.
+const RETENTION_SECS: u64 = 30 * 24 * 60 * 60;
+/// Offline tag operations accepted in one request.
+const MAX_OPERATIONS: usize = 10_000;
+
+/// Drop old tombstones, remembering how far they went. Run periodically.
+pub fn prune(conn: &rusqlite::Connection) -> Result<(), AppError> {
+    let cutoff = unix_now().saturating_sub(RETENTION_SECS);
+    let pruned: Option<i64> = conn.query_row(
+        "SELECT MAX(seq) FROM sync_tombstones WHERE at < ?",
+        [cutoff as i64],
+        |row| row.get(0),
+    )?;
+    if let Some(pruned) = pruned {
+        conn.execute("DELETE FROM sync_tombstones WHERE seq <= ?", [pruned])?;
+        conn.execute(
+            "UPDATE sync_state SET pruned_seq = MAX(pruned_seq, ?) WHERE id = 1",
+            [pruned],
+        )?;
+    }
+    Ok(())
+}
+
+/// Rows the caller can see that changed after `since`, and rows deleted
+/// after it. Everything instead when `since` is missing, older than the
+/// tombstones kept, or from another database.
+pub fn sync(
+    conn: &rusqlite::Connection,
+    access: &CollectionAccess,
+    since: Option<i64>,
+) -> Result<SyncResponse, AppError> {
+    let (seq, pruned_seq) = sync_point(conn)?;
+    let since = since.filter(|&since| since >= pruned_seq && since <= seq);
+    let full = since.is_none();
+    // Every row has been stamped at least once, with 1 or above
+    let since = since.unwrap_or(0);
+
+    let images = select(
+        conn,
+        "SELECT i.uuid, i.path, i.collection, i.gallery, i.width, i.height, i.file_size, i.blurhash, \
+         i.dominant_color, i.taken_at, i.rating, i.color_label, i.flag FROM images i WHERE i.sync_seq > ?",
+        since,
+        access,
+        "i.collection",
+        |row| {
+            Ok(SyncImage {
+                uuid: row.get(0)?,
+                path: row.get(1)?,
+                collection: row.get(2)?,
+                gallery: row.get(3)?,
+                width: row.get(4)?,
+                height: row.get(5)?,
+                file_size: row.get(6)?,
+                blurhash: row.get(7)?,
+                dominant_color: row.get(8)?,
+                taken_at: row.get(9)?,
+                rating: row.get(10)?,
+                color_label: row.get(11)?,
+                flag: row.get(12)?,
+            })
+        },
+    )?;
+    let tag_groups = select(
+        conn,
+        "SELECT uuid, name FROM tag_groups WHERE sync_seq > ?",
+        since,
+        &CollectionAccess::All,
+        "",
+        |row| Ok(SyncTagGroup { uuid: row.get(0)?, name: row.get(1)? }),
+    )?;
+    let tags = select(
+        conn,
+        "SELECT uuid, name, tag_group_uuid FROM tags WHERE sync_seq > ?",
+        since,
+        &CollectionAccess::All,
+        "",
+        |row| {
+            Ok(SyncTag {
+                uuid: row.get(0)?,
+                name: row.get(1)?,
+                group_uuid: row.get(2)?,
+            })
+        },
+    )?;
+    let models = select(
+        conn,
+        "SELECT uuid, name, collection FROM models WHERE sync_seq > ?",
+        since,
+        access,
+        "collection",
+        |row| {
+            Ok(SyncModel {
+                uuid: row.get(0)?,
+                name: row.get(1)?,
+                collection: row.get(2)?,
+            })
+        },
+    )?;
+    let image_tags = select(
+        conn,
+        "SELECT it.image_uuid, it.tag_uuid FROM image_tags it JOIN images i ON i.uuid = it.image_uuid \
+         WHERE it.sync_seq > ?",
+        since,
+        access,
+        "i.collection",
+        |row| Ok(SyncImageTag { image_uuid: row.get(0)?, tag_uuid: row.get(1)? }),
+    )?;
+    let image_models = select(
+        conn,
+        "SELECT im.image_uuid, im.model_uuid FROM image_models im JOIN images i ON i.uuid = im.image_uuid \
+         WHERE im.sync_seq > ?",
+        since,
+        access,
+        "i.collection",
+        |row| Ok(SyncImageModel { image_uuid: row.get(0)?, model_uuid: row.get(1)? }),
+    )?;
+
+    let mut tombstones = Vec::new();
+    if !full {
+        let mut visible = Vec::new();
+        // Only tags and groups belong to no collection. A tag or model pair
+        // deleted after its image has none either, and could name an image
+        // the caller can't see; the image's own tombstone covers it
+        let restriction = access_condition(access, "collection", &mut visible)
+            .map(|c| {
+                format!(" AND ((collection IS NULL AND table_name IN ('tags', 'tag_groups')) OR {c})")
+            })
+            .unwrap_or_default();
+        let mut params = vec![SqlValue::from(since)];
+        params.extend(visible.into_iter().map(SqlValue::from));
+        let mut stmt = conn.prepare(&format!(
+            "SELECT seq, table_name, key FROM sync_tombstones WHERE seq > ?{restriction} ORDER BY seq"
+        ))?;
+        tombstones = stmt
+            .query_map(rusqlite::params_from_iter(params), |row| {
+                Ok(SyncTombstone {
+                    seq: row.get(0)?,
+                    table: row.get(1)?,
+                    key: json_value(row, 2)?.unwrap_or_default(),
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        // A row deleted and then added back is sent as the row alone
+        let upserted = |t: &SyncTombstone| {
+            let key = |name: &str| t.key[name].as_str().unwrap_or_default();
+            match t.table.as_str() {
+                "images" => images.iter().any(|r| r.uuid == key("uuid")),
+                "tag_groups" => tag_groups.iter().any(|r| r.uuid == key("uuid")),
+                "tags" => tags.iter().any(|r| r.uuid == key("uuid")),
+                "models" => models.iter().any(|r| r.uuid == key("uuid")),
+                "image_tags" => image_tags
+                    .iter()
+                    .any(|r| r.image_uuid == key("image_uuid") && r.tag_uuid == key("tag_uuid")),
+                "image_models" => image_models
+                    .iter()
+                    .any(|r| r.image_uuid == key("image_uuid") && r.model_uuid == key("model_uuid")),
+                _ => false,
+            }
+        };
+        tombstones.retain(|t| !upserted(t));
+    }
+
+    Ok(SyncResponse {
+        seq,
+        full,
+        images,
+        tag_groups,
+        tags,
+        models,
+        image_tags,
+        image_models,
+        tombstones,
+    })
+}
+
+/// The current sequence, and the newest one whose tombstones are gone.
+fn sync_point(conn: &rusqlite::Connection) -> Result<(i64, i64), AppError> {
+    Ok(conn.query_row(
+        "SELECT seq, pruned_seq FROM sync_state WHERE id = 1",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?)
+}
+
+// --- Offline tag edits ---
+
+/// An image's tags before the offline operations and as they leave them.
+struct Tagging {
+    image: String,
+    before: Vec<TagRef>,
+    after: Vec<String>,
+}
+
+/// Apply tag operations a client queued offline after syncing at
+/// `request.since`. Operations run in time order, each deciding alone
+/// whether it stands; the images they change are written together at the
+/// end, as one undoable edit.
+pub fn reconcile_tags(
+    conn: &rusqlite::Connection,
+    caller: &Caller,
+    request: &ReconcileTagsRequest,
+) -> Result<Vec<TagOperationResult>, AppError> {
+    if request.operations.len() > MAX_OPERATIONS {
+        return Err(AppError::BadRequest(format!("At most {MAX_OPERATIONS} operations per request")));
+    }
+    let (seq, pruned_seq) = sync_point(conn)?;
+    if request.since > seq {
+        return Err(AppError::BadRequest("since is not a sync point of this server".into()));
+    }
+    if request.since < pruned_seq {
+        return Err(AppError::Gone(
+            "Changes since this sync point are no longer kept; sync again and review the edits".into(),
+        ));
+    }
+
+    // Ties keep the order the client sent them in
+    let mut order: Vec<usize> = (0..request.operations.len()).collect();
+    order.sort_by_key(|&i| request.operations[i].at);
+    let mut results: Vec<Option<TagOperationResult>> = request.operations.iter().map(|_| None).collect();
+    let mut images: Vec<Tagging> = Vec::new();
+    for i in order {
+        results[i] = Some(reconcile_one(conn, caller, request, &request.operations[i], &mut images)?);
+    }
+
+    // The batch lands whole or not at all, so a client can simply resend it
+    let tx = conn.unchecked_transaction()?;
+    let mut edit = Edit::new(caller);
+    for tagging in &images {
+        queries::replace_image_tags(conn, &tagging.image, &tagging.after, &caller.collections)?;
+        let after = queries::query_tags_of_image(conn, &tagging.image)?;
+        edit.tags(conn, &tagging.image, &tagging.before, &after)?;
+    }
+    tx.commit()?;
+    Ok(results.into_iter().flatten().collect())
+}
+
+fn reconcile_one(
+    conn: &rusqlite::Connection,
+    caller: &Caller,
+    request: &ReconcileTagsRequest,
+    operation: &TagOperation,
+    images: &mut Vec<Tagging>,
+) -> Result<TagOperationResult, AppError> {
+    let result = |status, server_at| TagOperationResult {
+        id: operation.id.clone(),
+        status,
+        error: None,
+        server_at,
+    };
+    let found = queries::query_image_path(conn, &operation.image_uuid, &caller.collections)
+        .and_then(|_| queries::query_tag(conn, &operation.tag_uuid));
+    match found {
+        Ok(_) => {}
+        Err(AppError::NotFound(msg)) => {
+            return Ok(TagOperationResult {
+                error: Some(msg),
+                ..result(TagOperationStatus::Failed, None)
+            })
+        }
+        Err(e) => return Err(e),
+    }
+    let index = match images.iter().position(|t| t.image == operation.image_uuid) {
+        Some(index) => index,
+        None => {
+            images.push(Tagging {
+                image: operation.image_uuid.clone(),
+                before: queries::query_tags_of_image(conn, &operation.image_uuid)?,
+                after: queries::query_image_tag_uuids(conn, &operation.image_uuid)?,
+            });
+            images.len() - 1
+        }
+    };
+    let tags = &mut images[index].after;
+    let add = operation.op == TagOperationKind::Add;
+    if tags.contains(&operation.tag_uuid) == add {
+        return Ok(result(TagOperationStatus::Unchanged, None));
+    }
+
+    // Nothing is written until the end, so this sees the server's state as
+    // of the request: the caller's own edits made online since `since`, from
+    // this or another device, count as changes too
+    if let Some(server_at) = changed_since(conn, &operation.image_uuid, &operation.tag_uuid, request.since)? {
+        // A clock running ahead doesn't get to win every conflict
+        let at = operation.at.min(unix_now());
+        if request.policy == ConflictPolicy::Reject || at <= server_at {
+            return Ok(result(TagOperationStatus::Conflict, Some(server_at)));
+        }
+    }
+    if add {
+        tags.push(operation.tag_uuid.clone());
+    } else {
+        tags.retain(|t| *t != operation.tag_uuid);
+    }
+    Ok(result(TagOperationStatus::Applied, None))
+}
+
+/// When an image-tag pair was last added or removed after sync point
+/// `since`, if it was. Only real additions and removals count, not the
+/// restamp an image gets when it moves to another collection.
+fn changed_since(
+    conn: &rusqlite::Connection,
+    image: &str,
+    tag: &str,
+    since: i64,
+) -> Result<Option<u64>, AppError> {
+    let at: Option<i64> = conn.query_row(
+        "SELECT MAX(at) FROM (
+            SELECT COALESCE(added_at, 0) AS at FROM image_tags
+            WHERE image_uuid = ?1 AND tag_uuid = ?2 AND added_seq > ?3
+            UNION ALL
+            SELECT at FROM sync_tombstones
+            WHERE seq > ?3 AND table_name = 'image_tags'
+              AND json_extract(key, '$.image_uuid') = ?1 AND json_extract(key, '$.tag_uuid') = ?2
+        )",
+        rusqlite::params![image, tag, since],
+        |row| row.get(0),
+    )?;
+    Ok(at.map(|at| at as u64))
+}
+
+/// Run a `... WHERE sync_seq > ?` query, restricted to the caller's
+/// collections through `column`.
+fn select<T>(
+    conn: &rusqlite::Connection,
+    sql: &str,
+    since: i64,
+    access: &CollectionAccess,
+    column: &str,
+    map: impl FnMut(&rusqlite::Row) -> rusqlite::Result<T>,
+) -> Result<Vec<T>, AppError> {
+    let mut visible = Vec::new();
+    let restriction = access_condition(access, column, &mut visible)
+        .map(|c| format!(" AND {c}"))
+        .unwrap_or_default();
+    let mut params = vec![SqlValue::from(since)];
+    params.extend(visible.into_iter().map(SqlValue::from));
+    let mut stmt = conn.prepare(&format!("{sql}{restriction}"))?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(params), map)?;
+    Ok(rows.collect::<Result<Vec<_>, _>>()?)
+}
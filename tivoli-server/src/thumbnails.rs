@@ -0,0 +1,481 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::UNIX_EPOCH;
+
+use axum::body::Bytes;
+use tokio::sync::{OnceCell, Semaphore};
+
+use crate::errors::AppError;
+use crate::models::{ThumbnailCacheStats, ThumbnailPurgeResult};
+use crate::watermark::Watermark;
+
+/// Prefix for files being written; anything left with it after a crash is
+/// swept on startup.
+const TEMP_PREFIX: &str = ".tmp-";
+
+/// Smallest and largest thumbnail widths we will render.
+const MIN_WIDTH: u32 = 50;
+const MAX_WIDTH: u32 = 1920;
+
+/// Cache width for full-size re-encodes, which only watermarked originals need.
+pub const FULL_SIZE: u32 = 0;
+
+pub fn clamp_width(width: u32) -> u32 {
+    width.clamp(MIN_WIDTH, MAX_WIDTH)
+}
+
+type RenderCell = Arc<OnceCell<Result<Bytes, AppError>>>;
+
+/// Identifies the version of a source file a thumbnail was rendered from.
+/// Baked into the cache file name so edits to the original invalidate it.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct SourceFingerprint {
+    pub mtime: u64,
+    pub size: u64,
+}
+
+impl SourceFingerprint {
+    pub fn from_metadata(meta: &std::fs::Metadata) -> Self {
+        let mtime = meta
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_secs());
+        SourceFingerprint { mtime, size: meta.len() }
+    }
+
+    fn encode(&self) -> String {
+        format!("{:x}-{:x}", self.mtime, self.size)
+    }
+}
+
+struct CacheEntry {
+    image_uuid: String,
+    width: u32,
+    /// Watermark id for watermarked renditions.
+    variant: Option<String>,
+    size: u64,
+    tick: u64,
+}
+
+#[derive(Default)]
+struct CacheIndex {
+    /// File name -> entry
+    entries: HashMap<String, CacheEntry>,
+    /// Access tick -> file name, oldest first
+    lru: BTreeMap<u64, String>,
+    total_bytes: u64,
+    tick: u64,
+    hits: u64,
+    misses: u64,
+    renders: u64,
+    evictions: u64,
+}
+
+impl CacheIndex {
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+
+    fn insert(
+        &mut self,
+        name: String,
+        image_uuid: String,
+        width: u32,
+        variant: Option<String>,
+        size: u64,
+    ) {
+        self.remove(&name);
+        let tick = self.next_tick();
+        self.lru.insert(tick, name.clone());
+        self.total_bytes += size;
+        self.entries.insert(name, CacheEntry { image_uuid, width, variant, size, tick });
+    }
+
+    fn touch(&mut self, name: &str) {
+        let tick = self.next_tick();
+        if let Some(entry) = self.entries.get_mut(name) {
+            self.lru.remove(&entry.tick);
+            entry.tick = tick;
+            self.lru.insert(tick, name.to_string());
+        }
+    }
+
+    fn remove(&mut self, name: &str) -> Option<CacheEntry> {
+        let entry = self.entries.remove(name)?;
+        self.lru.remove(&entry.tick);
+        self.total_bytes -= entry.size;
+        Some(entry)
+    }
+}
+
+/// Size-bounded LRU cache of resized JPEGs, stored as
+/// `{uuid}_{width}_{fingerprint}.jpg` in a single directory. Watermarked
+/// renditions add the watermark id, `{uuid}_{width}_{fingerprint}_{id}.jpg`,
+/// so they never stand in for clean ones.
+pub struct ThumbnailCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    writable: bool,
+    index: Mutex<CacheIndex>,
+    inflight: Mutex<HashMap<String, RenderCell>>,
+    workers: Arc<Semaphore>,
+    temp_seq: AtomicU64,
+}
+
+impl ThumbnailCache {
+    /// Open (creating if needed) the cache directory and index whatever is
+    /// already in it. Files from before fingerprinting (`{uuid}_{width}.jpg`)
+    /// cannot be validated and are removed.
+    ///
+    /// Never fails: if the directory can't be created or written to, the cache
+    /// runs read-only and resized images are served without being stored.
+    pub fn open(dir: PathBuf, max_bytes: u64, workers: usize) -> Self {
+        let writable = match prepare_dir(&dir) {
+            Ok(()) => true,
+            Err(e) => {
+                tracing::warn!(
+                    "Thumbnail cache {} is not writable, serving uncached resizes: {e}",
+                    dir.display()
+                );
+                false
+            }
+        };
+
+        let mut index = CacheIndex::default();
+        match scan_dir(&dir, writable) {
+            Ok(found) => {
+                for (name, uuid, width, variant, size) in found {
+                    index.insert(name, uuid, width, variant, size);
+                }
+            }
+            Err(e) if writable => {
+                tracing::warn!("Failed to index thumbnail cache {}: {e}", dir.display());
+            }
+            Err(_) => {}
+        }
+
+        let cache = ThumbnailCache {
+            dir,
+            max_bytes,
+            writable,
+            index: Mutex::new(index),
+            inflight: Mutex::new(HashMap::new()),
+            workers: Arc::new(Semaphore::new(workers.max(1))),
+            temp_seq: AtomicU64::new(0),
+        };
+        if cache.writable {
+            cache.evict_to_budget(&mut cache.lock());
+        }
+        cache
+    }
+
+    fn lock(&self) -> MutexGuard<'_, CacheIndex> {
+        // The index holds no invariants that a panic could break halfway
+        self.index.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn file_name(
+        uuid: &str,
+        width: u32,
+        fingerprint: SourceFingerprint,
+        variant: Option<&str>,
+    ) -> String {
+        match variant {
+            Some(variant) => format!("{uuid}_{width}_{}_{variant}.jpg", fingerprint.encode()),
+            None => format!("{uuid}_{width}_{}.jpg", fingerprint.encode()),
+        }
+    }
+
+    /// Serve a thumbnail from cache, rendering it if needed. Concurrent requests
+    /// for the same thumbnail share one render, and renders run on a bounded
+    /// number of blocking threads. With a watermark, `width` may be
+    /// [`FULL_SIZE`] for a watermarked copy of the original.
+    pub async fn get_or_render(
+        self: &Arc<Self>,
+        uuid: &str,
+        width: u32,
+        source: &Path,
+        fingerprint: SourceFingerprint,
+        watermark: Option<&Arc<Watermark>>,
+    ) -> Result<Bytes, AppError> {
+        let variant = watermark.map(|w| w.id().to_string());
+        let name = Self::file_name(uuid, width, fingerprint, variant.as_deref());
+        if let Some(bytes) = self.lookup(&name).await {
+            self.lock().hits += 1;
+            return Ok(bytes);
+        }
+        self.lock().misses += 1;
+
+        let cell = Arc::clone(
+            self.inflight
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .entry(name.clone())
+                .or_default(),
+        );
+        let result = cell
+            .get_or_init(|| async {
+                // A render for this key may have finished between our miss and
+                // joining the in-flight map
+                if let Some(bytes) = self.lookup(&name).await {
+                    return Ok(bytes);
+                }
+
+                let permit = Arc::clone(&self.workers)
+                    .acquire_owned()
+                    .await
+                    .map_err(|e| AppError::DbError(format!("Thumbnail pool closed: {e}")))?;
+                let cache = Arc::clone(self);
+                let uuid = uuid.to_string();
+                let source = source.to_path_buf();
+                let watermark = watermark.cloned();
+                tokio::task::spawn_blocking(move || {
+                    let _permit = permit;
+                    let buf = render_thumbnail(&source, width, watermark.as_deref())?;
+                    cache.put(&uuid, width, fingerprint, variant, &buf);
+                    Ok(Bytes::from(buf))
+                })
+                .await
+                .map_err(|e| AppError::DbError(format!("Thumbnail task failed: {e}")))?
+            })
+            .await
+            .clone();
+
+        // Whoever gets here first retires the cell; later arrivals hit the cache
+        let mut inflight = self.inflight.lock().unwrap_or_else(|e| e.into_inner());
+        if inflight.get(&name).is_some_and(|c| Arc::ptr_eq(c, &cell)) {
+            inflight.remove(&name);
+        }
+        result
+    }
+
+    /// Whether this exact (clean) thumbnail is already cached.
+    pub fn contains(&self, uuid: &str, width: u32, fingerprint: SourceFingerprint) -> bool {
+        self.lock()
+            .entries
+            .contains_key(&Self::file_name(uuid, width, fingerprint, None))
+    }
+
+    /// Read a cached file by name without touching the hit/miss counters.
+    async fn lookup(&self, name: &str) -> Option<Bytes> {
+        if !self.lock().entries.contains_key(name) {
+            return None;
+        }
+        match tokio::fs::read(self.dir.join(name)).await {
+            Ok(bytes) => {
+                self.lock().touch(name);
+                Some(Bytes::from(bytes))
+            }
+            Err(_) => {
+                // Removed behind our back (e.g. by another process)
+                self.lock().remove(name);
+                None
+            }
+        }
+    }
+
+    /// Store a freshly rendered thumbnail, dropping renditions of older source
+    /// versions and evicting least recently used entries to stay within budget.
+    /// The file is written under a temporary name and renamed into place so
+    /// readers never see a partial JPEG. Blocking: call from `spawn_blocking`.
+    fn put(
+        &self,
+        uuid: &str,
+        width: u32,
+        fingerprint: SourceFingerprint,
+        variant: Option<String>,
+        bytes: &[u8],
+    ) {
+        if !self.writable {
+            return;
+        }
+        let name = Self::file_name(uuid, width, fingerprint, variant.as_deref());
+        let seq = self.temp_seq.fetch_add(1, Ordering::Relaxed);
+        let temp_path = self
+            .dir
+            .join(format!("{TEMP_PREFIX}{}-{seq}-{name}", std::process::id()));
+        let written = std::fs::write(&temp_path, bytes)
+            .and_then(|_| std::fs::rename(&temp_path, self.dir.join(&name)));
+        if let Err(e) = written {
+            tracing::warn!("Failed to cache thumbnail: {e}");
+            let _ = std::fs::remove_file(&temp_path);
+            return;
+        }
+
+        let mut index = self.lock();
+        index.renders += 1;
+        let stale: Vec<String> = index
+            .entries
+            .iter()
+            .filter(|(n, e)| {
+                e.image_uuid == uuid && e.width == width && e.variant == variant && **n != name
+            })
+            .map(|(n, _)| n.clone())
+            .collect();
+        for n in stale {
+            index.remove(&n);
+            let _ = std::fs::remove_file(self.dir.join(&n));
+        }
+
+        index.insert(name, uuid.to_string(), width, variant, bytes.len() as u64);
+        self.evict_to_budget(&mut index);
+    }
+
+    fn evict_to_budget(&self, index: &mut CacheIndex) {
+        while index.total_bytes > self.max_bytes {
+            let Some((_, name)) = index.lru.pop_first() else {
+                break;
+            };
+            if let Some(entry) = index.entries.remove(&name) {
+                index.total_bytes -= entry.size;
+                index.evictions += 1;
+            }
+            let _ = std::fs::remove_file(self.dir.join(&name));
+        }
+    }
+
+    pub fn stats(&self) -> ThumbnailCacheStats {
+        let index = self.lock();
+        let images: HashSet<&str> = index.entries.values().map(|e| e.image_uuid.as_str()).collect();
+        ThumbnailCacheStats {
+            path: self.dir.display().to_string(),
+            max_bytes: self.max_bytes,
+            writable: self.writable,
+            total_bytes: index.total_bytes,
+            entry_count: index.entries.len() as u64,
+            image_count: images.len() as u64,
+            hits: index.hits,
+            misses: index.misses,
+            renders: index.renders,
+            evictions: index.evictions,
+        }
+    }
+
+    /// Remove every cached thumbnail. Blocking.
+    pub fn purge_all(&self) -> ThumbnailPurgeResult {
+        self.purge_where(|_| true)
+    }
+
+    /// Remove all cached sizes of the given images. Blocking.
+    pub fn purge_images(&self, uuids: &HashSet<String>) -> ThumbnailPurgeResult {
+        self.purge_where(|uuid| uuids.contains(uuid))
+    }
+
+    fn purge_where(&self, matches: impl Fn(&str) -> bool) -> ThumbnailPurgeResult {
+        let mut index = self.lock();
+        let names: Vec<String> = index
+            .entries
+            .iter()
+            .filter(|(_, e)| matches(&e.image_uuid))
+            .map(|(n, _)| n.clone())
+            .collect();
+
+        let mut result = ThumbnailPurgeResult { removed_files: 0, removed_bytes: 0 };
+        for name in names {
+            if let Some(entry) = index.remove(&name) {
+                result.removed_files += 1;
+                result.removed_bytes += entry.size;
+            }
+            let _ = std::fs::remove_file(self.dir.join(&name));
+        }
+        result
+    }
+}
+
+/// Decode the original and downscale it to `width`, re-encoding as JPEG.
+/// Images narrower than `width`, or any image at [`FULL_SIZE`], are
+/// re-encoded at their own size. The watermark goes on after resizing.
+pub fn render_thumbnail(
+    source: &Path,
+    width: u32,
+    watermark: Option<&Watermark>,
+) -> Result<Vec<u8>, AppError> {
+    let img = image::open(source)
+        .map_err(|e| AppError::BadRequest(format!("Failed to decode image: {e}")))?;
+
+    let thumb = if width != FULL_SIZE && img.width() > width {
+        img.thumbnail(width, u32::MAX)
+    } else {
+        img
+    };
+    let thumb = match watermark {
+        Some(watermark) => watermark.apply(thumb),
+        None => thumb,
+    };
+
+    let mut buf = Vec::new();
+    thumb
+        .write_to(&mut std::io::Cursor::new(&mut buf), image::ImageFormat::Jpeg)
+        .map_err(|e| AppError::BadRequest(format!("Failed to encode thumbnail: {e}")))?;
+    Ok(buf)
+}
+
+/// Create the cache directory (owner and group only on Unix) and check that
+/// we can actually write into it.
+fn prepare_dir(dir: &Path) -> std::io::Result<()> {
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o750);
+    builder.create(dir)?;
+
+    let probe = dir.join(format!(".write-probe-{}", std::process::id()));
+    std::fs::write(&probe, b"")?;
+    std::fs::remove_file(&probe)
+}
+
+type ScannedEntry = (String, String, u32, Option<String>, u64);
+
+/// List cached thumbnails as `(file name, uuid, width, variant, size)`, least
+/// recently modified first so the rebuilt LRU order roughly survives restarts.
+fn scan_dir(dir: &Path, writable: bool) -> std::io::Result<Vec<ScannedEntry>> {
+    let mut found = Vec::new();
+    for dir_entry in std::fs::read_dir(dir)? {
+        let dir_entry = dir_entry?;
+        let name = dir_entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with(TEMP_PREFIX) {
+            if writable {
+                let _ = std::fs::remove_file(dir_entry.path());
+            }
+            continue;
+        }
+        let Some((uuid, width, fingerprint, variant)) = parse_file_name(&name) else {
+            continue;
+        };
+        let uuid = uuid.to_string();
+        let variant = variant.map(str::to_string);
+        if fingerprint.is_none() {
+            if writable {
+                let _ = std::fs::remove_file(dir_entry.path());
+            }
+            continue;
+        }
+        let meta = dir_entry.metadata()?;
+        let modified = meta.modified().unwrap_or(UNIX_EPOCH);
+        found.push((modified, name, uuid, width, variant, meta.len()));
+    }
+    found.sort_by_key(|(modified, ..)| *modified);
+    Ok(found
+        .into_iter()
+        .map(|(_, name, uuid, width, variant, size)| (name, uuid, width, variant, size))
+        .collect())
+}
+
+/// Split `{uuid}_{width}[_{fingerprint}[_{variant}]].jpg`. Returns `None` for
+/// files that aren't ours.
+fn parse_file_name(name: &str) -> Option<(&str, u32, Option<&str>, Option<&str>)> {
+    let stem = name.strip_suffix(".jpg")?;
+    let mut parts = stem.split('_');
+    let uuid = parts.next()?;
+    let width = parts.next()?.parse().ok()?;
+    let fingerprint = parts.next();
+    let variant = parts.next();
+    if parts.next().is_some() {
+        return None;
+    }
+    Some((uuid, width, fingerprint, variant))
+}
@@ -0,0 +1,126 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use crate::errors::AppError;
+use crate::events;
+use crate::handlers::AppState;
+use crate::models::{VerifyIssue, VerifyProblem, VerifyStatus};
+use crate::queries;
+use crate::scanner;
+use crate::thumbnails::SourceFingerprint;
+use crate::unix_now;
+
+/// Background job that re-hashes originals and reports the ones that went
+/// missing or whose bytes no longer match what ingest recorded. It only
+/// reports; a forced ingest accepts intentional edits.
+#[derive(Default)]
+pub struct Verifier {
+    status: Mutex<VerifyStatus>,
+}
+
+impl Verifier {
+    fn lock(&self) -> MutexGuard<'_, VerifyStatus> {
+        self.status.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn status(&self) -> VerifyStatus {
+        self.lock().clone()
+    }
+
+    /// Kick off a verification of one collection (or everything) in the background.
+    pub fn start(state: &Arc<AppState>, collection: Option<String>) -> Result<VerifyStatus, AppError> {
+        let snapshot = {
+            let mut status = state.verifier.lock();
+            if status.running {
+                return Err(AppError::Conflict("Verification is already running".into()));
+            }
+            *status = VerifyStatus {
+                running: true,
+                collection: collection.clone(),
+                started_at: Some(unix_now()),
+                ..VerifyStatus::default()
+            };
+            status.clone()
+        };
+
+        let state = Arc::clone(state);
+        tokio::spawn(async move {
+            let worker = Arc::clone(&state);
+            let result = tokio::task::spawn_blocking(move || run(&worker, collection.as_deref()))
+                .await
+                .unwrap_or_else(|e| Err(AppError::DbError(format!("Verification task failed: {e}"))));
+            let mut status = state.verifier.lock();
+            status.running = false;
+            status.finished_at = Some(unix_now());
+            match result {
+                Ok(()) => tracing::info!(
+                    "Verification finished: {} ok, {} issues, {} unhashed",
+                    status.ok,
+                    status.issues.len(),
+                    status.unhashed
+                ),
+                Err(e) => {
+                    tracing::error!("Verification aborted: {e}");
+                    status.error = Some(e.to_string());
+                }
+            }
+        });
+        Ok(snapshot)
+    }
+}
+
+/// Blocking: hashes every original in turn on one blocking thread.
+fn run(state: &AppState, collection: Option<&str>) -> Result<(), AppError> {
+    let images = {
+        let conn = state.db.conn()?;
+        queries::query_image_fingerprints(&conn, collection)?
+    };
+    state.verifier.lock().total = images.len() as u64;
+
+    for image in images {
+        let Some(expected) = image.identity else {
+            let mut status = state.verifier.lock();
+            status.unhashed += 1;
+            status.processed += 1;
+            continue;
+        };
+
+        // Stored paths come from the database, but stay inside the galleries
+        // directory all the same
+        let path = state.galleries_path.join(&image.path);
+        let current = path
+            .canonicalize()
+            .ok()
+            .filter(|p| p.starts_with(&state.galleries_path))
+            .and_then(|p| Some((std::fs::metadata(&p).ok()?, scanner::hash_file(&p).ok()?)));
+
+        let problem = match &current {
+            None => Some(VerifyProblem::Missing),
+            Some((_, hash)) if *hash == expected.content_hash => None,
+            Some((meta, _)) if SourceFingerprint::from_metadata(meta).mtime == expected.file_mtime => {
+                Some(VerifyProblem::Corrupted)
+            }
+            Some(_) => Some(VerifyProblem::Modified),
+        };
+        let missing = matches!(problem, Some(VerifyProblem::Missing));
+        if events::original_missing(&*state.db.conn()?, &image.uuid, missing)? {
+            state.feed.wake();
+        }
+
+        let mut status = state.verifier.lock();
+        match problem {
+            None => status.ok += 1,
+            Some(problem) => {
+                tracing::warn!("Verification: {} is {}", image.path, problem.name());
+                status.issues.push(VerifyIssue {
+                    uuid: image.uuid,
+                    path: image.path,
+                    problem,
+                    expected_hash: expected.content_hash,
+                    actual_hash: current.map(|(_, hash)| hash),
+                });
+            }
+        }
+        status.processed += 1;
+    }
+    Ok(())
+}
@@ -0,0 +1,259 @@
+use std::path::PathBuf;
+
+use image::imageops::FilterType;
+use image::{DynamicImage, Rgba, RgbaImage};
+
+/// Where the overlay sits on the image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WatermarkPosition {
+    Center,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl WatermarkPosition {
+    pub fn name(&self) -> &'static str {
+        match self {
+            WatermarkPosition::Center => "center",
+            WatermarkPosition::TopLeft => "top_left",
+            WatermarkPosition::TopRight => "top_right",
+            WatermarkPosition::BottomLeft => "bottom_left",
+            WatermarkPosition::BottomRight => "bottom_right",
+        }
+    }
+}
+
+impl std::str::FromStr for WatermarkPosition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            WatermarkPosition::Center,
+            WatermarkPosition::TopLeft,
+            WatermarkPosition::TopRight,
+            WatermarkPosition::BottomLeft,
+            WatermarkPosition::BottomRight,
+        ]
+        .into_iter()
+        .find(|p| p.name() == s)
+        .ok_or_else(|| format!("unknown watermark position: {s}"))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum WatermarkOverlay {
+    /// Drawn in white block capitals with a dark shadow.
+    Text(String),
+    /// A PNG, alpha channel respected.
+    Image(PathBuf),
+}
+
+#[derive(Clone, Debug)]
+pub struct WatermarkConfig {
+    pub overlay: WatermarkOverlay,
+    pub position: WatermarkPosition,
+    /// 0 (invisible) to 1 (the overlay's own alpha).
+    pub opacity: f32,
+    /// Overlay width as a share of the image width.
+    pub scale: f32,
+}
+
+impl WatermarkConfig {
+    pub fn new(overlay: WatermarkOverlay) -> Self {
+        WatermarkConfig {
+            overlay,
+            position: WatermarkPosition::Center,
+            opacity: 0.4,
+            scale: 0.5,
+        }
+    }
+}
+
+/// A loaded watermark, ready to stamp onto decoded images.
+pub struct Watermark {
+    mark: RgbaImage,
+    position: WatermarkPosition,
+    opacity: f32,
+    scale: f32,
+    id: String,
+}
+
+impl Watermark {
+    pub fn load(config: &WatermarkConfig) -> Result<Watermark, String> {
+        if !(config.opacity > 0.0 && config.opacity <= 1.0) {
+            return Err("watermark opacity must be above 0 and at most 1".into());
+        }
+        if !(config.scale > 0.0 && config.scale <= 1.0) {
+            return Err("watermark scale must be above 0 and at most 1".into());
+        }
+        let (mark, source) = match &config.overlay {
+            WatermarkOverlay::Text(text) => {
+                let text = text.trim();
+                if text.is_empty() {
+                    return Err("watermark text must not be empty".into());
+                }
+                (render_text(text), text.as_bytes().to_vec())
+            }
+            WatermarkOverlay::Image(path) => {
+                let bytes = std::fs::read(path)
+                    .map_err(|e| format!("can't read {}: {e}", path.display()))?;
+                let mark = image::load_from_memory_with_format(&bytes, image::ImageFormat::Png)
+                    .map_err(|e| format!("{} is not a readable PNG: {e}", path.display()))?
+                    .to_rgba8();
+                (mark, bytes)
+            }
+        };
+
+        // Changing any setting changes the id, and with it the cache names
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(&source);
+        hasher.update(config.position.name().as_bytes());
+        hasher.update(&config.opacity.to_le_bytes());
+        hasher.update(&config.scale.to_le_bytes());
+        let id = hasher.finalize().to_hex()[..8].to_string();
+
+        Ok(Watermark {
+            mark,
+            position: config.position,
+            opacity: config.opacity,
+            scale: config.scale,
+            id,
+        })
+    }
+
+    /// Short hash of the overlay and its settings, naming cached variants.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Stamp the overlay onto `img`, scaled to its width.
+    pub fn apply(&self, img: DynamicImage) -> DynamicImage {
+        let mut base = img.to_rgb8();
+        let (width, height) = base.dimensions();
+        let mark_width = ((width as f32 * self.scale).round() as u32).clamp(1, width);
+        let mark_height = ((mark_width as f32 * self.mark.height() as f32 / self.mark.width() as f32)
+            .round() as u32)
+            .clamp(1, height);
+        let mark = image::imageops::resize(&self.mark, mark_width, mark_height, FilterType::Triangle);
+
+        let margin = width.min(height) / 30;
+        let (left, right) = (margin, width.saturating_sub(mark_width + margin));
+        let (top, bottom) = (margin, height.saturating_sub(mark_height + margin));
+        let (x0, y0) = match self.position {
+            WatermarkPosition::Center => ((width - mark_width) / 2, (height - mark_height) / 2),
+            WatermarkPosition::TopLeft => (left, top),
+            WatermarkPosition::TopRight => (right, top),
+            WatermarkPosition::BottomLeft => (left, bottom),
+            WatermarkPosition::BottomRight => (right, bottom),
+        };
+
+        for (x, y, Rgba([r, g, b, a])) in mark.enumerate_pixels() {
+            let (bx, by) = (x0 + x, y0 + y);
+            if bx >= width || by >= height || *a == 0 {
+                continue;
+            }
+            let alpha = *a as f32 / 255.0 * self.opacity;
+            let pixel = base.get_pixel_mut(bx, by);
+            for (channel, over) in pixel.0.iter_mut().zip([r, g, b]) {
+                *channel = (*channel as f32 * (1.0 - alpha) + *over as f32 * alpha).round() as u8;
+            }
+        }
+        DynamicImage::ImageRgb8(base)
+    }
+}
+
+/// Pixels per font dot in the rendered text; scaling happens per image.
+const DOT: u32 = 4;
+const SHADOW: u32 = 2;
+
+/// Render text with the built-in 5×7 font: white on transparent, with a
+/// dark drop shadow so it reads on light and dark photos alike.
+fn render_text(text: &str) -> RgbaImage {
+    let text = text.replace('©', "(C)").to_uppercase();
+    let glyphs: Vec<[u8; 7]> = text.chars().map(glyph).collect();
+    let width = (glyphs.len() as u32 * 6 - 1) * DOT + SHADOW;
+    let height = 7 * DOT + SHADOW;
+    let mut mark = RgbaImage::new(width, height);
+    for (offset, colour) in [(SHADOW, Rgba([0, 0, 0, 200])), (0, Rgba([255, 255, 255, 255]))] {
+        for (i, rows) in glyphs.iter().enumerate() {
+            for (row, bits) in rows.iter().enumerate() {
+                for col in 0..5u32 {
+                    if bits & (0x10 >> col) == 0 {
+                        continue;
+                    }
+                    let x = (i as u32 * 6 + col) * DOT + offset;
+                    let y = row as u32 * DOT + offset;
+                    for dy in 0..DOT {
+                        for dx in 0..DOT {
+                            mark.put_pixel(x + dx, y + dy, colour);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    mark
+}
+
+/// Rows of a 5×7 glyph, top to bottom, most significant bit leftmost.
+/// Characters without a glyph are drawn as `?`.
+fn glyph(c: char) -> [u8; 7] {
+    match c {
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x0A, 0x04, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        ' ' => [0x00; 7],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        '_' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '!' => [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04],
+        '\'' => [0x04, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00],
+        '"' => [0x0A, 0x0A, 0x00, 0x00, 0x00, 0x00, 0x00],
+        '&' => [0x0C, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0D],
+        '@' => [0x0E, 0x11, 0x01, 0x0D, 0x15, 0x15, 0x0E],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        '+' => [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00],
+        '#' => [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A],
+        '=' => [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00],
+        _ => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    }
+}
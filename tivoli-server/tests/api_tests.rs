@@ -1,16 +1,56 @@
 use reqwest::Client;
 use serde_json::{json, Value};
+use tivoli_server::{Config, Role, WatermarkConfig, WatermarkOverlay, WatermarkPosition};
 use tokio::sync::oneshot;
 
+/// Fresh scratch directory under the system temp dir.
+fn temp_dir(label: &str) -> std::path::PathBuf {
+    static COUNTER: std::sync::atomic::AtomicU32 = std::sync::atomic::AtomicU32::new(0);
+    let n = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let dir = std::env::temp_dir().join(format!("tivoli-test-{label}-{}-{n}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+/// Private copy of the sample database for tests that persist changes.
+fn temp_db() -> String {
+    let path = temp_dir("db").with_extension("db");
+    std::fs::copy("../data/sample.db", &path).unwrap();
+    path.display().to_string()
+}
+
+/// Poll a background job's status endpoint until it stops running.
+async fn wait_for_job(client: &Client, url: &str) -> Value {
+    for _ in 0..600 {
+        let status: Value = client.get(url).send().await.unwrap().json().await.unwrap();
+        if !status["running"].as_bool().unwrap() {
+            return status;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    panic!("job at {url} did not finish");
+}
+
 /// Spawn the app on a random port, return base URL.
 async fn spawn_app() -> String {
+    spawn_app_with_config(Config::new("../data/sample.db", "../galleries")).await
+}
+
+/// Most tests exercise handlers, not authentication, so they run with
+/// API keys turned off.
+async fn spawn_app_with_config(mut config: Config) -> String {
+    config.auth_disabled = true;
+    spawn_app_with_auth(config).await
+}
+
+async fn spawn_app_with_auth(config: Config) -> String {
     let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
     let port = listener.local_addr().unwrap().port();
     let base_url = format!("http://127.0.0.1:{port}");
 
     let (tx, rx) = oneshot::channel();
     tokio::spawn(async move {
-        let app = tivoli_server::build_app("../data/sample.db", "../galleries");
+        let app = tivoli_server::build_app_with_config(&config).unwrap();
         tx.send(()).unwrap();
         axum::serve(listener, app).await.unwrap();
     });
@@ -22,7 +62,7 @@ async fn spawn_app() -> String {
 
 async fn search(client: &Client, base: &str, filters: Value) -> Value {
     client
-        .post(&format!("{base}/images/search"))
+        .post(format!("{base}/images/search"))
         .json(&json!({ "filters": filters }))
         .send()
         .await
@@ -39,7 +79,7 @@ async fn search_count(client: &Client, base: &str, filters: Value) -> usize {
 
 async fn search_options(client: &Client, base: &str, filters: Value) -> Value {
     client
-        .post(&format!("{base}/images/search/options"))
+        .post(format!("{base}/images/search/options"))
         .json(&json!({ "filters": filters }))
         .send()
         .await
@@ -49,6 +89,20 @@ async fn search_options(client: &Client, base: &str, filters: Value) -> Value {
         .unwrap()
 }
 
+/// Run futures concurrently on the test runtime and collect their outputs in order.
+async fn futures_join_all<F>(futures: impl Iterator<Item = F>) -> Vec<F::Output>
+where
+    F: std::future::Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let handles: Vec<_> = futures.map(tokio::spawn).collect();
+    let mut outputs = Vec::new();
+    for handle in handles {
+        outputs.push(handle.await.unwrap());
+    }
+    outputs
+}
+
 // ─── GET /collections ───
 
 #[tokio::test]
@@ -56,7 +110,7 @@ async fn test_collections_returns_all_four() {
     let base = spawn_app().await;
     let client = Client::new();
     let resp: Vec<Value> = client
-        .get(&format!("{base}/collections"))
+        .get(format!("{base}/collections"))
         .send()
         .await
         .unwrap()
@@ -77,7 +131,7 @@ async fn test_collections_counts_match() {
     let base = spawn_app().await;
     let client = Client::new();
     let resp: Vec<Value> = client
-        .get(&format!("{base}/collections"))
+        .get(format!("{base}/collections"))
         .send()
         .await
         .unwrap()
@@ -118,7 +172,7 @@ async fn test_galleries_unfiltered() {
     let base = spawn_app().await;
     let client = Client::new();
     let resp: Vec<Value> = client
-        .get(&format!("{base}/galleries"))
+        .get(format!("{base}/galleries"))
         .send()
         .await
         .unwrap()
@@ -134,7 +188,7 @@ async fn test_galleries_filtered_by_collection() {
     let base = spawn_app().await;
     let client = Client::new();
     let resp: Vec<Value> = client
-        .get(&format!("{base}/galleries?collection=lumiere-studio"))
+        .get(format!("{base}/galleries?collection=lumiere-studio"))
         .send()
         .await
         .unwrap()
@@ -153,7 +207,7 @@ async fn test_galleries_unknown_collection() {
     let base = spawn_app().await;
     let client = Client::new();
     let resp: Vec<Value> = client
-        .get(&format!("{base}/galleries?collection=nonexistent"))
+        .get(format!("{base}/galleries?collection=nonexistent"))
         .send()
         .await
         .unwrap()
@@ -171,7 +225,7 @@ async fn test_models_unfiltered() {
     let base = spawn_app().await;
     let client = Client::new();
     let resp: Vec<Value> = client
-        .get(&format!("{base}/models"))
+        .get(format!("{base}/models"))
         .send()
         .await
         .unwrap()
@@ -187,7 +241,7 @@ async fn test_models_filtered_by_collection() {
     let base = spawn_app().await;
     let client = Client::new();
     let resp: Vec<Value> = client
-        .get(&format!("{base}/models?collection=raw-collective"))
+        .get(format!("{base}/models?collection=raw-collective"))
         .send()
         .await
         .unwrap()
@@ -212,7 +266,7 @@ async fn test_tags_returns_five_groups() {
     let base = spawn_app().await;
     let client = Client::new();
     let resp: Vec<Value> = client
-        .get(&format!("{base}/tags"))
+        .get(format!("{base}/tags"))
         .send()
         .await
         .unwrap()
@@ -228,7 +282,7 @@ async fn test_tags_groups_are_named() {
     let base = spawn_app().await;
     let client = Client::new();
     let resp: Vec<Value> = client
-        .get(&format!("{base}/tags"))
+        .get(format!("{base}/tags"))
         .send()
         .await
         .unwrap()
@@ -249,7 +303,7 @@ async fn test_tags_nested_structure() {
     let base = spawn_app().await;
     let client = Client::new();
     let resp: Vec<Value> = client
-        .get(&format!("{base}/tags"))
+        .get(format!("{base}/tags"))
         .send()
         .await
         .unwrap()
@@ -304,7 +358,7 @@ async fn test_search_gallery_eq() {
 /// Helper: get model UUID by name and collection
 async fn get_model_uuid(client: &Client, base: &str, name: &str, collection: &str) -> String {
     let resp: Vec<Value> = client
-        .get(&format!("{base}/models?collection={collection}"))
+        .get(format!("{base}/models?collection={collection}"))
         .send()
         .await
         .unwrap()
@@ -322,7 +376,7 @@ async fn get_model_uuid(client: &Client, base: &str, name: &str, collection: &st
 /// Helper: get tag UUID by name
 async fn get_tag_uuid(client: &Client, base: &str, tag_name: &str) -> String {
     let resp: Vec<Value> = client
-        .get(&format!("{base}/tags"))
+        .get(format!("{base}/tags"))
         .send()
         .await
         .unwrap()
@@ -426,7 +480,7 @@ async fn test_search_tags_any_of() {
     )
     .await;
     // golden-hour: 8 images, backlit: 2 images, some overlap possible
-    assert!(count >= 2 && count <= 10);
+    assert!((2..=10).contains(&count));
 }
 
 #[tokio::test]
@@ -549,7 +603,7 @@ async fn test_search_invalid_op_for_field() {
     let base = spawn_app().await;
     let client = Client::new();
     let resp = client
-        .post(&format!("{base}/images/search"))
+        .post(format!("{base}/images/search"))
         .json(&json!({"filters": [{"field": "collection", "op": "any_of", "value": ["x"]}]}))
         .send()
         .await
@@ -563,7 +617,7 @@ async fn test_search_exact_on_tags() {
     let client = Client::new();
     // exact on tags is now supported — should return 200 (even with unknown UUIDs, just 0 results)
     let resp = client
-        .post(&format!("{base}/images/search"))
+        .post(format!("{base}/images/search"))
         .json(&json!({"filters": [{"field": "tags", "op": "exact", "value": ["x"]}]}))
         .send()
         .await
@@ -576,7 +630,7 @@ async fn test_search_malformed_json() {
     let base = spawn_app().await;
     let client = Client::new();
     let resp = client
-        .post(&format!("{base}/images/search"))
+        .post(format!("{base}/images/search"))
         .header("content-type", "application/json")
         .body("{bad json")
         .send()
@@ -678,7 +732,7 @@ async fn test_get_image_file_valid() {
     let uuid = results.as_array().unwrap()[0]["uuid"].as_str().unwrap();
 
     let resp = client
-        .get(&format!("{base}/images/{uuid}/file"))
+        .get(format!("{base}/images/{uuid}/file"))
         .send()
         .await
         .unwrap();
@@ -694,7 +748,7 @@ async fn test_get_image_file_not_found() {
     let base = spawn_app().await;
     let client = Client::new();
     let resp = client
-        .get(&format!(
+        .get(format!(
             "{base}/images/00000000-0000-0000-0000-000000000000/file"
         ))
         .send()
@@ -712,7 +766,7 @@ async fn test_get_image_file_content_is_jpeg() {
     let uuid = results.as_array().unwrap()[0]["uuid"].as_str().unwrap();
 
     let body = client
-        .get(&format!("{base}/images/{uuid}/file"))
+        .get(format!("{base}/images/{uuid}/file"))
         .send()
         .await
         .unwrap()
@@ -742,7 +796,7 @@ async fn test_update_tags_set_and_verify() {
 
     // Set tags to exactly [outdoor, moody]
     let resp = client
-        .put(&format!("{base}/images/{image_uuid}/tags"))
+        .put(format!("{base}/images/{image_uuid}/tags"))
         .json(&json!({ "tag_uuids": [outdoor, moody] }))
         .send()
         .await
@@ -751,7 +805,7 @@ async fn test_update_tags_set_and_verify() {
 
     // Verify via filter options that the tags are searchable on this image
     let resp = client
-        .post(&format!("{base}/images/search/options"))
+        .post(format!("{base}/images/search/options"))
         .json(&json!({"filters": [{"field": "tags", "op": "exact", "value": [outdoor, moody]}]}))
         .send()
         .await
@@ -770,7 +824,7 @@ async fn test_update_tags_clear_all() {
 
     // Clear all tags
     let resp = client
-        .put(&format!("{base}/images/{image_uuid}/tags"))
+        .put(format!("{base}/images/{image_uuid}/tags"))
         .json(&json!({ "tag_uuids": [] }))
         .send()
         .await
@@ -780,7 +834,7 @@ async fn test_update_tags_clear_all() {
     // Verify: searching for any tag on this specific image should find nothing
     // (use filter options to check that clearing worked)
     let resp = client
-        .post(&format!("{base}/images/search/options"))
+        .post(format!("{base}/images/search/options"))
         .json(&json!({"filters": []}))
         .send()
         .await
@@ -797,7 +851,7 @@ async fn test_update_tags_invalid_image_uuid() {
     let client = Client::new();
 
     let resp = client
-        .put(&format!(
+        .put(format!(
             "{base}/images/00000000-0000-0000-0000-000000000000/tags"
         ))
         .json(&json!({ "tag_uuids": [] }))
@@ -816,10 +870,4792 @@ async fn test_update_tags_invalid_tag_uuid() {
     let image_uuid = results.as_array().unwrap()[0]["uuid"].as_str().unwrap();
 
     let resp = client
-        .put(&format!("{base}/images/{image_uuid}/tags"))
+        .put(format!("{base}/images/{image_uuid}/tags"))
         .json(&json!({ "tag_uuids": ["nonexistent-tag-uuid"] }))
         .send()
         .await
         .unwrap();
     assert_eq!(resp.status(), 400);
 }
+
+// ─── /admin/thumbnails ───
+
+#[tokio::test]
+async fn test_thumbnail_cache_stats_track_generated_thumbnails() {
+    let base = spawn_app().await;
+    let client = Client::new();
+
+    let results = search(&client, &base, json!([])).await;
+    let uuid = results.as_array().unwrap()[1]["uuid"].as_str().unwrap();
+
+    for _ in 0..2 {
+        let resp = client
+            .get(format!("{base}/images/{uuid}/file?w=120"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+    }
+
+    let stats: Value = client
+        .get(format!("{base}/admin/thumbnails"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(stats["entry_count"].as_u64().unwrap() >= 1);
+    assert!(stats["total_bytes"].as_u64().unwrap() > 0);
+    assert!(stats["hits"].as_u64().unwrap() >= 1);
+}
+
+#[tokio::test]
+async fn test_thumbnail_cache_purge_by_image() {
+    let base = spawn_app().await;
+    let client = Client::new();
+
+    let results = search(&client, &base, json!([])).await;
+    let uuid = results.as_array().unwrap()[2]["uuid"].as_str().unwrap();
+
+    client
+        .get(format!("{base}/images/{uuid}/file?w=140"))
+        .send()
+        .await
+        .unwrap();
+
+    let purged: Value = client
+        .delete(format!("{base}/admin/thumbnails?image={uuid}"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(purged["removed_files"].as_u64().unwrap() >= 1);
+
+    // Regenerated on next request
+    let resp = client
+        .get(format!("{base}/images/{uuid}/file?w=140"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+}
+
+#[tokio::test]
+async fn test_thumbnail_cache_purge_gallery_requires_collection() {
+    let base = spawn_app().await;
+    let client = Client::new();
+
+    let resp = client
+        .delete(format!("{base}/admin/thumbnails?gallery=film-noir"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn test_thumbnail_cache_custom_dir_evicts_to_budget() {
+    let cache_dir = temp_dir("thumbs");
+    let mut config = Config::new("../data/sample.db", "../galleries");
+    config.thumbnail_cache_dir = Some(cache_dir.clone());
+    config.thumbnail_cache_max_bytes = 1;
+    let base = spawn_app_with_config(config).await;
+    let client = Client::new();
+
+    let results = search(&client, &base, json!([])).await;
+    for image in results.as_array().unwrap().iter().take(2) {
+        let uuid = image["uuid"].as_str().unwrap();
+        let resp = client
+            .get(format!("{base}/images/{uuid}/file?w=100"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+    }
+
+    let stats: Value = client
+        .get(format!("{base}/admin/thumbnails"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(stats["path"].as_str().unwrap(), cache_dir.display().to_string());
+    assert_eq!(stats["entry_count"].as_u64().unwrap(), 0);
+    assert_eq!(stats["evictions"].as_u64().unwrap(), 2);
+    assert_eq!(std::fs::read_dir(&cache_dir).unwrap().count(), 0);
+}
+
+#[tokio::test]
+async fn test_thumbnail_cache_unwritable_dir_serves_uncached() {
+    // A directory can't be created underneath a regular file
+    let blocker = temp_dir("blocker");
+    std::fs::write(&blocker, b"").unwrap();
+    let mut config = Config::new("../data/sample.db", "../galleries");
+    config.thumbnail_cache_dir = Some(blocker.join("thumbs"));
+    let base = spawn_app_with_config(config).await;
+    let client = Client::new();
+
+    let results = search(&client, &base, json!([])).await;
+    let uuid = results.as_array().unwrap()[0]["uuid"].as_str().unwrap();
+    let body = client
+        .get(format!("{base}/images/{uuid}/file?w=100"))
+        .send()
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+    assert_eq!(&body[..2], &[0xFF, 0xD8]);
+
+    let stats: Value = client
+        .get(format!("{base}/admin/thumbnails"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(!stats["writable"].as_bool().unwrap());
+    assert_eq!(stats["entry_count"].as_u64().unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_thumbnail_concurrent_requests_render_once() {
+    let mut config = Config::new("../data/sample.db", "../galleries");
+    config.thumbnail_cache_dir = Some(temp_dir("single-flight"));
+    config.thumbnail_workers = 1;
+    let base = spawn_app_with_config(config).await;
+    let client = Client::new();
+
+    let results = search(&client, &base, json!([])).await;
+    let uuid = results.as_array().unwrap()[0]["uuid"].as_str().unwrap().to_string();
+
+    let requests = (0..8).map(|_| {
+        let client = client.clone();
+        let url = format!("{base}/images/{uuid}/file?w=160");
+        async move { client.get(url).send().await.unwrap().bytes().await.unwrap() }
+    });
+    let bodies = futures_join_all(requests).await;
+    assert!(bodies.iter().all(|b| b == &bodies[0]));
+
+    let stats: Value = client
+        .get(format!("{base}/admin/thumbnails"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(stats["renders"].as_u64().unwrap(), 1);
+    assert_eq!(stats["entry_count"].as_u64().unwrap(), 1);
+}
+
+// ─── /admin/pregenerate ───
+
+#[tokio::test]
+async fn test_pregenerate_collection_fills_cache() {
+    let mut config = Config::new("../data/sample.db", "../galleries");
+    config.thumbnail_cache_dir = Some(temp_dir("pregen"));
+    config.pregenerate_cpu_percent = 100;
+    let base = spawn_app_with_config(config).await;
+    let client = Client::new();
+
+    let resp = client
+        .post(format!("{base}/admin/pregenerate"))
+        .json(&json!({ "collection": "golden-hour-photo", "widths": [60] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 202);
+
+    let status = wait_for_job(&client, &format!("{base}/admin/pregenerate")).await;
+    assert_eq!(status["collection"].as_str().unwrap(), "golden-hour-photo");
+    assert_eq!(status["total"].as_u64().unwrap(), 13);
+    assert_eq!(status["processed"].as_u64().unwrap(), 13);
+    assert_eq!(status["rendered"].as_u64().unwrap(), 13);
+    assert_eq!(status["failed"].as_u64().unwrap(), 0);
+
+    let stats: Value = client
+        .get(format!("{base}/admin/thumbnails"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(stats["entry_count"].as_u64().unwrap(), 13);
+}
+
+#[tokio::test]
+async fn test_pregenerate_rejects_empty_widths() {
+    let base = spawn_app().await;
+    let client = Client::new();
+
+    let resp = client
+        .post(format!("{base}/admin/pregenerate"))
+        .json(&json!({ "widths": [] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+// ─── /admin/ingest ───
+
+#[tokio::test]
+async fn test_ingest_adds_placeholders_to_search_results() {
+    let db_path = temp_db();
+    let base = spawn_app_with_config(Config::new(&db_path, "../galleries")).await;
+    let client = Client::new();
+
+    let collection = json!([{"field": "collection", "op": "eq", "value": "noir-atelier"}]);
+    let before = search(&client, &base, collection.clone()).await;
+    assert!(before.as_array().unwrap().iter().all(|img| img["blurhash"].is_null()));
+
+    let resp = client
+        .post(format!("{base}/admin/ingest"))
+        .json(&json!({ "collection": "noir-atelier" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 202);
+    let status = wait_for_job(&client, &format!("{base}/admin/ingest")).await;
+    assert_eq!(status["total"].as_u64().unwrap(), 14);
+    assert_eq!(status["updated"].as_u64().unwrap(), 14);
+    assert_eq!(status["failed"].as_u64().unwrap(), 0);
+
+    let after = search(&client, &base, collection.clone()).await;
+    for img in after.as_array().unwrap() {
+        assert!(img["blurhash"].as_str().unwrap().len() >= 6);
+        let color = img["dominant_color"].as_str().unwrap();
+        assert_eq!(color.len(), 7);
+        assert!(color.starts_with('#'));
+    }
+    // Other collections untouched
+    let others = search(
+        &client,
+        &base,
+        json!([{"field": "collection", "op": "eq", "value": "lumiere-studio"}]),
+    )
+    .await;
+    assert!(others.as_array().unwrap().iter().all(|img| img["blurhash"].is_null()));
+
+    // Persisted to disk
+    let reloaded = spawn_app_with_config(Config::new(&db_path, "../galleries")).await;
+    let uuid = after[0]["uuid"].as_str().unwrap();
+    let detail: Value = client
+        .get(format!("{reloaded}/images/{uuid}"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(detail["blurhash"], after[0]["blurhash"]);
+}
+
+// ─── Image metadata ───
+
+/// Copy a directory tree (galleries are only two levels deep).
+fn copy_dir(from: &std::path::Path, to: &std::path::Path) {
+    std::fs::create_dir_all(to).unwrap();
+    for entry in std::fs::read_dir(from).unwrap() {
+        let entry = entry.unwrap();
+        let target = to.join(entry.file_name());
+        if entry.file_type().unwrap().is_dir() {
+            copy_dir(&entry.path(), &target);
+        } else {
+            std::fs::copy(entry.path(), target).unwrap();
+        }
+    }
+}
+
+/// Insert an APP1 segment right after a JPEG's SOI marker.
+fn insert_app1(path: &std::path::Path, payload: &[u8]) {
+    let jpeg = std::fs::read(path).unwrap();
+    let mut out = jpeg[..2].to_vec();
+    out.extend_from_slice(&[0xFF, 0xE1]);
+    out.extend_from_slice(&((payload.len() + 2) as u16).to_be_bytes());
+    out.extend_from_slice(payload);
+    out.extend_from_slice(&jpeg[2..]);
+    std::fs::write(path, out).unwrap();
+}
+
+fn exif_payload() -> Vec<u8> {
+    use exif::{Field, In, Rational, Tag, Value};
+    let fields = [
+        Field { tag: Tag::Make, ifd_num: In::PRIMARY, value: Value::Ascii(vec![b"Leica".to_vec()]) },
+        Field { tag: Tag::Model, ifd_num: In::PRIMARY, value: Value::Ascii(vec![b"M11".to_vec()]) },
+        Field {
+            tag: Tag::LensModel,
+            ifd_num: In::PRIMARY,
+            value: Value::Ascii(vec![b"Summilux-M 50 f/1.4".to_vec()]),
+        },
+        Field {
+            tag: Tag::FocalLength,
+            ifd_num: In::PRIMARY,
+            value: Value::Rational(vec![Rational { num: 50, denom: 1 }]),
+        },
+        Field {
+            tag: Tag::FNumber,
+            ifd_num: In::PRIMARY,
+            value: Value::Rational(vec![Rational { num: 14, denom: 10 }]),
+        },
+        Field {
+            tag: Tag::ExposureTime,
+            ifd_num: In::PRIMARY,
+            value: Value::Rational(vec![Rational { num: 1, denom: 250 }]),
+        },
+        Field { tag: Tag::PhotographicSensitivity, ifd_num: In::PRIMARY, value: Value::Short(vec![400]) },
+        Field {
+            tag: Tag::DateTimeOriginal,
+            ifd_num: In::PRIMARY,
+            value: Value::Ascii(vec![b"2024:06:15 21:30:05".to_vec()]),
+        },
+        Field {
+            tag: Tag::Copyright,
+            ifd_num: In::PRIMARY,
+            value: Value::Ascii(vec![b"EXIF copyright".to_vec()]),
+        },
+    ];
+    let mut writer = exif::experimental::Writer::new();
+    for field in &fields {
+        writer.push_field(field);
+    }
+    let mut tiff = std::io::Cursor::new(Vec::new());
+    writer.write(&mut tiff, false).unwrap();
+    [b"Exif\0\0".as_slice(), tiff.get_ref()].concat()
+}
+
+const XMP_PACKET: &str = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+<rdf:Description rdf:about="" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:aux="http://ns.adobe.com/exif/1.0/aux/" aux:Lens="Noctilux-M 50 f/0.95">
+<dc:rights><rdf:Alt><rdf:li xml:lang="x-default">(c) Noir Atelier</rdf:li></rdf:Alt></dc:rights>
+<dc:description><rdf:Alt><rdf:li xml:lang="x-default">Fedora in the rain</rdf:li></rdf:Alt></dc:description>
+</rdf:Description>
+</rdf:RDF>
+</x:xmpmeta>"#;
+
+/// Private copy of noir-atelier where `vincent-fedora.jpg` carries EXIF and
+/// XMP (captured 2024-06-15) and `vincent-shadow-play.jpg` only XMP.
+fn galleries_with_metadata() -> std::path::PathBuf {
+    let galleries = temp_dir("galleries");
+    copy_dir(
+        std::path::Path::new("../galleries/noir-atelier"),
+        &galleries.join("noir-atelier"),
+    );
+    let xmp = [b"http://ns.adobe.com/xap/1.0/\0".as_slice(), XMP_PACKET.as_bytes()].concat();
+    let fedora = galleries.join("noir-atelier/film-noir/vincent-fedora.jpg");
+    insert_app1(&fedora, &exif_payload());
+    insert_app1(&fedora, &xmp);
+    insert_app1(&galleries.join("noir-atelier/film-noir/vincent-shadow-play.jpg"), &xmp);
+    galleries
+}
+
+/// Run ingest over noir-atelier and wait for it to finish.
+async fn ingest_noir_atelier(client: &Client, base: &str) {
+    let resp = client
+        .post(format!("{base}/admin/ingest"))
+        .json(&json!({ "collection": "noir-atelier" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 202);
+    let status = wait_for_job(client, &format!("{base}/admin/ingest")).await;
+    assert_eq!(status["failed"].as_u64().unwrap(), 0);
+}
+
+/// A server over `galleries_with_metadata`, with noir-atelier ingested.
+async fn spawn_ingested_metadata_app(client: &Client) -> String {
+    let galleries = galleries_with_metadata();
+    let base = spawn_app_with_config(Config::new(&temp_db(), galleries.to_str().unwrap())).await;
+    ingest_noir_atelier(client, &base).await;
+    base
+}
+
+/// `GET /images/{uuid}` of the image at `path`.
+async fn image_detail_by_path(client: &Client, base: &str, path: &str) -> Value {
+    let results = search(client, base, json!([])).await;
+    let uuid = results
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|img| img["path"] == path)
+        .unwrap()["uuid"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    client.get(format!("{base}/images/{uuid}")).send().await.unwrap().json().await.unwrap()
+}
+
+#[tokio::test]
+async fn test_metadata_from_exif_with_xmp_rights_and_caption() {
+    let client = Client::new();
+    let base = spawn_ingested_metadata_app(&client).await;
+
+    let detail = image_detail_by_path(&client, &base, "noir-atelier/film-noir/vincent-fedora.jpg").await;
+    let metadata = &detail["metadata"];
+    assert_eq!(metadata["camera_make"], "Leica");
+    assert_eq!(metadata["camera_model"], "M11");
+    assert_eq!(metadata["lens"], "Summilux-M 50 f/1.4");
+    assert_eq!(metadata["focal_length"].as_f64().unwrap(), 50.0);
+    assert_eq!(metadata["aperture"].as_f64().unwrap(), 1.4);
+    assert_eq!(metadata["shutter_speed"], "1/250");
+    assert_eq!(metadata["iso"].as_u64().unwrap(), 400);
+    assert_eq!(metadata["captured_at"], "2024-06-15T21:30:05");
+    assert_eq!(metadata["copyright"], "(c) Noir Atelier");
+    assert_eq!(metadata["caption"], "Fedora in the rain");
+}
+
+#[tokio::test]
+async fn test_metadata_falls_back_to_xmp_lens() {
+    let client = Client::new();
+    let base = spawn_ingested_metadata_app(&client).await;
+
+    let detail = image_detail_by_path(&client, &base, "noir-atelier/film-noir/vincent-shadow-play.jpg").await;
+    assert_eq!(detail["metadata"]["lens"], "Noctilux-M 50 f/0.95");
+    assert!(detail["metadata"]["camera_model"].is_null());
+}
+
+#[tokio::test]
+async fn test_search_captured_at_by_month_and_range() {
+    let client = Client::new();
+    let base = spawn_ingested_metadata_app(&client).await;
+    let filter_count = |filters: Value| search_count(&client, &base, filters);
+
+    assert_eq!(
+        filter_count(json!([{"field": "captured_at", "op": "eq", "value": "2024-06"}])).await,
+        1
+    );
+    assert_eq!(
+        filter_count(json!([{"field": "captured_at", "op": "between", "value": ["2024-01-01", "2024-06-15"]}]))
+            .await,
+        1
+    );
+    assert_eq!(
+        filter_count(json!([{"field": "captured_at", "op": "gt", "value": "2024-06"}])).await,
+        0
+    );
+}
+
+#[tokio::test]
+async fn test_search_lens_iso_and_aperture() {
+    let client = Client::new();
+    let base = spawn_ingested_metadata_app(&client).await;
+    let filter_count = |filters: Value| search_count(&client, &base, filters);
+
+    assert_eq!(
+        filter_count(json!([{"field": "lens", "op": "eq", "value": "Noctilux-M 50 f/0.95"}])).await,
+        1
+    );
+    assert_eq!(
+        filter_count(json!([{"field": "iso", "op": "gte", "value": 400}])).await,
+        1
+    );
+    assert_eq!(
+        filter_count(json!([{"field": "aperture", "op": "lt", "value": 2}])).await,
+        1
+    );
+}
+
+#[tokio::test]
+async fn test_search_lens_rejects_range_ops() {
+    let client = Client::new();
+    let base = spawn_ingested_metadata_app(&client).await;
+
+    let resp = client
+        .post(format!("{base}/images/search"))
+        .json(&json!({ "filters": [{"field": "lens", "op": "gt", "value": "a"}] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+// ─── POST /images/search/timeline ───
+
+/// A server over `galleries_with_metadata`, ingested, where
+/// `vincent-shadow-play.jpg` has no EXIF date and a file mtime of
+/// 2023-01-02 03:04:05 UTC to stand in for one.
+async fn spawn_timeline_app(client: &Client) -> String {
+    let galleries = galleries_with_metadata();
+    let old = std::fs::File::options()
+        .write(true)
+        .open(galleries.join("noir-atelier/film-noir/vincent-shadow-play.jpg"))
+        .unwrap();
+    old.set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_672_628_645))
+        .unwrap();
+    drop(old);
+
+    let base = spawn_app_with_config(Config::new(&temp_db(), galleries.to_str().unwrap())).await;
+    ingest_noir_atelier(client, &base).await;
+    base
+}
+
+async fn timeline(client: &Client, base: &str, body: Value) -> Value {
+    let resp = client.post(format!("{base}/images/search/timeline")).json(&body).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    resp.json().await.unwrap()
+}
+
+#[tokio::test]
+async fn test_timeline_by_day_falls_back_to_file_mtime() {
+    let client = Client::new();
+    let base = spawn_timeline_app(&client).await;
+
+    let by_day = timeline(
+        &client,
+        &base,
+        json!({
+            "filters": [{"field": "taken_at", "op": "lt", "value": "2025"}],
+            "granularity": "day"
+        }),
+    )
+    .await;
+    assert_eq!(by_day["granularity"], "day");
+    assert_eq!(
+        by_day["buckets"],
+        json!([
+            {"period": "2023-01-02", "image_count": 1},
+            {"period": "2024-06-15", "image_count": 1}
+        ])
+    );
+}
+
+#[tokio::test]
+async fn test_timeline_defaults_to_months() {
+    let client = Client::new();
+    let base = spawn_timeline_app(&client).await;
+
+    // The rest were just copied, so they're recent
+    let noir = json!([{"field": "collection", "op": "eq", "value": "noir-atelier"}]);
+    let by_month = timeline(&client, &base, json!({ "filters": noir })).await;
+    let buckets = by_month["buckets"].as_array().unwrap();
+    assert_eq!(by_month["granularity"], "month");
+    assert_eq!(buckets[0], json!({"period": "2023-01", "image_count": 1}));
+    assert_eq!(buckets[1], json!({"period": "2024-06", "image_count": 1}));
+    let total: u64 = buckets.iter().map(|b| b["image_count"].as_u64().unwrap()).sum();
+    assert_eq!(total, 14);
+    assert_eq!(by_month["undated_count"], 0);
+}
+
+#[tokio::test]
+async fn test_timeline_counts_uningested_images_as_undated() {
+    let client = Client::new();
+    let base = spawn_timeline_app(&client).await;
+
+    let everything = timeline(&client, &base, json!({ "filters": [], "granularity": "year" })).await;
+    assert!(everything["undated_count"].as_u64().unwrap() > 0);
+}
+
+#[tokio::test]
+async fn test_timeline_rejects_unknown_granularity() {
+    let base = spawn_app().await;
+    let resp = Client::new()
+        .post(format!("{base}/images/search/timeline"))
+        .json(&json!({ "filters": [], "granularity": "week" }))
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_client_error());
+}
+
+#[tokio::test]
+async fn test_search_taken_at_by_month_and_range() {
+    let client = Client::new();
+    let base = spawn_timeline_app(&client).await;
+
+    assert_eq!(
+        search_count(
+            &client,
+            &base,
+            json!([{"field": "taken_at", "op": "eq", "value": "2024-06"}])
+        )
+        .await,
+        1
+    );
+    assert_eq!(
+        search_count(
+            &client,
+            &base,
+            json!([{"field": "taken_at", "op": "between", "value": ["2023", "2024-06"]}])
+        )
+        .await,
+        2
+    );
+}
+
+// ─── XMP keywords and sidecars ───
+
+/// UUID of a tag by name, from `/tags`.
+async fn tag_uuid(client: &Client, base: &str, name: &str) -> String {
+    let groups: Value = client
+        .get(format!("{base}/tags"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    groups
+        .as_array()
+        .unwrap()
+        .iter()
+        .flat_map(|g| g["tags"].as_array().unwrap())
+        .find(|t| t["name"] == name)
+        .unwrap()["uuid"]
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+/// Tag names on the image at `path`, plus its UUID.
+async fn image_tags_by_path(client: &Client, base: &str, path: &str) -> (String, Vec<String>) {
+    let results = search(
+        client,
+        base,
+        json!([{"field": "collection", "op": "eq", "value": "noir-atelier"}]),
+    )
+    .await;
+    let uuid = results
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|img| img["path"] == path)
+        .unwrap()["uuid"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let detail: Value = client
+        .get(format!("{base}/images/{uuid}"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let tags = detail["tags"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|t| t["name"].as_str().unwrap().to_string())
+        .collect();
+    (uuid, tags)
+}
+
+/// A server over a copy of noir-atelier with XMP keywords embedded in
+/// `vincent-fedora.jpg`, a `.xmp` sidecar next to `vincent-shadow-play.jpg`
+/// and keyword rules, ingested after clearing both images' tags so every tag
+/// on them came from XMP.
+async fn spawn_keyword_app(client: &Client) -> String {
+    let galleries = temp_dir("galleries");
+    copy_dir(
+        std::path::Path::new("../galleries/noir-atelier"),
+        &galleries.join("noir-atelier"),
+    );
+    let keywords = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+<rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+<rdf:Description rdf:about="" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:lr="http://ns.adobe.com/lightroom/1.0/">
+<dc:subject><rdf:Bag><rdf:li>Golden Hour</rdf:li><rdf:li>Client Select</rdf:li><rdf:li>Nonexistent</rdf:li></rdf:Bag></dc:subject>
+<lr:hierarchicalSubject><rdf:Bag><rdf:li>Mood|Dramatic</rdf:li><rdf:li>Shoot|Light|Backlit</rdf:li><rdf:li>Mood|Outdoor</rdf:li></rdf:Bag></lr:hierarchicalSubject>
+</rdf:Description>
+</rdf:RDF>
+</x:xmpmeta>"#;
+    insert_app1(
+        &galleries.join("noir-atelier/film-noir/vincent-fedora.jpg"),
+        &[b"http://ns.adobe.com/xap/1.0/\0".as_slice(), keywords.as_bytes()].concat(),
+    );
+    std::fs::write(
+        galleries.join("noir-atelier/film-noir/vincent-shadow-play.xmp"),
+        r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+<rdf:Description rdf:about="" xmlns:dc="http://purl.org/dc/elements/1.1/">
+<dc:subject><rdf:Bag><rdf:li>Noir</rdf:li></rdf:Bag></dc:subject>
+</rdf:Description></rdf:RDF></x:xmpmeta>"#,
+    )
+    .unwrap();
+
+    let rules = temp_dir("rules").with_extension("json");
+    std::fs::write(
+        &rules,
+        json!([
+            {"keyword": "Client Select", "ignore": true},
+            {"keyword": "Noir", "tag": "low-key"},
+            {"prefix": "Shoot|Light", "group": "lighting"}
+        ])
+        .to_string(),
+    )
+    .unwrap();
+
+    let mut config = Config::new(&temp_db(), galleries.to_str().unwrap());
+    config.keyword_rules_path = Some(rules);
+    let base = spawn_app_with_config(config).await;
+
+    for path in ["vincent-fedora.jpg", "vincent-shadow-play.jpg"] {
+        let (uuid, _) =
+            image_tags_by_path(client, &base, &format!("noir-atelier/film-noir/{path}")).await;
+        client
+            .put(format!("{base}/images/{uuid}/tags"))
+            .json(&json!({ "tag_uuids": [] }))
+            .send()
+            .await
+            .unwrap();
+    }
+    ingest_noir_atelier(client, &base).await;
+    base
+}
+
+#[tokio::test]
+async fn test_ingest_reports_imported_keyword_count() {
+    let client = Client::new();
+    let base = spawn_keyword_app(&client).await;
+    let status: Value = client
+        .get(format!("{base}/admin/ingest"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(status["tags_imported"].as_u64().unwrap(), 5);
+}
+
+#[tokio::test]
+async fn test_ingest_maps_embedded_keywords_through_rules() {
+    let client = Client::new();
+    let base = spawn_keyword_app(&client).await;
+
+    // "Mood|Outdoor" names a tag from another group, so it falls back to the
+    // name; "Client Select" is ignored and "Nonexistent" matches nothing
+    let (_, mut tags) =
+        image_tags_by_path(&client, &base, "noir-atelier/film-noir/vincent-fedora.jpg").await;
+    tags.sort();
+    assert_eq!(tags, ["backlit", "dramatic", "golden-hour", "outdoor"]);
+}
+
+#[tokio::test]
+async fn test_ingest_reads_keywords_from_sidecar_file() {
+    let client = Client::new();
+    let base = spawn_keyword_app(&client).await;
+    let (_, tags) =
+        image_tags_by_path(&client, &base, "noir-atelier/film-noir/vincent-shadow-play.jpg").await;
+    assert_eq!(tags, ["low-key"]);
+}
+
+#[tokio::test]
+async fn test_imported_keywords_recorded_in_tag_history() {
+    let client = Client::new();
+    let base = spawn_keyword_app(&client).await;
+    let (uuid, _) =
+        image_tags_by_path(&client, &base, "noir-atelier/film-noir/vincent-shadow-play.jpg").await;
+
+    let history: Value = client
+        .get(format!("{base}/images/{uuid}/tags/history"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(history[0]["actor"], "local");
+    assert_eq!(history[0]["before"], json!([]));
+    assert_eq!(history[0]["after"][0]["name"], "low-key");
+}
+
+/// A server over a copy of noir-atelier where `vincent-fedora.jpg` is tagged
+/// golden-hour and moody and `vincent-shadow-play.jpg` already has a sidecar
+/// with a rating and a stale keyword. Returns the base URL, the galleries
+/// root and the fedora image's UUID.
+async fn spawn_sidecar_app(client: &Client) -> (String, std::path::PathBuf, String) {
+    let galleries = temp_dir("galleries");
+    copy_dir(
+        std::path::Path::new("../galleries/noir-atelier"),
+        &galleries.join("noir-atelier"),
+    );
+    let base = spawn_app_with_config(Config::new(&temp_db(), galleries.to_str().unwrap())).await;
+
+    let (fedora, _) =
+        image_tags_by_path(client, &base, "noir-atelier/film-noir/vincent-fedora.jpg").await;
+    let golden_hour = tag_uuid(client, &base, "golden-hour").await;
+    let moody = tag_uuid(client, &base, "moody").await;
+    client
+        .put(format!("{base}/images/{fedora}/tags"))
+        .json(&json!({ "tag_uuids": [golden_hour, moody] }))
+        .send()
+        .await
+        .unwrap();
+
+    std::fs::write(
+        galleries.join("noir-atelier/film-noir/vincent-shadow-play.xmp"),
+        r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+<rdf:Description rdf:about="" xmlns:xmp="http://ns.adobe.com/xap/1.0/" xmlns:dc="http://purl.org/dc/elements/1.1/" xmp:Rating="4">
+<dc:subject><rdf:Bag><rdf:li>stale-keyword</rdf:li></rdf:Bag></dc:subject>
+</rdf:Description></rdf:RDF></x:xmpmeta>"#,
+    )
+    .unwrap();
+    (base, galleries, fedora)
+}
+
+/// Runs a sidecar export of noir-atelier and returns the finished job status.
+async fn export_sidecars(client: &Client, base: &str) -> Value {
+    let resp = client
+        .post(format!("{base}/admin/sidecars"))
+        .json(&json!({ "collection": "noir-atelier" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 202);
+    wait_for_job(client, &format!("{base}/admin/sidecars")).await
+}
+
+#[tokio::test]
+async fn test_sidecar_export_writes_every_image() {
+    let client = Client::new();
+    let (base, _, _) = spawn_sidecar_app(&client).await;
+    let status = export_sidecars(&client, &base).await;
+    assert_eq!(status["total"].as_u64().unwrap(), 14);
+    assert_eq!(status["written"].as_u64().unwrap(), 14);
+    assert_eq!(status["failed"].as_u64().unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_sidecar_export_writes_flat_and_hierarchical_keywords() {
+    let client = Client::new();
+    let (base, galleries, _) = spawn_sidecar_app(&client).await;
+    export_sidecars(&client, &base).await;
+
+    let sidecar = std::fs::read_to_string(galleries.join("noir-atelier/film-noir/vincent-fedora.xmp"))
+        .unwrap();
+    assert!(sidecar.contains("<rdf:li>golden-hour</rdf:li>"));
+    assert!(sidecar.contains("<rdf:li>lighting|golden-hour</rdf:li>"));
+    assert!(sidecar.contains("<rdf:li>mood|moody</rdf:li>"));
+}
+
+#[tokio::test]
+async fn test_sidecar_export_replaces_only_keywords_in_existing_sidecar() {
+    let client = Client::new();
+    let (base, galleries, _) = spawn_sidecar_app(&client).await;
+    export_sidecars(&client, &base).await;
+
+    let merged =
+        std::fs::read_to_string(galleries.join("noir-atelier/film-noir/vincent-shadow-play.xmp"))
+            .unwrap();
+    assert!(merged.contains(r#"xmp:Rating="4""#));
+    assert!(!merged.contains("stale-keyword"));
+    assert!(merged.contains("<lr:hierarchicalSubject"));
+}
+
+#[tokio::test]
+async fn test_sidecar_export_leaves_unchanged_files_alone() {
+    let client = Client::new();
+    let (base, _, _) = spawn_sidecar_app(&client).await;
+    export_sidecars(&client, &base).await;
+
+    let status = export_sidecars(&client, &base).await;
+    assert_eq!(status["written"].as_u64().unwrap(), 0);
+    assert_eq!(status["unchanged"].as_u64().unwrap(), 14);
+}
+
+#[tokio::test]
+async fn test_sidecar_keywords_restored_by_forced_ingest() {
+    let client = Client::new();
+    let (base, _, fedora) = spawn_sidecar_app(&client).await;
+    export_sidecars(&client, &base).await;
+
+    client
+        .put(format!("{base}/images/{fedora}/tags"))
+        .json(&json!({ "tag_uuids": [] }))
+        .send()
+        .await
+        .unwrap();
+    client
+        .post(format!("{base}/admin/ingest"))
+        .json(&json!({ "collection": "noir-atelier", "force": true }))
+        .send()
+        .await
+        .unwrap();
+    wait_for_job(&client, &format!("{base}/admin/ingest")).await;
+    let (_, mut tags) =
+        image_tags_by_path(&client, &base, "noir-atelier/film-noir/vincent-fedora.jpg").await;
+    tags.sort();
+    assert_eq!(tags, ["golden-hour", "moody"]);
+}
+
+// ─── Duplicates and similar images ───
+
+/// A server over a copy of noir-atelier, ingested, where `vincent-fedora.jpg`
+/// has real content and `vincent-shadow-play.jpg` is the same shot exported
+/// again smaller. The sample placeholders all look alike otherwise.
+async fn spawn_duplicates_app(client: &Client) -> String {
+    let galleries = temp_dir("galleries");
+    copy_dir(
+        std::path::Path::new("../galleries/noir-atelier"),
+        &galleries.join("noir-atelier"),
+    );
+    let film_noir = galleries.join("noir-atelier/film-noir");
+    let shot = image::RgbImage::from_fn(1280, 1920, |x, y| {
+        let block = (x / 142) * 31 + (y / 240) * 17;
+        let v = (block.wrapping_mul(2_654_435_761) >> 24) as u8;
+        image::Rgb([v, v / 2, 255 - v])
+    });
+    shot.save(film_noir.join("vincent-fedora.jpg")).unwrap();
+    image::DynamicImage::ImageRgb8(shot)
+        .thumbnail(600, 600)
+        .save(film_noir.join("vincent-shadow-play.jpg"))
+        .unwrap();
+
+    let base = spawn_app_with_config(Config::new(&temp_db(), galleries.to_str().unwrap())).await;
+    ingest_noir_atelier(client, &base).await;
+    base
+}
+
+#[tokio::test]
+async fn test_duplicates_cluster_resized_copy_with_original() {
+    let client = Client::new();
+    let base = spawn_duplicates_app(&client).await;
+
+    let clusters: Value = client
+        .get(format!("{base}/images/duplicates?collection=noir-atelier&max_distance=2"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let clusters = clusters.as_array().unwrap();
+    let copy = clusters
+        .iter()
+        .find(|c| {
+            c["images"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .any(|img| img["path"] == "noir-atelier/film-noir/vincent-fedora.jpg")
+        })
+        .expect("resized copy should cluster with its original");
+    let mut paths: Vec<&str> = copy["images"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|img| img["path"].as_str().unwrap())
+        .collect();
+    paths.sort();
+    assert_eq!(
+        paths,
+        [
+            "noir-atelier/film-noir/vincent-fedora.jpg",
+            "noir-atelier/film-noir/vincent-shadow-play.jpg"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_duplicates_empty_for_uningested_collection() {
+    let client = Client::new();
+    let base = spawn_duplicates_app(&client).await;
+
+    // Nothing outside noir-atelier has been ingested, so no clusters there
+    let others: Value = client
+        .get(format!("{base}/images/duplicates?collection=lumiere-studio"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(others, json!([]));
+}
+
+#[tokio::test]
+async fn test_search_similar_to_finds_copies_but_not_the_image_itself() {
+    let client = Client::new();
+    let base = spawn_duplicates_app(&client).await;
+
+    let (fedora_uuid, _) =
+        image_tags_by_path(&client, &base, "noir-atelier/film-noir/vincent-fedora.jpg").await;
+    let similar = search(
+        &client,
+        &base,
+        json!([{"field": "similar_to", "op": "eq", "value": fedora_uuid, "max_distance": 2}]),
+    )
+    .await;
+    let similar_paths: Vec<&str> = similar
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|img| img["path"].as_str().unwrap())
+        .collect();
+    assert!(similar_paths.contains(&"noir-atelier/film-noir/vincent-shadow-play.jpg"));
+    assert!(!similar_paths.contains(&"noir-atelier/film-noir/vincent-fedora.jpg"));
+}
+
+#[tokio::test]
+async fn test_search_max_distance_rejected_on_other_fields() {
+    let base = spawn_app().await;
+    let resp = Client::new()
+        .post(format!("{base}/images/search"))
+        .json(&json!({ "filters": [{"field": "tags", "op": "any_of", "value": [], "max_distance": 3}] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+// ─── Colour palettes ───
+
+/// A server over a copy of noir-atelier, ingested, where `vincent-fedora.jpg`
+/// is mostly warm red with a teal band down the right-hand side.
+async fn spawn_palette_app(client: &Client) -> String {
+    let galleries = temp_dir("galleries");
+    copy_dir(
+        std::path::Path::new("../galleries/noir-atelier"),
+        &galleries.join("noir-atelier"),
+    );
+    let shot = image::RgbImage::from_fn(640, 960, |x, _| {
+        if x < 448 { image::Rgb([200, 40, 40]) } else { image::Rgb([40, 150, 140]) }
+    });
+    shot.save(galleries.join("noir-atelier/film-noir/vincent-fedora.jpg")).unwrap();
+
+    let base = spawn_app_with_config(Config::new(&temp_db(), galleries.to_str().unwrap())).await;
+    ingest_noir_atelier(client, &base).await;
+    base
+}
+
+fn result_paths(results: Value) -> Vec<String> {
+    results
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|img| img["path"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_palette_lists_dominant_colours_by_weight() {
+    let client = Client::new();
+    let base = spawn_palette_app(&client).await;
+
+    let (fedora, _) =
+        image_tags_by_path(&client, &base, "noir-atelier/film-noir/vincent-fedora.jpg").await;
+    let detail: Value = client
+        .get(format!("{base}/images/{fedora}"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let palette = detail["palette"].as_array().unwrap();
+    assert_eq!(palette.len(), 2, "{palette:?}");
+    let weight = palette[0]["weight"].as_f64().unwrap();
+    assert!((0.6..0.8).contains(&weight), "{palette:?}");
+    let red = palette[0]["color"].as_str().unwrap();
+    let channel = |i: usize| u8::from_str_radix(&red[1 + 2 * i..3 + 2 * i], 16).unwrap();
+    assert!(channel(0) > 180 && channel(1) < 70 && channel(2) < 70, "{red}");
+}
+
+#[tokio::test]
+async fn test_search_color_matches_near_and_minor_colours() {
+    let client = Client::new();
+    let base = spawn_palette_app(&client).await;
+
+    // A slightly different red, and the minor teal colour, both match
+    for hex in ["#c83232", "2a9d8f"] {
+        let results = search(&client, &base, json!([{"field": "color", "op": "eq", "value": hex}])).await;
+        assert_eq!(result_paths(results), ["noir-atelier/film-noir/vincent-fedora.jpg"], "{hex}");
+    }
+}
+
+#[tokio::test]
+async fn test_search_color_tolerance_widened_by_max_distance() {
+    let client = Client::new();
+    let base = spawn_palette_app(&client).await;
+
+    // Orange is too far from red by default, but not with a wider tolerance
+    let orange = json!({"field": "color", "op": "eq", "value": "#e07020"});
+    assert_eq!(search(&client, &base, json!([orange])).await, json!([]));
+    let mut wide = orange.clone();
+    wide["max_distance"] = json!(60);
+    assert_eq!(result_paths(search(&client, &base, json!([wide])).await).len(), 1);
+}
+
+#[tokio::test]
+async fn test_search_color_rejects_names_and_other_ops() {
+    let base = spawn_app().await;
+    let client = Client::new();
+    for clause in [
+        json!({"field": "color", "op": "eq", "value": "red"}),
+        json!({"field": "color", "op": "any_of", "value": ["#c83232"]}),
+    ] {
+        let resp = client
+            .post(format!("{base}/images/search"))
+            .json(&json!({ "filters": [clause] }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 400);
+    }
+}
+
+// ─── Culling ───
+
+/// UUIDs of the film-noir images, in search order.
+async fn film_noir_uuids(client: &Client, base: &str) -> Vec<String> {
+    let images = search(client, base, json!([{"field": "gallery", "op": "eq", "value": "film-noir"}])).await;
+    result_uuids(images)
+}
+
+fn result_uuids(results: Value) -> Vec<String> {
+    results
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|img| img["uuid"].as_str().unwrap().to_string())
+        .collect()
+}
+
+/// A server where the third film-noir image is rated 5 and labelled red, the
+/// second is rated 3, and both are picks. Returns the base URL and the
+/// film-noir UUIDs.
+async fn spawn_culled_app(client: &Client) -> (String, Vec<String>) {
+    let base = spawn_app_with_config(Config::new(&temp_db(), "../galleries")).await;
+    let uuids = film_noir_uuids(client, &base).await;
+
+    let resp = client
+        .patch(format!("{base}/images/{}/culling", uuids[2]))
+        .json(&json!({ "rating": 5, "color_label": "red" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 204);
+    let resp = client
+        .patch(format!("{base}/images/culling"))
+        .json(&json!({ "image_uuids": [uuids[1], uuids[2]], "flag": "pick" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.json::<Value>().await.unwrap(), json!({ "updated": 2 }));
+    client
+        .patch(format!("{base}/images/{}/culling", uuids[1]))
+        .json(&json!({ "rating": 3 }))
+        .send()
+        .await
+        .unwrap();
+    (base, uuids)
+}
+
+#[tokio::test]
+async fn test_culling_starts_unrated_unlabelled_and_unflagged() {
+    let base = spawn_app_with_config(Config::new(&temp_db(), "../galleries")).await;
+    let images = search(
+        &Client::new(),
+        &base,
+        json!([{"field": "gallery", "op": "eq", "value": "film-noir"}]),
+    )
+    .await;
+    assert!(images.as_array().unwrap().len() >= 3);
+    assert_eq!(images[0]["rating"], 0);
+    assert_eq!(images[0]["color_label"], "none");
+    assert_eq!(images[0]["flag"], "none");
+}
+
+#[tokio::test]
+async fn test_culling_single_and_bulk_updates_combine() {
+    let client = Client::new();
+    let (base, uuids) = spawn_culled_app(&client).await;
+
+    let detail: Value = client
+        .get(format!("{base}/images/{}", uuids[2]))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(detail["rating"], 5);
+    assert_eq!(detail["color_label"], "red");
+    assert_eq!(detail["flag"], "pick");
+}
+
+#[tokio::test]
+async fn test_search_by_rating_flag_and_color_label() {
+    let client = Client::new();
+    let (base, uuids) = spawn_culled_app(&client).await;
+
+    let rated = search(&client, &base, json!([{"field": "rating", "op": "gte", "value": 3}])).await;
+    assert_eq!(result_uuids(rated).len(), 2);
+    let picks = search(&client, &base, json!([{"field": "flag", "op": "eq", "value": "pick"}])).await;
+    assert_eq!(result_uuids(picks).len(), 2);
+    let unlabeled = search(
+        &client,
+        &base,
+        json!([
+            {"field": "gallery", "op": "eq", "value": "film-noir"},
+            {"field": "color_label", "op": "none_of", "value": ["red"]}
+        ]),
+    )
+    .await;
+    assert_eq!(result_uuids(unlabeled).len(), uuids.len() - 1);
+}
+
+#[tokio::test]
+async fn test_search_sorts_by_rating_then_flag() {
+    let client = Client::new();
+    let (base, uuids) = spawn_culled_app(&client).await;
+
+    // Best first, then picks before unflagged
+    let sorted: Value = client
+        .post(format!("{base}/images/search"))
+        .json(&json!({
+            "filters": [{"field": "gallery", "op": "eq", "value": "film-noir"}],
+            "sort": [{"field": "rating", "direction": "desc"}, {"field": "flag", "direction": "desc"}]
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let sorted = result_uuids(sorted);
+    assert_eq!(&sorted[..2], [uuids[2].as_str(), uuids[1].as_str()]);
+    assert_eq!(sorted.len(), uuids.len());
+}
+
+#[tokio::test]
+async fn test_culling_rejects_invalid_updates_without_partial_writes() {
+    let base = spawn_app_with_config(Config::new(&temp_db(), "../galleries")).await;
+    let client = Client::new();
+    let uuids = film_noir_uuids(&client, &base).await;
+
+    for (url, body, status) in [
+        (format!("{base}/images/{}/culling", uuids[0]), json!({ "rating": 6 }), 400),
+        (format!("{base}/images/{}/culling", uuids[0]), json!({}), 400),
+        (format!("{base}/images/{}/culling", uuids[0]), json!({ "flag": "maybe" }), 422),
+        (format!("{base}/images/culling"), json!({ "image_uuids": [uuids[0], "nope"], "rating": 1 }), 404),
+    ] {
+        let resp = client.patch(url).json(&body).send().await.unwrap();
+        assert_eq!(resp.status(), status, "{body}");
+    }
+    // The failed bulk update left the existing image alone
+    let unrated = search(&client, &base, json!([{"field": "rating", "op": "eq", "value": 0}])).await;
+    assert!(result_uuids(unrated).contains(&uuids[0]));
+}
+
+#[tokio::test]
+async fn test_search_rejects_unknown_flag() {
+    let base = spawn_app().await;
+    let resp = Client::new()
+        .post(format!("{base}/images/search"))
+        .json(&json!({ "filters": [{"field": "flag", "op": "eq", "value": "maybe"}] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+// ─── Comments ───
+
+/// A server over the sample galleries, and the first film-noir image's UUID.
+async fn spawn_comments_app(client: &Client) -> (String, String) {
+    let base = spawn_app_with_config(Config::new(&temp_db(), "../galleries")).await;
+    let images = search(client, &base, json!([{"field": "gallery", "op": "eq", "value": "film-noir"}])).await;
+    (base, images[0]["uuid"].as_str().unwrap().to_string())
+}
+
+async fn post_comment(client: &Client, comments_url: &str, body: Value) -> Value {
+    let resp = client.post(comments_url).json(&body).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    resp.json().await.unwrap()
+}
+
+/// A server where the first film-noir image has a note with a region and an
+/// edited reply to it. Returns the base URL, the image's UUID and the note's.
+async fn spawn_comment_thread(client: &Client) -> (String, String, String) {
+    let (base, image) = spawn_comments_app(client).await;
+    let comments_url = format!("{base}/images/{image}/comments");
+    let note = post_comment(
+        client,
+        &comments_url,
+        json!({
+            "body": "Lift the shadows on the left",
+            "region": {"x": 0.1, "y": 0.2, "width": 0.3, "height": 0.4}
+        }),
+    )
+    .await;
+    let note_uuid = note["uuid"].as_str().unwrap().to_string();
+    let reply =
+        post_comment(client, &comments_url, json!({ "body": "Done in v2", "parent_uuid": note_uuid })).await;
+    client
+        .patch(format!("{comments_url}/{}", reply["uuid"].as_str().unwrap()))
+        .json(&json!({ "body": "Done in v3" }))
+        .send()
+        .await
+        .unwrap();
+    (base, image, note_uuid)
+}
+
+#[tokio::test]
+async fn test_comment_created_with_author_and_region() {
+    let client = Client::new();
+    let (base, image) = spawn_comments_app(&client).await;
+    let images = search(&client, &base, json!([{"field": "gallery", "op": "eq", "value": "film-noir"}])).await;
+    assert_eq!(images[0]["comment_count"], 0);
+
+    let note = post_comment(
+        &client,
+        &format!("{base}/images/{image}/comments"),
+        json!({
+            "body": "Lift the shadows on the left",
+            "region": {"x": 0.1, "y": 0.2, "width": 0.3, "height": 0.4}
+        }),
+    )
+    .await;
+    assert_eq!(note["author"], "local");
+    assert_eq!(note["region"]["width"], 0.3);
+    assert_eq!(note["parent_uuid"], Value::Null);
+}
+
+#[tokio::test]
+async fn test_comment_reply_names_its_parent() {
+    let client = Client::new();
+    let (base, image) = spawn_comments_app(&client).await;
+    let comments_url = format!("{base}/images/{image}/comments");
+    let note = post_comment(&client, &comments_url, json!({ "body": "Lift the shadows on the left" })).await;
+    let note_uuid = note["uuid"].as_str().unwrap();
+
+    let reply =
+        post_comment(&client, &comments_url, json!({ "body": "Done in v2", "parent_uuid": note_uuid })).await;
+    assert_eq!(reply["parent_uuid"], note_uuid);
+    assert_eq!(reply["region"], Value::Null);
+}
+
+#[tokio::test]
+async fn test_comment_edit_records_updated_at() {
+    let client = Client::new();
+    let (base, image) = spawn_comments_app(&client).await;
+    let comments_url = format!("{base}/images/{image}/comments");
+    let note = post_comment(&client, &comments_url, json!({ "body": "Done in v2" })).await;
+
+    let edited: Value = client
+        .patch(format!("{comments_url}/{}", note["uuid"].as_str().unwrap()))
+        .json(&json!({ "body": "Done in v3" }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(edited["body"], "Done in v3");
+    assert!(edited["updated_at"].is_u64());
+}
+
+#[tokio::test]
+async fn test_comments_listed_as_a_thread() {
+    let client = Client::new();
+    let (base, image, _) = spawn_comment_thread(&client).await;
+
+    let thread: Value = client
+        .get(format!("{base}/images/{image}/comments"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let bodies: Vec<&str> = thread.as_array().unwrap().iter().map(|c| c["body"].as_str().unwrap()).collect();
+    assert_eq!(bodies, ["Lift the shadows on the left", "Done in v3"]);
+}
+
+#[tokio::test]
+async fn test_search_has_comments_with_counts() {
+    let client = Client::new();
+    let (base, image, _) = spawn_comment_thread(&client).await;
+
+    let commented = search(&client, &base, json!([{"field": "has_comments", "op": "eq", "value": true}])).await;
+    assert_eq!(commented.as_array().unwrap().len(), 1);
+    assert_eq!(commented[0]["uuid"], image.as_str());
+    assert_eq!(commented[0]["comment_count"], 2);
+    let total = search_count(&client, &base, json!([])).await;
+    let quiet = search_count(&client, &base, json!([{"field": "has_comments", "op": "eq", "value": false}])).await;
+    assert_eq!(quiet, total - 1);
+}
+
+#[tokio::test]
+async fn test_comments_reject_invalid_bodies_and_unknown_images() {
+    let client = Client::new();
+    let (base, image) = spawn_comments_app(&client).await;
+    let comments_url = format!("{base}/images/{image}/comments");
+
+    for (body, status) in [
+        (json!({ "body": "" }), 400),
+        (json!({ "body": "hi", "region": {"x": 0.9, "y": 0, "width": 0.2, "height": 0.1} }), 400),
+        (json!({ "body": "hi", "parent_uuid": "nope" }), 400),
+    ] {
+        let resp = client.post(&comments_url).json(&body).send().await.unwrap();
+        assert_eq!(resp.status(), status, "{body}");
+    }
+    let resp = client.get(format!("{base}/images/nope/comments")).send().await.unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+#[tokio::test]
+async fn test_search_has_comments_rejects_non_boolean() {
+    let base = spawn_app().await;
+    let resp = Client::new()
+        .post(format!("{base}/images/search"))
+        .json(&json!({ "filters": [{"field": "has_comments", "op": "eq", "value": "yes"}] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn test_deleting_comment_takes_its_replies() {
+    let client = Client::new();
+    let (base, image, note_uuid) = spawn_comment_thread(&client).await;
+    let comments_url = format!("{base}/images/{image}/comments");
+
+    let resp = client.delete(format!("{comments_url}/{note_uuid}")).send().await.unwrap();
+    assert_eq!(resp.status(), 204);
+    let thread: Value = client.get(&comments_url).send().await.unwrap().json().await.unwrap();
+    assert_eq!(thread, json!([]));
+    let resp = client.delete(format!("{comments_url}/{note_uuid}")).send().await.unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+/// A server with tagger keys for marta and jon and an admin key, and the
+/// comments URL of the first image. Returns the base URL, that URL and the
+/// keys in that order.
+async fn spawn_comment_authors_app() -> (String, String, [String; 3]) {
+    let db_path = temp_db();
+    let keys = [("marta", "tagger"), ("jon", "tagger"), ("ops", "admin")]
+        .map(|(name, role)| cli_key(&db_path, name, role));
+    let base = spawn_app_with_auth(Config::new(&db_path, "../galleries")).await;
+    let images: Value = Client::new()
+        .post(format!("{base}/images/search"))
+        .bearer_auth(&keys[0])
+        .json(&json!({ "filters": [] }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let comments_url = format!("{base}/images/{}/comments", images[0]["uuid"].as_str().unwrap());
+    (base, comments_url, keys)
+}
+
+async fn post_comment_as(client: &Client, comments_url: &str, key: &str, body: Value) -> Value {
+    let resp = client.post(comments_url).bearer_auth(key).json(&body).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    resp.json().await.unwrap()
+}
+
+async fn edit_comment_as(client: &Client, comments_url: &str, key: &str, uuid: &str) -> reqwest::StatusCode {
+    let request = client.patch(format!("{comments_url}/{uuid}")).bearer_auth(key);
+    request.json(&json!({ "body": "edited" })).send().await.unwrap().status()
+}
+
+async fn delete_comment_as(client: &Client, comments_url: &str, key: &str, uuid: &str) -> reqwest::StatusCode {
+    client.delete(format!("{comments_url}/{uuid}")).bearer_auth(key).send().await.unwrap().status()
+}
+
+#[tokio::test]
+async fn test_comment_author_is_always_the_caller() {
+    let client = Client::new();
+    let (_, comments_url, [marta, _, _]) = spawn_comment_authors_app().await;
+    let body = json!({ "author": "jon", "body": "Crop tighter" });
+    let note = post_comment_as(&client, &comments_url, &marta, body).await;
+    assert_eq!(note["author"], "marta");
+}
+
+#[tokio::test]
+async fn test_comment_changed_only_by_its_author() {
+    let client = Client::new();
+    let (_, comments_url, [marta, jon, _]) = spawn_comment_authors_app().await;
+    let note = post_comment_as(&client, &comments_url, &marta, json!({ "body": "Crop tighter" })).await;
+    let note = note["uuid"].as_str().unwrap();
+
+    assert_eq!(edit_comment_as(&client, &comments_url, &jon, note).await, 403);
+    assert_eq!(delete_comment_as(&client, &comments_url, &jon, note).await, 403);
+    assert_eq!(edit_comment_as(&client, &comments_url, &marta, note).await, 200);
+}
+
+#[tokio::test]
+async fn test_comment_with_others_replies_deleted_only_by_admin() {
+    let client = Client::new();
+    let (_, comments_url, [marta, jon, _]) = spawn_comment_authors_app().await;
+    let note = post_comment_as(&client, &comments_url, &marta, json!({ "body": "Crop tighter" })).await;
+    let note = note["uuid"].as_str().unwrap();
+
+    let body = json!({ "body": "Will do", "parent_uuid": note });
+    let reply = post_comment_as(&client, &comments_url, &jon, body).await;
+    assert_eq!(delete_comment_as(&client, &comments_url, &marta, note).await, 403);
+    assert_eq!(delete_comment_as(&client, &comments_url, &jon, reply["uuid"].as_str().unwrap()).await, 204);
+    assert_eq!(delete_comment_as(&client, &comments_url, &marta, note).await, 204);
+}
+
+#[tokio::test]
+async fn test_admin_changes_anyones_comment() {
+    let client = Client::new();
+    let (_, comments_url, [_, jon, admin]) = spawn_comment_authors_app().await;
+    let other = post_comment_as(&client, &comments_url, &jon, json!({ "body": "Off topic" })).await;
+    let other = other["uuid"].as_str().unwrap();
+    assert_eq!(edit_comment_as(&client, &comments_url, &admin, other).await, 200);
+    assert_eq!(delete_comment_as(&client, &comments_url, &admin, other).await, 204);
+}
+
+// ─── Albums ───
+
+/// A server with a "Mood board" album created from a selection spanning two
+/// collections, with the first image repeated. Returns the base URL, the
+/// three selectable UUIDs (two from noir-atelier around one from
+/// lumiere-studio, only the first two in the album) and the created album.
+async fn spawn_album_app(client: &Client) -> (String, [String; 3], Value) {
+    let base = spawn_app_with_config(Config::new(&temp_db(), "../galleries")).await;
+    let noir =
+        search(client, &base, json!([{"field": "collection", "op": "eq", "value": "noir-atelier"}])).await;
+    let lumiere =
+        search(client, &base, json!([{"field": "collection", "op": "eq", "value": "lumiere-studio"}])).await;
+    let uuids = [&noir[0], &lumiere[0], &noir[1]].map(|img| img["uuid"].as_str().unwrap().to_string());
+    let (a, b) = (&uuids[0], &uuids[1]);
+
+    let resp = client
+        .post(format!("{base}/albums"))
+        .json(&json!({ "name": "Mood board", "image_uuids": [a, b, a] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let album = resp.json().await.unwrap();
+    (base, uuids, album)
+}
+
+fn album_order(album: &Value) -> Vec<String> {
+    album["images"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|img| img["uuid"].as_str().unwrap().to_string())
+        .collect()
+}
+
+fn album_url(base: &str, album: &Value) -> String {
+    format!("{base}/albums/{}", album["uuid"].as_str().unwrap())
+}
+
+/// Adds `c` to the album between `a` and `b`.
+async fn insert_into_album(client: &Client, album_url: &str, c: &str) -> Value {
+    client
+        .post(format!("{album_url}/images"))
+        .json(&json!({ "image_uuids": [c], "position": 1 }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_album_created_without_repeated_images() {
+    let client = Client::new();
+    let (_, [a, b, _], album) = spawn_album_app(&client).await;
+    assert_eq!(album_order(&album), [a, b]);
+}
+
+#[tokio::test]
+async fn test_album_images_added_at_position_skipping_members() {
+    let client = Client::new();
+    let (base, [a, b, c], album) = spawn_album_app(&client).await;
+
+    let album: Value = client
+        .post(format!("{}/images", album_url(&base, &album)))
+        .json(&json!({ "image_uuids": [c, b], "position": 1 }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(album_order(&album), [a, c, b]);
+}
+
+#[tokio::test]
+async fn test_album_reordered_only_with_every_image() {
+    let client = Client::new();
+    let (base, [a, b, c], album) = spawn_album_app(&client).await;
+    let album_url = album_url(&base, &album);
+    insert_into_album(&client, &album_url, &c).await;
+
+    let album: Value = client
+        .put(format!("{album_url}/order"))
+        .json(&json!({ "image_uuids": [b, a, c] }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(album_order(&album), [&b, &a, &c].map(String::as_str));
+    let resp = client
+        .put(format!("{album_url}/order"))
+        .json(&json!({ "image_uuids": [b, a] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn test_album_image_removed() {
+    let client = Client::new();
+    let (base, [a, b, c], album) = spawn_album_app(&client).await;
+    let album_url = album_url(&base, &album);
+    insert_into_album(&client, &album_url, &c).await;
+
+    let album: Value = client
+        .delete(format!("{album_url}/images/{a}"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(album_order(&album), [c, b]);
+}
+
+#[tokio::test]
+async fn test_album_renamed_and_described() {
+    let client = Client::new();
+    let (base, _, album) = spawn_album_app(&client).await;
+
+    let album: Value = client
+        .patch(album_url(&base, &album))
+        .json(&json!({ "name": "Final picks", "description": "For the client" }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(album["name"], "Final picks");
+    assert_eq!(album["description"], "For the client");
+}
+
+#[tokio::test]
+async fn test_albums_listed_with_image_counts() {
+    let client = Client::new();
+    let (base, _, _) = spawn_album_app(&client).await;
+
+    let albums: Value = client.get(format!("{base}/albums")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(albums.as_array().unwrap().len(), 1);
+    assert_eq!(albums[0]["image_count"], 2);
+}
+
+#[tokio::test]
+async fn test_search_by_album() {
+    let client = Client::new();
+    let (base, [a, b, _], album) = spawn_album_app(&client).await;
+
+    let in_album =
+        search(&client, &base, json!([{"field": "album", "op": "eq", "value": album["uuid"]}])).await;
+    let mut found: Vec<&str> = in_album.as_array().unwrap().iter().map(|i| i["uuid"].as_str().unwrap()).collect();
+    found.sort();
+    let mut expected = vec![a.as_str(), b.as_str()];
+    expected.sort();
+    assert_eq!(found, expected);
+    let none = search(&client, &base, json!([{"field": "album", "op": "any_of", "value": ["nope"]}])).await;
+    assert_eq!(none, json!([]));
+}
+
+#[tokio::test]
+async fn test_search_album_rejects_none_of() {
+    let base = spawn_app().await;
+    let resp = Client::new()
+        .post(format!("{base}/images/search"))
+        .json(&json!({ "filters": [{"field": "album", "op": "none_of", "value": ["nope"]}] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn test_albums_reject_unknown_images_and_blank_names() {
+    let client = Client::new();
+    let (base, _, album) = spawn_album_app(&client).await;
+
+    let resp = client
+        .post(format!("{}/images", album_url(&base, &album)))
+        .json(&json!({ "image_uuids": ["nope"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let resp = client.post(format!("{base}/albums")).json(&json!({ "name": "  " })).send().await.unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn test_deleted_album_gone_from_search() {
+    let client = Client::new();
+    let (base, _, album) = spawn_album_app(&client).await;
+    let album_url = album_url(&base, &album);
+
+    let resp = client.delete(&album_url).send().await.unwrap();
+    assert_eq!(resp.status(), 204);
+    let resp = client.get(&album_url).send().await.unwrap();
+    assert_eq!(resp.status(), 404);
+    let in_album = json!([{"field": "album", "op": "eq", "value": album["uuid"]}]);
+    assert_eq!(search_count(&client, &base, in_album).await, 0);
+}
+
+// ─── Saved searches ───
+
+fn summer_editorial_filters() -> Value {
+    json!([
+        {"field": "collection", "op": "eq", "value": "lumiere-studio"},
+        {"field": "gallery", "op": "eq", "value": "summer-editorial"}
+    ])
+}
+
+/// A server with one saved search for lumiere-studio's summer-editorial
+/// gallery, sorted by path descending. Returns the base URL, the saved search
+/// and its URL.
+async fn spawn_saved_search_app(client: &Client) -> (String, Value, String) {
+    let base = spawn_app_with_config(Config::new(&temp_db(), "../galleries")).await;
+    let resp = client
+        .post(format!("{base}/saved-searches"))
+        .json(&json!({
+            "name": "Summer editorial",
+            "filters": summer_editorial_filters(),
+            "sort": [{"field": "path", "direction": "desc"}]
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let saved: Value = resp.json().await.unwrap();
+    let url = format!("{base}/saved-searches/{}", saved["uuid"].as_str().unwrap());
+    (base, saved, url)
+}
+
+#[tokio::test]
+async fn test_saved_search_keeps_filters_and_sort() {
+    let (_, saved, _) = spawn_saved_search_app(&Client::new()).await;
+    assert_eq!(saved["filters"], summer_editorial_filters());
+    assert_eq!(saved["sort"], json!([{"field": "path", "direction": "desc"}]));
+}
+
+#[tokio::test]
+async fn test_saved_search_runs_like_images_search() {
+    let client = Client::new();
+    let (base, saved, url) = spawn_saved_search_app(&client).await;
+
+    let ran: Value = client.get(format!("{url}/images")).send().await.unwrap().json().await.unwrap();
+    let direct: Value = client
+        .post(format!("{base}/images/search"))
+        .json(&json!({ "filters": saved["filters"], "sort": saved["sort"] }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(ran, direct);
+    assert_eq!(ran.as_array().unwrap().len(), 5);
+}
+
+#[tokio::test]
+async fn test_saved_search_replaced_by_put() {
+    let client = Client::new();
+    let (_, _, url) = spawn_saved_search_app(&client).await;
+
+    let updated: Value = client
+        .put(&url)
+        .json(&json!({ "name": "All lumiere", "filters": [summer_editorial_filters()[0]] }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(updated["name"], "All lumiere");
+    assert_eq!(updated["sort"], json!([]));
+    let ran: Value = client.get(format!("{url}/images")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(ran.as_array().unwrap().len(), 14);
+}
+
+#[tokio::test]
+async fn test_saved_searches_listed() {
+    let client = Client::new();
+    let (base, _, _) = spawn_saved_search_app(&client).await;
+    let list: Value = client.get(format!("{base}/saved-searches")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(list.as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_saved_search_rejects_broken_filters_when_saved() {
+    let client = Client::new();
+    let (base, _, url) = spawn_saved_search_app(&client).await;
+
+    for filters in [
+        json!([{"field": "collection", "op": "any_of", "value": ["a"]}]),
+        json!([{"field": "rating", "op": "gt", "value": "lots"}]),
+        json!([{"field": "color", "op": "eq", "value": "beige"}]),
+    ] {
+        let resp = client
+            .post(format!("{base}/saved-searches"))
+            .json(&json!({ "name": "Broken", "filters": filters }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 400, "{filters}");
+        let resp = client.put(&url).json(&json!({ "name": "Broken", "filters": filters })).send().await.unwrap();
+        assert_eq!(resp.status(), 400, "{filters}");
+    }
+}
+
+#[tokio::test]
+async fn test_deleted_saved_search_no_longer_runs() {
+    let client = Client::new();
+    let (_, _, url) = spawn_saved_search_app(&client).await;
+
+    let resp = client.delete(&url).send().await.unwrap();
+    assert_eq!(resp.status(), 204);
+    let resp = client.get(format!("{url}/images")).send().await.unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+// ─── /admin/verify and moved files ───
+
+/// A server over a copy of noir-atelier, ingested, where `vincent-fedora.jpg`
+/// is tagged moody and then, on disk, renamed into another gallery, joined by
+/// a brand new shot, and two other files are rotted and edited. Returns the
+/// base URL and the fedora image's UUID.
+async fn spawn_changed_files_app(client: &Client) -> (String, String) {
+    let galleries = temp_dir("galleries");
+    copy_dir(
+        std::path::Path::new("../galleries/noir-atelier"),
+        &galleries.join("noir-atelier"),
+    );
+    let base = spawn_app_with_config(Config::new(&temp_db(), galleries.to_str().unwrap())).await;
+    ingest_noir_atelier(client, &base).await;
+
+    let (fedora, _) =
+        image_tags_by_path(client, &base, "noir-atelier/film-noir/vincent-fedora.jpg").await;
+    let moody = tag_uuid(client, &base, "moody").await;
+    client
+        .put(format!("{base}/images/{fedora}/tags"))
+        .json(&json!({ "tag_uuids": [moody] }))
+        .send()
+        .await
+        .unwrap();
+
+    let noir = galleries.join("noir-atelier");
+    // Renamed into another gallery
+    std::fs::rename(
+        noir.join("film-noir/vincent-fedora.jpg"),
+        noir.join("smoke-and-shadows/fedora-final.jpg"),
+    )
+    .unwrap();
+    // Brand new shot
+    image::RgbImage::from_pixel(64, 96, image::Rgb([200, 40, 40]))
+        .save(noir.join("film-noir/new-shot.jpg"))
+        .unwrap();
+    // Bytes flipped behind the filesystem's back: mtime unchanged
+    let rotted = noir.join("film-noir/iris-venetian-blind.jpg");
+    let mtime = std::fs::metadata(&rotted).unwrap().modified().unwrap();
+    let mut bytes = std::fs::read(&rotted).unwrap();
+    let middle = bytes.len() / 2;
+    bytes[middle] ^= 0xFF;
+    std::fs::write(&rotted, bytes).unwrap();
+    std::fs::File::options().write(true).open(&rotted).unwrap().set_modified(mtime).unwrap();
+    // Edited: contents and mtime both change
+    let edited = noir.join("film-noir/iris-cigarette-holder.jpg");
+    let mut bytes = std::fs::read(&edited).unwrap();
+    bytes.extend_from_slice(b"trailing edit");
+    std::fs::write(&edited, bytes).unwrap();
+    std::fs::File::options()
+        .write(true)
+        .open(&edited)
+        .unwrap()
+        .set_modified(mtime + std::time::Duration::from_secs(60))
+        .unwrap();
+    (base, fedora)
+}
+
+/// Runs verification over noir-atelier and returns the finished job status.
+async fn verify_noir_atelier(client: &Client, base: &str) -> Value {
+    let resp = client
+        .post(format!("{base}/admin/verify"))
+        .json(&json!({ "collection": "noir-atelier" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 202);
+    wait_for_job(client, &format!("{base}/admin/verify")).await
+}
+
+/// Sorted `(path, problem)` pairs from a verify job status.
+fn verify_problems(status: &Value) -> Vec<(String, String)> {
+    let mut found: Vec<(String, String)> = status["issues"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|i| {
+            (
+                i["path"].as_str().unwrap().to_string(),
+                i["problem"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect();
+    found.sort();
+    found
+}
+
+/// Re-ingests noir-atelier without forcing and returns the finished job status.
+async fn reingest_noir_atelier(client: &Client, base: &str) -> Value {
+    client
+        .post(format!("{base}/admin/ingest"))
+        .json(&json!({ "collection": "noir-atelier" }))
+        .send()
+        .await
+        .unwrap();
+    wait_for_job(client, &format!("{base}/admin/ingest")).await
+}
+
+#[tokio::test]
+async fn test_verify_reports_missing_corrupted_and_modified_files() {
+    let client = Client::new();
+    let (base, _) = spawn_changed_files_app(&client).await;
+
+    let status = verify_noir_atelier(&client, &base).await;
+    assert_eq!(status["total"].as_u64().unwrap(), 14);
+    assert_eq!(status["ok"].as_u64().unwrap(), 11);
+    assert_eq!(
+        verify_problems(&status),
+        [
+            ("noir-atelier/film-noir/iris-cigarette-holder.jpg".to_string(), "modified".to_string()),
+            ("noir-atelier/film-noir/iris-venetian-blind.jpg".to_string(), "corrupted".to_string()),
+            ("noir-atelier/film-noir/vincent-fedora.jpg".to_string(), "missing".to_string()),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_verify_announces_missing_original_once() {
+    let client = Client::new();
+    let (base, fedora) = spawn_changed_files_app(&client).await;
+
+    let mut events = EventReader::new(client.get(format!("{base}/events")).send().await.unwrap());
+    verify_noir_atelier(&client, &base).await;
+    // Only the missing original is announced; the image itself stays
+    let (_, kind, data) = events.next().await;
+    assert_eq!(kind, "image.missing");
+    assert_eq!(data["image_uuid"], fedora.as_str());
+    assert_eq!(data["path"], "noir-atelier/film-noir/vincent-fedora.jpg");
+
+    // and only once, however often verification runs: the next event is ingest's
+    let status = verify_noir_atelier(&client, &base).await;
+    assert_eq!(verify_problems(&status).len(), 3);
+    reingest_noir_atelier(&client, &base).await;
+    let (_, kind, _) = events.next().await;
+    assert!(kind == "image.moved" || kind == "image.added", "{kind}");
+}
+
+#[tokio::test]
+async fn test_ingest_follows_moved_file_keeping_uuid_and_tags() {
+    let client = Client::new();
+    let (base, fedora) = spawn_changed_files_app(&client).await;
+
+    let status = reingest_noir_atelier(&client, &base).await;
+    assert_eq!(status["moved"].as_u64().unwrap(), 1);
+    assert_eq!(status["discovered"].as_u64().unwrap(), 1);
+    assert_eq!(status["failed"].as_u64().unwrap(), 0);
+
+    let (moved, tags) =
+        image_tags_by_path(&client, &base, "noir-atelier/smoke-and-shadows/fedora-final.jpg").await;
+    assert_eq!(moved, fedora);
+    assert_eq!(tags, ["moody"]);
+    let detail: Value = client
+        .get(format!("{base}/images/{fedora}"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(detail["gallery"], "smoke-and-shadows");
+}
+
+#[tokio::test]
+async fn test_ingest_adds_new_file_with_dimensions_and_blurhash() {
+    let client = Client::new();
+    let (base, _) = spawn_changed_files_app(&client).await;
+    reingest_noir_atelier(&client, &base).await;
+
+    let (new_shot, _) =
+        image_tags_by_path(&client, &base, "noir-atelier/film-noir/new-shot.jpg").await;
+    let detail: Value = client
+        .get(format!("{base}/images/{new_shot}"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(detail["width"], 64);
+    assert_eq!(detail["height"], 96);
+    assert!(detail["blurhash"].is_string());
+}
+
+#[tokio::test]
+async fn test_verify_after_ingest_reports_only_content_changes() {
+    let client = Client::new();
+    let (base, _) = spawn_changed_files_app(&client).await;
+    verify_noir_atelier(&client, &base).await;
+    reingest_noir_atelier(&client, &base).await;
+
+    // The move is resolved; the content changes are still reported
+    let status = verify_noir_atelier(&client, &base).await;
+    assert_eq!(status["total"].as_u64().unwrap(), 15);
+    assert_eq!(
+        verify_problems(&status),
+        [
+            ("noir-atelier/film-noir/iris-cigarette-holder.jpg".to_string(), "modified".to_string()),
+            ("noir-atelier/film-noir/iris-venetian-blind.jpg".to_string(), "corrupted".to_string()),
+        ]
+    );
+}
+
+// ─── API keys ───
+
+/// Run `tivoli-server keys ...` against a database file.
+fn keys_cli(db_path: &str, args: &[&str]) -> std::process::Output {
+    std::process::Command::new(env!("CARGO_BIN_EXE_tivoli-server"))
+        .arg("keys")
+        .args(args)
+        .env("TIVOLI_DB_PATH", db_path)
+        .output()
+        .unwrap()
+}
+
+/// Create a key with `tivoli-server keys create` and return it.
+fn cli_key(db_path: &str, name: &str, role: &str) -> String {
+    cli_key_with(db_path, &["create", name, "--role", role])
+}
+
+/// Run `tivoli-server keys` with `args` that create a key, and return the key.
+fn cli_key_with(db_path: &str, args: &[&str]) -> String {
+    let out = keys_cli(db_path, args);
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    String::from_utf8(out.stdout).unwrap().trim().to_string()
+}
+
+/// Keys created from the command line, one per role plus a revoked admin key.
+struct CliKeys {
+    reader: String,
+    writer: String,
+    admin: String,
+    revoked: String,
+}
+
+fn seed_cli_keys(db_path: &str) -> CliKeys {
+    let keys = CliKeys {
+        reader: cli_key(db_path, "viewer app", "viewer"),
+        writer: cli_key(db_path, "tagging station", "tagger"),
+        admin: cli_key(db_path, "ops", "admin"),
+        revoked: cli_key(db_path, "old laptop", "admin"),
+    };
+    let revoked_id = keys.revoked.split('_').nth(1).unwrap();
+    assert!(keys_cli(db_path, &["revoke", revoked_id]).status.success());
+    keys
+}
+
+async fn spawn_keyed_app() -> (String, CliKeys) {
+    let db_path = temp_db();
+    let keys = seed_cli_keys(&db_path);
+    (spawn_app_with_auth(Config::new(&db_path, "../galleries")).await, keys)
+}
+
+#[test]
+fn test_keys_cli_revokes_a_key_once() {
+    let db_path = temp_db();
+    let key = cli_key(&db_path, "old laptop", "admin");
+    let id = key.split('_').nth(1).unwrap();
+    assert!(keys_cli(&db_path, &["revoke", id]).status.success());
+    assert!(!keys_cli(&db_path, &["revoke", id]).status.success());
+}
+
+#[test]
+fn test_keys_cli_rejects_unknown_role() {
+    assert!(!keys_cli(&temp_db(), &["create", "x", "--role", "root"]).status.success());
+}
+
+#[test]
+fn test_keys_cli_lists_keys_without_secrets() {
+    let db_path = temp_db();
+    let keys = seed_cli_keys(&db_path);
+    let listing = String::from_utf8(keys_cli(&db_path, &["list"]).stdout).unwrap();
+    assert_eq!(listing.lines().count(), 4);
+    assert!(listing.contains("tagging station"));
+    assert!(!listing.contains(&keys.writer));
+}
+
+#[test]
+fn test_keys_stored_only_as_hashes() {
+    let db_path = temp_db();
+    let keys = seed_cli_keys(&db_path);
+    let conn = rusqlite::Connection::open(&db_path).unwrap();
+    let hashes: Vec<String> = conn
+        .prepare("SELECT key_hash FROM api_keys")
+        .unwrap()
+        .query_map([], |row| row.get(0))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    assert!(hashes.iter().all(|h| h.len() == 64 && !h.contains(&keys.writer[4..])));
+}
+
+#[tokio::test]
+async fn test_request_without_key_rejected() {
+    let (base, _) = spawn_keyed_app().await;
+    let resp = Client::new().get(format!("{base}/collections")).send().await.unwrap();
+    assert_eq!(resp.status(), 401);
+    assert_eq!(resp.headers()["www-authenticate"], "Bearer");
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["code"], "missing_credentials");
+}
+
+#[tokio::test]
+async fn test_unknown_altered_or_revoked_key_rejected() {
+    let (base, keys) = spawn_keyed_app().await;
+    let client = Client::new();
+    let reader = &keys.reader;
+    for bad in ["tvl_nope_nope", keys.revoked.as_str(), &format!("{}x", &reader[..reader.len() - 1])] {
+        let resp = client.get(format!("{base}/collections")).bearer_auth(bad).send().await.unwrap();
+        assert_eq!(resp.status(), 401, "{bad}");
+        let body: Value = resp.json().await.unwrap();
+        assert_eq!(body["code"], "invalid_credentials");
+    }
+}
+
+#[tokio::test]
+async fn test_key_accepted_as_bearer_token_or_header() {
+    let (base, keys) = spawn_keyed_app().await;
+    let client = Client::new();
+    let resp = client.get(format!("{base}/collections")).bearer_auth(&keys.reader).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = client.get(format!("{base}/tags")).header("X-API-Key", &keys.reader).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+}
+
+#[tokio::test]
+async fn test_viewer_key_searches_but_cannot_write() {
+    let (base, keys) = spawn_keyed_app().await;
+    let client = Client::new();
+
+    // Searching is a POST but only reads
+    let resp = client
+        .post(format!("{base}/images/search"))
+        .bearer_auth(&keys.reader)
+        .json(&json!({ "filters": [] }))
+        .send()
+        .await
+        .unwrap();
+    let images: Value = resp.json().await.unwrap();
+    let image = images[0]["uuid"].as_str().unwrap();
+    let rate = |key: &str| {
+        client
+            .patch(format!("{base}/images/{image}/culling"))
+            .bearer_auth(key)
+            .json(&json!({ "rating": 4 }))
+            .send()
+    };
+    let resp = rate(&keys.reader).await.unwrap();
+    assert_eq!(resp.status(), 403);
+    let body: Value = resp.json().await.unwrap();
+    assert_eq!(body["code"], "insufficient_role");
+    assert_eq!(rate(&keys.writer).await.unwrap().status(), 204);
+}
+
+#[tokio::test]
+async fn test_admin_routes_need_admin_key() {
+    let (base, keys) = spawn_keyed_app().await;
+    let client = Client::new();
+    let resp = client.get(format!("{base}/admin/ingest")).bearer_auth(&keys.writer).send().await.unwrap();
+    assert_eq!(resp.status(), 403);
+    let resp = client.get(format!("{base}/admin/ingest")).bearer_auth(&keys.admin).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+}
+
+#[tokio::test]
+async fn test_cors_preflight_needs_no_key() {
+    let (base, _) = spawn_keyed_app().await;
+    let resp = Client::new()
+        .request(reqwest::Method::OPTIONS, format!("{base}/images/search"))
+        .header("Origin", "http://example.com")
+        .header("Access-Control-Request-Method", "POST")
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+}
+
+/// A server with an admin key from the command line and a viewer key for
+/// lumiere-studio created through `POST /keys`. Returns the base URL, the
+/// admin key and the created key's response.
+async fn spawn_key_admin_app(client: &Client) -> (String, String, Value) {
+    let db_path = temp_db();
+    let admin = cli_key(&db_path, "ops", "admin");
+    let base = spawn_app_with_auth(Config::new(&db_path, "../galleries")).await;
+
+    let resp = client
+        .post(format!("{base}/keys"))
+        .bearer_auth(&admin)
+        .json(&json!({ "name": "scanner", "collections": ["lumiere-studio"] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    (base, admin, resp.json().await.unwrap())
+}
+
+async fn collections_status(client: &Client, base: &str, key: &str) -> reqwest::StatusCode {
+    client.get(format!("{base}/collections")).bearer_auth(key).send().await.unwrap().status()
+}
+
+/// The key with `id` from `GET /keys`, checking there are two in all.
+async fn listed_key(client: &Client, base: &str, admin: &str, id: &str) -> Value {
+    let keys: Vec<Value> =
+        client.get(format!("{base}/keys")).bearer_auth(admin).send().await.unwrap().json().await.unwrap();
+    assert_eq!(keys.len(), 2);
+    keys.into_iter().find(|k| k["id"] == id).unwrap()
+}
+
+#[tokio::test]
+async fn test_key_created_over_http_works_straight_away() {
+    let client = Client::new();
+    let (base, _, created) = spawn_key_admin_app(&client).await;
+    assert_eq!(created["role"], "viewer");
+    assert_eq!(created["collections"], json!(["lumiere-studio"]));
+    assert_eq!(collections_status(&client, &base, created["key"].as_str().unwrap()).await, 200);
+}
+
+#[tokio::test]
+async fn test_keys_managed_only_by_admins() {
+    let client = Client::new();
+    let (base, _, created) = spawn_key_admin_app(&client).await;
+    let resp = client
+        .get(format!("{base}/keys"))
+        .bearer_auth(created["key"].as_str().unwrap())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+}
+
+#[tokio::test]
+async fn test_keys_listed_without_secret() {
+    let client = Client::new();
+    let (base, admin, created) = spawn_key_admin_app(&client).await;
+    let id = created["id"].as_str().unwrap();
+    assert!(listed_key(&client, &base, &admin, id).await["key"].is_null());
+}
+
+#[tokio::test]
+async fn test_key_revoked_on_running_server_straight_away() {
+    let client = Client::new();
+    let (base, admin, created) = spawn_key_admin_app(&client).await;
+    let (key, id) = (created["key"].as_str().unwrap(), created["id"].as_str().unwrap());
+
+    let revoke = || client.delete(format!("{base}/keys/{id}")).bearer_auth(&admin).send();
+    assert_eq!(revoke().await.unwrap().status(), 204);
+    assert_eq!(collections_status(&client, &base, key).await, 401);
+    assert_eq!(revoke().await.unwrap().status(), 404);
+    assert!(listed_key(&client, &base, &admin, id).await["revoked_at"].is_u64());
+}
+
+// ─── Users and sessions ───
+
+/// Run `tivoli-server users ...` against a database file, feeding `stdin`.
+fn users_cli(db_path: &str, args: &[&str], stdin: &str) -> std::process::Output {
+    use std::io::Write;
+    let mut child = std::process::Command::new(env!("CARGO_BIN_EXE_tivoli-server"))
+        .arg("users")
+        .args(args)
+        .env("TIVOLI_DB_PATH", db_path)
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+async fn login(client: &Client, base: &str, username: &str, password: &str) -> reqwest::Response {
+    client
+        .post(format!("{base}/auth/login"))
+        .json(&json!({ "username": username, "password": password }))
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn session_token(client: &Client, base: &str, username: &str, password: &str) -> String {
+    let session: Value = login(client, base, username, password).await.json().await.unwrap();
+    session["token"].as_str().unwrap().to_string()
+}
+
+/// A server with the admin `odile` created from the command line.
+async fn spawn_odile_app() -> String {
+    let db_path = temp_db();
+    let out = users_cli(&db_path, &["create", "odile", "--role", "admin"], "correct horse\n");
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    spawn_app_with_auth(Config::new(&db_path, "../galleries")).await
+}
+
+async fn post_user(client: &Client, base: &str, admin: &str, body: Value) -> reqwest::Response {
+    client.post(format!("{base}/users")).bearer_auth(admin).json(&body).send().await.unwrap()
+}
+
+/// A server where `odile` has created the tagger `remy` over HTTP. Returns
+/// the base URL, odile's session token and the created user.
+async fn spawn_users_app(client: &Client) -> (String, String, Value) {
+    let base = spawn_odile_app().await;
+    let admin = session_token(client, &base, "odile", "correct horse").await;
+    let resp = post_user(
+        client,
+        &base,
+        &admin,
+        json!({ "username": "remy", "password": "retouch-all-day", "role": "tagger" }),
+    )
+    .await;
+    assert_eq!(resp.status(), 201);
+    let remy = resp.json().await.unwrap();
+    (base, admin, remy)
+}
+
+async fn list_users(client: &Client, base: &str, admin: &str) -> Value {
+    client
+        .get(format!("{base}/users"))
+        .bearer_auth(admin)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+#[test]
+fn test_users_cli_creates_and_lists_users() {
+    let db_path = temp_db();
+    let out = users_cli(&db_path, &["create", "odile", "--role", "admin"], "correct horse\n");
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    let listing = String::from_utf8(users_cli(&db_path, &["list"], "").stdout).unwrap();
+    assert_eq!(listing.lines().count(), 1);
+    assert!(listing.contains("admin\todile"));
+}
+
+#[test]
+fn test_users_cli_rejects_short_password() {
+    let db_path = temp_db();
+    assert!(!users_cli(&db_path, &["create", "short", "--role", "admin"], "abc\n").status.success());
+}
+
+#[tokio::test]
+async fn test_login_rejects_wrong_password_and_unknown_user() {
+    let base = spawn_odile_app().await;
+    let client = Client::new();
+    for (username, password) in [("odile", "wrong password"), ("nobody", "correct horse")] {
+        let resp = login(&client, &base, username, password).await;
+        assert_eq!(resp.status(), 401);
+        let body: Value = resp.json().await.unwrap();
+        assert_eq!(body["code"], "invalid_login");
+    }
+}
+
+#[tokio::test]
+async fn test_login_ignores_username_case_and_starts_session() {
+    let base = spawn_odile_app().await;
+    let resp = login(&Client::new(), &base, "Odile", "correct horse").await;
+    assert_eq!(resp.status(), 200);
+    let session: Value = resp.json().await.unwrap();
+    assert!(session["token"].as_str().unwrap().starts_with("tvs_"));
+    assert_eq!(session["user"]["role"], "admin");
+    assert!(session["expires_at"].as_u64().unwrap() > 0);
+}
+
+#[tokio::test]
+async fn test_auth_me_describes_session_user() {
+    let base = spawn_odile_app().await;
+    let client = Client::new();
+    let admin = session_token(&client, &base, "odile", "correct horse").await;
+
+    let me: Value = client
+        .get(format!("{base}/auth/me"))
+        .bearer_auth(&admin)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(me["name"], "odile");
+    assert_eq!(me["kind"], "user");
+    assert_eq!(me["role"], "admin");
+}
+
+#[tokio::test]
+async fn test_admin_creates_enabled_users_over_http() {
+    let (_, _, remy) = spawn_users_app(&Client::new()).await;
+    assert_eq!(remy["disabled"], false);
+}
+
+#[tokio::test]
+async fn test_usernames_unique_ignoring_case() {
+    let client = Client::new();
+    let (base, admin, _) = spawn_users_app(&client).await;
+    let resp = post_user(
+        &client,
+        &base,
+        &admin,
+        json!({ "username": "REMY", "password": "retouch-all-day", "role": "viewer" }),
+    )
+    .await;
+    assert_eq!(resp.status(), 409);
+}
+
+#[tokio::test]
+async fn test_user_created_over_http_needs_long_password() {
+    let client = Client::new();
+    let base = spawn_odile_app().await;
+    let admin = session_token(&client, &base, "odile", "correct horse").await;
+    let body = json!({ "username": "vera", "password": "short", "role": "viewer" });
+    let resp = post_user(&client, &base, &admin, body).await;
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn test_users_listed_only_to_admins_and_without_hashes() {
+    let client = Client::new();
+    let (base, admin, _) = spawn_users_app(&client).await;
+    let tagger = session_token(&client, &base, "remy", "retouch-all-day").await;
+
+    let resp = client.get(format!("{base}/users")).bearer_auth(&tagger).send().await.unwrap();
+    assert_eq!(resp.status(), 403);
+    let users = list_users(&client, &base, &admin).await;
+    assert_eq!(users.as_array().unwrap().len(), 2);
+    // Password hashes never leave the server
+    assert!(users[0].get("password_hash").is_none());
+}
+
+#[tokio::test]
+async fn test_logout_ends_only_that_session() {
+    let client = Client::new();
+    let (base, admin, _) = spawn_users_app(&client).await;
+    let tagger = session_token(&client, &base, "remy", "retouch-all-day").await;
+
+    let resp = client.post(format!("{base}/auth/logout")).bearer_auth(&tagger).send().await.unwrap();
+    assert_eq!(resp.status(), 204);
+    let resp = client.get(format!("{base}/tags")).bearer_auth(&tagger).send().await.unwrap();
+    assert_eq!(resp.status(), 401);
+    let resp = client.get(format!("{base}/tags")).bearer_auth(&admin).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+}
+
+#[tokio::test]
+async fn test_user_update_with_one_invalid_field_changes_nothing() {
+    let client = Client::new();
+    let (base, admin, remy) = spawn_users_app(&client).await;
+    let remy_uuid = remy["uuid"].as_str().unwrap();
+
+    let resp = client
+        .patch(format!("{base}/users/{remy_uuid}"))
+        .bearer_auth(&admin)
+        .json(&json!({ "role": "admin", "password": "short" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let users = list_users(&client, &base, &admin).await;
+    let remy = users.as_array().unwrap().iter().find(|u| u["uuid"] == remy_uuid).unwrap();
+    assert_eq!(remy["role"], "tagger");
+}
+
+#[tokio::test]
+async fn test_disabling_user_ends_sessions_and_blocks_login() {
+    let client = Client::new();
+    let (base, admin, remy) = spawn_users_app(&client).await;
+    let tagger = session_token(&client, &base, "remy", "retouch-all-day").await;
+
+    let resp = client
+        .patch(format!("{base}/users/{}", remy["uuid"].as_str().unwrap()))
+        .bearer_auth(&admin)
+        .json(&json!({ "disabled": true }))
+        .send()
+        .await
+        .unwrap();
+    let remy: Value = resp.json().await.unwrap();
+    assert_eq!(remy["disabled"], true);
+    let resp = client.get(format!("{base}/tags")).bearer_auth(&tagger).send().await.unwrap();
+    assert_eq!(resp.status(), 401);
+    assert_eq!(login(&client, &base, "remy", "retouch-all-day").await.status(), 401);
+}
+
+// ─── Role permissions ───
+
+#[tokio::test]
+async fn test_role_permissions_for_every_route() {
+    let db_path = temp_db();
+    let keys = ["viewer", "tagger", "admin"].map(|role| (role, cli_key(&db_path, role, role)));
+    let base = spawn_app_with_auth(Config::new(&db_path, "../galleries")).await;
+    let client = Client::new();
+
+    // Every route with the least role allowed to call it. Paths name missing
+    // resources and bodies are valid but inert, so allowed calls end in 2xx,
+    // 400 or 404 without changing anything worth undoing.
+    let none = Value::Null;
+    let routes: Vec<(&str, &str, Value, &str)> = vec![
+        ("POST", "/images/search", json!({ "filters": [] }), "viewer"),
+        ("POST", "/images/search/options", json!({ "filters": [] }), "viewer"),
+        ("POST", "/images/search/timeline", json!({ "filters": [] }), "viewer"),
+        ("GET", "/images/duplicates", none.clone(), "viewer"),
+        ("PATCH", "/images/culling", json!({ "image_uuids": ["missing"], "rating": 1 }), "tagger"),
+        ("GET", "/images/missing", none.clone(), "viewer"),
+        ("GET", "/images/missing/file", none.clone(), "viewer"),
+        ("PUT", "/images/missing/tags", json!({ "tag_uuids": [] }), "tagger"),
+        ("GET", "/images/missing/tags/history", none.clone(), "viewer"),
+        ("POST", "/images/missing/tags/revert", json!({ "entry_id": 1 }), "tagger"),
+        ("POST", "/undo", none.clone(), "tagger"),
+        ("POST", "/redo", none.clone(), "tagger"),
+        ("PATCH", "/images/missing/culling", json!({ "rating": 1 }), "tagger"),
+        ("GET", "/images/missing/comments", none.clone(), "viewer"),
+        ("POST", "/images/missing/comments", json!({ "body": "x" }), "tagger"),
+        ("PATCH", "/images/missing/comments/missing", json!({ "body": "x" }), "tagger"),
+        ("DELETE", "/images/missing/comments/missing", none.clone(), "tagger"),
+        ("GET", "/collections", none.clone(), "viewer"),
+        ("GET", "/galleries", none.clone(), "viewer"),
+        ("GET", "/albums", none.clone(), "viewer"),
+        ("POST", "/albums", json!({ "name": "" }), "tagger"),
+        ("GET", "/albums/missing", none.clone(), "viewer"),
+        ("PATCH", "/albums/missing", json!({ "name": "x" }), "tagger"),
+        ("DELETE", "/albums/missing", none.clone(), "tagger"),
+        ("POST", "/albums/missing/images", json!({ "image_uuids": [] }), "tagger"),
+        ("DELETE", "/albums/missing/images/missing", none.clone(), "tagger"),
+        ("PUT", "/albums/missing/order", json!({ "image_uuids": [] }), "tagger"),
+        ("GET", "/saved-searches", none.clone(), "viewer"),
+        ("POST", "/saved-searches", json!({ "name": "", "filters": [] }), "tagger"),
+        ("GET", "/saved-searches/missing", none.clone(), "viewer"),
+        ("PUT", "/saved-searches/missing", json!({ "name": "x", "filters": [] }), "tagger"),
+        ("DELETE", "/saved-searches/missing", none.clone(), "tagger"),
+        ("GET", "/saved-searches/missing/images", none.clone(), "viewer"),
+        ("GET", "/shares", none.clone(), "tagger"),
+        ("POST", "/shares", json!({ "kind": "album", "album_uuid": "missing" }), "tagger"),
+        ("DELETE", "/shares/missing", none.clone(), "tagger"),
+        ("GET", "/models", none.clone(), "viewer"),
+        ("GET", "/tags", none.clone(), "viewer"),
+        ("POST", "/tags", json!({ "name": "", "group_uuid": "missing" }), "admin"),
+        ("PATCH", "/tags/missing", json!({ "name": "x" }), "admin"),
+        ("DELETE", "/tags/missing", none.clone(), "admin"),
+        ("POST", "/tag-groups", json!({ "name": "" }), "admin"),
+        ("PATCH", "/tag-groups/missing", json!({ "name": "x" }), "admin"),
+        ("DELETE", "/tag-groups/missing", none.clone(), "admin"),
+        ("GET", "/auth/me", none.clone(), "viewer"),
+        ("GET", "/users", none.clone(), "admin"),
+        ("POST", "/users", json!({ "username": "", "password": "", "role": "viewer" }), "admin"),
+        ("PATCH", "/users/missing", json!({ "role": "viewer" }), "admin"),
+        ("PUT", "/users/missing/collections", json!({ "collections": null }), "admin"),
+        ("GET", "/keys", none.clone(), "admin"),
+        ("POST", "/keys", json!({ "name": "" }), "admin"),
+        ("DELETE", "/keys/missing", none.clone(), "admin"),
+        ("GET", "/events", none.clone(), "viewer"),
+        ("GET", "/sync", none.clone(), "viewer"),
+        ("POST", "/sync/tags", json!({ "since": 1, "operations": [] }), "tagger"),
+        ("GET", "/audit", none.clone(), "admin"),
+        ("GET", "/admin/thumbnails", none.clone(), "admin"),
+        ("DELETE", "/admin/thumbnails?image=missing", none.clone(), "admin"),
+        ("GET", "/admin/ingest", none.clone(), "admin"),
+        ("POST", "/admin/ingest", json!({ "collection": "missing" }), "admin"),
+        ("GET", "/admin/sidecars", none.clone(), "admin"),
+        ("POST", "/admin/sidecars", json!({ "collection": "missing" }), "admin"),
+        ("GET", "/admin/verify", none.clone(), "admin"),
+        ("POST", "/admin/verify", json!({ "collection": "missing" }), "admin"),
+        ("GET", "/admin/pregenerate", none.clone(), "admin"),
+        ("POST", "/admin/pregenerate", json!({ "widths": [] }), "admin"),
+        ("POST", "/auth/logout", none.clone(), "viewer"),
+    ];
+    let rank = |role: &str| ["viewer", "tagger", "admin"].iter().position(|r| *r == role).unwrap();
+
+    for (method, path, body, least) in &routes {
+        for (role, key) in &keys {
+            let mut request = client
+                .request(method.parse().unwrap(), format!("{base}{path}"))
+                .bearer_auth(key);
+            if !body.is_null() {
+                request = request.json(body);
+            }
+            let resp = request.send().await.unwrap();
+            let status = resp.status().as_u16();
+            if rank(role) < rank(least) {
+                assert_eq!(status, 403, "{role} {method} {path}");
+                let body: Value = resp.json().await.unwrap();
+                assert_eq!(body["code"], "insufficient_role", "{role} {method} {path}");
+            } else {
+                assert!(![401, 403, 405, 422].contains(&status), "{role} {method} {path}: {status}");
+            }
+        }
+    }
+}
+
+// ─── Tag vocabulary ───
+
+/// A server with a "Props" tag group holding a cigarette-holder tag, which is
+/// on the first image. Returns the base URL, the group's, tag's and image's UUIDs.
+async fn spawn_vocabulary_app(client: &Client) -> (String, String, String, String) {
+    let base = spawn_app_with_config(Config::new(&temp_db(), "../galleries")).await;
+    let group: Value = client
+        .post(format!("{base}/tag-groups"))
+        .json(&json!({ "name": "Props" }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let group_uuid = group["uuid"].as_str().unwrap().to_string();
+    let resp = client
+        .post(format!("{base}/tags"))
+        .json(&json!({ "name": "cigarette-holder", "group_uuid": group_uuid }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let tag: Value = resp.json().await.unwrap();
+    let tag_uuid = tag["uuid"].as_str().unwrap().to_string();
+
+    let images = search(client, &base, json!([])).await;
+    let image = images[0]["uuid"].as_str().unwrap().to_string();
+    let resp = client
+        .put(format!("{base}/images/{image}/tags"))
+        .json(&json!({ "tag_uuids": [tag_uuid] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 204);
+    (base, group_uuid, tag_uuid, image)
+}
+
+/// Renames the tag to "holder" and moves it to a group other than
+/// `group_uuid`. Returns the updated tag and the group it moved to.
+async fn move_tag_out_of(client: &Client, base: &str, group_uuid: &str, tag_uuid: &str) -> (Value, Value) {
+    let groups: Value = client.get(format!("{base}/tags")).send().await.unwrap().json().await.unwrap();
+    let other_group = groups
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|g| g["uuid"] != group_uuid)
+        .unwrap()
+        .clone();
+    let resp = client
+        .patch(format!("{base}/tags/{tag_uuid}"))
+        .json(&json!({ "name": "holder", "group_uuid": other_group["uuid"] }))
+        .send()
+        .await
+        .unwrap();
+    (resp.json().await.unwrap(), other_group)
+}
+
+#[tokio::test]
+async fn test_tag_group_created_with_trimmed_unique_name() {
+    let base = spawn_app_with_config(Config::new(&temp_db(), "../galleries")).await;
+    let client = Client::new();
+
+    let resp = client
+        .post(format!("{base}/tag-groups"))
+        .json(&json!({ "name": "  Props " }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let group: Value = resp.json().await.unwrap();
+    assert_eq!(group["name"], "Props");
+    let resp = client
+        .post(format!("{base}/tag-groups"))
+        .json(&json!({ "name": "Props" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 409);
+}
+
+#[tokio::test]
+async fn test_tag_created_in_existing_group_only() {
+    let client = Client::new();
+    let (base, group_uuid, _, _) = spawn_vocabulary_app(&client).await;
+
+    let resp = client
+        .post(format!("{base}/tags"))
+        .json(&json!({ "name": "ashtray", "group_uuid": group_uuid }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let tag: Value = resp.json().await.unwrap();
+    assert_eq!(tag["group"], "Props");
+    let resp = client
+        .post(format!("{base}/tags"))
+        .json(&json!({ "name": "fedora", "group_uuid": "missing" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn test_tag_renamed_and_moved_between_groups() {
+    let client = Client::new();
+    let (base, group_uuid, tag_uuid, _) = spawn_vocabulary_app(&client).await;
+    let (tag, other_group) = move_tag_out_of(&client, &base, &group_uuid, &tag_uuid).await;
+    assert_eq!(tag["name"], "holder");
+    assert_eq!(tag["group"], other_group["name"]);
+}
+
+#[tokio::test]
+async fn test_tag_group_renamed_with_its_tags() {
+    let client = Client::new();
+    let (base, group_uuid, tag_uuid, _) = spawn_vocabulary_app(&client).await;
+    let (_, other_group) = move_tag_out_of(&client, &base, &group_uuid, &tag_uuid).await;
+
+    let resp = client
+        .patch(format!("{base}/tag-groups/{}", other_group["uuid"].as_str().unwrap()))
+        .json(&json!({ "name": "Renamed" }))
+        .send()
+        .await
+        .unwrap();
+    let renamed: Value = resp.json().await.unwrap();
+    assert_eq!(renamed["name"], "Renamed");
+    assert!(renamed["tags"].as_array().unwrap().iter().any(|t| t["name"] == "holder"));
+}
+
+#[tokio::test]
+async fn test_tag_group_deleted_only_once_empty() {
+    let client = Client::new();
+    let (base, group_uuid, tag_uuid, _) = spawn_vocabulary_app(&client).await;
+    let (_, other_group) = move_tag_out_of(&client, &base, &group_uuid, &tag_uuid).await;
+
+    let resp = client
+        .delete(format!("{base}/tag-groups/{}", other_group["uuid"].as_str().unwrap()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 409);
+    let resp = client.delete(format!("{base}/tag-groups/{group_uuid}")).send().await.unwrap();
+    assert_eq!(resp.status(), 204);
+}
+
+#[tokio::test]
+async fn test_deleting_tag_takes_it_off_every_image() {
+    let client = Client::new();
+    let (base, _, tag_uuid, image) = spawn_vocabulary_app(&client).await;
+
+    let resp = client.delete(format!("{base}/tags/{tag_uuid}")).send().await.unwrap();
+    assert_eq!(resp.status(), 204);
+    let detail: Value = client
+        .get(format!("{base}/images/{image}"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(detail["tags"].as_array().unwrap().iter().all(|t| t["uuid"] != tag_uuid.as_str()));
+    let resp = client.delete(format!("{base}/tags/{tag_uuid}")).send().await.unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+// ─── Collection access ───
+
+/// Keys limited to collections: a viewer for lumiere-studio, a tagger for
+/// raw-collective and noir-atelier, and an admin whose list is ignored.
+struct ScopedApp {
+    base: String,
+    client_key: String,
+    retoucher: String,
+    admin: String,
+    lumiere_image: String,
+    raw_image: String,
+}
+
+fn seed_scoped_keys(db_path: &str) -> [String; 3] {
+    [
+        cli_key_with(db_path, &["create", "lumiere client", "--collections", "lumiere-studio"]),
+        cli_key_with(
+            db_path,
+            &["create", "retoucher", "--collections", "raw-collective,noir-atelier", "--role", "tagger"],
+        ),
+        cli_key_with(db_path, &["create", "ops", "--role", "admin", "--collections", "lumiere-studio"]),
+    ]
+}
+
+async fn spawn_scoped_app(client: &Client) -> ScopedApp {
+    let db_path = temp_db();
+    let [client_key, retoucher, admin] = seed_scoped_keys(&db_path);
+    let base = spawn_app_with_auth(Config::new(&db_path, "../galleries")).await;
+    let everything = post_json_as(client, &base, "/images/search", &admin, json!({ "filters": [] })).await;
+    let first_in = |collection: &str| {
+        everything
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|i| i["collection"] == collection)
+            .unwrap()["uuid"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    };
+    ScopedApp {
+        lumiere_image: first_in("lumiere-studio"),
+        raw_image: first_in("raw-collective"),
+        base,
+        client_key,
+        retoucher,
+        admin,
+    }
+}
+
+async fn get_as(client: &Client, base: &str, path: &str, key: &str) -> reqwest::Response {
+    client.get(format!("{base}{path}")).bearer_auth(key).send().await.unwrap()
+}
+
+async fn get_json_as(client: &Client, base: &str, path: &str, key: &str) -> Value {
+    get_as(client, base, path, key).await.json().await.unwrap()
+}
+
+async fn post_as(client: &Client, base: &str, path: &str, key: &str, body: Value) -> reqwest::Response {
+    client.post(format!("{base}{path}")).bearer_auth(key).json(&body).send().await.unwrap()
+}
+
+async fn post_json_as(client: &Client, base: &str, path: &str, key: &str, body: Value) -> Value {
+    post_as(client, base, path, key, body).await.json().await.unwrap()
+}
+
+/// The distinct collections of a list of images, sorted.
+fn collections_of(images: &Value) -> Vec<String> {
+    let collections: std::collections::BTreeSet<String> = images
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|i| i["collection"].as_str().unwrap().to_string())
+        .collect();
+    collections.into_iter().collect()
+}
+
+/// Creates a mixed album with one lumiere-studio and one raw-collective image.
+async fn create_mixed_album(client: &Client, app: &ScopedApp) -> String {
+    let body = json!({ "name": "Mixed", "image_uuids": [app.lumiere_image, app.raw_image] });
+    let album = post_json_as(client, &app.base, "/albums", &app.admin, body).await;
+    album["uuid"].as_str().unwrap().to_string()
+}
+
+/// Creates the viewer `noir`, limited to noir-atelier, and logs in as them.
+/// Returns the created user and the session token.
+async fn create_noir_user(client: &Client, app: &ScopedApp) -> (Value, String) {
+    let body = json!({
+        "username": "noir",
+        "password": "client-password",
+        "role": "viewer",
+        "collections": ["noir-atelier"]
+    });
+    let user = post_json_as(client, &app.base, "/users", &app.admin, body).await;
+    (user, session_token(client, &app.base, "noir", "client-password").await)
+}
+
+#[test]
+fn test_keys_cli_lists_collection_limits() {
+    let db_path = temp_db();
+    seed_scoped_keys(&db_path);
+    let listing = String::from_utf8(keys_cli(&db_path, &["list"]).stdout).unwrap();
+    assert!(listing.contains("raw-collective,noir-atelier"));
+}
+
+#[tokio::test]
+async fn test_admin_sees_every_collection_whatever_its_list() {
+    let client = Client::new();
+    let app = spawn_scoped_app(&client).await;
+    let all = json!({ "filters": [] });
+    let everything = post_json_as(&client, &app.base, "/images/search", &app.admin, all).await;
+    assert_eq!(everything.as_array().unwrap().len(), 55);
+}
+
+#[tokio::test]
+async fn test_collection_and_gallery_listings_cover_visible_collections() {
+    let client = Client::new();
+    let app = spawn_scoped_app(&client).await;
+    let collections = get_json_as(&client, &app.base, "/collections", &app.client_key).await;
+    assert_eq!(collections.as_array().unwrap().len(), 1);
+    assert_eq!(collections[0]["name"], "lumiere-studio");
+    let galleries = get_json_as(&client, &app.base, "/galleries", &app.client_key).await;
+    assert_eq!(collections_of(&galleries), ["lumiere-studio"]);
+}
+
+#[tokio::test]
+async fn test_models_cover_visible_collections() {
+    let client = Client::new();
+    let app = spawn_scoped_app(&client).await;
+    let models = get_json_as(&client, &app.base, "/models", &app.client_key).await;
+    assert_eq!(models.as_array().unwrap().len(), 7);
+    let models = get_json_as(&client, &app.base, "/models?collection=raw-collective", &app.client_key).await;
+    assert!(models.as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_searches_cover_visible_collections() {
+    let client = Client::new();
+    let app = spawn_scoped_app(&client).await;
+    let all = json!({ "filters": [] });
+    let images = post_json_as(&client, &app.base, "/images/search", &app.client_key, all).await;
+    assert_eq!(images.as_array().unwrap().len(), 14);
+    assert_eq!(collections_of(&images), ["lumiere-studio"]);
+    let filters = json!({ "filters": [{ "field": "collection", "op": "eq", "value": "raw-collective" }] });
+    let images = post_json_as(&client, &app.base, "/images/search", &app.client_key, filters).await;
+    assert!(images.as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_search_options_and_timeline_cover_visible_collections() {
+    let client = Client::new();
+    let app = spawn_scoped_app(&client).await;
+    let all = json!({ "filters": [] });
+    let options =
+        post_json_as(&client, &app.base, "/images/search/options", &app.client_key, all.clone()).await;
+    assert_eq!(options["image_count"], 14);
+    assert_eq!(options["collections"], json!(["lumiere-studio"]));
+    assert!(options["models"].as_array().unwrap().iter().all(|m| m["collection"] == "lumiere-studio"));
+    let timeline = post_json_as(&client, &app.base, "/images/search/timeline", &app.client_key, all).await;
+    let buckets = timeline["buckets"].as_array().unwrap();
+    let dated: u64 = buckets.iter().map(|b| b["image_count"].as_u64().unwrap()).sum();
+    assert_eq!(dated + timeline["undated_count"].as_u64().unwrap(), 14);
+}
+
+#[tokio::test]
+async fn test_duplicates_cover_visible_collections() {
+    let client = Client::new();
+    let app = spawn_scoped_app(&client).await;
+    let clusters = get_json_as(&client, &app.base, "/images/duplicates", &app.client_key).await;
+    for cluster in clusters.as_array().unwrap() {
+        assert_eq!(collections_of(&cluster["images"]), ["lumiere-studio"]);
+    }
+}
+
+#[tokio::test]
+async fn test_hidden_images_not_found() {
+    let client = Client::new();
+    let app = spawn_scoped_app(&client).await;
+    let raw_image = &app.raw_image;
+    for path in [
+        format!("/images/{raw_image}"),
+        format!("/images/{raw_image}/file"),
+        format!("/images/{raw_image}/file?w=200"),
+        format!("/images/{raw_image}/comments"),
+    ] {
+        assert_eq!(get_as(&client, &app.base, &path, &app.client_key).await.status(), 404, "{path}");
+    }
+    let path = format!("/images/{}/file?w=200", app.lumiere_image);
+    assert_eq!(get_as(&client, &app.base, &path, &app.client_key).await.status(), 200);
+}
+
+#[tokio::test]
+async fn test_hidden_images_cannot_be_changed() {
+    let client = Client::new();
+    let app = spawn_scoped_app(&client).await;
+    let rate = |image: &str| {
+        client
+            .patch(format!("{}/images/{image}/culling", app.base))
+            .bearer_auth(&app.retoucher)
+            .json(&json!({ "rating": 3 }))
+            .send()
+    };
+    assert_eq!(rate(&app.lumiere_image).await.unwrap().status(), 404);
+    assert_eq!(rate(&app.raw_image).await.unwrap().status(), 204);
+}
+
+#[tokio::test]
+async fn test_albums_show_only_visible_members() {
+    let client = Client::new();
+    let app = spawn_scoped_app(&client).await;
+    let album_uuid = create_mixed_album(&client, &app).await;
+
+    let albums = get_json_as(&client, &app.base, "/albums", &app.client_key).await;
+    assert_eq!(albums[0]["image_count"], 1);
+    let seen = get_json_as(&client, &app.base, &format!("/albums/{album_uuid}"), &app.client_key).await;
+    assert_eq!(seen["images"].as_array().unwrap().len(), 1);
+    assert_eq!(seen["images"][0]["uuid"], app.lumiere_image.as_str());
+}
+
+#[tokio::test]
+async fn test_albums_changed_only_by_callers_seeing_every_member() {
+    let client = Client::new();
+    let app = spawn_scoped_app(&client).await;
+    let album_uuid = create_mixed_album(&client, &app).await;
+    let base = &app.base;
+
+    let resp = client
+        .put(format!("{base}/albums/{album_uuid}/order"))
+        .bearer_auth(&app.retoucher)
+        .json(&json!({ "image_uuids": [app.raw_image] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+    let resp =
+        client.delete(format!("{base}/albums/{album_uuid}")).bearer_auth(&app.retoucher).send().await.unwrap();
+    assert_eq!(resp.status(), 403);
+    let full = get_json_as(&client, base, &format!("/albums/{album_uuid}"), &app.admin).await;
+    assert_eq!(full["images"].as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_albums_without_visible_members_hidden() {
+    let client = Client::new();
+    let app = spawn_scoped_app(&client).await;
+    let body = json!({ "name": "Lumiere only", "image_uuids": [app.lumiere_image] });
+    let hidden = post_json_as(&client, &app.base, "/albums", &app.admin, body).await;
+    let hidden_uuid = hidden["uuid"].as_str().unwrap();
+
+    let albums = get_json_as(&client, &app.base, "/albums", &app.retoucher).await;
+    assert!(albums.as_array().unwrap().iter().all(|a| a["uuid"] != hidden_uuid));
+    let path = format!("/albums/{hidden_uuid}");
+    assert_eq!(get_as(&client, &app.base, &path, &app.retoucher).await.status(), 404);
+}
+
+#[tokio::test]
+async fn test_saved_searches_naming_hidden_collections_hidden() {
+    let client = Client::new();
+    let app = spawn_scoped_app(&client).await;
+    let filters = json!([{ "field": "collection", "op": "eq", "value": "lumiere-studio" }]);
+    let body = json!({ "name": "Theirs", "filters": filters });
+
+    let resp = post_as(&client, &app.base, "/saved-searches", &app.retoucher, body.clone()).await;
+    assert_eq!(resp.status(), 403);
+    let saved = post_json_as(&client, &app.base, "/saved-searches", &app.admin, body).await;
+    let path = format!("/saved-searches/{}", saved["uuid"].as_str().unwrap());
+    assert_eq!(get_as(&client, &app.base, &path, &app.client_key).await.status(), 200);
+    assert_eq!(get_as(&client, &app.base, &path, &app.retoucher).await.status(), 404);
+    let searches = get_json_as(&client, &app.base, "/saved-searches", &app.retoucher).await;
+    assert!(searches.as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_similarity_reference_must_be_visible() {
+    let client = Client::new();
+    let app = spawn_scoped_app(&client).await;
+    let similar = |uuid: &str| json!({ "filters": [{ "field": "similar_to", "op": "eq", "value": uuid }] });
+    let resp = post_as(&client, &app.base, "/images/search", &app.retoucher, similar(&app.lumiere_image)).await;
+    assert_eq!(resp.status(), 404);
+    let resp = post_as(&client, &app.base, "/images/search", &app.retoucher, similar(&app.raw_image)).await;
+    assert_eq!(resp.status(), 200);
+}
+
+#[tokio::test]
+async fn test_user_collections_limit_their_sessions() {
+    let client = Client::new();
+    let app = spawn_scoped_app(&client).await;
+    let (user, token) = create_noir_user(&client, &app).await;
+    assert_eq!(user["collections"], json!(["noir-atelier"]));
+
+    let me = get_json_as(&client, &app.base, "/auth/me", &token).await;
+    assert_eq!(me["collections"], json!(["noir-atelier"]));
+    let images = post_json_as(&client, &app.base, "/images/search", &token, json!({ "filters": [] })).await;
+    assert_eq!(collections_of(&images), ["noir-atelier"]);
+}
+
+#[tokio::test]
+async fn test_admin_lifts_user_collection_limit() {
+    let client = Client::new();
+    let app = spawn_scoped_app(&client).await;
+    let (user, token) = create_noir_user(&client, &app).await;
+
+    let resp = client
+        .put(format!("{}/users/{}/collections", app.base, user["uuid"].as_str().unwrap()))
+        .bearer_auth(&app.admin)
+        .json(&json!({ "collections": null }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.json::<Value>().await.unwrap()["collections"], Value::Null);
+    let images = post_json_as(&client, &app.base, "/images/search", &token, json!({ "filters": [] })).await;
+    assert_eq!(images.as_array().unwrap().len(), 55);
+}
+
+// ─── Share links ───
+
+/// A watermarking server with an unrestricted tagger key `studio`, a tagger
+/// limited to raw-collective and an admin, where `studio` has shared
+/// bridal-collection as a proof with downloads off.
+struct ShareApp {
+    base: String,
+    tagger: String,
+    retoucher: String,
+    admin: String,
+    share: Value,
+    token: String,
+    /// The first image in the shared gallery.
+    image: String,
+}
+
+async fn spawn_share_app(client: &Client) -> ShareApp {
+    let db_path = temp_db();
+    let tagger = cli_key(&db_path, "studio", "tagger");
+    let retoucher =
+        cli_key_with(&db_path, &["create", "retoucher", "--role", "tagger", "--collections", "raw-collective"]);
+    let admin = cli_key(&db_path, "ops", "admin");
+    let mut config = Config::new(&db_path, "../galleries");
+    config.thumbnail_cache_dir = Some(temp_dir("shares"));
+    config.watermark = Some(WatermarkConfig::new(WatermarkOverlay::Text("Proof".into())));
+    let base = spawn_app_with_auth(config).await;
+
+    let resp = post_as(
+        client,
+        &base,
+        "/shares",
+        &tagger,
+        json!({ "kind": "gallery", "collection": "lumiere-studio", "gallery": "bridal-collection",
+                "allow_download": false, "watermark": true }),
+    )
+    .await;
+    assert_eq!(resp.status(), 201);
+    let share: Value = resp.json().await.unwrap();
+    let token = share["token"].as_str().unwrap().to_string();
+    let view = share_view(client, &base, &token).await;
+    let image = view["images"][0]["uuid"].as_str().unwrap().to_string();
+    ShareApp { base, tagger, retoucher, admin, share, token, image }
+}
+
+async fn share_view(client: &Client, base: &str, token: &str) -> Value {
+    client.get(format!("{base}/share/{token}")).send().await.unwrap().json().await.unwrap()
+}
+
+async fn share_file(client: &Client, base: &str, token: &str, uuid: &str, query: &str) -> reqwest::Response {
+    client.get(format!("{base}/share/{token}/images/{uuid}/file{query}")).send().await.unwrap()
+}
+
+async fn first_in_gallery(client: &Client, app: &ShareApp, gallery: &str) -> String {
+    let filters = json!([{ "field": "gallery", "op": "eq", "value": gallery }]);
+    let body = json!({ "filters": filters });
+    let images = post_json_as(client, &app.base, "/images/search", &app.tagger, body).await;
+    images[0]["uuid"].as_str().unwrap().to_string()
+}
+
+/// `studio` makes a "Selects" album of a film-noir and a street-fashion
+/// image, which the raw-collective retoucher then shares. Returns the album,
+/// the street-fashion image and the retoucher's share.
+async fn create_album_share(client: &Client, app: &ShareApp) -> (Value, String, Value) {
+    let noir = first_in_gallery(client, app, "film-noir").await;
+    let raw = first_in_gallery(client, app, "street-fashion").await;
+    let body = json!({ "name": "Selects", "image_uuids": [noir, raw] });
+    let album = post_json_as(client, &app.base, "/albums", &app.tagger, body).await;
+    let body = json!({ "kind": "album", "album_uuid": album["uuid"] });
+    let resp = post_as(client, &app.base, "/shares", &app.retoucher, body).await;
+    assert_eq!(resp.status(), 201);
+    (album, raw, resp.json().await.unwrap())
+}
+
+/// `studio` saves a search for noir-atelier and shares it. Returns the token.
+async fn create_saved_search_share(client: &Client, app: &ShareApp) -> String {
+    let body =
+        json!({ "name": "Noir", "filters": [{ "field": "collection", "op": "eq", "value": "noir-atelier" }] });
+    let saved = post_json_as(client, &app.base, "/saved-searches", &app.tagger, body).await;
+    let body = json!({ "kind": "saved_search", "saved_search_uuid": saved["uuid"] });
+    let share = post_json_as(client, &app.base, "/shares", &app.tagger, body).await;
+    share["token"].as_str().unwrap().to_string()
+}
+
+async fn list_shares(client: &Client, base: &str, key: &str) -> Value {
+    get_json_as(client, base, "/shares", key).await
+}
+
+async fn revoke_share(client: &Client, base: &str, key: &str, id: &str) -> reqwest::StatusCode {
+    client.delete(format!("{base}/shares/{id}")).bearer_auth(key).send().await.unwrap().status()
+}
+
+/// The admin creates `proofer`, a tagger limited to raw-collective, who shares
+/// street-fashion. Returns the user's URL, the share token and the first
+/// street-fashion image.
+async fn create_proofer_share(client: &Client, app: &ShareApp) -> (String, String, String) {
+    let body = json!({ "username": "proofer", "password": "proofer-password", "role": "tagger",
+                       "collections": ["raw-collective"] });
+    let user = post_json_as(client, &app.base, "/users", &app.admin, body).await;
+    let user_url = format!("{}/users/{}", app.base, user["uuid"].as_str().unwrap());
+    let session = session_token(client, &app.base, "proofer", "proofer-password").await;
+    let body = json!({ "kind": "gallery", "collection": "raw-collective", "gallery": "street-fashion" });
+    let share = post_json_as(client, &app.base, "/shares", &session, body).await;
+    let raw = first_in_gallery(client, app, "street-fashion").await;
+    (user_url, share["token"].as_str().unwrap().to_string(), raw)
+}
+
+#[tokio::test]
+async fn test_share_created_with_its_path() {
+    let app = spawn_share_app(&Client::new()).await;
+    assert_eq!(app.share["kind"], "gallery");
+    assert_eq!(app.share["created_by"], "studio");
+    assert!(app.share["revoked_at"].is_null());
+    assert_eq!(app.share["path"], format!("/share/{}", app.token));
+}
+
+#[tokio::test]
+async fn test_share_viewed_without_credentials() {
+    let client = Client::new();
+    let app = spawn_share_app(&client).await;
+
+    let resp = client.get(format!("{}/share/{}", app.base, app.token)).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let view: Value = resp.json().await.unwrap();
+    assert_eq!(view["name"], "bridal-collection");
+    assert_eq!(view["kind"], "gallery");
+    assert_eq!(view["allow_download"], false);
+    assert_eq!(view["watermark"], true);
+    assert_eq!(view["images"].as_array().unwrap().len(), 5);
+}
+
+#[tokio::test]
+async fn test_altered_share_token_not_found() {
+    let client = Client::new();
+    let app = spawn_share_app(&client).await;
+    let token = &app.token;
+
+    let (payload, _) = token.rsplit_once('.').unwrap();
+    let forged_expiry = token.replacen(&format!(".{}.", app.share["expires_at"]), ".9999999999.", 1);
+    for bad in [format!("{payload}.AAAA"), forged_expiry, "nonsense".into()] {
+        let resp = client.get(format!("{}/share/{bad}", app.base)).send().await.unwrap();
+        assert_eq!(resp.status(), 404, "{bad}");
+    }
+}
+
+#[tokio::test]
+async fn test_share_without_downloads_serves_resized_copies_only() {
+    let client = Client::new();
+    let app = spawn_share_app(&client).await;
+
+    let resp = share_file(&client, &app.base, &app.token, &app.image, "?w=200").await;
+    assert_eq!(resp.status(), 200);
+    assert_eq!(resp.headers()["content-type"], "image/jpeg");
+    assert_eq!(share_file(&client, &app.base, &app.token, &app.image, "").await.status(), 403);
+}
+
+#[tokio::test]
+async fn test_share_serves_only_its_own_images() {
+    let client = Client::new();
+    let app = spawn_share_app(&client).await;
+    let outside = first_in_gallery(&client, &app, "film-noir").await;
+    assert_eq!(share_file(&client, &app.base, &app.token, &outside, "?w=200").await.status(), 404);
+}
+
+#[tokio::test]
+async fn test_album_share_shows_only_what_its_creator_sees() {
+    let client = Client::new();
+    let app = spawn_share_app(&client).await;
+    let (_, raw, album_share) = create_album_share(&client, &app).await;
+    let album_token = album_share["token"].as_str().unwrap();
+
+    let view = share_view(&client, &app.base, album_token).await;
+    assert_eq!(view["name"], "Selects");
+    assert_eq!(view["images"].as_array().unwrap().len(), 1);
+    assert_eq!(view["images"][0]["uuid"], raw.as_str());
+    assert_eq!(share_file(&client, &app.base, album_token, &raw, "").await.status(), 200);
+}
+
+#[tokio::test]
+async fn test_saved_search_share_runs_on_every_visit() {
+    let client = Client::new();
+    let app = spawn_share_app(&client).await;
+    let saved_token = create_saved_search_share(&client, &app).await;
+    let view = share_view(&client, &app.base, &saved_token).await;
+    assert_eq!(view["images"].as_array().unwrap().len(), 14);
+}
+
+#[tokio::test]
+async fn test_share_rejects_missing_targets_and_short_expiry() {
+    let client = Client::new();
+    let app = spawn_share_app(&client).await;
+    for body in [
+        json!({ "kind": "gallery", "collection": "lumiere-studio", "gallery": "missing" }),
+        json!({ "kind": "album", "album_uuid": "missing" }),
+    ] {
+        assert_eq!(post_as(&client, &app.base, "/shares", &app.tagger, body).await.status(), 404);
+    }
+    let body = json!({ "name": "Selects", "image_uuids": [app.image] });
+    let album = post_json_as(&client, &app.base, "/albums", &app.tagger, body).await;
+    assert!(album["uuid"].is_string());
+    let body = json!({ "kind": "album", "album_uuid": album["uuid"], "expires_in": 10 });
+    assert_eq!(post_as(&client, &app.base, "/shares", &app.tagger, body).await.status(), 400);
+}
+
+#[tokio::test]
+async fn test_restricted_callers_manage_only_their_own_shares() {
+    let client = Client::new();
+    let app = spawn_share_app(&client).await;
+    create_album_share(&client, &app).await;
+    create_saved_search_share(&client, &app).await;
+
+    assert_eq!(list_shares(&client, &app.base, &app.tagger).await.as_array().unwrap().len(), 3);
+    assert_eq!(list_shares(&client, &app.base, &app.retoucher).await.as_array().unwrap().len(), 1);
+    let id = app.share["id"].as_str().unwrap();
+    assert_eq!(revoke_share(&client, &app.base, &app.retoucher, id).await, 404);
+    assert_eq!(revoke_share(&client, &app.base, &app.tagger, id).await, 204);
+    assert_eq!(revoke_share(&client, &app.base, &app.tagger, id).await, 204);
+}
+
+#[tokio::test]
+async fn test_revoked_share_gone() {
+    let client = Client::new();
+    let app = spawn_share_app(&client).await;
+    let id = app.share["id"].as_str().unwrap();
+    assert_eq!(revoke_share(&client, &app.base, &app.tagger, id).await, 204);
+
+    let resp = client.get(format!("{}/share/{}", app.base, app.token)).send().await.unwrap();
+    assert_eq!(resp.status(), 410);
+    assert_eq!(share_file(&client, &app.base, &app.token, &app.image, "?w=200").await.status(), 410);
+    let listed = list_shares(&client, &app.base, &app.tagger).await;
+    let revoked = listed.as_array().unwrap().iter().find(|s| s["id"] == id).unwrap();
+    assert!(revoked["revoked_at"].is_u64());
+}
+
+#[tokio::test]
+async fn test_share_follows_its_creators_current_access() {
+    let client = Client::new();
+    let app = spawn_share_app(&client).await;
+    let (user_url, user_token, raw) = create_proofer_share(&client, &app).await;
+
+    let view = share_view(&client, &app.base, &user_token).await;
+    assert!(!view["images"].as_array().unwrap().is_empty());
+    assert_eq!(share_file(&client, &app.base, &user_token, &raw, "?w=200").await.status(), 200);
+    let set_collections = client
+        .put(format!("{user_url}/collections"))
+        .bearer_auth(&app.admin)
+        .json(&json!({ "collections": ["noir-atelier"] }))
+        .send();
+    assert_eq!(set_collections.await.unwrap().status(), 200);
+    let view = share_view(&client, &app.base, &user_token).await;
+    assert!(view["images"].as_array().unwrap().is_empty());
+    assert_eq!(share_file(&client, &app.base, &user_token, &raw, "?w=200").await.status(), 404);
+}
+
+#[tokio::test]
+async fn test_share_stops_with_its_creators_account() {
+    let client = Client::new();
+    let app = spawn_share_app(&client).await;
+    let (user_url, user_token, _) = create_proofer_share(&client, &app).await;
+
+    let disable = client.patch(&user_url).bearer_auth(&app.admin).json(&json!({ "disabled": true })).send();
+    assert_eq!(disable.await.unwrap().status(), 200);
+    let resp = client.get(format!("{}/share/{user_token}", app.base)).send().await.unwrap();
+    assert_eq!(resp.status(), 410);
+}
+
+// ─── Watermarks ───
+
+/// Mean absolute per-channel difference between two same-sized images over
+/// the given quadrant (0 = top left, 3 = bottom right).
+fn quadrant_difference(a: &image::RgbImage, b: &image::RgbImage, quadrant: u32) -> f64 {
+    let (w, h) = (a.width() / 2, a.height() / 2);
+    let (x0, y0) = ((quadrant % 2) * w, (quadrant / 2) * h);
+    let mut total = 0u64;
+    for y in y0..y0 + h {
+        for x in x0..x0 + w {
+            for (p, q) in a.get_pixel(x, y).0.iter().zip(b.get_pixel(x, y).0) {
+                total += p.abs_diff(q) as u64;
+            }
+        }
+    }
+    total as f64 / (w * h * 3) as f64
+}
+
+/// A server stamping a bottom-right text mark on images served to viewers,
+/// with a key per role and the first sunset-session image.
+struct WatermarkApp {
+    base: String,
+    viewer: String,
+    tagger: String,
+    admin: String,
+    uuid: String,
+}
+
+async fn spawn_watermark_app(client: &Client) -> WatermarkApp {
+    let db_path = temp_db();
+    let [viewer, tagger, admin] = ["viewer", "tagger", "admin"].map(|role| cli_key(&db_path, role, role));
+    let mut config = Config::new(&db_path, "../galleries");
+    config.thumbnail_cache_dir = Some(temp_dir("watermark"));
+    config.watermark = Some(WatermarkConfig {
+        overlay: WatermarkOverlay::Text("Proof (c) Tivoli".into()),
+        position: WatermarkPosition::BottomRight,
+        opacity: 0.8,
+        scale: 0.4,
+    });
+    config.watermark_roles = vec![Role::Viewer];
+    let base = spawn_app_with_auth(config).await;
+    let body = json!({ "filters": [{ "field": "gallery", "op": "eq", "value": "sunset-session" }] });
+    let images = post_json_as(client, &base, "/images/search", &tagger, body).await;
+    let uuid = images[0]["uuid"].as_str().unwrap().to_string();
+    WatermarkApp { base, viewer, tagger, admin, uuid }
+}
+
+async fn fetch_bytes(client: &Client, base: &str, path: &str, key: Option<&str>) -> Vec<u8> {
+    let mut request = client.get(format!("{base}{path}"));
+    if let Some(key) = key {
+        request = request.bearer_auth(key);
+    }
+    let resp = request.send().await.unwrap();
+    assert_eq!(resp.status(), 200, "{path}");
+    resp.bytes().await.unwrap().to_vec()
+}
+
+fn decode_rgb(bytes: &[u8]) -> image::RgbImage {
+    image::load_from_memory(bytes).unwrap().to_rgb8()
+}
+
+/// Shares sunset-session as the tagger, with or without the mark, and
+/// returns the link's path.
+async fn watermark_share(client: &Client, app: &WatermarkApp, watermark: bool) -> String {
+    let body = json!({
+        "kind": "gallery",
+        "collection": "golden-hour-photo",
+        "gallery": "sunset-session",
+        "watermark": watermark
+    });
+    let share = post_json_as(client, &app.base, "/shares", &app.tagger, body).await;
+    share["path"].as_str().unwrap().to_string()
+}
+
+#[tokio::test]
+async fn test_watermark_stamped_for_configured_roles_in_its_corner() {
+    let client = Client::new();
+    let app = spawn_watermark_app(&client).await;
+    let path = format!("/images/{}/file?w=400", app.uuid);
+
+    let clean = decode_rgb(&fetch_bytes(&client, &app.base, &path, Some(&app.tagger)).await);
+    let marked = decode_rgb(&fetch_bytes(&client, &app.base, &path, Some(&app.viewer)).await);
+    assert_eq!(clean.dimensions(), marked.dimensions());
+    assert!(quadrant_difference(&clean, &marked, 3) > 1.0);
+    assert!(quadrant_difference(&clean, &marked, 0) < 0.1);
+}
+
+#[tokio::test]
+async fn test_watermarked_original_reencoded_with_the_mark() {
+    let client = Client::new();
+    let app = spawn_watermark_app(&client).await;
+    let path = format!("/images/{}/file", app.uuid);
+
+    let original = fetch_bytes(&client, &app.base, &path, Some(&app.tagger)).await;
+    let marked_full = fetch_bytes(&client, &app.base, &path, Some(&app.viewer)).await;
+    assert_ne!(original, marked_full);
+    let (original_img, marked_full_img) = (decode_rgb(&original), decode_rgb(&marked_full));
+    assert_eq!(original_img.dimensions(), marked_full_img.dimensions());
+    assert!(quadrant_difference(&original_img, &marked_full_img, 3) > 1.0);
+}
+
+#[tokio::test]
+async fn test_watermarked_variants_cached_beside_clean_ones() {
+    let client = Client::new();
+    let app = spawn_watermark_app(&client).await;
+    let resized = format!("/images/{}/file?w=400", app.uuid);
+    let full = format!("/images/{}/file", app.uuid);
+
+    let clean = fetch_bytes(&client, &app.base, &resized, Some(&app.tagger)).await;
+    let marked = fetch_bytes(&client, &app.base, &resized, Some(&app.viewer)).await;
+    fetch_bytes(&client, &app.base, &full, Some(&app.tagger)).await;
+    fetch_bytes(&client, &app.base, &full, Some(&app.viewer)).await;
+    assert_eq!(fetch_bytes(&client, &app.base, &resized, Some(&app.viewer)).await, marked);
+    assert_eq!(fetch_bytes(&client, &app.base, &resized, Some(&app.tagger)).await, clean);
+    let stats = get_json_as(&client, &app.base, "/admin/thumbnails", &app.admin).await;
+    assert_eq!(stats["entry_count"], 3);
+    assert_eq!(stats["renders"], 3);
+}
+
+#[tokio::test]
+async fn test_share_links_stamp_only_when_asked() {
+    let client = Client::new();
+    let app = spawn_watermark_app(&client).await;
+    let (resized, full) = (format!("/images/{}/file?w=400", app.uuid), format!("/images/{}/file", app.uuid));
+    let clean = fetch_bytes(&client, &app.base, &resized, Some(&app.tagger)).await;
+    let marked = fetch_bytes(&client, &app.base, &resized, Some(&app.viewer)).await;
+    let original = fetch_bytes(&client, &app.base, &full, Some(&app.tagger)).await;
+
+    let marked_share = watermark_share(&client, &app, true).await;
+    let clean_share = watermark_share(&client, &app, false).await;
+    assert_eq!(fetch_bytes(&client, &app.base, &format!("{marked_share}{resized}"), None).await, marked);
+    assert_eq!(fetch_bytes(&client, &app.base, &format!("{clean_share}{resized}"), None).await, clean);
+    assert_eq!(fetch_bytes(&client, &app.base, &format!("{clean_share}{full}"), None).await, original);
+}
+
+#[tokio::test]
+async fn test_share_cannot_ask_for_watermark_when_none_configured() {
+    let db_path = temp_db();
+    let tagger = cli_key(&db_path, "tagger", "tagger");
+    let base = spawn_app_with_auth(Config::new(&db_path, "../galleries")).await;
+    let body = json!({
+        "kind": "gallery", "collection": "golden-hour-photo", "gallery": "sunset-session", "watermark": true
+    });
+    assert_eq!(post_as(&Client::new(), &base, "/shares", &tagger, body).await.status(), 400);
+}
+
+#[tokio::test]
+async fn test_watermark_roles_without_watermark_rejected() {
+    let mut config = Config::new(&temp_db(), "../galleries");
+    config.watermark_roles = vec![Role::Viewer];
+    let err = tivoli_server::build_app_with_config(&config).err().unwrap();
+    assert!(err.contains("TIVOLI_WATERMARK_ROLES"), "{err}");
+}
+
+#[tokio::test]
+async fn test_missing_watermark_image_rejected() {
+    let mut config = Config::new(&temp_db(), "../galleries");
+    config.watermark_roles = vec![Role::Viewer];
+    config.watermark = Some(WatermarkConfig::new(WatermarkOverlay::Image("missing.png".into())));
+    let err = tivoli_server::build_app_with_config(&config).err().unwrap();
+    assert!(err.starts_with("Failed to load watermark"), "{err}");
+}
+
+#[test]
+fn test_misspelt_watermark_role_stops_startup() {
+    // A misspelt role would otherwise get clean originals
+    let out = std::process::Command::new(env!("CARGO_BIN_EXE_tivoli-server"))
+        .env("TIVOLI_DB_PATH", temp_db())
+        .env("TIVOLI_WATERMARK_TEXT", "proof")
+        .env("TIVOLI_WATERMARK_ROLES", "viewer,veiwer")
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("unknown role 'veiwer'"));
+}
+
+// ─── Audit log ───
+
+/// A server with a tagger key `station` and an admin key `ops`, plus the
+/// film-noir image UUIDs and two tag UUIDs to edit them with.
+struct AuditApp {
+    base: String,
+    tagger: String,
+    admin: String,
+    tagger_id: String,
+    uuids: Vec<String>,
+    tags: Vec<String>,
+}
+
+async fn spawn_audit_app(client: &Client) -> AuditApp {
+    let db_path = temp_db();
+    let tagger = cli_key(&db_path, "station", "tagger");
+    let admin = cli_key(&db_path, "ops", "admin");
+    let tagger_id = tagger.split('_').nth(1).unwrap().to_string();
+    let base = spawn_app_with_auth(Config::new(&db_path, "../galleries")).await;
+
+    let body = json!({ "filters": [{"field": "gallery", "op": "eq", "value": "film-noir"}] });
+    let images = post_json_as(client, &base, "/images/search", &tagger, body).await;
+    let uuids = result_uuids(images);
+    let groups = get_json_as(client, &base, "/tags", &tagger).await;
+    let tags: Vec<String> = groups
+        .as_array()
+        .unwrap()
+        .iter()
+        .flat_map(|g| g["tags"].as_array().unwrap())
+        .map(|t| t["uuid"].as_str().unwrap().to_string())
+        .take(2)
+        .collect();
+    AuditApp { base, tagger, admin, tagger_id, uuids, tags }
+}
+
+/// `GET /audit?{query}` as the admin.
+async fn audit(client: &Client, app: &AuditApp, query: &str) -> Vec<Value> {
+    let resp = get_as(client, &app.base, &format!("/audit?{query}"), &app.admin).await;
+    assert_eq!(resp.status(), 200, "{query}");
+    resp.json().await.unwrap()
+}
+
+/// Replaces the first image's tags with both tags as the tagger, under the
+/// request ID `retag-1`.
+async fn audited_retag(client: &Client, app: &AuditApp) -> reqwest::Response {
+    client
+        .put(format!("{}/images/{}/tags", app.base, app.uuids[0]))
+        .bearer_auth(&app.tagger)
+        .header("X-Request-Id", "retag-1")
+        .json(&json!({ "tag_uuids": app.tags }))
+        .send()
+        .await
+        .unwrap()
+}
+
+/// Rates the first three images 5 in one bulk edit as the tagger, and returns
+/// the generated request ID.
+async fn audited_bulk_rating(client: &Client, app: &AuditApp) -> String {
+    let resp = client
+        .patch(format!("{}/images/culling", app.base))
+        .bearer_auth(&app.tagger)
+        .json(&json!({ "image_uuids": &app.uuids[..3], "rating": 5 }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    resp.headers()["x-request-id"].to_str().unwrap().to_string()
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs()
+}
+
+#[tokio::test]
+async fn test_audit_records_tag_replacement_under_request_id() {
+    let client = Client::new();
+    let app = spawn_audit_app(&client).await;
+    let resp = audited_retag(&client, &app).await;
+    assert_eq!(resp.status(), 204);
+    assert_eq!(resp.headers()["x-request-id"], "retag-1");
+
+    let entries = audit(&client, &app, &format!("image={}", app.uuids[0])).await;
+    assert_eq!(entries.len(), 1, "{entries:?}");
+    let entry = &entries[0];
+    assert_eq!(entry["action"], "image.tags");
+    assert_eq!(entry["actor"], "station");
+    assert_eq!(entry["actor_id"], format!("key:{}", app.tagger_id));
+    assert_eq!(entry["request_id"], "retag-1");
+    assert_eq!(entry["target_kind"], "image");
+    let mut after: Vec<&str> =
+        entry["after"].as_array().unwrap().iter().map(|t| t["uuid"].as_str().unwrap()).collect();
+    let mut expected: Vec<&str> = app.tags.iter().map(String::as_str).collect();
+    after.sort();
+    expected.sort();
+    assert_eq!(after, expected);
+    assert!(entry["before"].is_array());
+    assert_ne!(entry["before"], entry["after"]);
+}
+
+#[tokio::test]
+async fn test_audit_skips_replacement_that_changes_nothing() {
+    let client = Client::new();
+    let app = spawn_audit_app(&client).await;
+    audited_retag(&client, &app).await;
+    assert_eq!(audited_retag(&client, &app).await.status(), 204);
+
+    let entries = audit(&client, &app, &format!("image={}", app.uuids[0])).await;
+    assert_eq!(entries.len(), 1, "{entries:?}");
+}
+
+#[tokio::test]
+async fn test_audit_records_bulk_edit_per_image_under_one_request_id() {
+    let client = Client::new();
+    let app = spawn_audit_app(&client).await;
+    let request_id = audited_bulk_rating(&client, &app).await;
+    assert!(!request_id.is_empty());
+
+    // Tests that share the sample database leave entries in it, so count by actor
+    let culling = audit(&client, &app, "action=image.culling&user=station").await;
+    assert_eq!(culling.len(), 3);
+    assert!(culling.iter().all(|e| e["request_id"] == request_id.as_str()));
+    assert!(culling.iter().all(|e| e["before"]["rating"] == 0 && e["after"]["rating"] == 5));
+}
+
+#[tokio::test]
+async fn test_audit_actions_filter_by_prefix_newest_first() {
+    let client = Client::new();
+    let app = spawn_audit_app(&client).await;
+    audited_retag(&client, &app).await;
+    audited_bulk_rating(&client, &app).await;
+
+    let image_entries = audit(&client, &app, "action=image&user=station").await;
+    assert_eq!(image_entries.len(), 4);
+    assert_eq!(image_entries[3]["action"], "image.tags");
+    assert!(audit(&client, &app, "action=imag").await.is_empty());
+}
+
+#[tokio::test]
+async fn test_audit_records_tag_deletion_and_each_image_it_left() {
+    let client = Client::new();
+    let app = spawn_audit_app(&client).await;
+    audited_retag(&client, &app).await;
+
+    let tag = &app.tags[0];
+    let resp = client.delete(format!("{}/tags/{tag}", app.base)).bearer_auth(&app.admin).send().await.unwrap();
+    assert_eq!(resp.status(), 204);
+    let deleted = audit(&client, &app, &format!("target={tag}")).await;
+    assert_eq!(deleted.len(), 1);
+    assert_eq!(deleted[0]["action"], "tag.delete");
+    assert_eq!(deleted[0]["actor"], "ops");
+    assert!(deleted[0]["after"].is_null());
+    let retagged = audit(&client, &app, &format!("image={}&action=image.tags&user=ops", app.uuids[0])).await;
+    assert_eq!(retagged.len(), 1);
+    assert_eq!(retagged[0]["before"].as_array().unwrap().len(), 2);
+    assert_eq!(retagged[0]["after"].as_array().unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_audit_filters_by_user_name_or_id() {
+    let client = Client::new();
+    let app = spawn_audit_app(&client).await;
+    audited_retag(&client, &app).await;
+    audited_bulk_rating(&client, &app).await;
+
+    assert_eq!(audit(&client, &app, "user=station").await.len(), 4);
+    assert_eq!(audit(&client, &app, &format!("user=key:{}", app.tagger_id)).await.len(), 4);
+}
+
+#[tokio::test]
+async fn test_audit_filters_by_time() {
+    let client = Client::new();
+    let app = spawn_audit_app(&client).await;
+    assert!(audit(&client, &app, &format!("since={}", unix_now() + 60)).await.is_empty());
+    assert!(audit(&client, &app, "until=1").await.is_empty());
+}
+
+#[tokio::test]
+async fn test_audit_records_keys_created_on_command_line() {
+    let client = Client::new();
+    let app = spawn_audit_app(&client).await;
+    let now = unix_now();
+
+    let all = audit(&client, &app, &format!("since={}&until={}", now - 60, now + 60)).await;
+    let cli: Vec<&Value> = all.iter().filter(|e| e["actor_id"] == "cli").collect();
+    assert_eq!(cli.len(), 2);
+    assert!(cli.iter().all(|e| e["action"] == "api_key.create" && e["actor"] == "command line"));
+    assert_eq!(cli[0]["target_id"], app.admin.split('_').nth(1).unwrap());
+}
+
+#[tokio::test]
+async fn test_audit_pages_backwards_from_an_entry() {
+    let client = Client::new();
+    let app = spawn_audit_app(&client).await;
+    audited_retag(&client, &app).await;
+    audited_bulk_rating(&client, &app).await;
+    let now = unix_now();
+
+    let all = audit(&client, &app, &format!("since={}&until={}", now - 60, now + 60)).await;
+    let page = audit(&client, &app, &format!("limit=2&before={}", all[0]["id"])).await;
+    assert_eq!(page.len(), 2);
+    assert_eq!(page[0]["id"], all[1]["id"]);
+}
+
+#[tokio::test]
+async fn test_audit_records_share_links_by_id_not_token() {
+    let client = Client::new();
+    let app = spawn_audit_app(&client).await;
+
+    let body = json!({ "kind": "gallery", "collection": "noir-atelier", "gallery": "film-noir" });
+    let share = post_json_as(&client, &app.base, "/shares", &app.tagger, body).await;
+    let share_id = share["id"].as_str().unwrap();
+    let resp =
+        client.delete(format!("{}/shares/{share_id}", app.base)).bearer_auth(&app.tagger).send().await.unwrap();
+    assert_eq!(resp.status(), 204);
+    let entries = audit(&client, &app, &format!("target={share_id}")).await;
+    assert_eq!(entries.len(), 2);
+    for entry in &entries {
+        assert_eq!(entry["after"], json!({ "id": share_id }));
+        assert!(!entry.to_string().contains(share["token"].as_str().unwrap()));
+    }
+}
+
+#[tokio::test]
+async fn test_audit_records_logins_and_logouts_without_token() {
+    let client = Client::new();
+    let app = spawn_audit_app(&client).await;
+    let body = json!({ "username": "retoucher", "password": "retoucher-password", "role": "viewer" });
+    let user = post_json_as(&client, &app.base, "/users", &app.admin, body).await;
+
+    let token = session_token(&client, &app.base, "retoucher", "retoucher-password").await;
+    let resp = client.post(format!("{}/auth/logout", app.base)).bearer_auth(&token).send().await.unwrap();
+    assert_eq!(resp.status(), 204);
+    assert_eq!(login(&client, &app.base, "retoucher", "wrong-password").await.status(), 401);
+    let entries = audit(&client, &app, "user=retoucher").await;
+    let actions: Vec<&str> = entries.iter().map(|e| e["action"].as_str().unwrap()).collect();
+    assert_eq!(actions, ["user.logout", "user.login"]);
+    assert!(entries.iter().all(|e| e["target_id"] == user["uuid"] && !e.to_string().contains(&token)));
+}
+
+#[tokio::test]
+async fn test_audit_rejects_zero_limit() {
+    let client = Client::new();
+    let app = spawn_audit_app(&client).await;
+    assert_eq!(get_as(&client, &app.base, "/audit?limit=0", &app.admin).await.status(), 400);
+}
+
+#[tokio::test]
+async fn test_change_rolled_back_when_audit_fails() {
+    let db_path = temp_db();
+    // Migrate the copy, then make recording a tag deletion fail
+    assert!(keys_cli(&db_path, &["list"]).status.success());
+    rusqlite::Connection::open(&db_path)
+        .unwrap()
+        .execute_batch(
+            "CREATE TRIGGER audit_fails BEFORE INSERT ON audit_log WHEN NEW.action = 'tag.delete'
+             BEGIN SELECT RAISE(ABORT, 'audit log unavailable'); END;",
+        )
+        .unwrap();
+    let base = spawn_app_with_config(Config::new(&db_path, "../galleries")).await;
+    let client = Client::new();
+
+    let images: Value = client
+        .post(format!("{base}/images/search"))
+        .json(&json!({ "filters": [{"field": "gallery", "op": "eq", "value": "film-noir"}] }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let image = images[0]["uuid"].as_str().unwrap().to_string();
+    let groups: Value = client.get(format!("{base}/tags")).send().await.unwrap().json().await.unwrap();
+    let tag = groups[0]["tags"][0]["uuid"].as_str().unwrap().to_string();
+    let resp = client
+        .put(format!("{base}/images/{image}/tags"))
+        .json(&json!({ "tag_uuids": [tag] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 204);
+
+    let resp = client.delete(format!("{base}/tags/{tag}")).send().await.unwrap();
+    assert_eq!(resp.status(), 500);
+    // Neither the tag nor its use on the image went away
+    let detail: Value = client.get(format!("{base}/images/{image}")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(detail["tags"][0]["uuid"], tag.as_str());
+    let groups: Value = client.get(format!("{base}/tags")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(groups[0]["tags"][0]["uuid"], tag.as_str());
+}
+
+// ─── Tag history, undo and redo ───
+
+/// A server with tagger keys for ana and ben, the film-noir image UUIDs and
+/// the sorted tag UUIDs the first of them starts with.
+struct HistoryApp {
+    base: String,
+    ana: String,
+    ben: String,
+    uuids: Vec<String>,
+    original: Vec<String>,
+}
+
+async fn spawn_history_app(client: &Client) -> HistoryApp {
+    let db_path = temp_db();
+    let ana = cli_key(&db_path, "ana", "tagger");
+    let ben = cli_key(&db_path, "ben", "tagger");
+    let base = spawn_app_with_auth(Config::new(&db_path, "../galleries")).await;
+
+    let body = json!({ "filters": [{"field": "gallery", "op": "eq", "value": "film-noir"}] });
+    let uuids = result_uuids(post_json_as(client, &base, "/images/search", &ana, body).await);
+    let detail = get_json_as(client, &base, &format!("/images/{}", uuids[0]), &ana).await;
+    let original = sorted_tag_uuids(&detail["tags"]);
+    assert!(!original.is_empty());
+    HistoryApp { base, ana, ben, uuids, original }
+}
+
+fn sorted_tag_uuids(tags: &Value) -> Vec<String> {
+    let mut uuids: Vec<String> =
+        tags.as_array().unwrap().iter().map(|t| t["uuid"].as_str().unwrap().to_string()).collect();
+    uuids.sort();
+    uuids
+}
+
+async fn history_detail(client: &Client, app: &HistoryApp, uuid: &str) -> Value {
+    get_json_as(client, &app.base, &format!("/images/{uuid}"), &app.ana).await
+}
+
+/// Ana accidentally clears the first image's tags.
+async fn wipe_tags(client: &Client, app: &HistoryApp) {
+    let resp = client
+        .put(format!("{}/images/{}/tags", app.base, app.uuids[0]))
+        .bearer_auth(&app.ana)
+        .json(&json!({ "tag_uuids": [] }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 204);
+}
+
+/// The first image's tag history, as ben sees it.
+async fn tag_history(client: &Client, app: &HistoryApp) -> Vec<Value> {
+    get_json_as(client, &app.base, &format!("/images/{}/tags/history", app.uuids[0]), &app.ben)
+        .await
+        .as_array()
+        .unwrap()
+        .clone()
+}
+
+/// Ben reverts the first image's tags to a history entry.
+async fn revert_tags(client: &Client, app: &HistoryApp, entry_id: Value) -> reqwest::Response {
+    let path = format!("/images/{}/tags/revert", app.uuids[0]);
+    post_as(client, &app.base, &path, &app.ben, json!({ "entry_id": entry_id })).await
+}
+
+/// Ana wipes the first image's tags and ben reverts the wipe.
+async fn wipe_and_revert(client: &Client, app: &HistoryApp) {
+    wipe_tags(client, app).await;
+    let history = tag_history(client, app).await;
+    assert_eq!(revert_tags(client, app, history[0]["id"].clone()).await.status(), 200);
+}
+
+/// `POST /undo` or `POST /redo` as `key`.
+async fn history_step(client: &Client, app: &HistoryApp, key: &str, step: &str) -> reqwest::Response {
+    client.post(format!("{}/{step}", app.base)).bearer_auth(key).send().await.unwrap()
+}
+
+async fn rate_as(
+    client: &Client,
+    app: &HistoryApp,
+    key: &str,
+    uuids: &[String],
+    rating: u8,
+) -> reqwest::StatusCode {
+    client
+        .patch(format!("{}/images/culling", app.base))
+        .bearer_auth(key)
+        .json(&json!({ "image_uuids": uuids, "rating": rating }))
+        .send()
+        .await
+        .unwrap()
+        .status()
+}
+
+#[tokio::test]
+async fn test_tag_history_shows_a_wipe_with_what_was_lost() {
+    let client = Client::new();
+    let app = spawn_history_app(&client).await;
+    wipe_tags(&client, &app).await;
+
+    let history = tag_history(&client, &app).await;
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0]["actor"], "ana");
+    assert_eq!(sorted_tag_uuids(&history[0]["before"]), app.original);
+    assert!(history[0]["after"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_any_tagger_reverts_to_a_history_entry() {
+    let client = Client::new();
+    let app = spawn_history_app(&client).await;
+    wipe_tags(&client, &app).await;
+    let history = tag_history(&client, &app).await;
+
+    let resp = revert_tags(&client, &app, history[0]["id"].clone()).await;
+    assert_eq!(resp.status(), 200);
+    let reverted: Value = resp.json().await.unwrap();
+    assert_eq!(sorted_tag_uuids(&reverted["tags"]), app.original);
+    assert!(reverted["missing"].as_array().unwrap().is_empty());
+    assert_eq!(sorted_tag_uuids(&history_detail(&client, &app, &app.uuids[0]).await["tags"]), app.original);
+}
+
+#[tokio::test]
+async fn test_revert_to_unknown_entry_not_found() {
+    let client = Client::new();
+    let app = spawn_history_app(&client).await;
+    assert_eq!(revert_tags(&client, &app, json!(999_999)).await.status(), 404);
+}
+
+#[tokio::test]
+async fn test_undo_takes_back_the_callers_own_last_change() {
+    let client = Client::new();
+    let app = spawn_history_app(&client).await;
+    let image = &app.uuids[0];
+    wipe_and_revert(&client, &app).await;
+
+    // Ben's undo takes back his revert, not ana's wipe
+    let resp = history_step(&client, &app, &app.ben, "undo").await;
+    assert_eq!(resp.status(), 200);
+    let undone: Value = resp.json().await.unwrap();
+    assert_eq!(undone["images"], json!([image]));
+    assert!(history_detail(&client, &app, image).await["tags"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_redo_replays_an_undone_change_once() {
+    let client = Client::new();
+    let app = spawn_history_app(&client).await;
+    wipe_and_revert(&client, &app).await;
+    history_step(&client, &app, &app.ben, "undo").await;
+
+    assert_eq!(history_step(&client, &app, &app.ben, "redo").await.status(), 200);
+    assert_eq!(sorted_tag_uuids(&history_detail(&client, &app, &app.uuids[0]).await["tags"]), app.original);
+    assert_eq!(history_step(&client, &app, &app.ben, "redo").await.status(), 409);
+}
+
+#[tokio::test]
+async fn test_bulk_edit_undone_as_one_operation() {
+    let client = Client::new();
+    let app = spawn_history_app(&client).await;
+    assert_eq!(rate_as(&client, &app, &app.ana, &app.uuids[..3], 4).await, 200);
+
+    let resp = history_step(&client, &app, &app.ana, "undo").await;
+    assert_eq!(resp.status(), 200);
+    let undone: Value = resp.json().await.unwrap();
+    assert_eq!(undone["images"].as_array().unwrap().len(), 3);
+    for uuid in &app.uuids[..3] {
+        assert_eq!(history_detail(&client, &app, uuid).await["rating"], 0);
+    }
+}
+
+#[tokio::test]
+async fn test_undo_blocked_by_a_later_edit_to_the_same_image() {
+    let client = Client::new();
+    let app = spawn_history_app(&client).await;
+    assert_eq!(rate_as(&client, &app, &app.ana, &app.uuids[..3], 4).await, 200);
+    history_step(&client, &app, &app.ana, "undo").await;
+    assert_eq!(history_step(&client, &app, &app.ana, "redo").await.status(), 200);
+
+    assert_eq!(rate_as(&client, &app, &app.ben, &app.uuids[1..2], 2).await, 200);
+    assert_eq!(history_step(&client, &app, &app.ana, "undo").await.status(), 409);
+    assert_eq!(history_detail(&client, &app, &app.uuids[0]).await["rating"], 4);
+    assert_eq!(history_step(&client, &app, &app.ben, "undo").await.status(), 200);
+    assert_eq!(history_step(&client, &app, &app.ana, "undo").await.status(), 200);
+    assert_eq!(history_detail(&client, &app, &app.uuids[1]).await["rating"], 0);
+}
+
+#[tokio::test]
+async fn test_new_edit_ends_the_chance_to_redo() {
+    let client = Client::new();
+    let app = spawn_history_app(&client).await;
+    assert_eq!(rate_as(&client, &app, &app.ana, &app.uuids[..3], 4).await, 200);
+    assert_eq!(history_step(&client, &app, &app.ana, "undo").await.status(), 200);
+
+    assert_eq!(rate_as(&client, &app, &app.ana, &app.uuids[3..4], 1).await, 200);
+    assert_eq!(history_step(&client, &app, &app.ana, "redo").await.status(), 409);
+}
+
+#[tokio::test]
+async fn test_undo_of_a_change_someone_reverted_conflicts() {
+    let client = Client::new();
+    let app = spawn_history_app(&client).await;
+    wipe_and_revert(&client, &app).await;
+    assert_eq!(rate_as(&client, &app, &app.ana, &app.uuids[3..4], 1).await, 200);
+
+    // Undo takes back that edit; ana's wipe under it was reverted by ben since
+    assert_eq!(history_step(&client, &app, &app.ana, "undo").await.status(), 200);
+    assert_eq!(history_step(&client, &app, &app.ana, "undo").await.status(), 409);
+    assert_eq!(sorted_tag_uuids(&history_detail(&client, &app, &app.uuids[0]).await["tags"]), app.original);
+}
+
+// ─── GET /events ───
+
+/// Reads Server-Sent Events off a streaming response.
+struct EventReader {
+    resp: reqwest::Response,
+    buffer: String,
+}
+
+impl EventReader {
+    fn new(resp: reqwest::Response) -> Self {
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.headers()["content-type"], "text/event-stream");
+        EventReader { resp, buffer: String::new() }
+    }
+
+    /// The next event's id, type and data, skipping keep-alive comments.
+    async fn next(&mut self) -> (String, String, Value) {
+        loop {
+            if let Some(end) = self.buffer.find("\n\n") {
+                let block: String = self.buffer.drain(..end + 2).collect();
+                let (mut id, mut event, mut data) = (String::new(), String::new(), String::new());
+                for line in block.lines() {
+                    if let Some(value) = line.strip_prefix("id:") {
+                        id = value.trim().to_string();
+                    } else if let Some(value) = line.strip_prefix("event:") {
+                        event = value.trim().to_string();
+                    } else if let Some(value) = line.strip_prefix("data:") {
+                        data.push_str(value.trim());
+                    }
+                }
+                if event.is_empty() {
+                    continue;
+                }
+                return (id, event, serde_json::from_str(&data).unwrap());
+            }
+            let chunk = tokio::time::timeout(std::time::Duration::from_secs(10), self.resp.chunk())
+                .await
+                .expect("no event within 10s")
+                .unwrap()
+                .expect("feed ended");
+            self.buffer.push_str(std::str::from_utf8(&chunk).unwrap());
+        }
+    }
+}
+
+/// A server with a tagger key, a viewer key limited to raw-collective and an
+/// admin key, two tag UUIDs, and the first film-noir and street-fashion images.
+struct FeedApp {
+    base: String,
+    tagger: String,
+    raw_viewer: String,
+    admin: String,
+    tags: Vec<String>,
+    noir: String,
+    raw: String,
+}
+
+async fn spawn_feed_app(client: &Client) -> FeedApp {
+    let db_path = temp_db();
+    let tagger = cli_key(&db_path, "station", "tagger");
+    let raw_viewer = cli_key_with(&db_path, &["create", "raw", "--collections", "raw-collective"]);
+    let admin = cli_key(&db_path, "ops", "admin");
+    let base = spawn_app_with_auth(Config::new(&db_path, "../galleries")).await;
+
+    let groups = get_json_as(client, &base, "/tags", &tagger).await;
+    let tags: Vec<String> = groups
+        .as_array()
+        .unwrap()
+        .iter()
+        .flat_map(|g| g["tags"].as_array().unwrap())
+        .map(|t| t["uuid"].as_str().unwrap().to_string())
+        .take(2)
+        .collect();
+    let first_in = |gallery: &str| {
+        let body = json!({ "filters": [{"field": "gallery", "op": "eq", "value": gallery}] });
+        let request = post_json_as(client, &base, "/images/search", &tagger, body);
+        async move { request.await[0]["uuid"].as_str().unwrap().to_string() }
+    };
+    let noir = first_in("film-noir").await;
+    let raw = first_in("street-fashion").await;
+    FeedApp { base, tagger, raw_viewer, admin, tags, noir, raw }
+}
+
+/// Subscribes to `/events` as `key`, resuming after `last_event_id` if given.
+async fn feed(client: &Client, app: &FeedApp, key: &str, last_event_id: Option<&str>) -> reqwest::Response {
+    let mut request = client.get(format!("{}/events", app.base)).bearer_auth(key);
+    if let Some(id) = last_event_id {
+        request = request.header("Last-Event-ID", id);
+    }
+    request.send().await.unwrap()
+}
+
+async fn feed_put_tags(client: &Client, app: &FeedApp, image: &str, tags: &[&str]) -> reqwest::StatusCode {
+    client
+        .put(format!("{}/images/{image}/tags", app.base))
+        .bearer_auth(&app.tagger)
+        .json(&json!({ "tag_uuids": tags }))
+        .send()
+        .await
+        .unwrap()
+        .status()
+}
+
+/// Tags the film-noir image with the first tag, then the street-fashion image
+/// with both.
+async fn tag_noir_then_raw(client: &Client, app: &FeedApp) {
+    assert_eq!(feed_put_tags(client, app, &app.noir, &[&app.tags[0]]).await, 204);
+    assert_eq!(feed_put_tags(client, app, &app.raw, &[&app.tags[0], &app.tags[1]]).await, 204);
+}
+
+async fn rename_second_tag(client: &Client, app: &FeedApp) {
+    let resp = client
+        .patch(format!("{}/tags/{}", app.base, app.tags[1]))
+        .bearer_auth(&app.admin)
+        .json(&json!({ "name": "renamed in feed" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+}
+
+#[tokio::test]
+async fn test_feed_announces_tag_changes_with_image_details() {
+    let client = Client::new();
+    let app = spawn_feed_app(&client).await;
+    let mut events = EventReader::new(feed(&client, &app, &app.tagger, None).await);
+
+    assert_eq!(feed_put_tags(&client, &app, &app.noir, &[&app.tags[0]]).await, 204);
+    let (_, kind, data) = events.next().await;
+    assert_eq!(kind, "image.tags");
+    assert_eq!(data["image_uuid"], app.noir.as_str());
+    assert_eq!(data["gallery"], "film-noir");
+    assert_eq!(data["tags"][0]["uuid"], app.tags[0].as_str());
+    assert!(data["at"].as_u64().is_some());
+}
+
+#[tokio::test]
+async fn test_feed_sends_nothing_for_a_change_that_changes_nothing() {
+    let client = Client::new();
+    let app = spawn_feed_app(&client).await;
+    let mut events = EventReader::new(feed(&client, &app, &app.tagger, None).await);
+    tag_noir_then_raw(&client, &app).await;
+    assert_eq!(feed_put_tags(&client, &app, &app.raw, &[&app.tags[1], &app.tags[0]]).await, 204);
+    rename_second_tag(&client, &app).await;
+
+    let (first_id, _, _) = events.next().await;
+    let (raw_id, _, data) = events.next().await;
+    assert_eq!(data["image_uuid"], app.raw.as_str());
+    assert!(raw_id.parse::<i64>().unwrap() > first_id.parse::<i64>().unwrap());
+    assert_eq!(events.next().await.1, "tag.updated");
+}
+
+#[tokio::test]
+async fn test_feed_limited_to_visible_collections() {
+    let client = Client::new();
+    let app = spawn_feed_app(&client).await;
+    let mut everything = EventReader::new(feed(&client, &app, &app.tagger, None).await);
+    let mut raw_only = EventReader::new(feed(&client, &app, &app.raw_viewer, None).await);
+    tag_noir_then_raw(&client, &app).await;
+
+    everything.next().await;
+    let (raw_id, _, _) = everything.next().await;
+    // The film-noir change never reaches this key
+    let (id, kind, data) = raw_only.next().await;
+    assert_eq!((id.as_str(), kind.as_str()), (raw_id.as_str(), "image.tags"));
+    assert_eq!(data["tags"].as_array().unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_feed_announces_tag_renames_to_every_subscriber() {
+    let client = Client::new();
+    let app = spawn_feed_app(&client).await;
+    let mut everything = EventReader::new(feed(&client, &app, &app.tagger, None).await);
+    let mut raw_only = EventReader::new(feed(&client, &app, &app.raw_viewer, None).await);
+    rename_second_tag(&client, &app).await;
+
+    for reader in [&mut everything, &mut raw_only] {
+        let (_, kind, data) = reader.next().await;
+        assert_eq!(kind, "tag.updated");
+        assert_eq!(data["uuid"], app.tags[1].as_str());
+        assert_eq!(data["name"], "renamed in feed");
+        assert_ne!(data["before"]["name"], "renamed in feed");
+    }
+}
+
+#[tokio::test]
+async fn test_feed_resumes_after_the_last_event_id() {
+    let client = Client::new();
+    let app = spawn_feed_app(&client).await;
+    let mut events = EventReader::new(feed(&client, &app, &app.tagger, None).await);
+    tag_noir_then_raw(&client, &app).await;
+    rename_second_tag(&client, &app).await;
+    let (first_id, _, _) = events.next().await;
+    let (raw_id, _, _) = events.next().await;
+    drop(events);
+
+    // A client that reconnects with the last id it saw gets what it missed
+    assert_eq!(feed_put_tags(&client, &app, &app.noir, &[]).await, 204);
+    let mut resumed = EventReader::new(feed(&client, &app, &app.tagger, Some(&first_id)).await);
+    let (id, _, _) = resumed.next().await;
+    assert_eq!(id, raw_id);
+    assert_eq!(resumed.next().await.1, "tag.updated");
+    let (_, kind, data) = resumed.next().await;
+    assert_eq!(kind, "image.tags");
+    assert_eq!(data["image_uuid"], app.noir.as_str());
+    assert!(data["tags"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_feed_announces_vocabulary_additions_and_removals() {
+    let client = Client::new();
+    let app = spawn_feed_app(&client).await;
+    let mut events = EventReader::new(feed(&client, &app, &app.tagger, None).await);
+
+    let body = json!({ "name": "Feed group" });
+    let group = post_json_as(&client, &app.base, "/tag-groups", &app.admin, body).await;
+    let group_uuid = group["uuid"].as_str().unwrap();
+    let body = json!({ "name": "Feed tag", "group_uuid": group_uuid });
+    let tag = post_json_as(&client, &app.base, "/tags", &app.admin, body).await;
+    let delete = |path: String| client.delete(format!("{}{path}", app.base)).bearer_auth(&app.admin).send();
+    assert_eq!(delete(format!("/tags/{}", tag["uuid"].as_str().unwrap())).await.unwrap().status(), 204);
+    assert_eq!(delete(format!("/tag-groups/{group_uuid}")).await.unwrap().status(), 204);
+
+    let (_, kind, data) = events.next().await;
+    assert_eq!((kind.as_str(), &data["name"]), ("tag_group.created", &json!("Feed group")));
+    let (_, kind, data) = events.next().await;
+    assert_eq!((kind.as_str(), &data["uuid"]), ("tag.created", &tag["uuid"]));
+    assert_eq!(events.next().await.1, "tag.deleted");
+    let (_, kind, data) = events.next().await;
+    assert_eq!((kind.as_str(), data["uuid"].as_str()), ("tag_group.deleted", Some(group_uuid)));
+}
+
+#[tokio::test]
+async fn test_feed_rejects_malformed_last_event_id() {
+    let client = Client::new();
+    let app = spawn_feed_app(&client).await;
+    assert_eq!(feed(&client, &app, &app.tagger, Some("yesterday")).await.status(), 400);
+}
+
+// ─── GET /sync ───
+
+/// A server with a tagger key and a viewer key limited to raw-collective, the
+/// first three tag UUIDs and the first film-noir image.
+struct SyncApp {
+    base: String,
+    tagger: String,
+    raw_viewer: String,
+    tags: Vec<String>,
+    noir: String,
+}
+
+async fn spawn_sync_app(client: &Client) -> SyncApp {
+    let db_path = temp_db();
+    let tagger = cli_key(&db_path, "station", "tagger");
+    let raw_viewer = cli_key_with(&db_path, &["create", "raw", "--collections", "raw-collective"]);
+    let base = spawn_app_with_auth(Config::new(&db_path, "../galleries")).await;
+    let mut app = SyncApp { base, tagger, raw_viewer, tags: Vec::new(), noir: String::new() };
+
+    let full = sync(client, &app, &app.tagger, None).await;
+    let tags = full["tags"].as_array().unwrap().iter().take(3);
+    app.tags = tags.map(|t| t["uuid"].as_str().unwrap().to_string()).collect();
+    let noir = full["images"].as_array().unwrap().iter().find(|i| i["gallery"] == "film-noir").unwrap();
+    app.noir = noir["uuid"].as_str().unwrap().to_string();
+    app
+}
+
+/// Fetches `/sync` as `key`, from `since` if given.
+async fn sync(client: &Client, app: &SyncApp, key: &str, since: Option<i64>) -> Value {
+    let mut url = format!("{}/sync", app.base);
+    if let Some(since) = since {
+        url.push_str(&format!("?since={since}"));
+    }
+    let resp = client.get(url).bearer_auth(key).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    resp.json().await.unwrap()
+}
+
+/// Sets the film-noir image's tags to the given indexes into `app.tags`.
+async fn sync_put_tags(client: &Client, app: &SyncApp, tags: [usize; 2]) {
+    let resp = client
+        .put(format!("{}/images/{}/tags", app.base, app.noir))
+        .bearer_auth(&app.tagger)
+        .json(&json!({ "tag_uuids": tags.map(|i| &app.tags[i]) }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 204);
+}
+
+/// Tags the film-noir image with the first two tags and returns the sync
+/// point just after.
+async fn sync_start(client: &Client, app: &SyncApp) -> i64 {
+    sync_put_tags(client, app, [0, 1]).await;
+    sync(client, app, &app.tagger, None).await["seq"].as_i64().unwrap()
+}
+
+/// The (image, `field`) pairs of a list of sync rows or tombstones.
+fn sync_pairs(rows: &Value, field: &str) -> Vec<(String, String)> {
+    rows.as_array()
+        .unwrap()
+        .iter()
+        .map(|r| {
+            let r = r.get("key").unwrap_or(r);
+            (r["image_uuid"].as_str().unwrap().to_string(), r[field].as_str().unwrap().to_string())
+        })
+        .collect()
+}
+
+async fn sync_rate(client: &Client, app: &SyncApp, rating: u8) {
+    let resp = client
+        .patch(format!("{}/images/culling", app.base))
+        .bearer_auth(&app.tagger)
+        .json(&json!({ "image_uuids": [app.noir], "rating": rating }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+}
+
+#[tokio::test]
+async fn test_sync_without_since_sends_everything() {
+    let client = Client::new();
+    let app = spawn_sync_app(&client).await;
+    let full = sync(&client, &app, &app.tagger, None).await;
+    assert_eq!(full["full"], true);
+    assert_eq!(full["images"].as_array().unwrap().len(), 55);
+    assert!(!full["tags"].as_array().unwrap().is_empty());
+    assert!(full["tombstones"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_sync_limited_to_visible_collections() {
+    let client = Client::new();
+    let app = spawn_sync_app(&client).await;
+    let raw = sync(&client, &app, &app.raw_viewer, None).await;
+    assert!(raw["images"].as_array().unwrap().iter().all(|i| i["collection"] == "raw-collective"));
+    assert!(raw["image_tags"].as_array().unwrap().iter().all(|t| t["image_uuid"] != app.noir.as_str()));
+
+    let start = sync_start(&client, &app).await;
+    sync_put_tags(&client, &app, [1, 2]).await;
+    let raw = sync(&client, &app, &app.raw_viewer, Some(start)).await;
+    assert!(raw["image_tags"].as_array().unwrap().is_empty());
+    assert!(raw["tombstones"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_sync_from_the_latest_point_sends_nothing() {
+    let client = Client::new();
+    let app = spawn_sync_app(&client).await;
+    let before = sync(&client, &app, &app.tagger, None).await["seq"].as_i64().unwrap();
+    let start = sync_start(&client, &app).await;
+    assert!(start > before);
+    let nothing = sync(&client, &app, &app.tagger, Some(start)).await;
+    assert_eq!(nothing["full"], false);
+    assert!(nothing["image_tags"].as_array().unwrap().is_empty());
+    assert!(nothing["tombstones"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_sync_sends_added_tags_and_tombstones_for_removed_ones() {
+    let client = Client::new();
+    let app = spawn_sync_app(&client).await;
+    let start = sync_start(&client, &app).await;
+    sync_put_tags(&client, &app, [1, 2]).await;
+    let delta = sync(&client, &app, &app.tagger, Some(start)).await;
+    assert_eq!(delta["full"], false);
+    assert!(delta["images"].as_array().unwrap().is_empty());
+    assert_eq!(sync_pairs(&delta["image_tags"], "tag_uuid"), vec![(app.noir.clone(), app.tags[2].clone())]);
+    assert_eq!(delta["tombstones"][0]["table"], "image_tags");
+    assert_eq!(sync_pairs(&delta["tombstones"], "tag_uuid"), vec![(app.noir.clone(), app.tags[0].clone())]);
+}
+
+#[tokio::test]
+async fn test_sync_sends_a_restored_tag_rather_than_its_tombstone() {
+    let client = Client::new();
+    let app = spawn_sync_app(&client).await;
+    let start = sync_start(&client, &app).await;
+    sync_put_tags(&client, &app, [1, 2]).await;
+    sync_put_tags(&client, &app, [0, 1]).await;
+    let delta = sync(&client, &app, &app.tagger, Some(start)).await;
+    assert_eq!(sync_pairs(&delta["image_tags"], "tag_uuid"), vec![(app.noir.clone(), app.tags[0].clone())]);
+    assert_eq!(sync_pairs(&delta["tombstones"], "tag_uuid"), vec![(app.noir.clone(), app.tags[2].clone())]);
+    assert!(delta["seq"].as_i64().unwrap() > start);
+}
+
+#[tokio::test]
+async fn test_sync_sends_culling_only_when_a_value_changes() {
+    let client = Client::new();
+    let app = spawn_sync_app(&client).await;
+    let start = sync(&client, &app, &app.tagger, None).await["seq"].as_i64().unwrap();
+    sync_rate(&client, &app, 4).await;
+    let rated = sync(&client, &app, &app.tagger, Some(start)).await;
+    assert_eq!(rated["images"][0]["uuid"], app.noir.as_str());
+    let after_rating = rated["seq"].as_i64().unwrap();
+    sync_rate(&client, &app, 4).await;
+    let unchanged = sync(&client, &app, &app.tagger, Some(after_rating)).await;
+    assert!(unchanged["images"].as_array().unwrap().is_empty());
+    assert_eq!(unchanged["seq"], after_rating);
+}
+
+#[tokio::test]
+async fn test_sync_from_an_unknown_point_starts_over() {
+    let client = Client::new();
+    let app = spawn_sync_app(&client).await;
+    let latest = sync(&client, &app, &app.tagger, None).await["seq"].as_i64().unwrap();
+    let stale = sync(&client, &app, &app.tagger, Some(latest + 100)).await;
+    assert_eq!(stale["full"], true);
+    assert_eq!(stale["images"].as_array().unwrap().len(), 55);
+}
+
+#[tokio::test]
+async fn test_sync_tombstones_of_deleted_images_stay_in_their_collection() {
+    let db_path = temp_db();
+    let admin = cli_key(&db_path, "ops", "admin");
+    let raw_viewer = cli_key_with(&db_path, &["create", "raw", "--collections", "raw-collective"]);
+    // An image is deleted by hand before its tags, so its pairs' tombstones
+    // can't tell which collection they were in
+    let (since, image) = {
+        let conn = rusqlite::Connection::open(&db_path).unwrap();
+        conn.execute_batch("PRAGMA foreign_keys = OFF").unwrap();
+        let since: i64 = conn.query_row("SELECT seq FROM sync_state", [], |row| row.get(0)).unwrap();
+        let image: String = conn
+            .query_row(
+                "SELECT uuid FROM images WHERE collection = 'noir-atelier' \
+                 AND uuid IN (SELECT image_uuid FROM image_tags) LIMIT 1",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        conn.execute("DELETE FROM images WHERE uuid = ?", [&image]).unwrap();
+        conn.execute("DELETE FROM image_tags WHERE image_uuid = ?", [&image]).unwrap();
+        (since, image)
+    };
+    let base = spawn_app_with_auth(Config::new(&db_path, "../galleries")).await;
+    let client = Client::new();
+    let tombstoned = |key: &str| {
+        let request = client.get(format!("{base}/sync?since={since}")).bearer_auth(key).send();
+        let image = image.clone();
+        async move {
+            let delta: Value = request.await.unwrap().json().await.unwrap();
+            let mut tables: Vec<String> = delta["tombstones"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter(|t| t["key"]["uuid"] == image.as_str() || t["key"]["image_uuid"] == image.as_str())
+                .map(|t| t["table"].as_str().unwrap().to_string())
+                .collect();
+            tables.dedup();
+            tables
+        }
+    };
+
+    assert_eq!(tombstoned(&admin).await, ["images", "image_tags"]);
+    assert!(tombstoned(&raw_viewer).await.is_empty());
+}
+
+// ─── POST /sync/tags ───
+
+/// A server with two tagger keys, `field` working offline and `desk` online.
+/// The desk tags the first film-noir image with tags 0 and 1, the field client
+/// syncs at `since`, then the desk swaps tag 1 for tag 2.
+struct ReconcileApp {
+    base: String,
+    field: String,
+    desk: String,
+    tags: Vec<String>,
+    image: String,
+    since: i64,
+    now: u64,
+}
+
+async fn spawn_reconcile_app(client: &Client) -> ReconcileApp {
+    let db_path = temp_db();
+    let field = cli_key(&db_path, "field", "tagger");
+    let desk = cli_key(&db_path, "desk", "tagger");
+    let base = spawn_app_with_auth(Config::new(&db_path, "../galleries")).await;
+    let now = unix_now();
+
+    let full = get_json_as(client, &base, "/sync", &desk).await;
+    let tags: Vec<String> =
+        full["tags"].as_array().unwrap().iter().map(|t| t["uuid"].as_str().unwrap().to_string()).collect();
+    let image = full["images"].as_array().unwrap().iter().find(|i| i["gallery"] == "film-noir").unwrap();
+    let image = image["uuid"].as_str().unwrap().to_string();
+    let put_tags = |indexes: [usize; 2]| {
+        let request = client
+            .put(format!("{base}/images/{image}/tags"))
+            .bearer_auth(&desk)
+            .json(&json!({ "tag_uuids": indexes.map(|i| &tags[i]) }))
+            .send();
+        async move { assert_eq!(request.await.unwrap().status(), 204) }
+    };
+    put_tags([0, 1]).await;
+    let since = get_json_as(client, &base, "/sync", &desk).await["seq"].as_i64().unwrap();
+
+    // While the field client is offline, the desk swaps one tag for another
+    put_tags([0, 2]).await;
+    // so that the field client's later edit is later by the server's clock too
+    tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+    ReconcileApp { base, field, desk, tags, image, since, now }
+}
+
+async fn reconcile(client: &Client, app: &ReconcileApp, body: Value) -> reqwest::Response {
+    post_as(client, &app.base, "/sync/tags", &app.field, body).await
+}
+
+/// The image's tags, sorted.
+async fn reconciled_tags(client: &Client, app: &ReconcileApp) -> Vec<String> {
+    let detail = get_json_as(client, &app.base, &format!("/images/{}", app.image), &app.desk).await;
+    sorted_tag_uuids(&detail["tags"])
+}
+
+/// The given indexes into `app.tags`, as the sorted UUIDs.
+fn tag_set(app: &ReconcileApp, indexes: &[usize]) -> Vec<String> {
+    let mut tags: Vec<String> = indexes.iter().map(|&i| app.tags[i].clone()).collect();
+    tags.sort();
+    tags
+}
+
+/// Sends the field client's queued edits, made an hour ago except for one
+/// made an hour from now, and returns the per-operation results.
+async fn reconcile_offline_batch(client: &Client, app: &ReconcileApp) -> Value {
+    let (image, tags, earlier) = (&app.image, &app.tags, app.now - 3600);
+    let resp = reconcile(
+        client,
+        app,
+        json!({
+            "since": app.since,
+            "operations": [
+                {"id": "add", "image_uuid": image, "tag_uuid": tags[3], "op": "add", "at": earlier},
+                {"id": "already", "image_uuid": image, "tag_uuid": tags[1], "op": "remove", "at": earlier},
+                {"id": "older", "image_uuid": image, "tag_uuid": tags[2], "op": "remove", "at": earlier},
+                {"id": "newer", "image_uuid": image, "tag_uuid": tags[2], "op": "remove", "at": app.now + 3600},
+                {"id": "no-tag", "image_uuid": image, "tag_uuid": "missing", "op": "add", "at": earlier},
+                {"id": "no-image", "image_uuid": "missing", "tag_uuid": tags[0], "op": "add", "at": earlier},
+            ],
+        }),
+    )
+    .await;
+    assert_eq!(resp.status(), 200);
+    resp.json().await.unwrap()
+}
+
+#[tokio::test]
+async fn test_offline_tag_operations_report_each_outcome() {
+    let client = Client::new();
+    let app = spawn_reconcile_app(&client).await;
+    let results = reconcile_offline_batch(&client, &app).await;
+    let statuses: Vec<(&str, &str)> = results
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| (r["id"].as_str().unwrap(), r["status"].as_str().unwrap()))
+        .collect();
+    assert_eq!(
+        statuses,
+        vec![
+            ("add", "applied"),
+            ("already", "unchanged"),
+            ("older", "conflict"),
+            ("newer", "applied"),
+            ("no-tag", "failed"),
+            ("no-image", "failed"),
+        ]
+    );
+    assert!(results[2]["server_at"].as_u64().unwrap() > app.now - 3600);
+    assert_eq!(results[4]["error"], "Tag not found");
+    assert_eq!(reconciled_tags(&client, &app).await, tag_set(&app, &[0, 3]));
+}
+
+#[tokio::test]
+async fn test_offline_tag_operations_undo_as_one_edit() {
+    let client = Client::new();
+    let app = spawn_reconcile_app(&client).await;
+    reconcile_offline_batch(&client, &app).await;
+    let undo = client.post(format!("{}/undo", app.base)).bearer_auth(&app.field).send().await.unwrap();
+    assert_eq!(undo.status(), 200);
+    assert_eq!(reconciled_tags(&client, &app).await, tag_set(&app, &[0, 2]));
+}
+
+#[tokio::test]
+async fn test_offline_tag_operations_reject_policy_keeps_changes_since_sync_point() {
+    let client = Client::new();
+    let app = spawn_reconcile_app(&client).await;
+    let (image, tags) = (&app.image, &app.tags);
+    let resp = reconcile(
+        &client,
+        &app,
+        json!({
+            "since": app.since,
+            "policy": "reject",
+            "operations": [
+                {"image_uuid": image, "tag_uuid": tags[2], "op": "remove", "at": app.now + 3600},
+                {"image_uuid": image, "tag_uuid": tags[4], "op": "add", "at": app.now},
+            ],
+        }),
+    )
+    .await;
+    let results: Value = resp.json().await.unwrap();
+    assert_eq!(results[0]["status"], "conflict");
+    assert_eq!(results[1]["status"], "applied");
+    assert_eq!(results[1]["id"], Value::Null);
+    assert_eq!(reconciled_tags(&client, &app).await, tag_set(&app, &[0, 2, 4]));
+}
+
+#[tokio::test]
+async fn test_offline_tag_operations_reject_unknown_sync_point() {
+    let client = Client::new();
+    let app = spawn_reconcile_app(&client).await;
+    let resp = reconcile(&client, &app, json!({ "since": app.since + 1_000_000, "operations": [] })).await;
+    assert_eq!(resp.status(), 400);
+}
+
+/// An image and one of its tags, read from the database file before the
+/// server starts, with the sync point at that moment.
+fn tagged_image(conn: &rusqlite::Connection, collection: &str) -> (i64, String, String) {
+    let since: i64 = conn.query_row("SELECT seq FROM sync_state", [], |row| row.get(0)).unwrap();
+    let (image, tag) = conn
+        .query_row(
+            "SELECT it.image_uuid, it.tag_uuid FROM image_tags it JOIN images i ON i.uuid = it.image_uuid \
+             WHERE i.collection = ? ORDER BY i.path LIMIT 1",
+            [collection],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    (since, image, tag)
+}
+
+#[tokio::test]
+async fn test_offline_tag_operations_ignore_collection_moves() {
+    let db_path = temp_db();
+    let out = keys_cli(&db_path, &["create", "field", "--role", "tagger"]);
+    let field = String::from_utf8(out.stdout).unwrap().trim().to_string();
+    // The image moves to another collection after the client synced, which
+    // restamps its tags without anyone changing them
+    let (since, image, tag) = {
+        let conn = rusqlite::Connection::open(&db_path).unwrap();
+        let tagged = tagged_image(&conn, "noir-atelier");
+        conn.execute("UPDATE images SET collection = 'golden-hour-photo' WHERE uuid = ?", [&tagged.1])
+            .unwrap();
+        tagged
+    };
+    let base = spawn_app_with_auth(Config::new(&db_path, "../galleries")).await;
+    let client = Client::new();
+
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+    let results: Value = client
+        .post(format!("{base}/sync/tags"))
+        .bearer_auth(&field)
+        .json(&json!({
+            "since": since,
+            "policy": "reject",
+            "operations": [{"image_uuid": image, "tag_uuid": tag, "op": "remove", "at": now - 3600}],
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(results[0]["status"], "applied");
+    let detail: Value = client
+        .get(format!("{base}/images/{image}"))
+        .bearer_auth(&field)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(detail["tags"].as_array().unwrap().iter().all(|t| t["uuid"] != tag.as_str()));
+}
+
+#[tokio::test]
+async fn test_offline_tag_operations_failing_batch_writes_nothing() {
+    let db_path = temp_db();
+    let out = keys_cli(&db_path, &["create", "field", "--role", "tagger"]);
+    let field = String::from_utf8(out.stdout).unwrap().trim().to_string();
+    // Writing the second image of the batch fails
+    let (since, first, first_tag, second, second_tag) = {
+        let conn = rusqlite::Connection::open(&db_path).unwrap();
+        let (since, first, first_tag) = tagged_image(&conn, "noir-atelier");
+        let (_, second, second_tag) = tagged_image(&conn, "lumiere-studio");
+        conn.execute_batch(&format!(
+            "CREATE TRIGGER audit_fails BEFORE INSERT ON audit_log WHEN NEW.image_uuid = '{second}'
+             BEGIN SELECT RAISE(ABORT, 'audit log unavailable'); END;"
+        ))
+        .unwrap();
+        (since, first, first_tag, second, second_tag)
+    };
+    let base = spawn_app_with_auth(Config::new(&db_path, "../galleries")).await;
+    let client = Client::new();
+    let tags_of = |image: &str| {
+        let request = client.get(format!("{base}/images/{image}")).bearer_auth(&field).send();
+        async move {
+            let detail: Value = request.await.unwrap().json().await.unwrap();
+            detail["tags"].as_array().unwrap().len()
+        }
+    };
+    let before = (tags_of(&first).await, tags_of(&second).await);
+
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+    let resp = client
+        .post(format!("{base}/sync/tags"))
+        .bearer_auth(&field)
+        .json(&json!({
+            "since": since,
+            "operations": [
+                {"image_uuid": first, "tag_uuid": first_tag, "op": "remove", "at": now - 2},
+                {"image_uuid": second, "tag_uuid": second_tag, "op": "remove", "at": now - 1},
+            ],
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 500);
+    assert_eq!((tags_of(&first).await, tags_of(&second).await), before);
+    // No half-written edit is left to undo either
+    let resp = client.post(format!("{base}/undo")).bearer_auth(&field).send().await.unwrap();
+    assert_eq!(resp.status(), 409);
+}